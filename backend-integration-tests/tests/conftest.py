@@ -123,7 +123,7 @@ def bootstrap_admin() -> dict:
 
 @pytest.fixture
 def seed_user(seeded_users: list[dict]) -> dict:
-    """First seed user (``user1@example.com`` / ``test1``)."""
+    """First seed user (``user1@example.com`` / ``testpass1``)."""
     return seeded_users[0]
 
 
@@ -1,7 +1,7 @@
 """Seed user pool for integration tests and ``task populate``.
 
 Bootstrap admin: ``admin@admin.com`` / ``admin123`` (must be listed in ``admins.txt``).
-Seed users: ``user1@example.com`` … ``user30@example.com`` with passwords ``test1`` … ``test30``.
+Seed users: ``user1@example.com`` … ``user30@example.com`` with passwords ``testpass1`` … ``testpass30``.
 """
 
 from __future__ import annotations
@@ -38,7 +38,7 @@ def seed_credentials(number: int) -> dict[str, Any]:
     return {
         "number": number,
         "email": f"user{number}@example.com",
-        "password": f"test{number}",
+        "password": f"testpass{number}",
         "company_name": f"Seed Company {number}",
     }
 
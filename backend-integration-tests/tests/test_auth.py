@@ -1,6 +1,14 @@
 """
 Integration tests for authentication endpoints.
 """
+import base64
+import hashlib
+import hmac
+import json
+import struct
+import time
+import uuid
+
 import pytest
 import httpx
 from typing import Dict
@@ -126,7 +134,7 @@ class TestLogin:
         seed_user: Dict[str, str],
         backend_health_check,
     ):
-        """Test login with seed user credentials (user1@example.com / test1)."""
+        """Test login with seed user credentials (user1@example.com / testpass1)."""
         response = await http_client.post(
             "/login",
             json={"email": seed_user["email"], "password": seed_user["password"]},
@@ -303,3 +311,475 @@ class TestTokenValidation:
         assert response.status_code in (401, 403), (
             "Request without token should return 401 or 403"
         )
+
+
+@pytest.mark.auth
+@pytest.mark.integration
+class TestSessions:
+    """Test refresh tokens, logout and session revocation."""
+
+    @pytest.fixture
+    async def session_user(self, http_client: httpx.AsyncClient) -> Dict[str, str]:
+        credentials = {
+            "email": f"session_{uuid.uuid4().hex[:8]}@example.com",
+            "password": "securepassword123",
+        }
+        response = await http_client.post("/register", json=credentials)
+        assert response.status_code == 200, response.text
+        return credentials
+
+    async def _login(self, http_client: httpx.AsyncClient, credentials: Dict[str, str]) -> dict:
+        response = await http_client.post("/login", json=credentials)
+        assert response.status_code == 200, response.text
+        return response.json()
+
+    async def test_refresh_rotates_tokens(
+        self,
+        http_client: httpx.AsyncClient,
+        session_user: Dict[str, str],
+        backend_health_check,
+    ):
+        tokens = await self._login(http_client, session_user)
+        assert tokens["refresh_token"] and tokens["expires_in"] > 0
+
+        response = await http_client.post(
+            "/refresh", json={"refresh_token": tokens["refresh_token"]}
+        )
+        assert response.status_code == 200, response.text
+        rotated = response.json()
+        assert rotated["refresh_token"] != tokens["refresh_token"]
+
+        # The old refresh token is single use
+        response = await http_client.post(
+            "/refresh", json={"refresh_token": tokens["refresh_token"]}
+        )
+        assert response.status_code == 401
+
+        response = await http_client.get(
+            "/sessions", headers={"Authorization": f"Bearer {rotated['token']}"}
+        )
+        assert response.status_code == 200, response.text
+        assert [s["current"] for s in response.json()] == [True]
+
+    async def test_logout_revokes_access_token(
+        self,
+        http_client: httpx.AsyncClient,
+        session_user: Dict[str, str],
+        backend_health_check,
+    ):
+        tokens = await self._login(http_client, session_user)
+        headers = {"Authorization": f"Bearer {tokens['token']}"}
+
+        response = await http_client.post("/logout", headers=headers)
+        assert response.status_code == 200, response.text
+
+        assert (await http_client.get("/sessions", headers=headers)).status_code == 401
+        response = await http_client.post(
+            "/refresh", json={"refresh_token": tokens["refresh_token"]}
+        )
+        assert response.status_code == 401
+
+    async def test_logout_all_and_admin_revoke(
+        self,
+        http_client: httpx.AsyncClient,
+        admin_authenticated_client: httpx.AsyncClient,
+        session_user: Dict[str, str],
+        backend_health_check,
+    ):
+        first = await self._login(http_client, session_user)
+        second = await self._login(http_client, session_user)
+
+        response = await http_client.post(
+            "/logout_all", headers={"Authorization": f"Bearer {first['token']}"}
+        )
+        assert response.status_code == 200, response.text
+        assert response.json()["revoked"] == 2
+        response = await http_client.get(
+            "/sessions", headers={"Authorization": f"Bearer {second['token']}"}
+        )
+        assert response.status_code == 401
+
+        third = await self._login(http_client, session_user)
+        response = await admin_authenticated_client.post(
+            f"/admin/users/{session_user['email']}/revoke_sessions"
+        )
+        assert response.status_code == 200, response.text
+        response = await http_client.get(
+            "/sessions", headers={"Authorization": f"Bearer {third['token']}"}
+        )
+        assert response.status_code == 401
+
+
+@pytest.mark.auth
+@pytest.mark.integration
+class TestRoles:
+    """Test role grants and permission checks."""
+
+    async def test_support_agent_answers_tickets_without_admin_rights(
+        self,
+        http_client: httpx.AsyncClient,
+        admin_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        credentials = {
+            "email": f"support_{uuid.uuid4().hex[:8]}@example.com",
+            "password": "securepassword123",
+        }
+        assert (await http_client.post("/register", json=credentials)).status_code == 200
+        login = await http_client.post("/login", json=credentials)
+        headers = {"Authorization": f"Bearer {login.json()['token']}"}
+
+        roles = (await http_client.get("/roles", headers=headers)).json()
+        assert roles["roles"] == ["shop_owner"]
+        assert (await http_client.get("/admin/support_all", headers=headers)).status_code == 404
+
+        response = await admin_authenticated_client.post(
+            f"/admin/users/{credentials['email']}/roles", json={"role": "support_agent"}
+        )
+        assert response.status_code == 200, response.text
+        assert "answer_support" in response.json()["permissions"]
+
+        assert (await http_client.get("/admin/support_all", headers=headers)).status_code == 200
+        assert (await http_client.get("/admin/listusers", headers=headers)).status_code == 404
+
+        response = await admin_authenticated_client.delete(
+            f"/admin/users/{credentials['email']}/roles/support_agent"
+        )
+        assert response.status_code == 200, response.text
+        assert (await http_client.get("/admin/support_all", headers=headers)).status_code == 404
+
+
+@pytest.mark.auth
+@pytest.mark.integration
+class TestPasswords:
+    """Test password rules, change-password and password reset."""
+
+    async def test_weak_passwords_are_rejected(
+        self,
+        http_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        email = f"weak_{uuid.uuid4().hex[:8]}@example.com"
+        for password in ["short1", "onlyletters", "12345678901", "password1"]:
+            response = await http_client.post(
+                "/register", json={"email": email, "password": password}
+            )
+            assert response.status_code == 400, password
+            assert "Weak password" in response.json()["message"]
+
+    async def test_change_password_keeps_current_session(
+        self,
+        http_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        credentials = {
+            "email": f"change_{uuid.uuid4().hex[:8]}@example.com",
+            "password": "securepassword123",
+        }
+        assert (await http_client.post("/register", json=credentials)).status_code == 200
+        first = (await http_client.post("/login", json=credentials)).json()
+        second = (await http_client.post("/login", json=credentials)).json()
+        headers = {"Authorization": f"Bearer {first['token']}"}
+
+        response = await http_client.post(
+            "/change_password",
+            json={"current_password": "wrongpassword1", "new_password": "changedpassword1"},
+            headers=headers,
+        )
+        assert response.status_code == 401
+
+        response = await http_client.post(
+            "/change_password",
+            json={
+                "current_password": credentials["password"],
+                "new_password": "changedpassword1",
+            },
+            headers=headers,
+        )
+        assert response.status_code == 200, response.text
+
+        assert (await http_client.get("/sessions", headers=headers)).status_code == 200
+        response = await http_client.get(
+            "/sessions", headers={"Authorization": f"Bearer {second['token']}"}
+        )
+        assert response.status_code == 401
+        assert (await http_client.post("/login", json=credentials)).status_code == 401
+        response = await http_client.post(
+            "/login",
+            json={"email": credentials["email"], "password": "changedpassword1"},
+        )
+        assert response.status_code == 200
+
+    async def test_password_reset_does_not_reveal_accounts(
+        self,
+        http_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        response = await http_client.post(
+            "/password_reset/request",
+            json={"email": f"nobody_{uuid.uuid4().hex[:8]}@example.com"},
+        )
+        assert response.status_code == 200
+
+        response = await http_client.post(
+            "/password_reset/confirm",
+            json={"token": "not-a-token", "new_password": "changedpassword1"},
+        )
+        assert response.status_code == 400
+
+
+@pytest.mark.auth
+@pytest.mark.integration
+class TestLoginThrottle:
+    """Test lockouts after repeated failed logins."""
+
+    async def test_account_is_locked_and_admin_unlocks_it(
+        self,
+        http_client: httpx.AsyncClient,
+        admin_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        credentials = {
+            "email": f"locked_{uuid.uuid4().hex[:8]}@example.com",
+            "password": "securepassword123",
+        }
+        assert (await http_client.post("/register", json=credentials)).status_code == 200
+
+        wrong = {"email": credentials["email"], "password": "wrongpassword1"}
+        for _ in range(5):
+            assert (await http_client.post("/login", json=wrong)).status_code == 401
+
+        # The right password does not help while the account is locked
+        response = await http_client.post("/login", json=credentials)
+        assert response.status_code == 429
+        assert int(response.headers["Retry-After"]) > 0
+
+        response = await admin_authenticated_client.get("/admin/lockouts")
+        assert response.status_code == 200
+        assert any(
+            l["kind"] == "account" and l["id"] == credentials["email"]
+            for l in response.json()
+        )
+
+        response = await admin_authenticated_client.delete(
+            f"/admin/lockouts/account/{credentials['email']}"
+        )
+        assert response.status_code == 200, response.text
+        assert (await http_client.post("/login", json=credentials)).status_code == 200
+
+
+def _totp(secret: str, step_offset: int = 0) -> str:
+    """TOTP code of an enrolled secret (RFC 6238, SHA1, 6 digits, 30 s)."""
+    key = base64.b32decode(secret + "=" * (-len(secret) % 8))
+    counter = int(time.time()) // 30 + step_offset
+    digest = hmac.new(key, struct.pack(">Q", counter), hashlib.sha1).digest()
+    offset = digest[-1] & 0x0F
+    value = struct.unpack(">I", digest[offset:offset + 4])[0] & 0x7FFFFFFF
+    return f"{value % 1_000_000:06d}"
+
+
+@pytest.mark.auth
+@pytest.mark.integration
+class TestTwoFactor:
+    """Test TOTP enrollment, the second login step and recovery codes."""
+
+    async def test_enrollment_and_two_step_login(
+        self,
+        http_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        credentials = {
+            "email": f"totp_{uuid.uuid4().hex[:8]}@example.com",
+            "password": "securepassword123",
+        }
+        assert (await http_client.post("/register", json=credentials)).status_code == 200
+        login = await http_client.post("/login", json=credentials)
+        headers = {"Authorization": f"Bearer {login.json()['token']}"}
+
+        response = await http_client.post(
+            "/two_factor/enroll", json={"password": "wrong-password"}, headers=headers
+        )
+        assert response.status_code == 401, response.text
+
+        response = await http_client.post(
+            "/two_factor/enroll",
+            json={"password": credentials["password"]},
+            headers=headers,
+        )
+        assert response.status_code == 200, response.text
+        secret = response.json()["secret"]
+        assert response.json()["otpauth_url"].startswith("otpauth://totp/")
+
+        response = await http_client.post(
+            "/two_factor/confirm", json={"code": _totp(secret)}, headers=headers
+        )
+        assert response.status_code == 200, response.text
+        recovery_codes = response.json()["recovery_codes"]
+        assert len(recovery_codes) == 10
+
+        response = await http_client.post("/login", json=credentials)
+        assert response.status_code == 200
+        assert response.json()["two_factor_required"] is True
+        assert "token" not in response.json()
+        challenge = response.json()["challenge"]
+
+        response = await http_client.post(
+            "/login/two_factor", json={"challenge": challenge, "code": "000000"}
+        )
+        assert response.status_code == 401
+
+        # The code of the next step, the current one was used to confirm
+        response = await http_client.post(
+            "/login/two_factor",
+            json={"challenge": challenge, "code": _totp(secret, 1)},
+        )
+        assert response.status_code == 200, response.text
+        assert "token" in response.json()
+
+        # Recovery codes work once
+        for expected in (200, 401):
+            challenge = (await http_client.post("/login", json=credentials)).json()[
+                "challenge"
+            ]
+            response = await http_client.post(
+                "/login/two_factor",
+                json={"challenge": challenge, "code": recovery_codes[0]},
+            )
+            assert response.status_code == expected
+
+        response = await http_client.post(
+            "/two_factor/disable",
+            json={"password": credentials["password"], "code": recovery_codes[1]},
+            headers=headers,
+        )
+        assert response.status_code == 200, response.text
+        response = await http_client.post("/login", json=credentials)
+        assert "token" in response.json()
+
+    async def test_admin_requirement_needs_two_factor_session(
+        self,
+        admin_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        response = await admin_authenticated_client.put(
+            "/admin/security", json={"require_admin_two_factor": True}
+        )
+        assert response.status_code == 409
+        response = await admin_authenticated_client.get("/admin/security")
+        assert response.json() == {"require_admin_two_factor": False}
+
+
+@pytest.mark.auth
+@pytest.mark.integration
+class TestImpersonation:
+    """Test impersonation sessions of admins."""
+
+    async def test_impersonation_is_marked_and_can_be_ended(
+        self,
+        http_client: httpx.AsyncClient,
+        admin_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        credentials = {
+            "email": f"target_{uuid.uuid4().hex[:8]}@example.com",
+            "password": "securepassword123",
+        }
+        assert (await http_client.post("/register", json=credentials)).status_code == 200
+
+        response = await admin_authenticated_client.post(
+            "/admin/impersonate",
+            json={"action": "impersonate", "email": credentials["email"]},
+        )
+        assert response.status_code == 200, response.text
+        assert response.json()["expires_in"] <= 3600
+        headers = {"Authorization": f"Bearer {response.json()['token']}"}
+
+        payload = response.json()["token"].split(".")[1]
+        claims = json.loads(base64.urlsafe_b64decode(payload + "=" * (-len(payload) % 4)))
+        assert claims["sub"] == credentials["email"]
+        assert "sub" in claims["act"]
+
+        response = await http_client.get("/impersonation", headers=headers)
+        assert response.status_code == 200
+        status = response.json()
+        assert status["impersonating"] is True
+        assert status["user"] == credentials["email"]
+        assert status["admin"] == claims["act"]["sub"]
+
+        # Keys would outlive the impersonation
+        response = await http_client.post(
+            "/api_keys",
+            json={
+                "name": "sync",
+                "scopes": ["read-catalog"],
+                "password": credentials["password"],
+            },
+            headers=headers,
+        )
+        assert response.status_code == 403
+
+        response = await admin_authenticated_client.get("/impersonation")
+        assert response.json()["impersonating"] is False
+
+        assert (
+            await http_client.post("/impersonation/end", headers=headers)
+        ).status_code == 200
+        assert (
+            await http_client.get("/impersonation", headers=headers)
+        ).status_code == 401
+
+
+class TestApiKeys:
+    """Test scoped API keys accepted instead of bearer tokens."""
+
+    async def test_api_key_scopes_and_revocation(
+        self,
+        http_client: httpx.AsyncClient,
+        seed_authenticated_client: httpx.AsyncClient,
+        seed_user: dict,
+        backend_health_check,
+    ):
+        key_request = {"name": "catalog sync", "scopes": ["read-catalog"]}
+        response = await seed_authenticated_client.post(
+            "/api_keys", json={**key_request, "password": "wrong password"}
+        )
+        assert response.status_code == 401
+
+        response = await seed_authenticated_client.post(
+            "/api_keys", json={**key_request, "password": seed_user["password"]}
+        )
+        assert response.status_code == 200, response.text
+        created = response.json()
+        assert created["key"].startswith("ak_")
+        assert "key_hash" not in created
+        headers = {"X-API-Key": created["key"]}
+
+        assert (await http_client.get("/user/carmakes", headers=headers)).status_code == 200
+        assert (
+            await http_client.get("/user/calculationstore", headers=headers)
+        ).status_code == 403
+        assert (await http_client.get("/sessions", headers=headers)).status_code == 403
+        assert (
+            await http_client.get("/user/carmakes", headers={"X-API-Key": created["key"] + "x"})
+        ).status_code == 401
+
+        keys = (await seed_authenticated_client.get("/api_keys")).json()
+        listed = next(k for k in keys if k["id"] == created["id"])
+        assert listed["last_used_at"] is not None
+        assert "key" not in listed
+
+        response = await seed_authenticated_client.delete(f"/api_keys/{created['id']}")
+        assert response.status_code == 200
+        assert (await http_client.get("/user/carmakes", headers=headers)).status_code == 401
+
+    async def test_api_key_needs_a_scope(
+        self,
+        seed_authenticated_client: httpx.AsyncClient,
+        seed_user: dict,
+        backend_health_check,
+    ):
+        response = await seed_authenticated_client.post(
+            "/api_keys",
+            json={"name": "nothing", "scopes": [], "password": seed_user["password"]},
+        )
+        assert response.status_code == 400
@@ -6,6 +6,10 @@ Integration tests for the full calculation flow (calc v2 path):
 Prerequisites: backend running at localhost:8080.
 For PDF tests: pdf_backend (WeasyPrint) running at localhost:5000.
 """
+import io
+import json
+import zipfile
+
 import pytest
 import httpx
 
@@ -240,7 +244,7 @@ class TestCalculationStorage:
 
         response = await licensed_client.get("/user/calculationstore/list")
         assert response.status_code == 200
-        items = response.json()
+        items = response.json()["items"]
         assert isinstance(items, list)
         assert len(items) > 0
 
@@ -306,6 +310,118 @@ class TestCalculationStorage:
         saved = get_response.json()
         assert saved["car"]["notes"] == "Updated notes"
 
+    async def test_rename_duplicate_delete_restore(
+        self,
+        licensed_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        save = await licensed_client.post("/user/calculationstore", json=SAMPLE_CALC)
+        assert save.status_code == 200
+        filename = save.json()["saved_file_path"]
+
+        rename = await licensed_client.post(
+            "/user/calculationstore/rename",
+            json={"filename": filename, "title": "Front bumper job"},
+        )
+        assert rename.status_code == 200
+        assert rename.json()["title"] == "Front bumper job"
+
+        duplicate = await licensed_client.post(
+            "/user/calculationstore/duplicate",
+            json={"filename": filename},
+        )
+        assert duplicate.status_code == 200
+        copy_name = duplicate.json()["saved_file_path"]
+        assert copy_name != filename
+        assert duplicate.json()["title"] == "Front bumper job (copy)"
+
+        delete = await licensed_client.delete(
+            "/user/calculationstore", params={"filename": filename}
+        )
+        assert delete.status_code == 200
+        gone = await licensed_client.get(
+            "/user/calculationstore", params={"filename": filename}
+        )
+        assert gone.status_code == 404
+        deleted = await licensed_client.get(
+            "/user/calculationstore/list", params={"deleted": "true"}
+        )
+        assert filename in [item["file_name"] for item in deleted.json()["items"]]
+
+        restore = await licensed_client.post(
+            "/user/calculationstore/restore", json={"filename": filename}
+        )
+        assert restore.status_code == 200
+        restored = await licensed_client.get(
+            "/user/calculationstore", params={"filename": filename}
+        )
+        assert restored.status_code == 200
+        assert restored.json()["title"] == "Front bumper job"
+
+    async def test_approved_estimate_cannot_be_renamed_or_deleted(
+        self,
+        licensed_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        save = await licensed_client.post("/user/calculationstore", json=SAMPLE_CALC)
+        assert save.status_code == 200
+        filename = save.json()["saved_file_path"]
+        approve = await licensed_client.post(
+            "/user/calculationstore/status",
+            json={"filename": filename, "status": "approved"},
+        )
+        assert approve.status_code == 200
+
+        rename = await licensed_client.post(
+            "/user/calculationstore/rename",
+            json={"filename": filename, "title": "Changed after approval"},
+        )
+        assert rename.status_code == 409
+        delete = await licensed_client.delete(
+            "/user/calculationstore", params={"filename": filename}
+        )
+        assert delete.status_code == 409
+
+        reopen = await licensed_client.post(
+            "/user/calculationstore/status",
+            json={"filename": filename, "status": "draft"},
+        )
+        assert reopen.status_code == 200
+        delete = await licensed_client.delete(
+            "/user/calculationstore", params={"filename": filename}
+        )
+        assert delete.status_code == 200
+
+    async def test_import_archive_reports_per_file_results(
+        self,
+        licensed_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        save = await licensed_client.post("/user/calculationstore", json=SAMPLE_CALC)
+        assert save.status_code == 200
+        filename = save.json()["saved_file_path"]
+        stored = await licensed_client.get(
+            "/user/calculationstore", params={"filename": filename}
+        )
+        changed = {**stored.json(), "title": "Imported copy"}
+
+        archive = io.BytesIO()
+        with zipfile.ZipFile(archive, "w") as zf:
+            zf.writestr(f"stored_calculations/{filename}", json.dumps(changed))
+            zf.writestr("stored_calculations/broken.json", '{"car": 5}')
+            zf.writestr("licenses/license.jwt", "token")
+        response = await licensed_client.post(
+            "/user/calculationstore/import",
+            files={"file": ("export.zip", archive.getvalue(), "application/zip")},
+        )
+        assert response.status_code == 200
+        report = response.json()
+        results = {f["path"]: f for f in report["files"]}
+        # The name is taken by a different calculation
+        assert results[f"stored_calculations/{filename}"]["result"] == "renamed"
+        assert results["stored_calculations/broken.json"]["result"] == "failed"
+        assert results["licenses/license.jwt"]["result"] == "skipped"
+
     async def test_retrieve_nonexistent_file_returns_error(
         self,
         licensed_client: httpx.AsyncClient,
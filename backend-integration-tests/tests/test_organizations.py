@@ -0,0 +1,184 @@
+"""
+Integration tests for organizations (shops) shared by several users.
+"""
+import uuid
+
+import pytest
+import httpx
+
+from .test_calc_flow import SAMPLE_CALC
+
+
+async def _register(http_client: httpx.AsyncClient, prefix: str) -> dict:
+    credentials = {
+        "email": f"{prefix}_{uuid.uuid4().hex[:8]}@example.com",
+        "password": "securepassword123",
+    }
+    assert (await http_client.post("/register", json=credentials)).status_code == 200
+    login = await http_client.post("/login", json=credentials)
+    assert login.status_code == 200, login.text
+    return {
+        "email": credentials["email"],
+        "headers": {"Authorization": f"Bearer {login.json()['token']}"},
+    }
+
+
+async def _join(http_client: httpx.AsyncClient, owner: dict, member: dict, role=None):
+    """Invite `member` to the shop of `owner` and accept the invitation."""
+    body = {"email": member["email"]}
+    if role:
+        body["role"] = role
+    response = await http_client.post(
+        "/organization/members", json=body, headers=owner["headers"]
+    )
+    assert response.status_code == 200, response.text
+    organization = response.json()["id"]
+    response = await http_client.post(
+        f"/organization_invitations/{organization}/accept", headers=member["headers"]
+    )
+    assert response.status_code == 200, response.text
+    return response
+
+
+@pytest.mark.auth
+@pytest.mark.integration
+class TestOrganizations:
+    """Test shop membership and the data members share."""
+
+    async def test_members_share_calculations_and_license(
+        self,
+        http_client: httpx.AsyncClient,
+        generate_license,
+        backend_health_check,
+    ):
+        owner = await _register(http_client, "owner")
+        employee = await _register(http_client, "estimator")
+        await generate_license(owner["email"], days=30)
+
+        saved = await http_client.post(
+            "/user/calculationstore", json=SAMPLE_CALC, headers=owner["headers"]
+        )
+        assert saved.status_code == 200, saved.text
+        file_name = saved.json()["saved_file_path"]
+
+        response = await _join(http_client, owner, employee)
+        members = {m["email"]: m["role"] for m in response.json()["members"]}
+        assert members == {owner["email"]: "shop_owner", employee["email"]: "shop_employee"}
+
+        # The license and the calculations belong to the shop
+        response = await http_client.get(
+            "/user/calculationstore",
+            params={"filename": file_name},
+            headers=employee["headers"],
+        )
+        assert response.status_code == 200, response.text
+
+        # Employees cannot manage the shop
+        response = await http_client.put(
+            "/organization", json={"name": "Renamed"}, headers=employee["headers"]
+        )
+        assert response.status_code == 403
+
+        response = await http_client.delete(
+            f"/organization/members/{employee['email']}", headers=owner["headers"]
+        )
+        assert response.status_code == 200, response.text
+        response = await http_client.get(
+            "/user/calculationstore",
+            params={"filename": file_name},
+            headers=employee["headers"],
+        )
+        assert response.status_code != 200
+
+    async def test_last_owner_and_roles(
+        self,
+        http_client: httpx.AsyncClient,
+        generate_license,
+        backend_health_check,
+    ):
+        owner = await _register(http_client, "owner")
+        employee = await _register(http_client, "estimator")
+        await generate_license(owner["email"], days=30)
+
+        response = await http_client.post(
+            "/organization/members",
+            json={"email": employee["email"], "role": "admin"},
+            headers=owner["headers"],
+        )
+        assert response.status_code == 400
+
+        await _join(http_client, owner, employee)
+
+        response = await http_client.delete(
+            f"/organization/members/{owner['email']}", headers=owner["headers"]
+        )
+        assert response.status_code == 409
+
+        response = await http_client.put(
+            "/organization", json={"name": "Body Shop"}, headers=owner["headers"]
+        )
+        assert response.status_code == 200, response.text
+        response = await http_client.get("/organization", headers=employee["headers"])
+        assert response.json()["name"] == "Body Shop"
+
+    async def test_owner_cannot_move_or_wipe_another_user(
+        self,
+        http_client: httpx.AsyncClient,
+        generate_license,
+        backend_health_check,
+    ):
+        owner = await _register(http_client, "owner")
+        victim = await _register(http_client, "victim")
+        await generate_license(owner["email"], days=30)
+        await generate_license(victim["email"], days=30)
+
+        saved = await http_client.post(
+            "/user/calculationstore", json=SAMPLE_CALC, headers=victim["headers"]
+        )
+        assert saved.status_code == 200, saved.text
+        file_name = saved.json()["saved_file_path"]
+        before = (await http_client.get("/organization", headers=victim["headers"])).json()
+
+        response = await http_client.post(
+            "/organization/members",
+            json={"email": victim["email"], "role": "shop_owner"},
+            headers=owner["headers"],
+        )
+        assert response.status_code == 200, response.text
+        organization = response.json()["id"]
+        assert victim["email"] not in {m["email"] for m in response.json()["members"]}
+
+        # Nothing changes until the invited user accepts
+        after = (await http_client.get("/organization", headers=victim["headers"])).json()
+        assert after["id"] == before["id"]
+        assert after["members"] == before["members"]
+        response = await http_client.get(
+            "/user/calculationstore",
+            params={"filename": file_name},
+            headers=victim["headers"],
+        )
+        assert response.status_code == 200, response.text
+
+        # Only the invited user can accept, and declining ends the invitation
+        response = await http_client.post(
+            f"/organization_invitations/{organization}/accept", headers=owner["headers"]
+        )
+        assert response.status_code == 404
+        invitations = (
+            await http_client.get("/organization_invitations", headers=victim["headers"])
+        ).json()
+        assert [i["organization"] for i in invitations] == [organization]
+        response = await http_client.delete(
+            f"/organization_invitations/{organization}", headers=victim["headers"]
+        )
+        assert response.status_code == 200, response.text
+        response = await http_client.post(
+            f"/organization_invitations/{organization}/accept", headers=victim["headers"]
+        )
+        assert response.status_code == 404
+        response = await http_client.get(
+            "/user/calculationstore",
+            params={"filename": file_name},
+            headers=victim["headers"],
+        )
+        assert response.status_code == 200, response.text
@@ -1,5 +1,6 @@
 """Integration tests for calculation PDF/HTML output via the PDF mock service."""
 
+import asyncio
 import uuid
 
 import httpx
@@ -131,3 +132,173 @@ class TestPdfGeneration:
         )
         with urllib.request.urlopen(html_req) as resp:
             assert resp.read() == MOCK_HTML_BODY
+
+
+class TestTemplatePreview:
+    async def test_preview_reports_template_errors(
+        self,
+        licensed_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        samples = (await licensed_authenticated_client.get("/user/list_samples")).json()
+        if not samples:
+            pytest.skip("No samples in the catalog")
+
+        response = await licensed_authenticated_client.post(
+            "/user/preview_template",
+            json={
+                "template_content": "<p>{{ data.metadata.order_number }}</p>\n{{ data.calculation.car.colour }}",
+                "sample": samples[0],
+            },
+        )
+        assert response.status_code == 200, response.text
+        preview = response.json()
+        assert preview["html"].startswith("<p>")
+        assert preview["error"]["kind"] == "undefined_variable"
+        assert preview["error"]["line"] == 2
+        assert preview["error"]["undefined_variable"] == "data.calculation.car.colour"
+
+        response = await licensed_authenticated_client.post(
+            "/user/preview_template",
+            json={"template_content": "{% for x in data %}", "sample": samples[0]},
+        )
+        assert response.status_code == 200, response.text
+        assert response.json()["html"] is None
+        assert response.json()["error"]["kind"] == "syntax_error"
+
+    async def test_upload_rejects_broken_template(
+        self,
+        licensed_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        name = f"doc_templates%2Fitest_{uuid.uuid4().hex[:8]}.html"
+        response = await licensed_authenticated_client.post(
+            f"/editor/upload_user_file/{name}",
+            files={"file": ("t.html", b"<p>{{ data.x </p>", "text/html")},
+        )
+        assert response.status_code == 400
+        assert "line 1" in response.json()["message"]
+
+
+class TestDocumentJobs:
+    async def test_job_stores_document_and_deduplicates(
+        self,
+        licensed_authenticated_client: httpx.AsyncClient,
+        pdfgen_mock,
+        backend_health_check,
+    ):
+        calculation = f"itest_{uuid.uuid4().hex[:8]}.json"
+        payload = {
+            **MINIMAL_CALCULATION_PAYLOAD,
+            "calculation": {"identifier": calculation},
+            "metadata": {"order_number": "000123"},
+            "calculation_file_name": calculation,
+        }
+        response = await licensed_authenticated_client.post("/user/documents/jobs", json=payload)
+        assert response.status_code == 202, response.text
+        job = response.json()
+        assert job["file_name"].startswith("estimate_000123_")
+
+        for _ in range(50):
+            job = (await licensed_authenticated_client.get(f"/user/documents/jobs/{job['id']}")).json()
+            if job["status"] in ("done", "failed"):
+                break
+            await asyncio.sleep(0.2)
+        assert job["status"] == "done", job
+
+        documents = await licensed_authenticated_client.get(
+            "/user/documents", params={"calculation": calculation}
+        )
+        assert [d["id"] for d in documents.json()] == [job["attachment_id"]]
+        download = await licensed_authenticated_client.get(f"/user/attachment/{job['attachment_id']}")
+        assert download.content == MOCK_PDF_BYTES
+
+        again = await licensed_authenticated_client.post("/user/documents/jobs", json=payload)
+        assert again.status_code == 200, again.text
+        assert again.json()["deduplicated"] is True
+        assert again.json()["attachment_id"] == job["attachment_id"]
+
+
+class TestDocumentFormats:
+    @pytest.mark.parametrize(
+        "fmt,content_type,magic",
+        [
+            ("docx", "application/vnd.openxmlformats-officedocument.wordprocessingml.document", b"PK"),
+            ("xlsx", "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", b"PK"),
+            ("markdown", "text/markdown", b"**"),
+        ],
+    )
+    async def test_generate_document_formats(
+        self,
+        licensed_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+        fmt: str,
+        content_type: str,
+        magic: bytes,
+    ):
+        response = await licensed_authenticated_client.post(
+            "/user/generate_document",
+            params={"format": fmt},
+            json={**MINIMAL_CALCULATION_PAYLOAD, "metadata": {"order_number": "42"}},
+        )
+        assert response.status_code == 200, response.text
+        assert response.headers["content-type"].startswith(content_type)
+        assert "estimate_42_" in response.headers["content-disposition"]
+        assert response.content.startswith(magic)
+
+
+class TestDocumentNumbering:
+    async def test_assigned_numbers_are_sequential(
+        self,
+        licensed_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        payload = {**MINIMAL_CALCULATION_PAYLOAD, "assign_number": True}
+        names = []
+        for _ in range(2):
+            response = await licensed_authenticated_client.post(
+                "/user/generate_document", params={"format": "markdown"}, json=payload
+            )
+            assert response.status_code == 200, response.text
+            names.append(response.headers["content-disposition"])
+
+        audit = await licensed_authenticated_client.get("/user/document_numbers")
+        assert audit.status_code == 200, audit.text
+        numbers = audit.json()
+        assert numbers[0]["sequence"] == numbers[1]["sequence"] + 1
+        assert numbers[0]["number"] in names[1]
+        assert numbers[1]["number"] in names[0]
+
+    async def test_failed_render_gives_number_back(
+        self,
+        licensed_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        payload = {**MINIMAL_CALCULATION_PAYLOAD, "assign_number": True}
+        first = await licensed_authenticated_client.post(
+            "/user/generate_document", params={"format": "markdown"}, json=payload
+        )
+        assert first.status_code == 200, first.text
+        broken = await licensed_authenticated_client.post(
+            "/user/generate_html_table",
+            json={**payload, "custom_template_content": "{% if %}"},
+        )
+        assert broken.status_code == 400
+        second = await licensed_authenticated_client.post(
+            "/user/generate_document", params={"format": "markdown"}, json=payload
+        )
+        assert second.status_code == 200, second.text
+
+        numbers = (await licensed_authenticated_client.get("/user/document_numbers")).json()
+        assert numbers[0]["sequence"] == numbers[1]["sequence"] + 1
+        assert all(n["status"] == "issued" for n in numbers)
+
+    async def test_number_format_requires_sequence(
+        self,
+        licensed_authenticated_client: httpx.AsyncClient,
+        backend_health_check,
+    ):
+        info = (await licensed_authenticated_client.get("/getcompanyinfo")).json()
+        info["document_numbering"] = {"format": "EST-{year}"}
+        response = await licensed_authenticated_client.post("/updatecompanyinfo", json=info)
+        assert response.status_code == 400
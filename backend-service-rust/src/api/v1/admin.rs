@@ -1,7 +1,21 @@
 use crate::{
-    auth::invite::{create_invite, list_active_invites, list_archived_invites},
+    auth::{
+        invite::{create_invite, list_active_invites, list_archived_invites},
+        organizations::{assign_personal_organization, user_organization},
+        passwords::{check_password_strength, set_password},
+        sessions::revoke_user_sessions,
+    },
+    calc::{
+        calculation_history::CALCULATIONS, calculation_index::index_calculation_file,
+        calculation_schema::upgrade_calculation_bytes,
+    },
+    db::{
+        calculations::get_entry,
+        api_keys::{list_user_api_keys, remove_api_key},
+        organizations::{list_organizations, remove_organization},
+    },
     errors::AppError,
-    exlogging::{get_latest_log_lines, get_latest_logs},
+    exlogging::{get_latest_log_lines, get_latest_logs, log_event, LogLevel},
     license_manager::{
         delete_license_file,
         generate_license_token,
@@ -11,11 +25,16 @@ use crate::{
     },
     middleware::AuthenticatedUser,
     models::{
-        invite::GenerateInviteRequest, license_requests::GenerateLicenseRequest, AdminStatus,
+        invite::GenerateInviteRequest,
+        license_requests::GenerateLicenseRequest,
+        roles::{Permission, Role, RoleRequest, UserRoles},
+        AdminStatus,
         BulkCreateUsersRequest, BulkCreateUsersResponse, ManageUserRequest, User,
     },
     state::AppState,
-    utils::{delete_user_data_gracefully, user_personal_directory_from_email},
+    utils::{
+        delete_organization_data_gracefully, safe_write_overwrite, organization_directory,
+    },
 };
 use axum::{
     extract::{Json as AxumJson, Path, Query, State},
@@ -28,7 +47,6 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::io::Write;
 use std::sync::Arc;
-use uuid::Uuid;
 use zip::write::FileOptions;
 use zip::ZipWriter;
 
@@ -62,14 +80,15 @@ pub async fn generate_license_handler(
         &user_email,
         expiry_date,
         level,
-        app_state.jwt_license_secret.as_bytes(),
+        &app_state.license_keys,
     )?;
 
-    // Save the license file
-    save_license_file(&user_email, &token, &app_state.data_dir_path).await?;
+    // Save the license file with the user's organization
+    let organization = user_organization(&app_state.db, &user_email)?;
+    save_license_file(&organization, &token, &app_state.data_dir_path).await?;
 
-    // Invalidate the cache for this user's license
-    app_state.license_cache.invalidate_license(&user_email);
+    // Invalidate the cache for this organization's license
+    app_state.license_cache.invalidate_license(&organization);
 
     // Return the generated token (or a confirmation message)
     Ok(Json(format!(
@@ -120,7 +139,8 @@ pub async fn list_user_licenses_handler(
     Path(user_email): Path<String>, // Extract user email from the path
 ) -> Result<impl IntoResponse, AppError> {
     // Call the new function to list license files
-    let license_files = list_license_files(&user_email, &app_state.data_dir_path).await?;
+    let organization = user_organization(&app_state.db, &user_email)?;
+    let license_files = list_license_files(&organization, &app_state.data_dir_path).await?;
 
     Ok(Json(license_files)) // Return the list of filenames as JSON
 }
@@ -132,13 +152,14 @@ pub async fn delete_user_license_handler(
     Path((user_email, license_filename)): Path<(String, String)>, // Extract email and filename from path
 ) -> Result<impl IntoResponse, AppError> {
     // Call the new function to delete the license file
-    delete_license_file(&user_email, &license_filename, &app_state.data_dir_path).await?;
+    let organization = user_organization(&app_state.db, &user_email)?;
+    delete_license_file(&organization, &license_filename, &app_state.data_dir_path).await?;
 
-    // Invalidate the cache for this user's license
-    // Note: If a user could have multiple *active* licenses cached under the same email key,
+    // Invalidate the cache for this organization's license
+    // Note: If an organization could have multiple *active* licenses cached under the same key,
     // invalidating the single key might not be sufficient. However, based on the LicenseCache
-    // structure caching a single LicenseData per email, this is appropriate.
-    app_state.license_cache.invalidate_license(&user_email);
+    // structure caching a single LicenseData per organization, this is appropriate.
+    app_state.license_cache.invalidate_license(&organization);
 
     Ok(StatusCode::OK) // Return 200 OK on successful deletion
 }
@@ -149,8 +170,9 @@ pub async fn get_user_license_handler(
     Path((user_email, license_filename)): Path<(String, String)>, // Extract email and filename from path
 ) -> Result<impl IntoResponse, AppError> {
     // Call the new function to delete the license file
+    let organization = user_organization(&app_state.db, &user_email)?;
     let jwt =
-        read_license_file_by_name(&user_email, &app_state.data_dir_path, &license_filename).await?;
+        read_license_file_by_name(&organization, &app_state.data_dir_path, &license_filename).await?;
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
@@ -164,6 +186,16 @@ pub async fn bulk_create_users(
     State(app_state): State<Arc<AppState>>,
     Json(request): Json<BulkCreateUsersRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    // Nothing is created when one of the passwords is too weak
+    for entry in request.users.iter().filter(|e| !e.email.trim().is_empty()) {
+        check_password_strength(&entry.password).map_err(|e| match e {
+            AppError::InvalidData(reason) => {
+                AppError::InvalidData(format!("{}: {}", entry.email.trim(), reason))
+            }
+            e => e,
+        })?;
+    }
+
     let mut created = Vec::new();
     let mut skipped = Vec::new();
 
@@ -179,11 +211,8 @@ pub async fn bulk_create_users(
         }
 
         let hashed_password = app_state.auth.hash_password(&entry.password)?;
-        let user = User {
-            id: Uuid::new_v4(),
-            email: email.clone(),
-            password_hash: hashed_password,
-        };
+        let mut user = User::new(email.clone(), hashed_password);
+        assign_personal_organization(&app_state.db, &mut user)?;
 
         match app_state.db.insert_user(&user) {
             Ok(()) => created.push(email),
@@ -205,29 +234,124 @@ pub async fn list_users(
     Ok(Json(emails)) // Return the list of emails as JSON
 }
 
+#[derive(Debug, Serialize)]
+pub struct RoleDescription {
+    pub role: Role,
+    pub permissions: &'static [Permission],
+}
+
+/// All roles and what each of them allows.
+pub async fn list_roles(
+    AuthenticatedUser(_admin_email): AuthenticatedUser,
+) -> Result<impl IntoResponse, AppError> {
+    let roles: Vec<_> = Role::ALL
+        .into_iter()
+        .map(|role| RoleDescription {
+            role,
+            permissions: role.permissions(),
+        })
+        .collect();
+    Ok(Json(roles))
+}
+
+pub async fn get_user_roles(
+    AuthenticatedUser(_admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = app_state
+        .db
+        .find_user_by_email(&email)?
+        .ok_or(AppError::UserNotFound)?;
+    Ok(Json(UserRoles::new(user.email, &user.roles)))
+}
+
+pub async fn grant_role(
+    AuthenticatedUser(admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(email): Path<String>,
+    Json(request): Json<RoleRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = app_state
+        .db
+        .find_user_by_email(&email)?
+        .ok_or(AppError::UserNotFound)?;
+    let mut roles = user.roles;
+    roles.push(request.role);
+    app_state.db.set_user_roles(&email, roles)?;
+    log_event(
+        LogLevel::Info,
+        format!("Role {:?} granted to {}", request.role, email),
+        Some(&admin_email),
+    );
+    get_user_roles(AuthenticatedUser(admin_email), State(app_state), Path(email)).await
+}
+
+pub async fn revoke_role(
+    AuthenticatedUser(admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path((email, role)): Path<(String, Role)>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = app_state
+        .db
+        .find_user_by_email(&email)?
+        .ok_or(AppError::UserNotFound)?;
+    if role == Role::Admin && user.roles.contains(&Role::Admin) {
+        let other_admin = app_state.db.get_all_user_emails()?.into_iter().any(|e| {
+            e != email
+                && matches!(app_state.db.find_user_by_email(&e), Ok(Some(u)) if u.roles.contains(&Role::Admin))
+        });
+        if !other_admin {
+            return Err(AppError::Conflict("The last admin cannot lose the admin role".to_string()));
+        }
+    }
+    let roles = user.roles.into_iter().filter(|r| *r != role).collect();
+    app_state.db.set_user_roles(&email, roles)?;
+    log_event(
+        LogLevel::Info,
+        format!("Role {:?} revoked from {}", role, email),
+        Some(&admin_email),
+    );
+    get_user_roles(AuthenticatedUser(admin_email), State(app_state), Path(email)).await
+}
+
 // Existing handler to manage user (delete, change password) (admin only)
 pub async fn manage_user(
-    AuthenticatedUser(_admin_email): AuthenticatedUser, // Ensure admin is authenticated
+    AuthenticatedUser(admin_email): AuthenticatedUser, // Ensure admin is authenticated
     State(app_state): State<Arc<AppState>>,
     Json(request): Json<ManageUserRequest>, // Extract the request payload
 ) -> Result<impl IntoResponse, AppError> {
     match request {
         ManageUserRequest::Delete { email } => {
-            // Handle delete action
+            // Handle delete action, the data goes with the last member of the organization
+            let organization = user_organization(&app_state.db, &email)?;
             app_state.db.delete_user_by_email(&email)?;
-            delete_user_data_gracefully(&app_state.data_dir_path, &email, &app_state.cache).await?;
+            revoke_user_sessions(&app_state, &email)?;
+            for api_key in list_user_api_keys(&app_state.db.api_keys_tree, &email)? {
+                remove_api_key(&app_state.db.api_keys_tree, &api_key.id)?;
+            }
+            if app_state.db.list_organization_members(&organization)?.is_empty() {
+                delete_organization_data_gracefully(&app_state.data_dir_path, &organization, &app_state.cache).await?;
+                remove_organization(&app_state.db.organizations_tree, &organization)?;
+            }
             Ok(StatusCode::OK) // Return 200 OK on successful deletion
         }
         ManageUserRequest::ChangePassword { email, data } => {
-            // Handle change_password action
-            // Hash the new password
-            let hashed_password = app_state.auth.hash_password(&data)?;
-            // Update the user's password hash in the database
-            app_state
-                .db
-                .change_user_password_hash(&email, hashed_password)?;
+            // Handle change_password action, same rules as for the user's own changes
+            set_password(&app_state, &email, &data, None).await?;
             Ok(StatusCode::OK) // Return 200 OK on successful password change
         }
+        ManageUserRequest::ResetTwoFactor { email } => {
+            // The user signs in with the password alone and may enroll again
+            app_state.db.set_user_two_factor(&email, None)?;
+            revoke_user_sessions(&app_state, &email)?;
+            log_event(
+                LogLevel::Warn,
+                format!("Two-factor authentication of {} reset", email),
+                Some(admin_email.as_str()),
+            );
+            Ok(StatusCode::OK)
+        }
     }
 }
 
@@ -272,6 +396,7 @@ pub async fn clear_all_cache(
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     app_state.cache.invalidate_all().await;
+    app_state.car_catalog.invalidate_all();
     Ok(Json(json! ({ "cache_invalidated": true })))
 }
 
@@ -317,13 +442,14 @@ fn create_zip_from_directory(
     Ok(())
 }
 
-/// Export a user's personal directory as a zip. Admin only. Zip is created, served, then deleted.
+/// Export the directory of a user's organization as a zip. Admin only. Zip is created, served, then deleted.
 pub async fn export_user_data_handler(
     AuthenticatedUser(_admin_email): AuthenticatedUser,
     State(app_state): State<Arc<AppState>>,
     Path(user_email): Path<String>,
 ) -> Result<Response<axum::body::Body>, AppError> {
-    let user_dir = user_personal_directory_from_email(&app_state.data_dir_path, &user_email)?;
+    let organization = user_organization(&app_state.db, &user_email)?;
+    let user_dir = organization_directory(&app_state.data_dir_path, &organization)?;
     if !user_dir.exists() {
         return Err(AppError::FileNotFound);
     }
@@ -357,3 +483,117 @@ pub async fn export_user_data_handler(
         .body(axum::body::Body::from(bytes))
         .unwrap())
 }
+
+#[derive(Debug, Deserialize)]
+pub struct MigrateCalculationsQuery {
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalculationMigrationFailure {
+    pub organization: String,
+    pub file: String,
+    pub error: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct CalculationMigrationReport {
+    pub dry_run: bool,
+    pub scanned: usize,
+    pub up_to_date: usize,
+    /// `organization/file` entries upgraded (or to be upgraded on a dry run).
+    pub migrated: Vec<String>,
+    pub failed: Vec<CalculationMigrationFailure>,
+}
+
+/// Upgrade every stored calculation of every organization to the current schema version.
+/// Files that cannot be upgraded are left untouched and reported.
+pub async fn migrate_calculations_handler(
+    AuthenticatedUser(admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<MigrateCalculationsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut report = CalculationMigrationReport {
+        dry_run: q.dry_run,
+        ..Default::default()
+    };
+
+    for organization in list_organizations(&app_state.db.organizations_tree)? {
+        let organization = organization.id;
+        let organization_dir = organization_directory(&app_state.data_dir_path, &organization)?;
+        let mut entries = match tokio::fs::read_dir(organization_dir.join(CALCULATIONS)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let file = entry.file_name().to_string_lossy().to_string();
+            report.scanned += 1;
+
+            let upgraded = tokio::fs::read(&path)
+                .await
+                .map_err(AppError::from)
+                .and_then(|content| upgrade_calculation_bytes(&content));
+            let result = match upgraded {
+                Ok(upgraded) if !upgraded.migrated() => {
+                    report.up_to_date += 1;
+                    continue;
+                }
+                Ok(_) if q.dry_run => Ok(()),
+                Ok(upgraded) => match serde_json::to_string_pretty(&upgraded.data) {
+                    Ok(json) => safe_write_overwrite(
+                        organization_dir.clone(),
+                        std::path::PathBuf::from(CALCULATIONS).join(&file),
+                        json,
+                        &app_state.cache,
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(AppError::from),
+                    Err(e) => Err(e.into()),
+                },
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {
+                    if !q.dry_run {
+                        // Listings read the index only, it has to follow the rewritten file
+                        let previous =
+                            get_entry(&app_state.db.calculations_tree, &organization, &file)?;
+                        index_calculation_file(
+                            &app_state.db,
+                            &organization,
+                            &path,
+                            previous.as_ref(),
+                        )
+                        .await?;
+                    }
+                    report.migrated.push(format!("{}/{}", organization, file))
+                }
+                Err(e) => report.failed.push(CalculationMigrationFailure {
+                    organization: organization.clone(),
+                    file,
+                    error: e.to_string(),
+                }),
+            }
+        }
+    }
+
+    log_event(
+        LogLevel::Info,
+        format!(
+            "Calculation migration{}: {} scanned, {} migrated, {} failed",
+            if q.dry_run { " (dry run)" } else { "" },
+            report.scanned,
+            report.migrated.len(),
+            report.failed.len()
+        ),
+        Some(admin_email),
+    );
+    Ok(Json(report))
+}
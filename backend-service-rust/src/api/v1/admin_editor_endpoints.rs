@@ -1,5 +1,5 @@
 use crate::{
-    api::v1::calc::data_endpoints::T1, calc::car_class_to_body_type::{self, CLASS_TYPE_MAPPING_FILE}, errors::AppError, exlogging::{self, log_event}, middleware::AuthenticatedUser, state::AppState, transactionalfs::list_files_raw, utils::get_file_as_string_by_path // Import the new CompanyInfo struct
+    api::v1::calc::data_endpoints::T1, calc::{car_class_to_body_type::{self, CLASS_TYPE_MAPPING_FILE}, templating::check_uploaded_template}, errors::AppError, exlogging::{self, log_event}, middleware::AuthenticatedUser, state::AppState, transactionalfs::list_files_raw, utils::{get_file_as_string_by_path, organization_catalog_directory, DataStorageCache} // Import the new CompanyInfo struct
 };
 use axum::{
     extract::{Multipart, State},
@@ -7,7 +7,7 @@ use axum::{
     Json,
 };
 use tokio::fs;
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{collections::HashMap, path::{Path, PathBuf}, sync::Arc};
 
 pub async fn get_file_list(
     AuthenticatedUser(_user_email): AuthenticatedUser, // Get user email from the authenticated user
@@ -28,24 +28,86 @@ pub async fn trigger_list_class_body_types_rebuild_global(
 }
 
 pub async fn run_list_class_body_types_rebuild(data_dir: &PathBuf, user: Option<String>) -> Result<HashMap<String, Vec<String>>, AppError> {
-    let file_path = PathBuf::from(&CLASS_TYPE_MAPPING_FILE);
     let common_path = crate::utils::common_directory(data_dir)?;
-    let exact_path = common_path.join(file_path);
-    let t1_path = common_path.join(T1);
     log_event(exlogging::LogLevel::Info, "Class body type rebuild triggered by admin or rebuild", user.clone());
-    let exist_t1 = tokio::fs::try_exists(&t1_path).await;
-    if exist_t1.is_err() || !exist_t1.unwrap() {
+    let t1_path = common_path.join(T1);
+    if !tokio::fs::try_exists(&t1_path).await.unwrap_or(false) {
         log_event(exlogging::LogLevel::Error, format!("File {:?} not found", &t1_path.as_os_str()), user.clone());
+        return Err(AppError::FileNotFound);
     }
-    let mapping = car_class_to_body_type::read_csv_and_map(&t1_path, &common_path).map_err(|e| AppError::InternalServerError(e.to_string()))?;
-    if tokio::fs::try_exists(&exact_path).await.unwrap_or(false) {
-        tokio::fs::remove_file(&exact_path).await?;
+    sync_class_body_mapping(&common_path, None, user).await?.ok_or(AppError::FileNotFound)
+}
+
+/// Regenerates the class/body mapping of a catalog root (common or a user catalog) from its own t1.csv
+/// and logs the class/body pairs that were added or removed. If the root has no t1.csv any more,
+/// its mapping is removed as well, so user catalogs fall back to the common one.
+/// `fallback_root` is the catalog whose mapping is served when this one has none; the logged diff is
+/// taken against it in that case.
+pub async fn sync_class_body_mapping(catalog_root: &Path, fallback_root: Option<&Path>, user: Option<String>) -> Result<Option<HashMap<String, Vec<String>>>, AppError> {
+    let catalog_root = catalog_root.to_path_buf();
+    let t1_path = catalog_root.join(T1);
+    let mapping_path = catalog_root.join(CLASS_TYPE_MAPPING_FILE);
+    let own_previous = car_class_to_body_type::read_mapping_yaml(&mapping_path);
+    let fallback = fallback_root.and_then(|root| car_class_to_body_type::read_mapping_yaml(root.join(CLASS_TYPE_MAPPING_FILE)));
+
+    if !tokio::fs::try_exists(&t1_path).await.unwrap_or(false) {
+        if tokio::fs::try_exists(&mapping_path).await.unwrap_or(false) {
+            fs::remove_file(&mapping_path).await?;
+            let diff = car_class_to_body_type::diff_mappings(&own_previous.unwrap_or_default(), &fallback.unwrap_or_default());
+            log_event(exlogging::LogLevel::Info, format!("Removed class body mapping {}, no t1.csv next to it, added: {:?}, removed: {:?}", mapping_path.to_string_lossy(), diff.added, diff.removed), user);
+        }
+        return Ok(None);
     }
-    log_event(exlogging::LogLevel::Debug, format!("Generating yaml file {}", exact_path.to_string_lossy()) , user.clone());
-    car_class_to_body_type::serialize_to_yaml(&mapping, exact_path).map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    let previous = own_previous.or(fallback);
+
+    let mapping = car_class_to_body_type::read_csv_and_map(&t1_path, &catalog_root).map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    log_event(exlogging::LogLevel::Debug, format!("Generating yaml file {}", mapping_path.to_string_lossy()) , user.clone());
+    car_class_to_body_type::serialize_to_yaml(&mapping, &mapping_path).map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    let diff = car_class_to_body_type::diff_mappings(&previous.unwrap_or_default(), &mapping);
+    if diff.is_empty() {
+        log_event(exlogging::LogLevel::Debug, format!("Class body mapping {} unchanged", mapping_path.to_string_lossy()), user);
+    } else {
+        log_event(exlogging::LogLevel::Info, format!("Class body mapping {} rebuilt, added: {:?}, removed: {:?}", mapping_path.to_string_lossy(), diff.added, diff.removed), user);
+    }
+    Ok(Some(mapping))
+}
+
+/// Keeps the organization's mapping in sync with its own t1.csv. The mapping is derived, it is not
+/// part of the catalog history, so reverting a t1.csv change and syncing again restores it as well.
+pub async fn sync_user_class_body_mapping(data_dir: &PathBuf, cache: &DataStorageCache, organization: &str) -> Result<Option<HashMap<String, Vec<String>>>, AppError> {
+    let catalog_path = organization_catalog_directory(data_dir, organization)?;
+    let common_path = crate::utils::common_directory(data_dir)?;
+    let mapping = sync_class_body_mapping(&catalog_path, Some(&common_path), Some(organization.to_string())).await?;
+    // The rebuilt mapping invalidates what the cache served from the old one
+    cache.invalidate(&catalog_path.join(CLASS_TYPE_MAPPING_FILE)).await;
     Ok(mapping)
 }
 
+/// Whether the mapping of a catalog root lags behind its t1.csv, e.g. after t1.csv was replaced on disk.
+pub async fn class_body_mapping_outdated(catalog_root: &Path) -> bool {
+    let modified = |p: PathBuf| async move { fs::metadata(p).await.and_then(|m| m.modified()).ok() };
+    match (modified(catalog_root.join(T1)).await, modified(catalog_root.join(CLASS_TYPE_MAPPING_FILE)).await) {
+        (Some(t1), Some(mapping)) => t1 > mapping,
+        (Some(_), None) | (None, Some(_)) => true,
+        (None, None) => false,
+    }
+}
+
+/// Paths in the admin editor are relative to the data directory, a t1.csv change may belong to any catalog root.
+async fn sync_mapping_if_t1(data_dir: &Path, path: &str, user: &str) -> Result<(), AppError> {
+    let full_path = data_dir.join(path);
+    if !full_path.ends_with(T1) {
+        return Ok(());
+    }
+    if let Some(catalog_root) = full_path.parent().and_then(Path::parent) {
+        let common_path = crate::utils::common_directory(&data_dir.to_path_buf())?;
+        let fallback = Some(common_path.as_path()).filter(|common| *common != catalog_root);
+        sync_class_body_mapping(catalog_root, fallback, Some(user.to_string())).await?;
+    }
+    Ok(())
+}
+
 pub async fn read_file(
     AuthenticatedUser(_user_email): AuthenticatedUser, // Get user email from the authenticated user
     State(app_state): State<Arc<AppState>>,
@@ -66,9 +128,10 @@ pub async fn delete_file(
 ) -> Result<impl IntoResponse, AppError> {
     exlogging::log_event(exlogging::LogLevel::Info, format!("Delete file admin request: {:?}", &path.to_string()), Some(user_email.as_str()));
     let user_path = &app_state.data_dir_path;
-    let end_path = user_path.join(path);
+    let end_path = user_path.join(&path);
     app_state.cache.invalidate(&end_path).await;
     fs::remove_file(end_path).await?;
+    sync_mapping_if_t1(user_path, &path, &user_email).await?;
     Ok("File deleted")
 }
 
@@ -96,8 +159,10 @@ pub async fn upload_file(
         .bytes()
         .await
         .map_err(|_| AppError::InternalServerError("Failed to read file data".to_string()))?;
+    check_uploaded_template(&path, &data)?;
     let end_path = user_path.join(PathBuf::from(&path));
     app_state.cache.invalidate(&end_path).await;
     tokio::fs::write(end_path, data).await?;
+    sync_mapping_if_t1(user_path, &path, &user_email).await?;
     Ok(Json("File uploaded and validated successfully"))
 }
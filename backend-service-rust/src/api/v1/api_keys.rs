@@ -0,0 +1,88 @@
+use crate::{
+    auth::{api_keys::create_api_key as issue_api_key, two_factor::verify_code},
+    db::api_keys::{get_api_key, list_user_api_keys, remove_api_key},
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    middleware::{AuthenticatedUser, Impersonator},
+    models::api_keys::{ApiKeySummary, CreateApiKeyRequest, CreatedApiKey},
+    state::AppState,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use std::sync::Arc;
+
+/// API keys of the user, oldest first. The keys themselves are never shown again.
+pub async fn list_api_keys(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut api_keys = list_user_api_keys(&app_state.db.api_keys_tree, &user_email)?;
+    api_keys.sort_by_key(|k| k.created_at);
+    let api_keys: Vec<ApiKeySummary> = api_keys.into_iter().map(ApiKeySummary::from).collect();
+    Ok(Json(api_keys))
+}
+
+/// Creates a key after checking the password again, and the second factor
+/// when it is enabled. Refused while impersonating, keys would outlive it.
+pub async fn create_api_key(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    Impersonator(impersonator): Impersonator,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if impersonator.is_some() {
+        return Err(AppError::Forbidden);
+    }
+    let user = app_state
+        .db
+        .find_user_by_email(&user_email)?
+        .ok_or(AppError::UserNotFound)?;
+    if !app_state
+        .auth
+        .verify_password(&req.password, &user.password_hash)?
+    {
+        return Err(AppError::InvalidCredentials);
+    }
+    if user.two_factor.as_ref().is_some_and(|t| t.enabled) {
+        let code = req.code.as_deref().ok_or(AppError::InvalidCredentials)?;
+        if !verify_code(&app_state, &user, code)? {
+            return Err(AppError::InvalidCredentials);
+        }
+    }
+    let (key, api_key) = issue_api_key(&app_state, &user_email, &req.name, req.scopes)?;
+    log_event(
+        LogLevel::Info,
+        format!(
+            "API key {} created with scopes {:?}",
+            api_key.id, api_key.scopes
+        ),
+        Some(user_email.as_str()),
+    );
+    Ok(Json(CreatedApiKey {
+        key,
+        api_key: api_key.into(),
+    }))
+}
+
+pub async fn revoke_api_key(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    match get_api_key(&app_state.db.api_keys_tree, &id)? {
+        Some(api_key) if api_key.user_email == user_email => {
+            remove_api_key(&app_state.db.api_keys_tree, &id)?;
+            log_event(
+                LogLevel::Info,
+                format!("API key {} revoked", id),
+                Some(user_email.as_str()),
+            );
+            Ok(StatusCode::OK)
+        }
+        _ => Err(AppError::NotFound),
+    }
+}
@@ -1,14 +1,15 @@
 use crate::{
-    auth::admin_check::is_admin_async,
+    auth::roles::has_permission,
     db::attachment::{
         handle_attachment, list_all_attachments_for_all_users, list_all_attachments_for_user, try_get_by_id_checked_or_public,
     },
     errors::AppError,
-    middleware::AuthenticatedUser,
+    middleware::{AuthenticatedUser, CurrentOrganization},
+    models::roles::Permission,
     state::AppState,
     utils::{
         get_file_bytes_no_cache, random::generate_random_id,
-        user_personal_directory_from_email, ATTACHMENTS,
+        organization_directory, ATTACHMENTS,
     }, // Import the new CompanyInfo struct
 };
 use axum::{
@@ -42,13 +43,14 @@ fn rename_file(original: &str, id: &str) -> String {
 
 pub async fn get_att_metadata(
     AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user = if is_admin_async(&user_email, &app_state.admin_file_path).await? {
+    let user = if has_permission(&app_state.db, &user_email, Permission::ViewAllAttachments)? {
         None
     } else {
-        Some(user_email.clone())
+        Some(organization)
     };
     let data = try_get_by_id_checked_or_public(&app_state.db.attachments_tree, &id, user)?;
     if data.is_none() {
@@ -70,38 +72,39 @@ pub async fn admin_all_attachments(
 }
 
 pub async fn my_attachments(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     Ok(Json(list_all_attachments_for_user(
         &app_state.db.attachments_tree,
-        &user_email,
+        &organization,
     )?))
 }
 
 pub async fn get_att_file(
     AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(id): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    let support_access = is_admin_async(&user_email, &app_state.admin_file_path).await?;
+    let support_access = has_permission(&app_state.db, &user_email, Permission::ViewAllAttachments)?;
     let user = if support_access {
         None
     } else {
-        Some(user_email.clone())
+        Some(organization.clone())
     };
     let data = try_get_by_id_checked_or_public(&app_state.db.attachments_tree, &id, user)?;
     match data {
         Some(handle) => {
             let user_path = if support_access {
-                crate::utils::user_personal_directory_from_email(
+                crate::utils::organization_directory(
                     &app_state.data_dir_path,
-                    &handle.owner_user_email,
+                    &handle.owner,
                 )?
             } else {
-                crate::utils::user_personal_directory_from_email(
+                crate::utils::organization_directory(
                     &app_state.data_dir_path,
-                    &user_email,
+                    &organization,
                 )?
             };
             let file: Vec<u8> = get_file_bytes_no_cache(&handle.file_path, &user_path).await?;
@@ -133,12 +136,12 @@ pub async fn get_att_file(
 
 // Handler for uploading user attachments
 pub async fn attach(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
     // Expecting a single file field
-    let user_att_path = user_personal_directory_from_email(&app_state.data_dir_path, &user_email)?
+    let user_att_path = organization_directory(&app_state.data_dir_path, &organization)?
         .join(ATTACHMENTS);
 
     let field = multipart
@@ -177,7 +180,7 @@ pub async fn attach(
     let mut handle = handle_attachment(
         &written_file_path,
         &filename,
-        &user_email,
+        &organization,
         &app_state.db,
         Some(size),
     )?;
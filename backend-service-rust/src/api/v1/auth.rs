@@ -1,29 +1,54 @@
 use crate::{
-    auth::invite::process_invite,
+    auth::{
+        invite::process_invite,
+        organizations::assign_personal_organization,
+        passwords::{
+            check_password_strength, request_password_reset as send_password_reset, reset_password,
+            set_password,
+        },
+        roles::apply_pending_roles,
+        sessions::{
+            refresh_session, revoke_session, revoke_user_sessions, start_impersonation,
+            start_session,
+        },
+        throttle,
+        two_factor::{
+            restore_login_challenge, start_login_challenge, take_login_challenge_token,
+            verify_code,
+        },
+    },
+    db::sessions::{get_session, list_user_sessions},
     errors::AppError,
-    middleware::AuthenticatedUser,
-    models::{ImpersonateRequest, LoginRequest, LoginResponse, RegisterRequest, User},
+    exlogging::{log_event, LogLevel},
+    middleware::{AuthenticatedUser, CurrentSession, Impersonator},
+    models::{
+        sessions::SessionSummary,
+        two_factor::{TwoFactorChallengeResponse, TwoFactorLoginRequest},
+        ChangePasswordRequest, ImpersonateRequest, ImpersonationStatus, LoginRequest,
+        PasswordResetConfirmRequest, PasswordResetRequest, RefreshRequest, RegisterRequest, User,
+    },
     state::AppState,
 };
 use axum::{
-    extract::{Json, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Json, Path, State},
+    http::{header::USER_AGENT, HeaderMap, StatusCode},
     response::IntoResponse,
 };
-use std::sync::Arc;
-use uuid::Uuid;
+use std::{net::SocketAddr, sync::Arc};
 
 pub async fn register(
     State(app_state): State<Arc<AppState>>,
     Json(req): Json<RegisterRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    check_password_strength(&req.password)?;
     let hashed_password = app_state.auth.hash_password(&req.password)?;
 
-    let user = User {
-        id: Uuid::new_v4(),
-        email: req.email.clone(),
-        password_hash: hashed_password,
-    };
+    if app_state.db.find_user_by_email(&req.email)?.is_some() {
+        return Err(AppError::UserExists);
+    }
+    let mut user = User::new(req.email.clone(), hashed_password);
+    apply_pending_roles(&app_state.db, &mut user)?;
+    assign_personal_organization(&app_state.db, &mut user)?;
 
     app_state.db.insert_user(&user)?;
 
@@ -40,56 +65,302 @@ pub async fn register(
                 &user.id, &user.email, &req.invite
             )
         );
-        process_invite(&req.email, &req.invite, &app_state).await?;
+        let organization = user.organization.as_deref().unwrap_or_default();
+        process_invite(&req.email, organization, &req.invite, &app_state).await?;
     }
 
     Ok(StatusCode::OK)
 }
 
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.chars().take(256).collect())
+}
+
 pub async fn login(
     State(app_state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user = app_state
-        .db
-        .find_user_by_email(&req.email)?
-        .ok_or(AppError::InvalidCredentials)?;
+    let ip = app_state.login_throttle.client_ip(&headers, peer);
+    // Locked out callers do not get to run bcrypt at all
+    app_state.login_throttle.check(&req.email, &ip)?;
 
-    if !app_state
-        .auth
-        .verify_password(&req.password, &user.password_hash)?
-    {
+    let user = app_state.db.find_user_by_email(&req.email)?;
+    let verified = match &user {
+        Some(user) => app_state
+            .auth
+            .verify_password(&req.password, &user.password_hash)?,
+        None => false,
+    };
+    let Some(user) = user.filter(|_| verified) else {
+        app_state.login_throttle.record_failure(&req.email, &ip)?;
         return Err(AppError::InvalidCredentials);
+    };
+    if user.has_two_factor() {
+        // Failures of the account are forgotten only once the code is right too
+        app_state.login_throttle.release(&req.email, &ip)?;
+        let (challenge, expires_in) =
+            start_login_challenge(&app_state, &user.email, user_agent(&headers))?;
+        return Ok(Json(TwoFactorChallengeResponse {
+            two_factor_required: true,
+            challenge,
+            expires_in,
+        })
+        .into_response());
     }
+    app_state.login_throttle.record_success(&user.email, &ip)?;
 
-    let token = app_state.auth.create_token(&user.email)?;
+    let tokens = start_session(&app_state, &user.email, user_agent(&headers), false)?;
 
     log::info!(
         "Auth event -> {}",
         format!("User logged in: {}", &user.email)
     );
 
-    Ok(Json(LoginResponse { token }))
+    Ok(Json(tokens).into_response())
 }
 
+/// Second step of the login for users with two-factor authentication: the
+/// challenge from `/login` and a TOTP or recovery code.
+pub async fn login_two_factor(
+    State(app_state): State<Arc<AppState>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(req): Json<TwoFactorLoginRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let ip = app_state.login_throttle.client_ip(&headers, peer);
+    let challenge = take_login_challenge_token(&app_state, &req.challenge)?;
+    app_state.login_throttle.check(&challenge.user_email, &ip)?;
+
+    let user = app_state
+        .db
+        .find_user_by_email(&challenge.user_email)?
+        .ok_or(AppError::Unauthorized)?;
+    if !verify_code(&app_state, &user, &req.code)? {
+        app_state.login_throttle.record_failure(&user.email, &ip)?;
+        restore_login_challenge(&app_state, &req.challenge, &challenge)?;
+        return Err(AppError::InvalidCredentials);
+    }
+    app_state.login_throttle.record_success(&user.email, &ip)?;
+
+    let tokens = start_session(&app_state, &user.email, challenge.user_agent, true)?;
+
+    log::info!(
+        "Auth event -> User logged in with second factor: {}",
+        &user.email
+    );
+
+    Ok(Json(tokens))
+}
+
+/// Signs the admin in as another user for at most an hour. The tokens carry
+/// the admin in the `act` claim and every request made with them is logged.
 pub async fn impersonate(
-    AuthenticatedUser(_admin_email): AuthenticatedUser, // Ensure admin is authenticated
+    AuthenticatedUser(admin_email): AuthenticatedUser, // Ensure admin is authenticated
+    Impersonator(impersonator): Impersonator,
     State(app_state): State<Arc<AppState>>,
+    headers: HeaderMap,
     Json(req): Json<ImpersonateRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    // No impersonation chains, the act claim names a single admin
+    if impersonator.is_some() {
+        return Err(AppError::Forbidden);
+    }
     let user = app_state
         .db
         .find_user_by_email(&req.email)?
         .ok_or(AppError::InvalidCredentials)?;
 
-    let token = app_state.auth.create_token(&user.email)?;
+    let tokens = start_impersonation(&app_state, &user.email, &admin_email, user_agent(&headers))?;
+
+    log_event(
+        LogLevel::Warn,
+        format!("Impersonation of {} started", &user.email),
+        Some(admin_email.as_str()),
+    );
 
+    Ok(Json(tokens))
+}
+
+/// Whether the token making the request impersonates the user, for the
+/// frontend to show a banner.
+pub async fn get_impersonation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    Impersonator(impersonator): Impersonator,
+    CurrentSession(session_id): CurrentSession,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let expires_at = match impersonator {
+        Some(_) => get_session(&app_state.db.sessions_tree, &session_id)?.map(|s| s.expires_at),
+        None => None,
+    };
+    Ok(Json(ImpersonationStatus {
+        impersonating: impersonator.is_some(),
+        user: user_email,
+        admin: impersonator,
+        expires_at,
+    }))
+}
+
+/// Ends the impersonation session making the request.
+pub async fn end_impersonation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    Impersonator(impersonator): Impersonator,
+    CurrentSession(session_id): CurrentSession,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let Some(admin_email) = impersonator else {
+        return Err(AppError::Conflict(
+            "The session is not an impersonation".to_string(),
+        ));
+    };
+    revoke_session(&app_state, &session_id)?;
+    log_event(
+        LogLevel::Info,
+        format!("Impersonation of {} ended", user_email),
+        Some(admin_email.as_str()),
+    );
+    Ok(StatusCode::OK)
+}
+
+/// Exchanges a refresh token for a new access and refresh token.
+pub async fn refresh(
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<RefreshRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(refresh_session(&app_state, &req.refresh_token)?))
+}
+
+/// Sets a new password, the current one is required. Other sessions of the
+/// user end, the one making the request stays signed in.
+pub async fn change_password(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentSession(session_id): CurrentSession,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = app_state
+        .db
+        .find_user_by_email(&user_email)?
+        .ok_or(AppError::UserNotFound)?;
+    if !app_state
+        .auth
+        .verify_password(&req.current_password, &user.password_hash)?
+    {
+        return Err(AppError::InvalidCredentials);
+    }
+    set_password(&app_state, &user_email, &req.new_password, Some(&session_id)).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Mails a reset token. Answers the same whether the account exists or not.
+pub async fn request_password_reset(
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    send_password_reset(&app_state, req.email.trim()).await?;
+    Ok(StatusCode::OK)
+}
+
+pub async fn confirm_password_reset(
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    reset_password(&app_state, &req.token, &req.new_password).await?;
+    Ok(StatusCode::OK)
+}
+
+/// Ends the session of the token making the request.
+pub async fn logout(
+    CurrentSession(session_id): CurrentSession,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    revoke_session(&app_state, &session_id)?;
+    Ok(StatusCode::OK)
+}
+
+/// Ends all sessions of the user, on all devices.
+pub async fn logout_all(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let revoked = revoke_user_sessions(&app_state, &user_email)?;
+    Ok(Json(serde_json::json!({ "revoked": revoked })))
+}
+
+/// Active sessions of the user, most recently used first.
+pub async fn list_sessions(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentSession(session_id): CurrentSession,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut sessions = list_user_sessions(&app_state.db.sessions_tree, &user_email)?;
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.refreshed_at));
+    let sessions: Vec<_> = sessions
+        .into_iter()
+        .map(|s| SessionSummary::new(s, &session_id))
+        .collect();
+    Ok(Json(sessions))
+}
+
+pub async fn delete_session(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    match get_session(&app_state.db.sessions_tree, &id)? {
+        Some(session) if session.user_email == user_email => {
+            revoke_session(&app_state, &id)?;
+            Ok(StatusCode::OK)
+        }
+        _ => Err(AppError::NotFound),
+    }
+}
+
+/// Ends all sessions of a user, e.g. after a stolen token was reported.
+pub async fn admin_revoke_sessions(
+    AuthenticatedUser(admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(user_email): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let revoked = revoke_user_sessions(&app_state, &user_email)?;
     log::info!(
-        "Impersonation event -> {}",
-        format!("User impersonated in: {}", &user.email)
+        "Auth event -> Sessions of {} revoked by {}",
+        &user_email, &admin_email
     );
+    Ok(Json(serde_json::json!({ "revoked": revoked })))
+}
+
+/// Accounts and addresses currently locked out of login.
+pub async fn admin_list_lockouts(
+    AuthenticatedUser(_admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(app_state.login_throttle.lockouts()))
+}
 
-    Ok(Json(LoginResponse { token }))
+/// Lifts the lockout of an account or an address (`kind` is `account` or `ip`).
+pub async fn admin_unlock(
+    AuthenticatedUser(admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path((kind, id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    if kind != throttle::ACCOUNT && kind != throttle::IP {
+        return Err(AppError::InvalidData(format!("Unknown lockout kind {:?}", kind)));
+    }
+    if !app_state.login_throttle.unlock(&kind, &id)? {
+        return Err(AppError::NotFound);
+    }
+    log_event(
+        LogLevel::Info,
+        format!("Login of {} {} unlocked", kind, id),
+        Some(admin_email.as_str()),
+    );
+    Ok(StatusCode::OK)
 }
 
 pub async fn health() -> impl IntoResponse {
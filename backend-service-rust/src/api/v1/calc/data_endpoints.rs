@@ -1,14 +1,17 @@
 use crate::{
+    api::v1::admin_editor_endpoints::{class_body_mapping_outdated, sync_user_class_body_mapping},
     calc::{
+        car_catalog::{CatalogMatch, CARS},
         car_class_to_body_type::CLASS_TYPE_MAPPING_FILE,
         cars::body_type_into_t1_entry,
         constants::CAR_PART_DETAIL_UKR_FIELD,
         seasons::get_current_season_info,
         t2,
         table_processing::{lookup, lookup_no_type_class},
+        vin::{self, DecodedVin},
     },
     errors::AppError,
-    middleware::AuthenticatedUser,
+    middleware::CurrentOrganization,
     state::AppState,
     utils::{
         self, list_catalog_files_user_common, parse_csv_file_async_safe,
@@ -20,36 +23,126 @@ use axum::{
     response::IntoResponse,
     Json,
 };
+use chrono::Datelike;
 use indexmap::IndexMap;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::{collections::HashSet, path::PathBuf, sync::Arc};
 
-const CARS: &str = "cars";
 const GLOBAL: &str = "global";
 pub const T1: &str = "tables/t1.csv";
 pub const REPAIR_TYPES_TABLE: &str = "tables/repair_types.csv";
 const SEASONS_YAML: &str = "seasons.yaml";
 
 pub async fn list_car_makes(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let data = list_catalog_files_user_common(&app_state.data_dir_path, &user_email, &CARS)
+    let data = list_catalog_files_user_common(&app_state.data_dir_path, &organization, &CARS)
         .await
         .map_err(AppError::IoError)?;
     let car_makes: Vec<String> = data.iter().map(|s| s.replace(".yaml", "")).collect();
     Ok(Json(car_makes))
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CarCatalogSearchQuery {
+    pub q: String,
+    pub year: Option<u16>,
+    pub limit: Option<usize>,
+}
+
+pub async fn search_car_catalog(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<CarCatalogSearchQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let catalog = app_state.car_catalog.get(&organization).await?;
+    Ok(Json(catalog.search(&q.q, q.year, q.limit.unwrap_or(20))))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CarGenerationQuery {
+    pub make: String,
+    pub model: String,
+    pub year: u16,
+}
+
+pub async fn get_car_generation(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<CarGenerationQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let catalog = app_state.car_catalog.get(&organization).await?;
+    let entry = catalog.get(&q.make, &q.model).ok_or(AppError::NotFound)?;
+    let generation = entry.data.generation_for_year(q.year).ok_or_else(|| {
+        AppError::InvalidData(format!(
+            "{} {} was not produced in {}",
+            entry.make, entry.model, q.year
+        ))
+    })?;
+    Ok(Json(generation))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecodeVinQuery {
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VinPrefill {
+    #[serde(flatten)]
+    pub decoded: DecodedVin,
+    // Known only when the model is given or the make has a single model for that year
+    pub model: Option<String>,
+    pub euro_class: Option<String>,
+    pub euro_body_types: Vec<String>,
+    pub models: Vec<CatalogMatch>,
+}
+
+pub async fn decode_vin(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path(vin_string): axum::extract::Path<String>,
+    Query(q): Query<DecodeVinQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let decoded = vin::decode_vin(&vin_string, chrono::Utc::now().year() as u16)?;
+    let catalog = app_state.car_catalog.get(&organization).await?;
+    let mut models = match &decoded.make {
+        Some(make) => catalog.models_of(make, decoded.model_year),
+        None => vec![],
+    };
+    if let Some(model) = q.model.as_deref() {
+        let model = model.to_lowercase();
+        models.retain(|m| m.model.to_lowercase() == model);
+    }
+    let chosen = match models.as_slice() {
+        [single] => Some(single.clone()),
+        _ => None,
+    };
+    Ok(Json(VinPrefill {
+        decoded,
+        model: chosen.as_ref().map(|m| m.model.clone()),
+        euro_class: chosen.as_ref().map(|m| m.data.euro_class.clone()),
+        euro_body_types: chosen.map(|m| m.data.euro_body_types).unwrap_or_default(),
+        models,
+    }))
+}
+
 pub async fn list_class_body_types(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
+    // Catch t1.csv changes that bypassed the editor endpoints
+    let user_catalog = utils::organization_catalog_directory(&app_state.data_dir_path, &organization)?;
+    if class_body_mapping_outdated(&user_catalog).await {
+        sync_user_class_body_mapping(&app_state.data_dir_path, &app_state.cache, &organization)
+            .await?;
+    }
     // Read the file content
     let file_path = PathBuf::from(&CLASS_TYPE_MAPPING_FILE);
     let path_in_userspace =
-        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &user_email, &file_path)
+        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &organization, &file_path)
             .await
             .map_err(AppError::IoError)?;
     let string = tokio::fs::read_to_string(path_in_userspace)
@@ -59,13 +152,13 @@ pub async fn list_class_body_types(
 }
 
 pub async fn get_cars_by(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(maker): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     let file_path = PathBuf::from(&CARS).join(sanitize_alphanumeric_and_dashes(&maker) + ".yaml");
     let cars_path =
-        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &user_email, &file_path)
+        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &organization, &file_path)
             .await
             .map_err(AppError::IoError)?;
     let cars_data = crate::calc::cars::parse_car_yaml(&cars_path)
@@ -74,13 +167,13 @@ pub async fn get_cars_by(
 }
 
 pub async fn get_car_parts_by_type_class(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path((class, body_type)): axum::extract::Path<(String, String)>,
 ) -> Result<impl IntoResponse, AppError> {
     let file_path = PathBuf::from(&T1);
     let cars_path =
-        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &user_email, &file_path)
+        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &organization, &file_path)
             .await
             .map_err(AppError::IoError)?;
     let cars_data = crate::calc::cars::parse_csv_t1(&cars_path)
@@ -114,12 +207,12 @@ pub struct LookupPartNoTypeClassQuery {
 }
 
 pub async fn list_all_repair_types(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     let table_file = crate::utils::get_file_path_user_common(
         &app_state.data_dir_path,
-        &user_email,
+        &organization,
         &REPAIR_TYPES_TABLE,
     )
     .await
@@ -139,7 +232,7 @@ pub async fn list_all_repair_types(
 }
 
 pub async fn lookup_all_tables(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Query(q): Query<LookupPartQuery>,
 ) -> Result<impl IntoResponse, AppError> {
@@ -148,7 +241,7 @@ pub async fn lookup_all_tables(
         &q.car_class,
         &q.part,
         &app_state.data_dir_path,
-        &user_email,
+        &organization,
         &app_state.cache,
     )
     .await?;
@@ -156,14 +249,14 @@ pub async fn lookup_all_tables(
 }
 
 pub async fn lookup_all_tables_all_types(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Query(q): Query<LookupPartNoTypeClassQuery>,
 ) -> Result<impl IntoResponse, AppError> {
     let parts_lines = lookup_no_type_class(
         &q.part,
         &app_state.data_dir_path,
-        &user_email,
+        &organization,
         &app_state.cache,
     )
     .await?;
@@ -181,12 +274,12 @@ fn get_unique_values_iter(table: &Vec<IndexMap<String, String>>, key: &str) -> V
 }
 
 pub async fn list_all_parts(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     let file_path = PathBuf::from(&T1);
     let t1 =
-        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &user_email, &file_path)
+        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &organization, &file_path)
             .await
             .map_err(AppError::IoError)?;
     let parsed = parse_csv_file_async_safe(&app_state.data_dir_path, &t1, &app_state.cache).await?;
@@ -197,14 +290,14 @@ pub async fn list_all_parts(
 }
 
 pub async fn get_global_file(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     // Read the file content
     let file_path = PathBuf::from(&GLOBAL).join(sanitize_alphanumeric_and_dashes_and_dots(&path));
     let path_in_userspace =
-        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &user_email, &file_path)
+        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &organization, &file_path)
             .await
             .map_err(AppError::IoError)?;
     let string = tokio::fs::read_to_string(path_in_userspace)
@@ -214,12 +307,12 @@ pub async fn get_global_file(
 }
 
 pub async fn get_season(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     let file_path = PathBuf::from(&GLOBAL).join(SEASONS_YAML);
     let path_in_userspace =
-        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &user_email, &file_path)
+        crate::utils::get_file_path_user_common(&app_state.data_dir_path, &organization, &file_path)
             .await
             .map_err(AppError::IoError)?;
     Ok(Json(get_current_season_info(&path_in_userspace).map_err(
@@ -228,14 +321,14 @@ pub async fn get_season(
 }
 
 pub async fn get_t2_parts_by_type_class(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path((_class, body_type)): axum::extract::Path<(String, String)>,
 ) -> Result<impl IntoResponse, AppError> {
     let real_body = body_type_into_t1_entry(&body_type);
     let data = t2::t2_rows_by_body_type(
         &real_body,
-        &user_email,
+        &organization,
         &app_state.data_dir_path,
         &app_state.cache,
     )
@@ -245,10 +338,10 @@ pub async fn get_t2_parts_by_type_class(
 }
 
 pub async fn get_t2_parts_all(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let data = t2::t2_rows_all(&user_email, &app_state.data_dir_path, &app_state.cache).await?;
+    let data = t2::t2_rows_all(&organization, &app_state.data_dir_path, &app_state.cache).await?;
 
     let (parsed, errors) = t2::parse_all_nofail(data);
 
@@ -0,0 +1,175 @@
+use crate::api::v1::calc::persistence_endpoints::{read_calculation, store_calculation};
+use crate::calc::calculation_history::CALCULATIONS;
+use crate::calc::calculation_index::indexed_calculation;
+use crate::calc::estimate_lifecycle::{allowed_transitions, check_transition, is_editable};
+use crate::db::estimates::{insert_transition, list_estimate_transitions, list_user_transitions};
+use crate::db::notifications::insert_notification;
+use crate::exlogging::{log_event, LogLevel};
+use crate::middleware::{AuthenticatedUser, CurrentOrganization};
+use crate::models::estimates::{EstimateStatus, StatusChangeRequest, StatusTransition};
+use crate::models::notifications::Notification;
+use crate::utils::organization_directory;
+use crate::{errors::AppError, state::AppState};
+use axum::extract::Query;
+use axum::{extract::State, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct EstimateQuery {
+    filename: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransitionsQuery {
+    /// Limits the history to one estimate.
+    filename: Option<String>,
+    /// Only transitions into this status.
+    status: Option<EstimateStatus>,
+    since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct EstimateStatusResponse {
+    file_name: String,
+    status: EstimateStatus,
+    editable: bool,
+    allowed_transitions: &'static [EstimateStatus],
+    history: Vec<StatusTransition>,
+}
+
+fn status_title(status: EstimateStatus) -> &'static str {
+    match status {
+        EstimateStatus::Draft => "Estimate reopened",
+        EstimateStatus::Sent => "Estimate sent to customer",
+        EstimateStatus::Approved => "Estimate approved",
+        EstimateStatus::InRepair => "Repair started",
+        EstimateStatus::Completed => "Repair completed",
+        EstimateStatus::Invoiced => "Estimate invoiced",
+        EstimateStatus::Cancelled => "Estimate cancelled",
+    }
+}
+
+pub async fn get_estimate_status(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<EstimateQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let organization_dir = organization_directory(&app_state.data_dir_path, &organization)?;
+    let entry = indexed_calculation(
+        &app_state.db,
+        &organization,
+        &organization_dir.join(CALCULATIONS),
+        &q.filename,
+    )
+    .await?;
+    let history = list_estimate_transitions(
+        &app_state.db.estimate_transitions_tree,
+        &organization,
+        &entry.file_name,
+    )?;
+
+    Ok(Json(EstimateStatusResponse {
+        file_name: entry.file_name,
+        status: entry.status,
+        editable: is_editable(entry.status),
+        allowed_transitions: allowed_transitions(entry.status),
+        history,
+    }))
+}
+
+/// Moves an estimate to another status, recording the transition in the document
+/// as a new revision and notifying the user.
+pub async fn change_estimate_status(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<StatusChangeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let organization_dir = organization_directory(&app_state.data_dir_path, &organization)?;
+    let entry = indexed_calculation(
+        &app_state.db,
+        &organization,
+        &organization_dir.join(CALCULATIONS),
+        &req.filename,
+    )
+    .await?;
+    check_transition(entry.status, req.status)?;
+
+    let transition = StatusTransition {
+        file_name: entry.file_name.clone(),
+        from: entry.status,
+        to: req.status,
+        actor: user_email.clone(),
+        at: Utc::now(),
+        comment: req.comment.filter(|c| !c.trim().is_empty()),
+    };
+    let mut data = read_calculation(&app_state, &organization, &entry.file_name).await?;
+    if data.status_history.is_empty() {
+        // Transitions made before the status was kept in the document
+        data.status_history = list_estimate_transitions(
+            &app_state.db.estimate_transitions_tree,
+            &organization,
+            &entry.file_name,
+        )?;
+    }
+    data.status = req.status;
+    data.status_history.push(transition.clone());
+    store_calculation(&app_state, &organization, &user_email, &entry.file_name, &data).await?;
+    insert_transition(
+        &app_state.db.estimate_transitions_tree,
+        &organization,
+        &transition,
+    )?;
+    log_event(
+        LogLevel::Info,
+        format!(
+            "Estimate {} moved from {} to {}",
+            transition.file_name, transition.from, transition.to
+        ),
+        Some(&user_email),
+    );
+
+    // The actor knows already, the rest of the shop is told
+    let mut body = format!(
+        "{}: {} → {} ({})",
+        entry.title, transition.from, transition.to, transition.actor
+    );
+    if let Some(comment) = &transition.comment {
+        body.push_str(&format!("\n{}", comment));
+    }
+    let members = app_state.db.list_organization_members(&organization)?;
+    for member in members.into_iter().filter(|m| m.email != user_email) {
+        let notification = Notification {
+            id: crate::utils::random::generate_random_id(6),
+            email: member.email,
+            title: status_title(transition.to).to_string(),
+            body: body.clone(),
+            read: false,
+            timestamp: transition.at,
+        };
+        insert_notification(&app_state.db.notifications_tree, &notification)?;
+    }
+
+    Ok(Json(transition))
+}
+
+/// Status transitions of the organization's estimates, oldest first.
+pub async fn list_estimate_transitions_handler(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<TransitionsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let tree = &app_state.db.estimate_transitions_tree;
+    let transitions = match &q.filename {
+        Some(file_name) => list_estimate_transitions(tree, &organization, file_name)?,
+        None => list_user_transitions(tree, &organization)?,
+    };
+    let transitions: Vec<StatusTransition> = transitions
+        .into_iter()
+        .filter(|t| q.status.is_none_or(|s| t.to == s))
+        .filter(|t| q.since.is_none_or(|since| t.at >= since))
+        .collect();
+    Ok(Json(transitions))
+}
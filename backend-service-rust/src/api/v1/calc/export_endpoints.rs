@@ -0,0 +1,233 @@
+use crate::api::v1::user::find_or_create_company_info;
+use crate::calc::calculation_export::{
+    csv_chunk, xlsx_workbook, Cell, ExportFormat, ExportLayout, ExportedCalculation, TaxSettings,
+};
+use crate::calc::calculation_history::CALCULATIONS;
+use crate::db::calculations::list_user_entries;
+use crate::calc::calculation_schema::upgrade_calculation_bytes;
+use crate::db::customers::list_user_customers;
+use crate::exlogging::{log_event, LogLevel};
+use crate::middleware::{AuthenticatedUser, CurrentOrganization};
+use crate::models::calculations::CalculationIndexEntry;
+use crate::models::customers::Customer;
+use crate::models::estimates::EstimateStatus;
+use crate::utils::organization_directory;
+use crate::{errors::AppError, state::AppState};
+use axum::body::Body;
+use axum::extract::Query;
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::{extract::State, response::IntoResponse};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// Lets Excel detect UTF-8 in CSV files with Cyrillic part names
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(default)]
+    format: ExportFormat,
+    #[serde(default)]
+    layout: ExportLayout,
+    /// Comma separated file names, all calculations if omitted.
+    files: Option<String>,
+    /// Range of the last modification time.
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    status: Option<EstimateStatus>,
+    #[serde(default)]
+    tax_rate: f64,
+    #[serde(default)]
+    tax_included: bool,
+}
+
+impl ExportQuery {
+    fn select(
+        &self,
+        entries: Vec<CalculationIndexEntry>,
+    ) -> Result<Vec<CalculationIndexEntry>, AppError> {
+        let files: Option<Vec<&str>> = self.files.as_deref().map(|files| {
+            files
+                .split(',')
+                .map(str::trim)
+                .filter(|f| !f.is_empty())
+                .collect()
+        });
+        if let Some(files) = &files {
+            let missing: Vec<&str> = files
+                .iter()
+                .filter(|f| !entries.iter().any(|e| e.file_name == **f && e.deleted_at.is_none()))
+                .copied()
+                .collect();
+            if !missing.is_empty() {
+                return Err(AppError::InvalidData(format!(
+                    "Unknown calculations: {}",
+                    missing.join(", ")
+                )));
+            }
+        }
+
+        let mut selected: Vec<CalculationIndexEntry> = entries
+            .into_iter()
+            .filter(|e| e.deleted_at.is_none())
+            .filter(|e| files.as_ref().is_none_or(|f| f.contains(&e.file_name.as_str())))
+            .filter(|e| self.from.is_none_or(|from| e.updated_at >= from))
+            .filter(|e| self.to.is_none_or(|to| e.updated_at <= to))
+            .filter(|e| self.status.is_none_or(|s| e.status == s))
+            .collect();
+        selected.sort_by_key(|e| e.updated_at);
+        Ok(selected)
+    }
+
+    fn tax(&self) -> TaxSettings {
+        TaxSettings {
+            tax_rate: self.tax_rate,
+            tax_included: self.tax_included,
+        }
+    }
+}
+
+/// Everything the export rows of a single calculation need, shared by the stream.
+struct ExportContext {
+    calculations_dir: PathBuf,
+    customers: HashMap<String, Customer>,
+    currency: String,
+    layout: ExportLayout,
+    tax: TaxSettings,
+    user_email: String,
+}
+
+impl ExportContext {
+    async fn rows(&self, entry: &CalculationIndexEntry) -> Result<Vec<Vec<Cell>>, AppError> {
+        let content = tokio::fs::read(self.calculations_dir.join(&entry.file_name)).await?;
+        let data = upgrade_calculation_bytes(&content)?.data;
+        let export = ExportedCalculation {
+            entry,
+            data: &data,
+            customer: data.customer_id.as_ref().and_then(|id| self.customers.get(id)),
+            currency: &self.currency,
+        };
+        Ok(export.rows(self.layout, self.tax))
+    }
+
+    /// Rows of a calculation as CSV; a file that cannot be read is logged and skipped,
+    /// since the response is already being sent.
+    async fn csv_rows(&self, entry: CalculationIndexEntry) -> Vec<u8> {
+        match self.rows(&entry).await.and_then(|rows| csv_chunk(&rows)) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                log_event(
+                    LogLevel::Warn,
+                    format!("Calculation {} not exported: {}", entry.file_name, e),
+                    Some(&self.user_email),
+                );
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Exports the selected calculations as CSV (streamed) or XLSX line items, or in the
+/// accounting layout with tax columns.
+pub async fn export_calculations(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<ExportQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    if !(0.0..=100.0).contains(&q.tax_rate) {
+        return Err(AppError::InvalidData(format!(
+            "Tax rate must be a percentage, got {}",
+            q.tax_rate
+        )));
+    }
+    let organization_dir = organization_directory(&app_state.data_dir_path, &organization)?;
+    let calculations_dir = organization_dir.join(CALCULATIONS);
+    let entries = list_user_entries(&app_state.db.calculations_tree, &organization)?;
+    let selected = q.select(entries)?;
+    log_event(
+        LogLevel::Info,
+        format!(
+            "Export of {} calculations as {:?} {:?}",
+            selected.len(),
+            q.layout,
+            q.format
+        ),
+        Some(&user_email),
+    );
+
+    let context = Arc::new(ExportContext {
+        calculations_dir,
+        customers: list_user_customers(&app_state.db.customers_tree, &organization)?
+            .into_iter()
+            .map(|c| (c.id.clone(), c))
+            .collect(),
+        currency: find_or_create_company_info(&app_state, &organization, &user_email)
+            .await?
+            .pricing_preferences
+            .preferred_currency,
+        layout: q.layout,
+        tax: q.tax(),
+        user_email,
+    });
+
+    let file_name = format!(
+        "{}_{}.{}",
+        q.layout.name(),
+        Utc::now().format("%Y-%m-%d"),
+        match q.format {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Xlsx => "xlsx",
+        }
+    );
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::CONTENT_DISPOSITION,
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file_name))
+            .map_err(|_| AppError::InternalServerError("Invalid Content-Disposition header".to_string()))?,
+    );
+
+    let header_row: Vec<_> = q
+        .layout
+        .headers()
+        .iter()
+        .map(|h| Cell::from(*h))
+        .collect();
+    let body = match q.format {
+        ExportFormat::Csv => {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/csv; charset=utf-8"),
+            );
+            let mut first = UTF8_BOM.to_vec();
+            first.extend(csv_chunk(&[header_row])?);
+            let rows = stream::iter(selected).then(move |entry| {
+                let context = context.clone();
+                async move { context.csv_rows(entry).await }
+            });
+            let chunks = stream::once(async move { first })
+                .chain(rows)
+                .map(Ok::<_, std::io::Error>);
+            Body::from_stream(chunks)
+        }
+        ExportFormat::Xlsx => {
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(
+                    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+                ),
+            );
+            let mut rows = Vec::new();
+            for entry in &selected {
+                rows.extend(context.rows(entry).await?);
+            }
+            Body::from(xlsx_workbook(q.layout, &rows)?)
+        }
+    };
+
+    Ok((headers, body))
+}
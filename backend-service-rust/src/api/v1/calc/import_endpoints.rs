@@ -0,0 +1,243 @@
+use crate::api::v1::admin_editor_endpoints::sync_user_class_body_mapping;
+use crate::api::v1::calc::data_endpoints::T1;
+use crate::api::v1::calc::persistence_endpoints::{
+    new_calculation_file_name, store_calculation, validate_file_name,
+};
+use crate::calc::calculation_history::{CalculationHistory, CALCULATIONS};
+use crate::calc::calculation_import::{
+    read_archive, ArchiveContent, ImportReport, ImportResult, ImportedFile, COMPANY_FILE,
+};
+use crate::calc::registry::link_registry;
+use crate::db::calculations::get_entry;
+use crate::exlogging::{log_event, LogLevel};
+use crate::auth::organizations::user_organization;
+use crate::middleware::{AuthenticatedUser, CurrentOrganization};
+use crate::models::calculations::{CarCalcData, CALCULATION_SCHEMA_VERSION};
+use crate::models::estimates::{EstimateStatus, StatusTransition};
+use crate::models::CompanyInfo;
+use crate::transactionalfs::{GitTransactionalFs, TransactionalFs};
+use crate::utils::{
+    safe_write_overwrite, organization_catalog_directory, organization_directory,
+    CATALOG,
+};
+use crate::{errors::AppError, state::AppState};
+use axum::extract::{Multipart, Path, State};
+use axum::{response::IntoResponse, Json};
+use chrono::Utc;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+async fn read_uploaded_archive(mut multipart: Multipart) -> Result<Vec<u8>, AppError> {
+    let field = multipart
+        .next_field()
+        .await
+        .map_err(|e| AppError::InvalidData(format!("Invalid upload: {}", e)))?
+        .ok_or(AppError::InvalidData("No file uploaded".to_string()))?;
+    let data = field
+        .bytes()
+        .await
+        .map_err(|e| AppError::InvalidData(format!("Failed to read the archive: {}", e)))?;
+    Ok(data.to_vec())
+}
+
+/// Stores an imported calculation, under a new name if the original one is taken by
+/// a different or deleted calculation. Identical calculations are skipped. Stored
+/// estimates are never overwritten, so an import cannot change an approved one.
+/// The archive's status history is not trusted: the calculation starts as a draft
+/// with a single transition recording the import.
+async fn import_calculation(
+    app_state: &AppState,
+    organization: &str,
+    user_email: &str,
+    actor: &str,
+    file_name: &str,
+    mut data: CarCalcData,
+) -> Result<(ImportResult, String), AppError> {
+    data.schema_version = CALCULATION_SCHEMA_VERSION;
+    // The registry is not part of the archive, references into another account are dropped
+    link_registry(
+        &app_state.db.customers_tree,
+        &app_state.db.vehicles_tree,
+        organization,
+        &mut data,
+    )?;
+
+    let mut result = ImportResult::Renamed;
+    if validate_file_name(file_name).is_ok() {
+        data.car.store_file_name = Some(file_name.to_string());
+        let organization_dir = organization_directory(&app_state.data_dir_path, organization)?;
+        let existing = get_entry(&app_state.db.calculations_tree, organization, file_name)?;
+        let taken =
+            existing.is_some() || organization_dir.join(CALCULATIONS).join(file_name).exists();
+        let history = CalculationHistory::new(
+            organization_dir,
+            user_email,
+            &app_state.cache,
+            app_state.calculation_history_git,
+            app_state.calculation_revisions_keep,
+        );
+        let json = serde_json::to_string_pretty(&data)?;
+        let deleted = existing.is_some_and(|e| e.deleted_at.is_some());
+        if !deleted && history.is_unchanged(file_name, json.as_bytes()).await? {
+            return Ok((ImportResult::Skipped, file_name.to_string()));
+        }
+        if !deleted && !taken {
+            result = ImportResult::Imported;
+        }
+    }
+    let target = match result {
+        ImportResult::Imported => file_name.to_string(),
+        _ => new_calculation_file_name(),
+    };
+    data.car.store_file_name = Some(target.clone());
+    data.status_history = vec![StatusTransition {
+        file_name: target.clone(),
+        from: data.status,
+        to: EstimateStatus::Draft,
+        actor: actor.to_string(),
+        at: Utc::now(),
+        comment: Some(format!("Imported by {}", actor)),
+    }];
+    data.status = EstimateStatus::Draft;
+    store_calculation(app_state, organization, user_email, &target, &data).await?;
+    Ok((result, target))
+}
+
+/// Restores calculations, company info and catalog files of an exported
+/// directory into the organization of `user_email`.
+async fn import_user_archive(
+    app_state: &AppState,
+    organization: &str,
+    user_email: &str,
+    actor: &str,
+    bytes: Vec<u8>,
+) -> Result<ImportReport, AppError> {
+    let files = tokio::task::spawn_blocking(move || read_archive(&bytes))
+        .await
+        .map_err(|e| AppError::InternalServerError(format!("import task join: {}", e)))??;
+    let organization_dir = organization_directory(&app_state.data_dir_path, organization)?;
+    let catalog_path = organization_catalog_directory(&app_state.data_dir_path, organization)?;
+    let mut report = ImportReport::default();
+    // Changed catalog files are committed together, once all entries are read
+    let mut catalog_files = Vec::new();
+    let mut catalog_entries = Vec::new();
+    let mut company = None;
+
+    for file in files {
+        let path = file.path;
+        let imported = match file.content {
+            ArchiveContent::Calculation { file_name, data } => {
+                match import_calculation(app_state, organization, user_email, actor, &file_name, *data)
+                    .await
+                {
+                    Ok((result, target)) => {
+                        ImportedFile::new(&path, result, Some(format!("{}/{}", CALCULATIONS, target)))
+                    }
+                    Err(e) => ImportedFile::failed(&path, e),
+                }
+            }
+            ArchiveContent::Company(imported) => {
+                company = Some((path, *imported));
+                continue;
+            }
+            ArchiveContent::Catalog { path: relative, content } => {
+                let target = format!("{}/{}", CATALOG, relative);
+                let relative = PathBuf::from(relative);
+                match tokio::fs::read(catalog_path.join(&relative)).await {
+                    Ok(existing) if existing == content => {
+                        ImportedFile::new(&path, ImportResult::Skipped, Some(target))
+                    }
+                    _ => {
+                        catalog_files.push((relative, content));
+                        catalog_entries.push((path, target));
+                        continue;
+                    }
+                }
+            }
+            ArchiveContent::Ignored => ImportedFile::new(&path, ImportResult::Skipped, None),
+            ArchiveContent::Invalid(error) => ImportedFile::failed(&path, error),
+        };
+        report.push(imported);
+    }
+
+    if !catalog_files.is_empty() {
+        let t1_changed = catalog_files.iter().any(|(relative, _)| relative.ends_with(T1));
+        let fs_manager =
+            GitTransactionalFs::new(catalog_path, actor.to_string(), &app_state.cache).await?;
+        let written = fs_manager
+            .write_files(catalog_files, "Catalog files imported from archive")
+            .await
+            .map_err(AppError::from);
+        if written.is_ok() && t1_changed {
+            sync_user_class_body_mapping(&app_state.data_dir_path, &app_state.cache, organization)
+                .await?;
+        }
+        for (path, target) in catalog_entries {
+            report.push(match &written {
+                Ok(_) => ImportedFile::new(&path, ImportResult::Imported, Some(target)),
+                Err(e) => ImportedFile::failed(&path, e),
+            });
+        }
+    }
+
+    // The company info lives outside the catalog repository, it is written once
+    // the catalog is committed
+    if let Some((path, imported)) = company {
+        let company = CompanyInfo {
+            email: user_email.to_string(),
+            ..imported
+        };
+        let written = match serde_json::to_string_pretty(&company) {
+            Ok(json) => safe_write_overwrite(
+                &organization_dir,
+                &PathBuf::from(COMPANY_FILE),
+                json,
+                &app_state.cache,
+            )
+            .await
+            .map_err(AppError::from),
+            Err(e) => Err(e.into()),
+        };
+        report.push(match written {
+            Ok(_) => ImportedFile::new(&path, ImportResult::Imported, Some(COMPANY_FILE.to_string())),
+            Err(e) => ImportedFile::failed(&path, e),
+        });
+    }
+
+    log_event(
+        LogLevel::Info,
+        format!(
+            "Archive imported into {}: {} imported, {} renamed, {} skipped, {} failed",
+            user_email, report.imported, report.renamed, report.skipped, report.failed
+        ),
+        Some(actor),
+    );
+    Ok(report)
+}
+
+/// Imports a ZIP archive in the format of the user data export into the own organization.
+pub async fn import_user_data(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let bytes = read_uploaded_archive(multipart).await?;
+    let report =
+        import_user_archive(&app_state, &organization, &user_email, &user_email, bytes).await?;
+    Ok(Json(report))
+}
+
+/// Admin counterpart of [`import_user_data`], e.g. to restore an account from a backup.
+pub async fn admin_import_user_data(
+    AuthenticatedUser(admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(user_email): Path<String>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let organization = user_organization(&app_state.db, &user_email)?;
+    let bytes = read_uploaded_archive(multipart).await?;
+    let report =
+        import_user_archive(&app_state, &organization, &user_email, &admin_email, bytes).await?;
+    Ok(Json(report))
+}
@@ -1,4 +1,7 @@
 pub mod data_endpoints;
 pub mod persistence_endpoints;
 pub mod output_endpoints;
-pub mod plugin_endpoints;
\ No newline at end of file
+pub mod plugin_endpoints;
+pub mod estimate_endpoints;
+pub mod export_endpoints;
+pub mod import_endpoints;
@@ -1,12 +1,17 @@
 use crate::api::v1::user::find_or_create_company_info;
-use crate::calc::templating::{
-    send_gen_doc_request, GeneratePdfInternalRequest, Metadata, TEMPLATES,
-};
+use crate::calc::document_jobs::{document_file_name, enqueue_document_job};
+use crate::calc::document_numbering::{assign_document_number, release_document_number};
+use crate::calc::templating::{GeneratePdfInternalRequest, Metadata, DEFAULT_TEMPLATE, TEMPLATES};
+use crate::db::attachment::{list_all_attachments_for_user, AttachmentLifecycle};
+use crate::db::document_jobs::get_job;
+use crate::db::document_numbers::list_assignments;
+use crate::models::documents::{AssignedNumber, DocumentFormat, DocumentJobStatus};
 use crate::exlogging::{self, log_event, LogLevel};
-use crate::middleware::AuthenticatedUser;
+use crate::middleware::{AuthenticatedUser, CurrentOrganization};
 use crate::utils::get_catalog_file_as_string;
 use crate::{errors::AppError, state::AppState};
-use axum::http::{HeaderMap, HeaderValue};
+use axum::extract::{Path, Query};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
 use axum::{extract::State, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -19,10 +24,103 @@ pub struct GeneratePdfRequest {
     pub template_name: Option<String>,
     pub calculation: serde_json::Value,
     pub metadata: Metadata,
+    /// Replaces `metadata.order_number` with the next number of the company's
+    /// sequence.
+    #[serde(default)]
+    pub assign_number: bool,
+    /// Stored calculation the document belongs to.
+    #[serde(default)]
+    pub calculation_file_name: Option<String>,
+}
+
+/// Builds the renderer input: the requested template (a stored template, custom
+/// content or the default one), the company info and the calculation.
+async fn print_request(
+    app_state: &Arc<AppState>,
+    organization: &str,
+    user_email: &str,
+    request: GeneratePdfRequest,
+) -> Result<GeneratePdfInternalRequest, AppError> {
+    let load_template = |template: String| {
+        get_catalog_file_as_string(
+            organization,
+            &app_state.cache,
+            &app_state.data_dir_path,
+            TEMPLATES,
+            ".html",
+            template,
+        )
+    };
+    let tpl_content = match (request.template_name, request.custom_template_content) {
+        (Some(template), _) => {
+            log_event(
+                LogLevel::Info,
+                format!("HTML template used: {:?}", &template),
+                Some(user_email),
+            );
+            Some(load_template(template).await?)
+        }
+        (None, Some(content)) => Some(content),
+        // Without a default template the external service falls back to its own
+        (None, None) => load_template(DEFAULT_TEMPLATE.to_string()).await.ok(),
+    };
+
+    Ok(GeneratePdfInternalRequest {
+        calculation: request.calculation,
+        company_info: find_or_create_company_info(app_state, organization, user_email).await?,
+        custom_template_content: tpl_content,
+        metadata: request.metadata,
+    })
+}
+
+/// Builds the renderer input and assigns a document number if requested.
+async fn numbered_print_request(
+    app_state: &Arc<AppState>,
+    organization: &str,
+    user_email: &str,
+    request: GeneratePdfRequest,
+    format: DocumentFormat,
+) -> Result<(GeneratePdfInternalRequest, Option<AssignedNumber>), AppError> {
+    let assign = request.assign_number;
+    let calculation = request.calculation_file_name.clone();
+    let mut internal_request = print_request(app_state, organization, user_email, request).await?;
+    let number = if assign {
+        Some(assign_document_number(
+            &app_state.db,
+            organization,
+            user_email,
+            &mut internal_request,
+            format,
+            calculation.as_deref(),
+        )?)
+    } else {
+        None
+    };
+    Ok((internal_request, number))
+}
+
+/// Renders the document, giving the assigned number back if rendering fails.
+async fn render_numbered(
+    app_state: &Arc<AppState>,
+    organization: &str,
+    user_email: &str,
+    request: &GeneratePdfInternalRequest,
+    number: Option<AssignedNumber>,
+    format: DocumentFormat,
+) -> Result<Vec<u8>, AppError> {
+    let result = app_state
+        .document_renderer
+        .render(request, user_email, format)
+        .await;
+    if let (Err(e), Some(number)) = (&result, number) {
+        release_document_number(&app_state.db, organization, number, &e.to_string())?;
+    }
+    result
 }
 
 pub async fn gen_pdf(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Json(request): Json<GeneratePdfRequest>,
 ) -> Result<impl IntoResponse, AppError> {
@@ -32,33 +130,8 @@ pub async fn gen_pdf(
         Some(&user_email),
     );
 
-    let mut tpl_content = request.custom_template_content;
-
-    if let Some(template) = request.template_name {
-        log_event(
-            LogLevel::Info,
-            format!("HTML template used: {:?}", &template),
-            Some(&user_email),
-        );
-        tpl_content = Some(
-            get_catalog_file_as_string(
-                &user_email,
-                &app_state.cache,
-                &app_state.data_dir_path,
-                TEMPLATES,
-                ".html",
-                template,
-            )
-            .await?,
-        );
-    }
-
-    let internal_request = GeneratePdfInternalRequest {
-        calculation: request.calculation,
-        company_info: find_or_create_company_info(&app_state, &user_email).await?,
-        custom_template_content: tpl_content,
-        metadata: request.metadata.clone(),
-    };
+    let (internal_request, number) =
+        numbered_print_request(&app_state, &organization, &user_email, request, DocumentFormat::Pdf).await?;
 
     log_event(
         exlogging::LogLevel::Debug,
@@ -69,29 +142,31 @@ pub async fn gen_pdf(
         Some(user_email.clone()),
     );
 
-    let stream = send_gen_doc_request(
-        internal_request,
-        &app_state.pdf_gen_api_url_post,
+    let pdf = render_numbered(
+        &app_state,
+        &organization,
         &user_email,
-        "pdf",
+        &internal_request,
+        number,
+        DocumentFormat::Pdf,
     )
-    .await?
-    .bytes_stream();
+    .await?;
 
+    let file_name = document_file_name(&internal_request, DocumentFormat::Pdf);
     let mut headers = HeaderMap::new();
     headers.insert("Content-Type", HeaderValue::from_static("application/pdf"));
     headers.insert(
         "Content-Disposition",
-        HeaderValue::from_static("attachment; filename=\"streamed.pdf\""),
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file_name))
+            .map_err(|_| AppError::InternalServerError("Invalid file name".to_string()))?,
     );
 
-    let body = axum::body::Body::from_stream(stream);
-
-    Ok((headers, body))
+    Ok((headers, pdf))
 }
 
 pub async fn gen_html(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Json(request): Json<GeneratePdfRequest>,
 ) -> Result<impl IntoResponse, AppError> {
@@ -101,33 +176,8 @@ pub async fn gen_html(
         Some(&user_email),
     );
 
-    let mut tpl_content = request.custom_template_content;
-
-    if let Some(template) = request.template_name {
-        log_event(
-            LogLevel::Info,
-            format!("HTML template used: {:?}", &template),
-            Some(&user_email),
-        );
-        tpl_content = Some(
-            get_catalog_file_as_string(
-                &user_email,
-                &app_state.cache,
-                &app_state.data_dir_path,
-                TEMPLATES,
-                ".html",
-                template,
-            )
-            .await?,
-        );
-    }
-
-    let internal_request = GeneratePdfInternalRequest {
-        calculation: request.calculation,
-        company_info: find_or_create_company_info(&app_state, &user_email).await?,
-        custom_template_content: tpl_content,
-        metadata: request.metadata.clone(),
-    };
+    let (internal_request, number) =
+        numbered_print_request(&app_state, &organization, &user_email, request, DocumentFormat::Html).await?;
 
     log_event(
         exlogging::LogLevel::Info,
@@ -138,14 +188,15 @@ pub async fn gen_html(
         Some(user_email.clone()),
     );
 
-    let stream = send_gen_doc_request(
-        internal_request,
-        &app_state.pdf_gen_api_url_post,
+    let html = render_numbered(
+        &app_state,
+        &organization,
         &user_email,
-        "html",
+        &internal_request,
+        number,
+        DocumentFormat::Html,
     )
-    .await?
-    .bytes_stream();
+    .await?;
 
     let mut headers = HeaderMap::new();
     headers.insert("Content-Type", HeaderValue::from_static("text/plain"));
@@ -154,7 +205,149 @@ pub async fn gen_html(
         HeaderValue::from_static("attachment; filename=\"streamed.html\""),
     );
 
-    let body = axum::body::Body::from_stream(stream);
+    Ok((headers, html))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentFormatQuery {
+    pub format: DocumentFormat,
+}
+
+/// Generates the document in the requested format, e.g. `?format=docx`.
+pub async fn gen_document(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DocumentFormatQuery>,
+    Json(request): Json<GeneratePdfRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    log_event(
+        LogLevel::Info,
+        format!("{:?} generation {:?}", query.format, &request.metadata),
+        Some(&user_email),
+    );
+
+    let (internal_request, number) =
+        numbered_print_request(&app_state, &organization, &user_email, request, query.format).await?;
+    let document = render_numbered(
+        &app_state,
+        &organization,
+        &user_email,
+        &internal_request,
+        number,
+        query.format,
+    )
+    .await?;
+
+    let file_name = document_file_name(&internal_request, query.format);
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "Content-Type",
+        HeaderValue::from_static(query.format.content_type()),
+    );
+    headers.insert(
+        "Content-Disposition",
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", file_name))
+            .map_err(|_| AppError::InternalServerError("Invalid file name".to_string()))?,
+    );
+
+    Ok((headers, document))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentJobRequest {
+    #[serde(flatten)]
+    pub document: GeneratePdfRequest,
+    #[serde(default)]
+    pub format: DocumentFormat,
+}
 
-    Ok((headers, body))
+/// Queues a document for generation and returns the job. The job is done
+/// right away if the same document was generated before.
+pub async fn create_document_job(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<DocumentJobRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let calculation = request.document.calculation_file_name.clone();
+    let (internal_request, number) =
+        numbered_print_request(&app_state, &organization, &user_email, request.document, request.format)
+            .await?;
+    let mut job = enqueue_document_job(
+        &app_state.db,
+        &organization,
+        request.format,
+        calculation,
+        internal_request,
+        number,
+    )?;
+    app_state.document_jobs_notify.notify_one();
+
+    let status = match job.status {
+        DocumentJobStatus::Done => StatusCode::OK,
+        _ => StatusCode::ACCEPTED,
+    };
+    job.request = None;
+    Ok((status, Json(job)))
+}
+
+pub async fn get_document_job(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut job = get_job(&app_state.db.document_jobs_tree, &organization, &id)?
+        .ok_or(AppError::NotFound)?;
+    job.request = None;
+    Ok(Json(job))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalculationDocumentsQuery {
+    pub calculation: String,
+}
+
+/// Generated documents of a stored calculation, newest first. Files are
+/// downloaded through the attachment endpoints.
+pub async fn list_calculation_documents(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<CalculationDocumentsQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut documents: Vec<_> =
+        list_all_attachments_for_user(&app_state.db.attachments_tree, &organization)?
+            .into_iter()
+            .filter(|a| {
+                matches!(a.lifecycle, AttachmentLifecycle::GeneratedDocument)
+                    && a.calculation.as_deref() == Some(query.calculation.as_str())
+            })
+            .map(|mut a| {
+                a.file_path = a.file_name.clone(); // hide real file path
+                a
+            })
+            .collect();
+    documents.sort_by_key(|a| std::cmp::Reverse(a.created_timestamp));
+    Ok(Json(documents))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DocumentNumbersQuery {
+    #[serde(default)]
+    pub year: Option<i32>,
+}
+
+/// Audit of assigned document numbers, newest first.
+pub async fn list_document_numbers(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(query): Query<DocumentNumbersQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut assignments: Vec<_> =
+        list_assignments(&app_state.db.document_numbers_tree, &organization)?
+            .into_iter()
+            .filter(|a| query.year.is_none_or(|year| a.year == year))
+            .collect();
+    assignments.reverse();
+    Ok(Json(assignments))
 }
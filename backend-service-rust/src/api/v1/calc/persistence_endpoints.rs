@@ -1,22 +1,48 @@
 use crate::exlogging::{log_event, LogLevel};
-use crate::middleware::AuthenticatedUser;
-use crate::models::calculations::CarCalcData;
+use crate::middleware::{AuthenticatedUser, CurrentOrganization};
+use crate::calc::calculation_diff::diff_calculations;
+use crate::calc::calculation_history::{CalculationHistory, CALCULATIONS};
+use crate::calc::calculation_index::{
+    index_calculation_file, indexed_calculation, CalculationListQuery,
+};
+use crate::calc::estimate_lifecycle::check_editable;
+use crate::calc::registry::link_registry;
+use crate::db::calculations::{get_entry, list_user_entries, upsert_entry};
+use crate::calc::calculation_schema::{upgrade_calculation, upgrade_calculation_bytes};
+use crate::models::calculations::{CarCalcData, CALCULATION_SCHEMA_VERSION};
+use crate::models::estimates::EstimateStatus;
 use crate::utils::{
-    get_file_summary, safe_read, safe_write_overwrite,
-    sanitize_alphanumeric_and_dashes, user_personal_directory_from_email,
+    safe_read, sanitize_alphanumeric_and_dashes_and_dots, SafeFsError,
+    organization_directory,
 };
 use crate::{errors::AppError, state::AppState};
 use axum::extract::Query;
 use axum::http::header::CONTENT_TYPE;
 use axum::{extract::State, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::path::PathBuf;
 use std::sync::Arc;
-const CALCULATIONS: &str = "stored_calculations";
 
 #[derive(Debug, Serialize, Deserialize)]
 struct SaveSuccessResponse {
     saved_file_path: String,
+    /// `None` if nothing changed since the previous save.
+    revision: Option<String>,
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameRequest {
+    filename: String,
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicateRequest {
+    filename: String,
+    /// Defaults to the source title with a "(copy)" suffix.
+    title: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,76 +50,356 @@ pub struct FileQuery {
     filename: String,
 }
 
-pub fn apply_and_return_file_name(data: &mut CarCalcData) -> String {
-    let file_name_existing = &data.car.store_file_name.clone();
-    if file_name_existing.is_some() {
-        file_name_existing.as_deref().unwrap().into()
-    } else {
-        data.car.store_file_name = Some(create_new_saved_file_path(data));
-        data.car.store_file_name.as_deref().unwrap().into()
+#[derive(Debug, Deserialize)]
+pub struct RevisionQuery {
+    filename: String,
+    revision: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionDiffQuery {
+    filename: String,
+    from: String,
+    /// Defaults to the latest revision.
+    to: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RevisionDiffResponse {
+    from: String,
+    to: String,
+    #[serde(flatten)]
+    diff: crate::calc::calculation_diff::CalculationDiff,
+}
+
+/// History of the organization's calculations, new revisions are authored by `user_email`.
+fn calculation_history<'a>(
+    app_state: &'a AppState,
+    organization: &str,
+    user_email: &str,
+) -> Result<CalculationHistory<'a>, AppError> {
+    let organization_dir = organization_directory(&app_state.data_dir_path, organization)?;
+    Ok(CalculationHistory::new(
+        organization_dir,
+        user_email,
+        &app_state.cache,
+        app_state.calculation_history_git,
+        app_state.calculation_revisions_keep,
+    ))
+}
+
+/// Storage key of the calculation: the file it was loaded from, or a new id-based name.
+/// The name shown to the user is the document title, see [`CarCalcData::display_title`].
+pub fn apply_and_return_file_name(data: &mut CarCalcData) -> Result<String, AppError> {
+    match data.car.store_file_name.as_deref() {
+        Some(file_name) => {
+            validate_file_name(file_name)?;
+            Ok(file_name.to_string())
+        }
+        None => {
+            let file_name = new_calculation_file_name();
+            data.car.store_file_name = Some(file_name.clone());
+            Ok(file_name)
+        }
     }
 }
 
-pub fn create_new_saved_file_path(data: &CarCalcData) -> String {
-    let carmake = data.car.make.clone().unwrap_or_default();
-    let carmodel = data.car.make.clone().unwrap_or_default();
-    let header = if carmake != String::default() && carmodel != String::default() {
-        format!("{}_{}", carmake, carmodel)
+pub fn new_calculation_file_name() -> String {
+    format!("{}.json", uuid::Uuid::new_v4())
+}
+
+pub fn validate_file_name(file_name: &str) -> Result<(), AppError> {
+    let valid = file_name.ends_with(".json")
+        && !file_name.starts_with('.')
+        && sanitize_alphanumeric_and_dashes_and_dots(file_name) == file_name;
+    if valid {
+        Ok(())
     } else {
-        format!("{}_{}", data.car.body_type, data.car.car_class)
+        Err(AppError::InvalidData(format!(
+            "Invalid calculation file name {}",
+            file_name
+        )))
+    }
+}
+
+/// Saves the document as a new revision and keeps the index up to date.
+pub async fn store_calculation(
+    app_state: &AppState,
+    organization: &str,
+    user_email: &str,
+    file_name: &str,
+    data: &CarCalcData,
+) -> Result<Option<String>, AppError> {
+    let json = serde_json::to_string_pretty(data)?;
+    let revision = calculation_history(app_state, organization, user_email)?
+        .save(file_name, json.into_bytes())
+        .await?;
+    if revision.is_some() {
+        let tree = &app_state.db.calculations_tree;
+        let organization_dir = organization_directory(&app_state.data_dir_path, organization)?;
+        let previous = get_entry(tree, organization, file_name)?;
+        let path = organization_dir.join(CALCULATIONS).join(file_name);
+        index_calculation_file(&app_state.db, organization, &path, previous.as_ref()).await?;
+    }
+    Ok(revision)
+}
+
+pub async fn read_calculation(
+    app_state: &AppState,
+    organization: &str,
+    file_name: &str,
+) -> Result<CarCalcData, AppError> {
+    validate_file_name(file_name)?;
+    let organization_dir = organization_directory(&app_state.data_dir_path, organization)?;
+    let file_path = PathBuf::from(&CALCULATIONS).join(file_name);
+    let content = match safe_read(&organization_dir, &file_path, &app_state.cache).await {
+        Ok(content) => content,
+        Err(SafeFsError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(AppError::FileNotFound)
+        }
+        Err(e) => return Err(e.into()),
     };
-    let file_name = format!("{}_{}_{:?}", header, data.car.year, chrono::Utc::now());
-    format!("{}.json", sanitize_alphanumeric_and_dashes(&file_name))
+    Ok(upgrade_calculation_bytes(&content)?.data)
 }
 
 // Handler for uploading user files
 pub async fn save_calculation(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
-    Json(mut req): Json<CarCalcData>,
+    Json(body): Json<Value>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Update timestamp on each save
-    let file_name = apply_and_return_file_name(&mut req);
-    let user_path = user_personal_directory_from_email(&app_state.data_dir_path, &user_email)?;
-    let file_path = PathBuf::from(&CALCULATIONS).join(&file_name);
-
-    let json = serde_json::to_string_pretty(&req)?; // or `to_vec` for bytes
+    // Clients still running an older frontend post older document versions
+    let mut req = upgrade_calculation(body)?.data;
+    req.schema_version = CALCULATION_SCHEMA_VERSION;
+    link_registry(
+        &app_state.db.customers_tree,
+        &app_state.db.vehicles_tree,
+        &organization,
+        &mut req,
+    )?;
+    let file_name = apply_and_return_file_name(&mut req)?;
+    // The status only changes through the status endpoint
+    let stored = match read_calculation(&app_state, &organization, &file_name).await {
+        Ok(stored) => Some(stored),
+        // A file that needs migration is replaced as a whole
+        Err(AppError::FileNotFound) | Err(AppError::InvalidData(_)) => None,
+        Err(e) => return Err(e),
+    };
+    let stored_status = stored.as_ref().map(|s| s.status);
+    (req.status, req.status_history) = stored
+        .map(|s| (s.status, s.status_history))
+        .unwrap_or_default();
 
-    // Save to file
     log_event(
         LogLevel::Info,
         format!("Save calculation {:?} as {:?}", req.car.vin, &file_name),
-        Some(user_email),
+        Some(&user_email),
     );
-    safe_write_overwrite(user_path, file_path, json, &app_state.cache).await?;
+    let previous = get_entry(&app_state.db.calculations_tree, &organization, &file_name)?;
+    if previous.as_ref().is_some_and(|p| p.deleted_at.is_some()) {
+        return Err(AppError::Conflict(format!(
+            "Calculation {} is deleted, restore it first",
+            file_name
+        )));
+    }
+    // The stored document decides, the index may not know the file yet; for a file that
+    // needs migration the indexed status is all there is.
+    // Saving an unchanged approved estimate is harmless, the editor saves on open
+    let status = stored_status
+        .or(previous.map(|p| p.status))
+        .unwrap_or_default();
+    let json = serde_json::to_string_pretty(&req)?;
+    let history = calculation_history(&app_state, &organization, &user_email)?;
+    if !history.is_unchanged(&file_name, json.as_bytes()).await? {
+        check_editable(status)?;
+    }
+    let revision = store_calculation(&app_state, &organization, &user_email, &file_name, &req).await?;
 
     Ok(Json(SaveSuccessResponse {
         saved_file_path: file_name,
+        revision,
+        title: req.display_title(),
     }))
 }
 
-pub async fn get_calculation_file(
+/// Sets the title of a calculation; an empty title falls back to the generated one.
+pub async fn rename_calculation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<RenameRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_file_name(&req.filename)?;
+    let organization_dir = organization_directory(&app_state.data_dir_path, &organization)?;
+    let entry = indexed_calculation(
+        &app_state.db,
+        &organization,
+        &organization_dir.join(CALCULATIONS),
+        &req.filename,
+    )
+    .await?;
+    check_editable(entry.status)?;
+    let mut data = read_calculation(&app_state, &organization, &req.filename).await?;
+    data.schema_version = CALCULATION_SCHEMA_VERSION;
+    data.title = Some(req.title.trim().to_string()).filter(|t| !t.is_empty());
+    let revision = store_calculation(&app_state, &organization, &user_email, &req.filename, &data).await?;
+
+    Ok(Json(SaveSuccessResponse {
+        saved_file_path: req.filename,
+        revision,
+        title: data.display_title(),
+    }))
+}
+
+/// Saves a copy of a calculation as a new draft estimate.
+pub async fn duplicate_calculation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<DuplicateRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut data = read_calculation(&app_state, &organization, &req.filename).await?;
+    let file_name = new_calculation_file_name();
+    data.schema_version = CALCULATION_SCHEMA_VERSION;
+    data.car.store_file_name = Some(file_name.clone());
+    data.status = EstimateStatus::Draft;
+    data.status_history.clear();
+    data.title = match req.title.as_deref().map(str::trim) {
+        Some(title) if !title.is_empty() => Some(title.to_string()),
+        _ => Some(format!("{} (copy)", data.display_title())),
+    };
+    let revision = store_calculation(&app_state, &organization, &user_email, &file_name, &data).await?;
+
+    Ok(Json(SaveSuccessResponse {
+        saved_file_path: file_name,
+        revision,
+        title: data.display_title(),
+    }))
+}
+
+/// Soft-deletes a calculation, see [`restore_calculation`].
+pub async fn delete_calculation(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Query(q): Query<FileQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let filename = q.filename;
-    let user_path = user_personal_directory_from_email(&app_state.data_dir_path, &user_email)?;
-    let file_path = PathBuf::from(&CALCULATIONS).join(filename);
+    validate_file_name(&q.filename)?;
+    let tree = &app_state.db.calculations_tree;
+    let organization_dir = organization_directory(&app_state.data_dir_path, &organization)?;
+    let mut entry =
+        indexed_calculation(&app_state.db, &organization, &organization_dir.join(CALCULATIONS), &q.filename).await?;
+    check_editable(entry.status)?;
+    calculation_history(&app_state, &organization, &user_email)?
+        .remove(&q.filename)
+        .await?;
+    entry.deleted_at = Some(chrono::Utc::now());
+    upsert_entry(tree, &organization, &entry)?;
+    log_event(
+        LogLevel::Info,
+        format!("Deleted calculation {}", q.filename),
+        Some(&user_email),
+    );
+    Ok(Json("Calculation deleted"))
+}
 
-    let content = safe_read(&user_path, &file_path, &app_state.cache).await?;
+pub async fn restore_calculation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<FileQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_file_name(&req.filename)?;
+    let tree = &app_state.db.calculations_tree;
+    let mut entry = get_entry(tree, &organization, &req.filename)?
+        .filter(|e| e.deleted_at.is_some())
+        .ok_or(AppError::FileNotFound)?;
+    calculation_history(&app_state, &organization, &user_email)?
+        .restore(&req.filename)
+        .await?;
+    entry.deleted_at = None;
+    upsert_entry(tree, &organization, &entry)?;
+    log_event(
+        LogLevel::Info,
+        format!("Restored calculation {}", req.filename),
+        Some(&user_email),
+    );
+    Ok(Json(entry))
+}
 
-    Ok(([(CONTENT_TYPE, "application/json")], Arc::unwrap_or_clone(content)))
+pub async fn get_calculation_file(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<FileQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let data = read_calculation(&app_state, &organization, &q.filename).await?;
+    let json = serde_json::to_vec(&data)?;
+
+    Ok(([(CONTENT_TYPE, "application/json")], json))
 }
 
+/// Search over the organization's calculations with filters, sorting and cursor pagination.
 pub async fn get_calculations_list(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<CalculationListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let entries = list_user_entries(&app_state.db.calculations_tree, &organization)?;
+    Ok(Json(q.apply(entries)?))
+}
+
+pub async fn list_calculation_revisions(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<FileQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let revisions = calculation_history(&app_state, &organization, &user_email)?
+        .list(&q.filename)
+        .await?;
+    Ok(Json(revisions))
+}
+
+pub async fn get_calculation_revision(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
+    Query(q): Query<RevisionQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let user_path = user_personal_directory_from_email(&app_state.data_dir_path, &user_email)?;
-    let file_path = user_path.join(CALCULATIONS);
+    let content = calculation_history(&app_state, &organization, &user_email)?
+        .read(&q.filename, &q.revision)
+        .await?;
+    let upgraded = upgrade_calculation_bytes(&content)?;
+    let json = serde_json::to_vec(&upgraded.data)?;
+
+    Ok(([(CONTENT_TYPE, "application/json")], json))
+}
 
-    let calcs = get_file_summary(file_path).await?;
+/// Semantic diff between two revisions of a calculation.
+pub async fn diff_calculation_revisions(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<RevisionDiffQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let history = calculation_history(&app_state, &organization, &user_email)?;
+    let to = match q.to {
+        Some(to) => to,
+        None => history
+            .list(&q.filename)
+            .await?
+            .into_iter()
+            .next()
+            .map(|r| r.id)
+            .ok_or(AppError::NotFound)?,
+    };
+    let from_data = upgrade_calculation_bytes(&history.read(&q.filename, &q.from).await?)?.data;
+    let to_data = upgrade_calculation_bytes(&history.read(&q.filename, &to).await?)?.data;
 
-    Ok(Json(calcs))
+    Ok(Json(RevisionDiffResponse {
+        from: q.from,
+        to,
+        diff: diff_calculations(&from_data, &to_data),
+    }))
 }
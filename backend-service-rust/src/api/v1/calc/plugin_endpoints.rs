@@ -14,7 +14,7 @@ use tokio::fs;
 
 use crate::{
     errors::AppError,
-    middleware::AuthenticatedUser,
+    middleware::CurrentOrganization,
     state::AppState,
     utils::{self, DataStorageCache},
 };
@@ -55,20 +55,20 @@ where
 }
 
 async fn bundle_plugins_for_user(
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
     _cache: &DataStorageCache, // TODO: use cache
 ) -> Result<String, AppError> {
-    let all_js_files = utils::all_files_with_extension(data_dir, user_email, PROCS, &JS_EXT).await?;
+    let all_js_files = utils::all_files_with_extension(data_dir, organization, PROCS, &JS_EXT).await?;
     bundle_plugins_as_array(all_js_files).await
 }
 
 pub async fn get_all_plugins(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     let bundle =
-        bundle_plugins_for_user(&user_email, &app_state.data_dir_path, &app_state.cache).await?;
+        bundle_plugins_for_user(&organization, &app_state.data_dir_path, &app_state.cache).await?;
     let mut headers = HeaderMap::new();
     headers.insert("content-type", HeaderValue::from_static("text/javascript"));
     let resp = (headers, bundle).into_response();
@@ -83,11 +83,11 @@ pub struct ProcessorEntry {
 }
 
 pub async fn list_processors(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<Json<Vec<ProcessorEntry>>, AppError> {
     let all_js_files =
-        utils::all_files_with_extension(&app_state.data_dir_path, &user_email, PROCS, &JS_EXT)
+        utils::all_files_with_extension(&app_state.data_dir_path, &organization, PROCS, &JS_EXT)
             .await?;
 
     let read_futures = all_js_files.into_iter().map(|path| async move {
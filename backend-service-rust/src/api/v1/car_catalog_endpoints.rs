@@ -0,0 +1,368 @@
+use crate::{
+    calc::{
+        car_catalog::CARS,
+        car_class_to_body_type::CLASS_TYPE_MAPPING_FILE,
+        car_schema::{validate_car_file, validate_model, CarFileReport},
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    middleware::{AuthenticatedUser, CurrentOrganization},
+    state::AppState,
+    transactionalfs::{GitTransactionalFs, TransactionalFs},
+    utils::{
+        all_files_with_extension, common_directory, sanitize_alphanumeric_and_dashes,
+        organization_catalog_directory,
+    },
+};
+use axum::{extract::State, response::IntoResponse, Json};
+use serde_yaml::{Mapping, Value};
+use std::{collections::HashSet, ffi::OsStr, path::PathBuf, sync::Arc};
+
+/// Which catalog an edit goes to. Shop edits shadow the common file of the same make:
+/// the first change copies the common models into the organization catalog.
+enum CatalogScope {
+    Organization(String),
+    Common,
+}
+
+impl CatalogScope {
+    fn root(&self, data_dir: &PathBuf) -> Result<PathBuf, AppError> {
+        Ok(match self {
+            CatalogScope::Organization(organization) => organization_catalog_directory(data_dir, organization)?,
+            CatalogScope::Common => common_directory(data_dir)?,
+        })
+    }
+}
+
+fn make_file(make: &str) -> Result<PathBuf, AppError> {
+    let clean = sanitize_alphanumeric_and_dashes(&make.to_lowercase());
+    if clean.is_empty() || clean != make.to_lowercase() {
+        return Err(AppError::InvalidData(format!(
+            "Invalid make name `{}`, use latin letters, digits and dashes",
+            make
+        )));
+    }
+    Ok(PathBuf::from(CARS).join(format!("{}.yaml", clean)))
+}
+
+async fn read_models(path: &PathBuf) -> Result<Option<Mapping>, AppError> {
+    if !tokio::fs::try_exists(path).await.unwrap_or(false) {
+        return Ok(None);
+    }
+    let content = tokio::fs::read_to_string(path).await?;
+    let value: Value = serde_yaml::from_str(&content)?;
+    match value {
+        Value::Null => Ok(Some(Mapping::new())),
+        Value::Mapping(m) => Ok(Some(m)),
+        _ => Err(AppError::InvalidData(format!(
+            "{} is not a mapping of models",
+            path.display()
+        ))),
+    }
+}
+
+/// Models of a make as seen in the scope, with the user file shadowing the common one.
+async fn load_make(
+    app_state: &AppState,
+    scope: &CatalogScope,
+    make: &str,
+) -> Result<Option<Mapping>, AppError> {
+    let file = make_file(make)?;
+    let own = read_models(&scope.root(&app_state.data_dir_path)?.join(&file)).await?;
+    match (own, scope) {
+        (Some(models), _) => Ok(Some(models)),
+        (None, CatalogScope::Organization(_)) => {
+            read_models(&common_directory(&app_state.data_dir_path)?.join(&file)).await
+        }
+        (None, CatalogScope::Common) => Ok(None),
+    }
+}
+
+async fn store_make(
+    app_state: &AppState,
+    scope: &CatalogScope,
+    author: &str,
+    make: &str,
+    models: &Mapping,
+    message: &str,
+) -> Result<(), AppError> {
+    let content = serde_yaml::to_string(models)?;
+    let fs_manager = GitTransactionalFs::new(
+        scope.root(&app_state.data_dir_path)?,
+        author.to_string(),
+        &app_state.cache,
+    )
+    .await?;
+    fs_manager
+        .write_file(content.into_bytes(), &make_file(make)?, message)
+        .await?;
+    log_event(LogLevel::Info, message, Some(author));
+    Ok(())
+}
+
+async fn known_classes(
+    app_state: &AppState,
+    scope: &CatalogScope,
+) -> Result<Option<HashSet<String>>, AppError> {
+    let path = match scope {
+        CatalogScope::Organization(organization) => crate::utils::get_file_path_user_common(
+            &app_state.data_dir_path,
+            organization,
+            &CLASS_TYPE_MAPPING_FILE,
+        )
+        .await
+        .ok(),
+        CatalogScope::Common => {
+            Some(common_directory(&app_state.data_dir_path)?.join(CLASS_TYPE_MAPPING_FILE))
+        }
+    };
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    match read_models(&path).await {
+        Ok(Some(mapping)) => Ok(Some(
+            mapping
+                .keys()
+                .filter_map(|k| k.as_str().map(str::to_string))
+                .collect(),
+        )),
+        _ => Ok(None),
+    }
+}
+
+async fn validate_scope(
+    app_state: &AppState,
+    scope: &CatalogScope,
+) -> Result<Vec<CarFileReport>, AppError> {
+    let classes = known_classes(app_state, scope).await?;
+    let mut files = match scope {
+        CatalogScope::Organization(organization) => {
+            all_files_with_extension(&app_state.data_dir_path, organization, CARS, OsStr::new("yaml"))
+                .await?
+        }
+        CatalogScope::Common => {
+            let mut files = vec![];
+            let dir = common_directory(&app_state.data_dir_path)?.join(CARS);
+            if let Ok(mut entries) = tokio::fs::read_dir(&dir).await {
+                while let Some(entry) = entries.next_entry().await? {
+                    if entry.path().extension() == Some(OsStr::new("yaml")) {
+                        files.push(entry.path());
+                    }
+                }
+            }
+            files
+        }
+    };
+    files.sort_by_key(|p| p.file_name().map(|n| n.to_os_string()));
+
+    let mut reports = Vec::with_capacity(files.len());
+    for path in files {
+        let make = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let content = tokio::fs::read_to_string(&path).await?;
+        reports.push(validate_car_file(&make, &content, classes.as_ref()));
+    }
+    Ok(reports)
+}
+
+async fn upsert_model(
+    app_state: &AppState,
+    scope: CatalogScope,
+    author: &str,
+    make: &str,
+    model: &str,
+    data: serde_json::Value,
+) -> Result<Value, AppError> {
+    if model.trim().is_empty() {
+        return Err(AppError::InvalidData("Model name cannot be empty".to_string()));
+    }
+    let value = serde_yaml::to_value(data)?;
+    let issues = validate_model(model, &value, known_classes(app_state, &scope).await?.as_ref());
+    if !issues.is_empty() {
+        let messages: Vec<String> = issues
+            .iter()
+            .map(|i| match &i.field {
+                Some(field) => format!("{}: {}", field, i.message),
+                None => i.message.clone(),
+            })
+            .collect();
+        return Err(AppError::InvalidData(messages.join("; ")));
+    }
+    let mut models = load_make(app_state, &scope, make).await?.ok_or(AppError::NotFound)?;
+    let existed = models.insert(Value::String(model.to_string()), value.clone()).is_some();
+    let message = format!(
+        "Car model {}/{} {}",
+        make,
+        model,
+        if existed { "updated" } else { "added" }
+    );
+    store_make(app_state, &scope, author, make, &models, &message).await?;
+    Ok(value)
+}
+
+async fn remove_model(
+    app_state: &AppState,
+    scope: CatalogScope,
+    author: &str,
+    make: &str,
+    model: &str,
+) -> Result<(), AppError> {
+    let mut models = load_make(app_state, &scope, make).await?.ok_or(AppError::NotFound)?;
+    if models.shift_remove(model).is_none() {
+        return Err(AppError::NotFound);
+    }
+    let message = format!("Car model {}/{} deleted", make, model);
+    store_make(app_state, &scope, author, make, &models, &message).await
+}
+
+async fn create_make(
+    app_state: &AppState,
+    scope: CatalogScope,
+    author: &str,
+    make: &str,
+) -> Result<(), AppError> {
+    if load_make(app_state, &scope, make).await?.is_some() {
+        return Err(AppError::InvalidData(format!("Make `{}` already exists", make)));
+    }
+    let message = format!("Car make {} created", make);
+    store_make(app_state, &scope, author, make, &Mapping::new(), &message).await
+}
+
+async fn remove_make(
+    app_state: &AppState,
+    scope: CatalogScope,
+    author: &str,
+    make: &str,
+) -> Result<(), AppError> {
+    let fs_manager = GitTransactionalFs::new(
+        scope.root(&app_state.data_dir_path)?,
+        author.to_string(),
+        &app_state.cache,
+    )
+    .await?;
+    let message = format!("Car make {} deleted", make);
+    fs_manager.delete_file(&make_file(make)?, &message).await?;
+    log_event(LogLevel::Info, message, Some(author));
+    Ok(())
+}
+
+pub async fn validate_user_car_catalog(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let reports = validate_scope(&app_state, &CatalogScope::Organization(organization)).await?;
+    Ok(Json(reports))
+}
+
+pub async fn get_user_car_make(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path(make): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let models = load_make(&app_state, &CatalogScope::Organization(organization), &make)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(models))
+}
+
+pub async fn create_user_car_make(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path(make): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    create_make(&app_state, CatalogScope::Organization(organization), &user_email, &make).await?;
+    Ok(Json("Make created"))
+}
+
+pub async fn delete_user_car_make(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path(make): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    remove_make(&app_state, CatalogScope::Organization(organization), &user_email, &make).await?;
+    Ok(Json("Make deleted"))
+}
+
+pub async fn put_user_car_model(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path((make, model)): axum::extract::Path<(String, String)>,
+    Json(data): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, AppError> {
+    let scope = CatalogScope::Organization(organization);
+    let stored = upsert_model(&app_state, scope, &user_email, &make, &model, data).await?;
+    Ok(Json(stored))
+}
+
+pub async fn delete_user_car_model(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path((make, model)): axum::extract::Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    let scope = CatalogScope::Organization(organization);
+    remove_model(&app_state, scope, &user_email, &make, &model).await?;
+    Ok(Json("Model deleted"))
+}
+
+pub async fn validate_common_car_catalog(
+    AuthenticatedUser(_user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let reports = validate_scope(&app_state, &CatalogScope::Common).await?;
+    Ok(Json(reports))
+}
+
+pub async fn get_common_car_make(
+    AuthenticatedUser(_user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path(make): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let models = load_make(&app_state, &CatalogScope::Common, &make)
+        .await?
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(models))
+}
+
+pub async fn create_common_car_make(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path(make): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    create_make(&app_state, CatalogScope::Common, &user_email, &make).await?;
+    Ok(Json("Make created"))
+}
+
+pub async fn delete_common_car_make(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path(make): axum::extract::Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    remove_make(&app_state, CatalogScope::Common, &user_email, &make).await?;
+    Ok(Json("Make deleted"))
+}
+
+pub async fn put_common_car_model(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path((make, model)): axum::extract::Path<(String, String)>,
+    Json(data): Json<serde_json::Value>,
+) -> Result<impl IntoResponse, AppError> {
+    let stored =
+        upsert_model(&app_state, CatalogScope::Common, &user_email, &make, &model, data).await?;
+    Ok(Json(stored))
+}
+
+pub async fn delete_common_car_model(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    axum::extract::Path((make, model)): axum::extract::Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    remove_model(&app_state, CatalogScope::Common, &user_email, &make, &model).await?;
+    Ok(Json("Model deleted"))
+}
@@ -0,0 +1,290 @@
+use crate::{
+    calc::{calculation_index::normalize_code, registry::repair_history},
+    db::{calculations, customers, vehicles},
+    errors::AppError,
+    middleware::CurrentOrganization,
+    models::customers::{Customer, CustomerInput, Vehicle, VehicleInput},
+    state::AppState,
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct CustomerListQuery {
+    /// Searches name, phone and email.
+    q: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VehicleListQuery {
+    /// Searches VIN, plate, make and model.
+    q: Option<String>,
+    customer_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VehicleHistoryQuery {
+    vin: Option<String>,
+    plate: Option<String>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+fn contains_ignore_case(value: Option<&str>, q: &str) -> bool {
+    value.is_some_and(|v| v.to_lowercase().contains(&q.to_lowercase()))
+}
+
+fn apply_customer_input(customer: &mut Customer, input: CustomerInput) -> Result<(), AppError> {
+    let name = input.name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidData("Customer name is required".to_string()));
+    }
+    customer.name = name.to_string();
+    customer.phone = non_empty(input.phone);
+    customer.email = non_empty(input.email);
+    customer.address = non_empty(input.address);
+    customer.notes = non_empty(input.notes);
+    customer.updated_at = Utc::now();
+    Ok(())
+}
+
+fn existing_customer(app_state: &AppState, organization: &str, id: &str) -> Result<Customer, AppError> {
+    customers::get_customer(&app_state.db.customers_tree, organization, id)?.ok_or(AppError::NotFound)
+}
+
+fn existing_vehicle(app_state: &AppState, organization: &str, id: &str) -> Result<Vehicle, AppError> {
+    vehicles::get_vehicle(&app_state.db.vehicles_tree, organization, id)?.ok_or(AppError::NotFound)
+}
+
+pub async fn list_customers(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<CustomerListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut list = customers::list_user_customers(&app_state.db.customers_tree, &organization)?;
+    if let Some(q) = non_empty(q.q) {
+        list.retain(|c| {
+            contains_ignore_case(Some(&c.name), &q)
+                || contains_ignore_case(c.phone.as_deref(), &q)
+                || contains_ignore_case(c.email.as_deref(), &q)
+        });
+    }
+    list.sort_by_key(|c| c.name.to_lowercase());
+    Ok(Json(list))
+}
+
+pub async fn create_customer(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<CustomerInput>,
+) -> Result<impl IntoResponse, AppError> {
+    let now = Utc::now();
+    let mut customer = Customer {
+        id: crate::utils::random::generate_random_id(8),
+        owner: organization,
+        name: String::new(),
+        phone: None,
+        email: None,
+        address: None,
+        notes: None,
+        created_at: now,
+        updated_at: now,
+    };
+    apply_customer_input(&mut customer, input)?;
+    customers::insert_customer(&app_state.db.customers_tree, &customer)?;
+    Ok(Json(customer))
+}
+
+pub async fn get_customer(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(existing_customer(&app_state, &organization, &id)?))
+}
+
+pub async fn update_customer(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(input): Json<CustomerInput>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut customer = existing_customer(&app_state, &organization, &id)?;
+    apply_customer_input(&mut customer, input)?;
+    customers::update_customer(&app_state.db.customers_tree, &customer)?;
+    Ok(Json(customer))
+}
+
+/// Deletes a customer; their vehicles stay registered without a customer.
+pub async fn delete_customer(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if !customers::remove_customer(&app_state.db.customers_tree, &organization, &id)? {
+        return Err(AppError::NotFound);
+    }
+    let tree = &app_state.db.vehicles_tree;
+    for mut vehicle in vehicles::list_user_vehicles(tree, &organization)? {
+        if vehicle.customer_id.as_deref() == Some(id.as_str()) {
+            vehicle.customer_id = None;
+            vehicles::update_vehicle(tree, &vehicle)?;
+        }
+    }
+    Ok(Json("Customer deleted"))
+}
+
+/// Validates the input and applies it; a VIN may be registered only once per organization.
+fn apply_vehicle_input(
+    app_state: &AppState,
+    vehicle: &mut Vehicle,
+    input: VehicleInput,
+) -> Result<(), AppError> {
+    let customer_id = non_empty(input.customer_id);
+    if let Some(customer_id) = &customer_id {
+        existing_customer(app_state, &vehicle.owner, customer_id)
+            .map_err(|_| AppError::InvalidData(format!("Unknown customer {}", customer_id)))?;
+    }
+    let vin = non_empty(input.vin).map(|v| normalize_code(&v));
+    let license_plate = non_empty(input.license_plate);
+    if vin.is_none() && license_plate.is_none() {
+        return Err(AppError::InvalidData(
+            "Either VIN or license plate is required".to_string(),
+        ));
+    }
+    if let Some(vin) = &vin {
+        let duplicate = vehicles::list_user_vehicles(&app_state.db.vehicles_tree, &vehicle.owner)?
+            .into_iter()
+            .find(|v| v.id != vehicle.id && v.vin.as_ref() == Some(vin));
+        if let Some(duplicate) = duplicate {
+            return Err(AppError::Conflict(format!(
+                "Vehicle with VIN {} is already registered as {}",
+                vin, duplicate.id
+            )));
+        }
+    }
+
+    vehicle.customer_id = customer_id;
+    vehicle.vin = vin;
+    vehicle.license_plate = license_plate;
+    vehicle.make = non_empty(input.make);
+    vehicle.model = non_empty(input.model);
+    vehicle.year = non_empty(input.year);
+    vehicle.notes = non_empty(input.notes);
+    vehicle.updated_at = Utc::now();
+    Ok(())
+}
+
+pub async fn list_vehicles(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<VehicleListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut list = vehicles::list_user_vehicles(&app_state.db.vehicles_tree, &organization)?;
+    if let Some(customer_id) = non_empty(q.customer_id) {
+        list.retain(|v| v.customer_id.as_ref() == Some(&customer_id));
+    }
+    if let Some(q) = non_empty(q.q) {
+        let code = normalize_code(&q);
+        list.retain(|v| {
+            v.vin.as_deref().is_some_and(|vin| !code.is_empty() && vin.contains(&code))
+                || v
+                    .license_plate
+                    .as_deref()
+                    .is_some_and(|p| !code.is_empty() && normalize_code(p).contains(&code))
+                || contains_ignore_case(v.make.as_deref(), &q)
+                || contains_ignore_case(v.model.as_deref(), &q)
+        });
+    }
+    list.sort_by_key(|v| std::cmp::Reverse(v.updated_at));
+    Ok(Json(list))
+}
+
+pub async fn create_vehicle(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(input): Json<VehicleInput>,
+) -> Result<impl IntoResponse, AppError> {
+    let now = Utc::now();
+    let mut vehicle = Vehicle {
+        id: crate::utils::random::generate_random_id(8),
+        owner: organization,
+        customer_id: None,
+        vin: None,
+        license_plate: None,
+        make: None,
+        model: None,
+        year: None,
+        notes: None,
+        created_at: now,
+        updated_at: now,
+    };
+    apply_vehicle_input(&app_state, &mut vehicle, input)?;
+    vehicles::insert_vehicle(&app_state.db.vehicles_tree, &vehicle)?;
+    Ok(Json(vehicle))
+}
+
+pub async fn get_vehicle(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(existing_vehicle(&app_state, &organization, &id)?))
+}
+
+pub async fn update_vehicle(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Json(input): Json<VehicleInput>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut vehicle = existing_vehicle(&app_state, &organization, &id)?;
+    apply_vehicle_input(&app_state, &mut vehicle, input)?;
+    vehicles::update_vehicle(&app_state.db.vehicles_tree, &vehicle)?;
+    Ok(Json(vehicle))
+}
+
+pub async fn delete_vehicle(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    if !vehicles::remove_vehicle(&app_state.db.vehicles_tree, &organization, &id)? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Json("Vehicle deleted"))
+}
+
+/// Repair history of a vehicle by VIN or license plate over all saved calculations.
+pub async fn vehicle_history(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Query(q): Query<VehicleHistoryQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let vin = non_empty(q.vin);
+    let plate = non_empty(q.plate);
+    if vin.is_none() && plate.is_none() {
+        return Err(AppError::InvalidData(
+            "Either vin or plate is required".to_string(),
+        ));
+    }
+    let entries = calculations::list_user_entries(&app_state.db.calculations_tree, &organization)?;
+
+    Ok(Json(repair_history(
+        vin.as_deref(),
+        plate.as_deref(),
+        vehicles::list_user_vehicles(&app_state.db.vehicles_tree, &organization)?,
+        customers::list_user_customers(&app_state.db.customers_tree, &organization)?,
+        entries,
+    )))
+}
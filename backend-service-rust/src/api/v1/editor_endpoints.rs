@@ -1,13 +1,17 @@
 use crate::{
-    calc::table_processing::{
-        all_tables_headers, find_issues_with_csv_async, fix_issues_with_csv_async,
+    api::v1::{admin_editor_endpoints::sync_user_class_body_mapping, calc::data_endpoints::T1},
+    calc::{
+        table_processing::{
+            all_tables_headers, find_issues_with_csv_async, fix_issues_with_csv_async,
+        },
+        templating::check_uploaded_template,
     },
     errors::AppError,
     exlogging,
-    middleware::AuthenticatedUser,
+    middleware::{AuthenticatedUser, CurrentOrganization},
     state::AppState,
     transactionalfs::{GitTransactionalFs, TransactionalFs},
-    utils::{get_file_as_string_by_path, user_catalog_directory_from_email, COMMON}, // Import the new CompanyInfo struct
+    utils::{get_file_as_string_by_path, organization_catalog_directory, COMMON}, // Import the new CompanyInfo struct
 };
 use axum::{
     extract::{Multipart, State},
@@ -15,7 +19,22 @@ use axum::{
     Json,
 };
 use serde::{Deserialize, Serialize};
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+async fn sync_mapping_if_t1(
+    app_state: &AppState,
+    organization: &str,
+    path: &str,
+) -> Result<(), AppError> {
+    if Path::new(path).ends_with(T1) {
+        sync_user_class_body_mapping(&app_state.data_dir_path, &app_state.cache, organization)
+            .await?;
+    }
+    Ok(())
+}
 
 pub async fn get_common_file_list(
     AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
@@ -32,31 +51,32 @@ pub async fn get_common_file_list(
 }
 
 pub async fn get_all_tables_headers(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let data = all_tables_headers(&app_state.data_dir_path, &user_email).await?;
+    let data = all_tables_headers(&app_state.data_dir_path, &organization).await?;
     Ok(Json(data))
 }
 
 pub async fn get_user_file_list(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     // Read the file list
-    let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
+    let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
     let fs_manager = GitTransactionalFs::new(user_path, user_email, &app_state.cache).await?;
     let data = fs_manager.list_files().await?;
     Ok(Json(data))
 }
 
 pub async fn read_user_file(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     // Read the file content
-    let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
+    let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
     let data = get_file_as_string_by_path(&user_path.join(&path), &user_path, &app_state.cache)
         .await
         .map_err(|e| AppError::InternalServerError(e.to_string()))?;
@@ -64,12 +84,12 @@ pub async fn read_user_file(
 }
 
 pub async fn check_user_file(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     if path.ends_with(".csv") {
-        let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
+        let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
         let data = find_issues_with_csv_async(&user_path, &user_path.join(&path), &app_state.cache)
             .await?;
         Ok(Json(data))
@@ -81,12 +101,13 @@ pub async fn check_user_file(
 }
 
 pub async fn fix_user_file(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     if path.ends_with(".csv") {
-        let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
+        let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
         let data =
             fix_issues_with_csv_async(&user_path, &user_path.join(&path), &app_state.cache).await?;
         let fs_manager =
@@ -94,6 +115,7 @@ pub async fn fix_user_file(
         fs_manager
             .commit_all_if_changed(&format!("Automatic fix for file {path}"))
             .await?;
+        sync_mapping_if_t1(&app_state, &organization, &path).await?;
         Ok(Json(data))
     } else {
         Err(AppError::BadRequest(
@@ -104,10 +126,11 @@ pub async fn fix_user_file(
 
 pub async fn list_commits(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     // Read the file content
-    let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
+    let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
     let fs_manager = GitTransactionalFs::new(user_path, user_email, &app_state.cache).await?;
     let data = fs_manager.list_commits().await?;
     Ok(Json(data))
@@ -121,23 +144,28 @@ pub struct RevertCommit {
 // If commit_hash is "last" - revert last commit, otherwise revert the hash
 pub async fn revert_commit(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Json(revert): Json<RevertCommit>,
 ) -> Result<impl IntoResponse, AppError> {
     // Read the file content
-    let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
-    let fs_manager = GitTransactionalFs::new(user_path, user_email, &app_state.cache).await?;
+    let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
+    let fs_manager =
+        GitTransactionalFs::new(user_path, user_email.clone(), &app_state.cache).await?;
     if revert.commit_hash == "last" {
         fs_manager.revert_last_commit().await?
     } else {
         fs_manager.revert_commit(&revert.commit_hash).await?;
     }
     app_state.cache.invalidate_all().await;
+    // The reverted commit may have touched t1.csv
+    sync_user_class_body_mapping(&app_state.data_dir_path, &app_state.cache, &organization).await?;
     Ok(Json(revert))
 }
 
 pub async fn delete_user_file(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
@@ -146,13 +174,14 @@ pub async fn delete_user_file(
         format!("Delete file request: {:?}", &path.to_string()),
         Some(user_email.as_str()),
     );
-    let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
+    let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
     let fs_manager =
         GitTransactionalFs::new(user_path, user_email.clone(), &app_state.cache).await?;
     fs_manager
         .delete_file(&PathBuf::from(&path), &format!("File {} deleted.", &path))
         .await?;
     app_state.cache.invalidate_all().await;
+    sync_mapping_if_t1(&app_state, &organization, &path).await?;
     Ok("File deleted")
 }
 
@@ -171,12 +200,13 @@ pub async fn read_common_file(
 // Handler for uploading user files
 pub async fn upload_user_file(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
     // Expecting a single file field
-    let user_path = user_catalog_directory_from_email(&app_state.data_dir_path, &user_email)?;
+    let user_path = organization_catalog_directory(&app_state.data_dir_path, &organization)?;
     let fs_manager =
         GitTransactionalFs::new(user_path, user_email.clone(), &app_state.cache).await?;
     let field = multipart
@@ -191,6 +221,7 @@ pub async fn upload_user_file(
         .bytes()
         .await
         .map_err(|_| AppError::InternalServerError("Failed to read file data".to_string()))?;
+    check_uploaded_template(&path, &data)?;
 
     fs_manager
         .write_file(
@@ -200,5 +231,6 @@ pub async fn upload_user_file(
         )
         .await?;
     log::info!("File uploaded as {:?} by {:?}", &path, &user_email);
+    sync_mapping_if_t1(&app_state, &organization, &path).await?;
     Ok(Json("File uploaded and validated successfully"))
 }
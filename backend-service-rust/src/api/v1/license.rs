@@ -1,27 +1,27 @@
 use axum::{extract::{State, Path}, response::IntoResponse, Json};
 use std::sync::Arc;
 use crate::{
-    cache::license_cache::get_license_cache, errors::AppError, license_manager::list_license_files, middleware::AuthenticatedUser, state::AppState
+    auth::organizations::user_organization, cache::license_cache::get_license_cache, errors::AppError, license_manager::list_license_files, middleware::{AuthenticatedUser, CurrentOrganization}, state::AppState
 };
 
 pub async fn get_license(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from extension
+    CurrentOrganization(organization): CurrentOrganization, // Licenses belong to the organization
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     let license_cache = get_license_cache(&app_state);
-    let license_data = license_cache.get_license(&user_email).await?;
+    let license_data = license_cache.get_license(&organization).await?;
     Ok(Json(license_data))
 }
 
 pub async fn get_license_list(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from extension
+    CurrentOrganization(organization): CurrentOrganization, // Licenses belong to the organization
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let license_files = list_license_files(&user_email, &app_state.data_dir_path).await?;
+    let license_files = list_license_files(&organization, &app_state.data_dir_path).await?;
     Ok(Json(license_files)) // Return the list of filenames as JSON
 }
 
-// Admin endpoint to invalidate license cache for a specific user
+// Admin endpoint to invalidate license cache for a specific user's organization
 // (Example of how to expose cache invalidation)
 pub async fn invalidate_license_cache_admin(
     // This handler is protected by the admin middleware, so we know the caller is an admin
@@ -30,6 +30,6 @@ pub async fn invalidate_license_cache_admin(
     Path(user_email_to_invalidate): Path<String>, // The email to invalidate from the path
 ) -> Result<impl IntoResponse, AppError> {
      let license_cache = get_license_cache(&app_state);
-     license_cache.invalidate_license(&user_email_to_invalidate);
+     license_cache.invalidate_license(&user_organization(&app_state.db, &user_email_to_invalidate)?);
      Ok(Json("License cache invalidated"))
 }
\ No newline at end of file
@@ -8,4 +8,9 @@ pub mod admin_editor_endpoints;
 pub mod support;
 pub mod editor_endpoints;
 pub mod attachments;
-pub mod templating_endpoints;
\ No newline at end of file
+pub mod templating_endpoints;
+pub mod car_catalog_endpoints;
+pub mod customers;
+pub mod organizations;
+pub mod two_factor;
+pub mod api_keys;
@@ -0,0 +1,341 @@
+use crate::{
+    auth::{
+        organizations::{create_organization, organization_has_data},
+        roles::ensure_permission,
+    },
+    db::organizations::{
+        get_invitation, get_organization, list_organization_invitations, list_organizations,
+        list_user_invitations, remove_invitation, remove_organization, save_invitation,
+        save_organization,
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    middleware::{AuthenticatedUser, CurrentOrganization},
+    models::{
+        organizations::{
+            MemberRequest, Organization, OrganizationInvitation, OrganizationMember,
+            OrganizationView, RenameOrganizationRequest,
+        },
+        roles::{Permission, Role},
+        User,
+    },
+    state::AppState,
+    utils::delete_organization_data_gracefully,
+};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use std::sync::Arc;
+
+fn shop_role(user: &User) -> Option<Role> {
+    [Role::ShopOwner, Role::ShopEmployee]
+        .into_iter()
+        .find(|role| user.roles.contains(role))
+}
+
+/// Roles of the user with the shop role replaced by `role`.
+fn with_shop_role(user: &User, role: Role) -> Vec<Role> {
+    let mut roles: Vec<Role> = user
+        .roles
+        .iter()
+        .copied()
+        .filter(|r| !matches!(r, Role::ShopOwner | Role::ShopEmployee))
+        .collect();
+    roles.push(role);
+    roles
+}
+
+/// Whether the shop would be left without an owner while others still work in it.
+fn is_last_owner(app_state: &AppState, organization: &str, email: &str) -> Result<bool, AppError> {
+    let members = app_state.db.list_organization_members(organization)?;
+    let owners = members
+        .iter()
+        .filter(|m| m.roles.contains(&Role::ShopOwner))
+        .count();
+    let is_owner = members
+        .iter()
+        .any(|m| m.email == email && m.roles.contains(&Role::ShopOwner));
+    Ok(is_owner && owners == 1 && members.len() > 1)
+}
+
+fn organization_view(
+    app_state: &AppState,
+    organization: Organization,
+) -> Result<OrganizationView, AppError> {
+    let members = app_state
+        .db
+        .list_organization_members(&organization.id)?
+        .iter()
+        .map(|user| OrganizationMember {
+            email: user.email.clone(),
+            role: shop_role(user),
+        })
+        .collect();
+    let invitations = list_organization_invitations(
+        &app_state.db.organization_invitations_tree,
+        &organization.id,
+    )?;
+    Ok(OrganizationView {
+        organization,
+        members,
+        invitations,
+    })
+}
+
+fn load_organization(app_state: &AppState, organization: &str) -> Result<Organization, AppError> {
+    get_organization(&app_state.db.organizations_tree, organization)?.ok_or(AppError::NotFound)
+}
+
+pub async fn get_current_organization(
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let organization = load_organization(&app_state, &organization)?;
+    Ok(Json(organization_view(&app_state, organization)?))
+}
+
+pub async fn rename_organization(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<RenameOrganizationRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_permission(&app_state.db, &user_email, Permission::ManageCompany)?;
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidData(
+            "Organization name is required".to_string(),
+        ));
+    }
+    let mut organization = load_organization(&app_state, &organization)?;
+    organization.name = name.to_string();
+    save_organization(&app_state.db.organizations_tree, &organization)?;
+    Ok(Json(organization_view(&app_state, organization)?))
+}
+
+/// Invites a registered user to the shop or changes the role of a member.
+/// Users of other shops only move once they accept the invitation.
+pub async fn add_organization_member(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<MemberRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_permission(&app_state.db, &user_email, Permission::ManageCompany)?;
+    if !matches!(request.role, Role::ShopOwner | Role::ShopEmployee) {
+        return Err(AppError::InvalidData(
+            "Members are shop_owner or shop_employee".to_string(),
+        ));
+    }
+    let member = app_state
+        .db
+        .find_user_by_email(&request.email)?
+        .ok_or(AppError::UserNotFound)?;
+    let current = load_organization(&app_state, &organization)?;
+
+    if member.organization.as_deref() == Some(organization.as_str()) {
+        if request.role != Role::ShopOwner
+            && is_last_owner(&app_state, &organization, &member.email)?
+        {
+            return Err(AppError::Conflict(format!(
+                "{} is the last owner of the shop",
+                member.email
+            )));
+        }
+        app_state
+            .db
+            .set_user_roles(&member.email, with_shop_role(&member, request.role))?;
+    } else {
+        save_invitation(
+            &app_state.db.organization_invitations_tree,
+            &OrganizationInvitation {
+                organization: organization.clone(),
+                organization_name: current.name.clone(),
+                email: member.email.clone(),
+                role: request.role,
+                invited_by: user_email.clone(),
+                created_at: Utc::now(),
+            },
+        )?;
+        log_event(
+            LogLevel::Info,
+            format!("{} invited to organization {}", member.email, organization),
+            Some(user_email.as_str()),
+        );
+    }
+    Ok(Json(organization_view(&app_state, current)?))
+}
+
+/// Withdraws an invitation of the shop.
+pub async fn cancel_organization_invitation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_permission(&app_state.db, &user_email, Permission::ManageCompany)?;
+    remove_invitation(
+        &app_state.db.organization_invitations_tree,
+        &email,
+        &organization,
+    )?
+    .ok_or(AppError::NotFound)?;
+    let organization = load_organization(&app_state, &organization)?;
+    Ok(Json(organization_view(&app_state, organization)?))
+}
+
+/// Invitations the current user received.
+pub async fn list_my_invitations(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(list_user_invitations(
+        &app_state.db.organization_invitations_tree,
+        &user_email,
+    )?))
+}
+
+/// Joins the shop that invited the current user. Their previous shop is only
+/// deleted when nobody is left in it and it holds no data.
+pub async fn accept_organization_invitation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(organization): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let invitation = get_invitation(
+        &app_state.db.organization_invitations_tree,
+        &user_email,
+        &organization,
+    )?
+    .ok_or(AppError::NotFound)?;
+    let target = load_organization(&app_state, &organization)?;
+    let member = app_state
+        .db
+        .find_user_by_email(&user_email)?
+        .ok_or(AppError::UserNotFound)?;
+    let previous = member.organization.clone().unwrap_or_default();
+    if previous != organization && is_last_owner(&app_state, &previous, &member.email)? {
+        return Err(AppError::Conflict(format!(
+            "{} is the last owner of their shop",
+            member.email
+        )));
+    }
+
+    app_state
+        .db
+        .set_user_roles(&member.email, with_shop_role(&member, invitation.role))?;
+    app_state
+        .db
+        .set_user_organization(&member.email, &organization)?;
+    remove_invitation(
+        &app_state.db.organization_invitations_tree,
+        &user_email,
+        &organization,
+    )?;
+    // Licenses follow their user's membership
+    app_state.license_cache.invalidate_license(&previous);
+    app_state.license_cache.invalidate_license(&organization);
+    log_event(
+        LogLevel::Info,
+        format!(
+            "Joined organization {}, invited by {}",
+            organization, invitation.invited_by
+        ),
+        Some(user_email.as_str()),
+    );
+
+    if !previous.is_empty()
+        && previous != organization
+        && app_state
+            .db
+            .list_organization_members(&previous)?
+            .is_empty()
+    {
+        if organization_has_data(&app_state.db, &app_state.data_dir_path, &previous)? {
+            log_event(
+                LogLevel::Info,
+                format!("Organization {} has no members left, its data is kept", previous),
+                Some(user_email.as_str()),
+            );
+        } else {
+            delete_organization_data_gracefully(
+                &app_state.data_dir_path,
+                &previous,
+                &app_state.cache,
+            )
+            .await?;
+            remove_organization(&app_state.db.organizations_tree, &previous)?;
+        }
+    }
+    Ok(Json(organization_view(&app_state, target)?))
+}
+
+/// Refuses an invitation.
+pub async fn decline_organization_invitation(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Path(organization): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    remove_invitation(
+        &app_state.db.organization_invitations_tree,
+        &user_email,
+        &organization,
+    )?
+    .ok_or(AppError::NotFound)?;
+    Ok(StatusCode::OK)
+}
+
+/// Removes a member, who gets an empty shop of their own.
+pub async fn remove_organization_member(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Path(email): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_permission(&app_state.db, &user_email, Permission::ManageCompany)?;
+    let member = app_state
+        .db
+        .find_user_by_email(&email)?
+        .filter(|user| user.organization.as_deref() == Some(organization.as_str()))
+        .ok_or(AppError::UserNotFound)?;
+    if is_last_owner(&app_state, &organization, &member.email)? {
+        return Err(AppError::Conflict(format!(
+            "{} is the last owner of the shop",
+            member.email
+        )));
+    }
+
+    let personal = create_organization(&app_state.db, &member.email)?;
+    app_state
+        .db
+        .set_user_roles(&member.email, with_shop_role(&member, Role::ShopOwner))?;
+    app_state
+        .db
+        .set_user_organization(&member.email, &personal.id)?;
+    app_state.license_cache.invalidate_license(&organization);
+    log_event(
+        LogLevel::Info,
+        format!(
+            "{} removed from organization {}",
+            member.email, organization
+        ),
+        Some(user_email.as_str()),
+    );
+    let organization = load_organization(&app_state, &organization)?;
+    Ok(Json(organization_view(&app_state, organization)?))
+}
+
+pub async fn admin_list_organizations(
+    AuthenticatedUser(_admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let organizations = list_organizations(&app_state.db.organizations_tree)?
+        .into_iter()
+        .map(|organization| organization_view(&app_state, organization))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Json(organizations))
+}
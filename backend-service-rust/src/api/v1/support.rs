@@ -2,7 +2,7 @@ use crate::{
     db::{attachment, requests},
     errors::AppError,
     exlogging::{log_event, store_frontend_failure},
-    middleware::AuthenticatedUser,
+    middleware::{AuthenticatedUser, CurrentOrganization},
     models::requests::{FrontendFailureReport, SupportRequest, SupportRequestMessage},
     state::AppState,
 };
@@ -138,6 +138,7 @@ pub async fn user_get_all(
 
 pub async fn user_submit(
     AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Json(mut r): Json<SupportRequest>,
 ) -> Result<impl IntoResponse, AppError> {
@@ -147,7 +148,7 @@ pub async fn user_submit(
     for attachment_id in r.attachments.iter() {
         attachment::set_attachment_lifecycle_checked(
             attachment_id,
-            Some(user_email.clone()),
+            Some(organization.clone()),
             &app_state.db.attachments_tree,
             attachment::AttachmentLifecycle::SupportRequestActive,
             None
@@ -1,41 +1,100 @@
 use crate::{
-    calc::templating::{self, SAMPLES, TEMPLATES},
+    api::v1::user::find_or_create_company_info,
+    calc::templating::{
+        self, preview_template, sample_calculation, GeneratePdfInternalRequest, Metadata,
+        SAMPLES, TEMPLATES,
+    },
     errors::AppError,
-    middleware::AuthenticatedUser,
+    middleware::{AuthenticatedUser, CurrentOrganization},
     state::AppState,
     utils::get_catalog_file_as_string, // Import the new CompanyInfo struct
 };
 use axum::{extract::State, response::IntoResponse, Json};
+use serde::Deserialize;
 use std::sync::Arc;
 
 pub async fn list_templates(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let data = templating::list_templates(&user_email, &app_state.data_dir_path).await?;
+    let data = templating::list_templates(&organization, &app_state.data_dir_path).await?;
     Ok(Json(data))
 }
 
 pub async fn list_samples(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let data = templating::list_samples(&user_email, &app_state.data_dir_path).await?;
+    let data = templating::list_samples(&organization, &app_state.data_dir_path).await?;
     Ok(Json(data))
 }
 
 pub async fn get_template(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    get_catalog_file_as_string(&user_email, &app_state.cache, &app_state.data_dir_path, TEMPLATES, ".html", path).await
+    get_catalog_file_as_string(&organization, &app_state.cache, &app_state.data_dir_path, TEMPLATES, ".html", path).await
 }
 
 pub async fn get_sample(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     axum::extract::Path(path): axum::extract::Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
-    get_catalog_file_as_string(&user_email, &app_state.cache, &app_state.data_dir_path, SAMPLES, ".json", path).await
+    get_catalog_file_as_string(&organization, &app_state.cache, &app_state.data_dir_path, SAMPLES, ".json", path).await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TemplatePreviewRequest {
+    /// Stored template to render.
+    #[serde(default)]
+    pub template_name: Option<String>,
+    /// Unsaved template source, used if no template name is given.
+    #[serde(default)]
+    pub template_content: Option<String>,
+    /// Sample to render the template against.
+    pub sample: String,
+    #[serde(default)]
+    pub metadata: Option<Metadata>,
+}
+
+/// Renders a template against a sample, reporting template errors with their
+/// location instead of failing.
+pub async fn preview_template_with_sample(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
+    State(app_state): State<Arc<AppState>>,
+    Json(request): Json<TemplatePreviewRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let template = match (request.template_name, request.template_content) {
+        (Some(name), _) => {
+            get_catalog_file_as_string(&organization, &app_state.cache, &app_state.data_dir_path, TEMPLATES, ".html", name).await?
+        }
+        (None, Some(content)) => content,
+        (None, None) => {
+            return Err(AppError::InvalidData(
+                "Either template_name or template_content is required".to_string(),
+            ))
+        }
+    };
+    let sample = get_catalog_file_as_string(&organization, &app_state.cache, &app_state.data_dir_path, SAMPLES, ".json", request.sample).await?;
+    let sample: serde_json::Value = serde_json::from_str(&sample)
+        .map_err(|e| AppError::InvalidData(format!("Invalid sample: {}", e)))?;
+    let calculation = sample_calculation(sample);
+    let metadata = request.metadata.unwrap_or_else(|| Metadata {
+        order_number: calculation
+            .pointer("/order/orderNumber")
+            .and_then(|n| n.as_str())
+            .map(str::to_string),
+        order_notes: None,
+    });
+
+    let print_request = GeneratePdfInternalRequest {
+        custom_template_content: None,
+        company_info: find_or_create_company_info(&app_state, &organization, &user_email).await?,
+        calculation,
+        metadata,
+    };
+    Ok(Json(preview_template(&template, &print_request)))
 }
@@ -0,0 +1,156 @@
+use crate::{
+    auth::two_factor::{
+        begin_enrollment, confirm_enrollment, is_required_for_admin, mark_session_two_factor,
+        regenerate_recovery_codes, verify_code,
+    },
+    db::{
+        sessions::get_session,
+        two_factor::{get_security_settings, save_security_settings},
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    middleware::{AuthenticatedUser, CurrentSession, Impersonator},
+    models::{
+        two_factor::{
+            RecoveryCodes, SecuritySettings, TwoFactorCodeRequest, TwoFactorDisableRequest,
+            TwoFactorEnrollRequest, TwoFactorStatus,
+        },
+        User,
+    },
+    state::AppState,
+};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use std::sync::Arc;
+
+fn load_user(app_state: &AppState, email: &str) -> Result<User, AppError> {
+    app_state
+        .db
+        .find_user_by_email(email)?
+        .ok_or(AppError::UserNotFound)
+}
+
+pub async fn get_two_factor_status(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = load_user(&app_state, &user_email)?;
+    Ok(Json(TwoFactorStatus {
+        enabled: user.has_two_factor(),
+        recovery_codes_left: user
+            .two_factor
+            .filter(|t| t.enabled)
+            .map_or(0, |t| t.recovery_code_hashes.len()),
+        required_for_admin: is_required_for_admin(&app_state)?,
+    }))
+}
+
+/// Returns a new secret and its provisioning URI after checking the password
+/// again. Nothing changes at login until the enrollment is confirmed. Refused
+/// while impersonating, the factor would outlive it.
+pub async fn enroll_two_factor(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    Impersonator(impersonator): Impersonator,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<TwoFactorEnrollRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if impersonator.is_some() {
+        return Err(AppError::Forbidden);
+    }
+    let user = load_user(&app_state, &user_email)?;
+    if !app_state
+        .auth
+        .verify_password(&req.password, &user.password_hash)?
+    {
+        return Err(AppError::InvalidCredentials);
+    }
+    Ok(Json(begin_enrollment(&app_state, &user)?))
+}
+
+/// Enables two-factor authentication with the first code from the app. The
+/// current session counts as signed in with it. Refused while impersonating.
+pub async fn confirm_two_factor(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    Impersonator(impersonator): Impersonator,
+    CurrentSession(session_id): CurrentSession,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    if impersonator.is_some() {
+        return Err(AppError::Forbidden);
+    }
+    let user = load_user(&app_state, &user_email)?;
+    let recovery_codes = confirm_enrollment(&app_state, &user, &req.code)?;
+    mark_session_two_factor(&app_state, &session_id)?;
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+pub async fn regenerate_two_factor_recovery_codes(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<TwoFactorCodeRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = load_user(&app_state, &user_email)?;
+    if !verify_code(&app_state, &user, &req.code)? {
+        return Err(AppError::InvalidCredentials);
+    }
+    // Reload, verifying may have used up a code
+    let user = load_user(&app_state, &user_email)?;
+    let recovery_codes = regenerate_recovery_codes(&app_state, &user)?;
+    Ok(Json(RecoveryCodes { recovery_codes }))
+}
+
+/// Turns two-factor authentication off, both the password and a code are required.
+pub async fn disable_two_factor(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+    Json(req): Json<TwoFactorDisableRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = load_user(&app_state, &user_email)?;
+    if !app_state
+        .auth
+        .verify_password(&req.password, &user.password_hash)?
+        || !verify_code(&app_state, &user, &req.code)?
+    {
+        return Err(AppError::InvalidCredentials);
+    }
+    app_state.db.set_user_two_factor(&user_email, None)?;
+    log_event(
+        LogLevel::Info,
+        "Two-factor authentication disabled",
+        Some(user_email.as_str()),
+    );
+    Ok(StatusCode::OK)
+}
+
+pub async fn admin_get_security_settings(
+    AuthenticatedUser(_admin_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    Ok(Json(get_security_settings(&app_state.db.settings_tree)?))
+}
+
+/// Turning on the two-factor requirement needs a session signed in with the
+/// second factor, so admins cannot lock themselves out.
+pub async fn admin_update_security_settings(
+    AuthenticatedUser(admin_email): AuthenticatedUser,
+    CurrentSession(session_id): CurrentSession,
+    State(app_state): State<Arc<AppState>>,
+    Json(settings): Json<SecuritySettings>,
+) -> Result<impl IntoResponse, AppError> {
+    if settings.require_admin_two_factor {
+        let signed_in_with_two_factor =
+            get_session(&app_state.db.sessions_tree, &session_id)?.is_some_and(|s| s.two_factor);
+        if !signed_in_with_two_factor {
+            return Err(AppError::Conflict(
+                "Sign in with two-factor authentication before requiring it".to_string(),
+            ));
+        }
+    }
+    save_security_settings(&app_state.db.settings_tree, &settings)?;
+    log_event(
+        LogLevel::Info,
+        format!("Security settings changed: {:?}", settings),
+        Some(admin_email.as_str()),
+    );
+    Ok(Json(settings))
+}
@@ -1,12 +1,17 @@
 use crate::{
     cache::license_cache::get_license_cache,
+    auth::{organizations::user_organization, roles::ensure_permission},
+    calc::document_numbering::format_document_number,
     errors::AppError,
     license_manager::{
         decode_license_token, decode_license_token_no_validation, list_license_files,
         read_license_file_by_name, save_license_file, LicenseData,
     },
-    middleware::AuthenticatedUser,
-    models::{CompanyInfo, PricingPreferences},
+    middleware::{AuthenticatedUser, CurrentOrganization},
+    models::{
+        roles::{Permission, UserRoles},
+        CompanyInfo, DocumentNumbering, PricingPreferences,
+    },
     state::AppState,
     utils, // Import the new CompanyInfo struct
 };
@@ -42,6 +47,7 @@ pub async fn get_calc_details(
 // Handler for uploading license files
 pub async fn upload_license(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     mut multipart: Multipart,
 ) -> Result<impl IntoResponse, AppError> {
@@ -72,10 +78,12 @@ pub async fn upload_license(
 
     // Decode and validate the license token
     // This will return AppError::LicenseExpired if expired or AppError::Unauthorized if invalid signature
-    let claims = decode_license_token(&token, app_state.jwt_license_secret.as_bytes())?;
+    let claims = decode_license_token(&token, &app_state.license_keys)?;
 
-    // Check if the license is for the authenticated user
-    if claims.sub != user_email {
+    // Check if the license is for the authenticated user or another member of the shop
+    if claims.sub != user_email
+        && !matches!(user_organization(&app_state.db, &claims.sub), Ok(o) if o == organization)
+    {
         return Err(AppError::InvalidData(
             "License is not for this user".to_string(),
         ));
@@ -87,23 +95,24 @@ pub async fn upload_license(
     // Let's rely on the error kind from decode_license_token which is handled by AppError.
 
     // If valid, save the license file
-    save_license_file(&user_email, &token, &app_state.data_dir_path).await?;
+    save_license_file(&organization, &token, &app_state.data_dir_path).await?;
 
-    // Invalidate the user's license cache entry to force a reload from the new file
+    // Invalidate the organization's license cache entry to force a reload from the new file
     let license_cache = get_license_cache(&app_state);
-    license_cache.invalidate_license(&user_email);
+    license_cache.invalidate_license(&organization);
 
     Ok(Json("License uploaded and validated successfully"))
 }
 
 pub async fn find_or_create_company_info(
     app_state: &Arc<AppState>,
-    user: &str,
+    organization: &str,
+    contact_email: &str,
 ) -> Result<CompanyInfo, AppError> {
-    // Get the user's data directory path
-    let user_dir = utils::user_personal_directory_from_email(&app_state.data_dir_path, user)
+    // Get the organization's data directory path
+    let user_dir = utils::organization_directory(&app_state.data_dir_path, organization)
         .map_err(|e| {
-            AppError::InternalServerError(format!("Failed to get user directory: {}", e))
+            AppError::InternalServerError(format!("Failed to get organization directory: {}", e))
         })?;
 
     let company_info_path = user_dir.join("company.json");
@@ -112,14 +121,15 @@ pub async fn find_or_create_company_info(
     if !company_info_path.exists() {
         // If not, create it with dummy data
         let dummy_info = CompanyInfo {
-            email: user.to_string(),
+            email: contact_email.to_string(),
             license: None, // Or some default license info if applicable
             company_name: "Company Name".to_string(), // Or derive from email
             company_addr: "".to_string(),
             current_time: Utc::now(),
             lang_output: "ua".into(),
             lang_ui: "ua".into(),
-            pricing_preferences: PricingPreferences::default()
+            pricing_preferences: PricingPreferences::default(),
+            document_numbering: DocumentNumbering::default(),
         };
 
         let dummy_json = serde_json::to_string_pretty(&dummy_info).map_err(|e| {
@@ -149,28 +159,33 @@ pub async fn find_or_create_company_info(
 // Handler to get company information from company.json
 pub async fn get_company_info(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     // Return the CompanyInfo as a JSON response
     Ok(Json(
-        find_or_create_company_info(&app_state, &user_email).await?,
+        find_or_create_company_info(&app_state, &organization, &user_email).await?,
     ))
 }
 
 pub async fn update_company_info(
     AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     Json(mut company_info_input): Json<CompanyInfo>,
 ) -> Result<impl IntoResponse, AppError> {
-    // Get the user's data directory path
-    let user_dir = utils::user_personal_directory_from_email(&app_state.data_dir_path, &user_email)
+    ensure_permission(&app_state.db, &user_email, Permission::ManageCompany)?;
+
+    // Get the organization's data directory path
+    let user_dir = utils::organization_directory(&app_state.data_dir_path, &organization)
         .map_err(|e| {
-            AppError::InternalServerError(format!("Failed to get user directory: {}", e))
+            AppError::InternalServerError(format!("Failed to get organization directory: {}", e))
         })?;
 
     let company_info_path = user_dir.join("company.json");
 
     company_info_input.current_time = Utc::now();
+    format_document_number(&company_info_input.document_numbering.format, 0, 0)?;
 
     let json = serde_json::to_string_pretty(&company_info_input).map_err(|e| {
         AppError::BadRequest(format!("Failed to serialize dummy company info: {}", e))
@@ -199,14 +214,14 @@ pub async fn update_company_info(
 
 // New handler to check if the authenticated user has an active license
 pub async fn get_active_license(
-    AuthenticatedUser(user_email): AuthenticatedUser, // Get user email from the authenticated user
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
     let license_cache = get_license_cache(&app_state);
 
     // Attempt to get the license from the cache or load it from disk.
     // The get_license method handles decoding and expiration checks.
-    match license_cache.get_license(&user_email).await {
+    match license_cache.get_license(&organization).await {
         Ok(license) => {
             // If get_license succeeds, it means a valid, non-expired license was found.
             Ok(Json(ActiveLicenseResponse {
@@ -233,22 +248,22 @@ pub async fn get_active_license(
 }
 
 pub async fn license_file_data(
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
     file_name: String,
 ) -> Result<LicenseData, AppError> {
-    let file = read_license_file_by_name(user_email, data_dir, &file_name).await?;
+    let file = read_license_file_by_name(organization, data_dir, &file_name).await?;
     let claims = decode_license_token_no_validation(&file)?;
     Ok(LicenseData::new(claims))
 }
 
 pub async fn list_licenses(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
 ) -> Result<impl IntoResponse, AppError> {
-    let license_files = list_license_files(&user_email, &app_state.data_dir_path).await?;
+    let license_files = list_license_files(&organization, &app_state.data_dir_path).await?;
     let results: Vec<Result<LicenseData, AppError>> = stream::iter(license_files)
-        .map(|f| license_file_data(&user_email, &app_state.data_dir_path, f))
+        .map(|f| license_file_data(&organization, &app_state.data_dir_path, f))
         .buffer_unordered(10)
         .collect()
         .await;
@@ -264,3 +279,15 @@ pub async fn list_licenses(
 
     Ok(Json(licenses))
 }
+
+/// Roles of the current user and what they allow.
+pub async fn get_my_roles(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    State(app_state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    let user = app_state
+        .db
+        .find_user_by_email(&user_email)?
+        .ok_or(AppError::UserNotFound)?;
+    Ok(Json(UserRoles::new(user.email, &user.roles)))
+}
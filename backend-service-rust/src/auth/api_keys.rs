@@ -0,0 +1,134 @@
+use axum::http::Method;
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    db::api_keys::{get_api_key, list_user_api_keys, save_api_key},
+    errors::AppError,
+    models::api_keys::{ApiKey, ApiKeyScope},
+    state::AppState,
+    utils::random::generate_random_id,
+};
+
+/// Header carrying the key, instead of `Authorization: Bearer <jwt>`.
+pub const API_KEY_HEADER: &str = "x-api-key";
+/// Keys are `ak_{id}.{secret}`, the prefix makes leaked keys easy to search for.
+const API_KEY_PREFIX: &str = "ak_";
+const MAX_API_KEYS_PER_USER: usize = 20;
+/// `last_used_at` is written at most this often, not on every request.
+const LAST_USED_RESOLUTION_SECONDS: i64 = 60;
+
+/// Tables and car catalog of the shop.
+const CATALOG_PATHS: &[&str] = &[
+    "/api/v1/editor/",
+    "/api/v1/user/carmakes",
+    "/api/v1/user/carmodels/",
+    "/api/v1/user/carcatalog/",
+    "/api/v1/user/carparts",
+    "/api/v1/user/all_parts",
+    "/api/v1/user/lookup_all_tables",
+    "/api/v1/user/list_class_body_types",
+    "/api/v1/user/list_all_repair_types",
+    "/api/v1/user/global/",
+    "/api/v1/user/decode_vin/",
+];
+/// Catalog paths that change files although they are GET requests.
+const CATALOG_WRITING_GET_PATHS: &[&str] = &["/api/v1/editor/fix_user_file/"];
+const CALCULATION_PATHS: &[&str] = &["/api/v1/user/calculationstore"];
+
+fn hash_key_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+fn starts_with_any(path: &str, prefixes: &[&str]) -> bool {
+    prefixes.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// Scope an API key needs for a request. Everything else, e.g. account,
+/// session and admin routes, is out of reach of API keys.
+pub fn required_scope(method: &Method, path: &str) -> Option<ApiKeyScope> {
+    let reading = method == Method::GET || method == Method::HEAD;
+    if starts_with_any(path, CATALOG_PATHS) {
+        if reading && !starts_with_any(path, CATALOG_WRITING_GET_PATHS) {
+            Some(ApiKeyScope::ReadCatalog)
+        } else {
+            Some(ApiKeyScope::WriteCatalog)
+        }
+    } else if reading && starts_with_any(path, CALCULATION_PATHS) {
+        Some(ApiKeyScope::ReadCalculations)
+    } else {
+        None
+    }
+}
+
+/// Creates a key and returns it along with its record. The key itself is
+/// not stored and cannot be shown again.
+pub fn create_api_key(
+    app_state: &AppState,
+    user_email: &str,
+    name: &str,
+    mut scopes: Vec<ApiKeyScope>,
+) -> Result<(String, ApiKey), AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidData(
+            "API key name is required".to_string(),
+        ));
+    }
+    scopes.sort_unstable();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return Err(AppError::InvalidData(
+            "API key needs at least one scope".to_string(),
+        ));
+    }
+    if list_user_api_keys(&app_state.db.api_keys_tree, user_email)?.len() >= MAX_API_KEYS_PER_USER {
+        return Err(AppError::Conflict(format!(
+            "At most {} API keys per user",
+            MAX_API_KEYS_PER_USER
+        )));
+    }
+
+    let id = generate_random_id(12);
+    let secret = generate_random_id(40);
+    let api_key = ApiKey {
+        id: id.clone(),
+        user_email: user_email.to_string(),
+        name: name.to_string(),
+        key_hash: hash_key_secret(&secret),
+        scopes,
+        created_at: Utc::now(),
+        last_used_at: None,
+    };
+    save_api_key(&app_state.db.api_keys_tree, &api_key)?;
+    Ok((format!("{}{}.{}", API_KEY_PREFIX, id, secret), api_key))
+}
+
+/// Looks up the key of a request and notes that it was used.
+pub fn authenticate_api_key(app_state: &AppState, key: &str) -> Result<ApiKey, AppError> {
+    let (id, secret) = key
+        .strip_prefix(API_KEY_PREFIX)
+        .and_then(|key| key.split_once('.'))
+        .ok_or(AppError::Unauthorized)?;
+    let mut api_key = get_api_key(&app_state.db.api_keys_tree, id)?
+        .filter(|api_key| {
+            // Constant time, so response times tell nothing about the stored hash
+            api_key
+                .key_hash
+                .as_bytes()
+                .ct_eq(hash_key_secret(secret).as_bytes())
+                .into()
+        })
+        .ok_or(AppError::Unauthorized)?;
+
+    let now = Utc::now();
+    let stale = api_key
+        .last_used_at
+        .is_none_or(|used| now - used > Duration::seconds(LAST_USED_RESOLUTION_SECONDS));
+    if stale {
+        api_key.last_used_at = Some(now);
+        save_api_key(&app_state.db.api_keys_tree, &api_key)?;
+    }
+    Ok(api_key)
+}
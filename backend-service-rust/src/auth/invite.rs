@@ -25,6 +25,7 @@ async fn try_find_invite_by_path(path: &Path) -> Result<Invite, AppError> {
 
 pub async fn process_invite(
     email: &str,
+    organization: &str,
     invite_code: &str,
     state: &AppState,
 ) -> Result<(), AppError> {
@@ -36,6 +37,7 @@ pub async fn process_invite(
             invite.evaluation_license_duration_days,
             &invite.evaluation_license_type,
             email,
+            organization,
             state,
         )
         .await?;
@@ -84,6 +86,7 @@ async fn generate_license_by_invite(
     days: isize,
     license_type: &str,
     user_email: &str,
+    organization: &str,
     state: &AppState,
 ) -> Result<(), AppError> {
     let expiry_date = Utc::now() + Duration::days(days as i64);
@@ -92,11 +95,11 @@ async fn generate_license_by_invite(
         user_email,
         expiry_date,
         Some(license_type.to_string()),
-        state.jwt_license_secret.as_bytes(),
+        &state.license_keys,
     )?;
 
     // Save the license file
-    save_license_file(user_email, &token, &state.data_dir_path).await?;
+    save_license_file(organization, &token, &state.data_dir_path).await?;
     Ok(())
 }
 
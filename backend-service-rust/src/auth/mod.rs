@@ -2,16 +2,21 @@
 use jsonwebtoken::{encode, decode, Header, EncodingKey, DecodingKey, Validation};
 use bcrypt::{hash, verify, DEFAULT_COST};
 use crate::{
-    models::Claims,
+    models::{Actor, Claims},
     errors::AppError,
 };
-use std::time::{SystemTime, UNIX_EPOCH};
 
-pub mod admin_check;
+pub mod api_keys;
 pub mod invite;
+pub mod organizations;
+pub mod passwords;
+pub mod roles;
+pub mod sessions;
+pub mod throttle;
+pub mod two_factor;
 
-// Token expiration time (e.g., 7 days)
-const ONE_WEEK: usize = 60 * 60 * 24 * 7;
+// Access tokens are short-lived, sessions continue through refresh tokens
+pub const ACCESS_TOKEN_TTL: usize = 60 * 15;
 
 // Auth struct holds the JWT keys
 pub struct Auth {
@@ -39,17 +44,23 @@ impl Auth {
         verify(password, hash).map_err(AppError::BcryptError)
     }
 
-    /// Creates a new JWT token for the given user email.
-    pub fn create_token(&self, user_email: &str) -> Result<String, AppError> {
-        // Calculate expiration time
-        let expiration_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap() // Safe to unwrap unless system time is before epoch
-            .as_secs() as usize + ONE_WEEK;
-
+    /// Creates a new JWT access token for the given user email and session,
+    /// valid until `exp` (seconds since the epoch). `impersonator` is the admin
+    /// acting as the user, if any.
+    pub fn create_token(
+        &self,
+        user_email: &str,
+        session_id: &str,
+        impersonator: Option<&str>,
+        exp: usize,
+    ) -> Result<String, AppError> {
         let claims = Claims {
             sub: user_email.to_owned(), // Subject is the user's email
-            exp: expiration_time,       // Expiration time
+            exp,                        // Expiration time
+            sid: Some(session_id.to_owned()),
+            act: impersonator.map(|admin| Actor {
+                sub: admin.to_owned(),
+            }),
         };
 
         // Encode the claims into a JWT
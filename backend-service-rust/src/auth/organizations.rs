@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::{
+    db::{
+        attachment::{list_all_attachments_for_all_users, update_attachment},
+        organizations::{has_owner_entries, move_owner_entries, save_organization},
+        users::AppDb,
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::{organizations::Organization, User},
+    utils::{legacy_user_directory, organization_directory, ORGANIZATIONS},
+};
+
+const ORGANIZATIONS_MIGRATION: &str = "user_directories_to_organizations";
+
+pub fn create_organization(db: &AppDb, name: &str) -> Result<Organization, AppError> {
+    let organization = Organization {
+        id: Uuid::new_v4().to_string(),
+        name: name.to_string(),
+        created_at: Utc::now(),
+    };
+    save_organization(&db.organizations_tree, &organization)?;
+    Ok(organization)
+}
+
+/// Gives a new account a shop of its own. Members are added to other shops
+/// by their owners later.
+pub fn assign_personal_organization(db: &AppDb, user: &mut User) -> Result<(), AppError> {
+    if user.organization.is_none() {
+        user.organization = Some(create_organization(db, &user.email)?.id);
+    }
+    Ok(())
+}
+
+/// Trees whose records are keyed by the owning organization.
+fn owner_keyed_trees(db: &AppDb) -> [&sled::Tree; 7] {
+    [
+        &db.calculations_tree,
+        &db.estimate_transitions_tree,
+        &db.customers_tree,
+        &db.vehicles_tree,
+        &db.document_jobs_tree,
+        &db.document_counters_tree,
+        &db.document_numbers_tree,
+    ]
+}
+
+fn contains_files(dir: &Path) -> Result<bool, AppError> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !path.is_dir() || contains_files(&path)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Whether the organization has any files or stored records, such a shop is
+/// never deleted just because its last member left.
+pub fn organization_has_data(
+    db: &AppDb,
+    data_dir: &PathBuf,
+    organization: &str,
+) -> Result<bool, AppError> {
+    for tree in owner_keyed_trees(db) {
+        if has_owner_entries(tree, organization)? {
+            return Ok(true);
+        }
+    }
+    contains_files(&organization_directory(data_dir, organization)?)
+}
+
+/// The organization whose data the user works with.
+pub fn user_organization(db: &AppDb, email: &str) -> Result<String, AppError> {
+    db.find_user_by_email(email)?
+        .ok_or(AppError::UserNotFound)?
+        .organization
+        .ok_or_else(|| AppError::InternalServerError(format!("{} has no organization", email)))
+}
+
+/// Moves what is left in `from` into `to`, keeping entries that already made
+/// it there. Files present in both stay in `from`.
+fn merge_directory(from: &Path, to: &Path) -> Result<(), AppError> {
+    if !to.exists() {
+        std::fs::rename(from, to)?;
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(from)? {
+        let path = entry?.path();
+        let target = to.join(path.file_name().unwrap_or_default());
+        if path.is_dir() && target.is_dir() {
+            merge_directory(&path, &target)?;
+        } else if !target.exists() {
+            std::fs::rename(&path, &target)?;
+        } else {
+            log_event(
+                LogLevel::Warn,
+                format!("{} left in place, {} exists", path.display(), target.display()),
+                None::<String>,
+            );
+        }
+    }
+    // Only succeeds once everything was moved
+    let _ = std::fs::remove_dir(from);
+    Ok(())
+}
+
+/// Moves the data of every account from `users/{email}` into an organization
+/// of its own, once: the directory goes to `orgs/{id}` and stored records
+/// keyed by email are keyed by the organization id.
+///
+/// The organization id is stored with the user before anything moves and
+/// every step can run again, so a start interrupted halfway resumes the
+/// accounts it did not finish.
+pub fn migrate_to_organizations(db: &AppDb, data_dir: &Path) -> Result<(), AppError> {
+    if db.migrations_tree.contains_key(ORGANIZATIONS_MIGRATION)? {
+        return Ok(());
+    }
+
+    let keyed_by_owner = owner_keyed_trees(db);
+    let organizations_dir = data_dir.join(ORGANIZATIONS);
+    std::fs::create_dir_all(&organizations_dir)?;
+
+    let emails = db.get_all_user_emails()?;
+    for email in &emails {
+        let Some(mut user) = db.find_user_by_email(email)? else {
+            continue;
+        };
+        let organization = match user.organization.clone() {
+            Some(organization) => organization,
+            None => {
+                assign_personal_organization(db, &mut user)?;
+                let organization = user.organization.clone().unwrap_or_default();
+                db.set_user_organization(email, &organization)?;
+                organization
+            }
+        };
+
+        let legacy_dir = legacy_user_directory(data_dir, email);
+        let organization_dir = organizations_dir.join(&organization);
+        if legacy_dir.exists() {
+            merge_directory(&legacy_dir, &organization_dir)?;
+        }
+
+        for tree in keyed_by_owner {
+            move_owner_entries(tree, email, &organization)?;
+        }
+        let legacy_prefix = legacy_dir.to_string_lossy().to_string();
+        for (_, mut handle) in list_all_attachments_for_all_users(&db.attachments_tree)? {
+            if handle.owner == *email {
+                handle.owner = organization.clone();
+                if let Some(rest) = handle.file_path.strip_prefix(&legacy_prefix) {
+                    handle.file_path = format!("{}{}", organization_dir.to_string_lossy(), rest);
+                }
+                update_attachment(&db.attachments_tree, &handle)?;
+            }
+        }
+    }
+
+    db.migrations_tree
+        .insert(ORGANIZATIONS_MIGRATION, Utc::now().to_rfc3339().as_bytes())?;
+    db.migrations_tree.flush()?;
+    log_event(
+        LogLevel::Info,
+        format!("Data of {} accounts moved to organizations", emails.len()),
+        None::<String>,
+    );
+    Ok(())
+}
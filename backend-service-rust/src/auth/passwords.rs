@@ -0,0 +1,187 @@
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    auth::sessions::{revoke_session, revoke_user_sessions},
+    db::{
+        password_resets::{remove_password_resets, save_password_reset, take_password_reset},
+        sessions::list_user_sessions,
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    mail::Mail,
+    models::sessions::PasswordReset,
+    state::AppState,
+    utils::random::generate_random_id,
+};
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+/// bcrypt ignores everything after 72 bytes.
+const MAX_PASSWORD_BYTES: usize = 72;
+const RESET_TOKEN_TTL_MINUTES: i64 = 60;
+
+const COMMON_PASSWORDS: &[&str] = &[
+    "12345678",
+    "123456789",
+    "1234567890",
+    "password",
+    "password1",
+    "qwerty123",
+    "qwertyuiop",
+    "1q2w3e4r",
+    "1qaz2wsx",
+    "abc12345",
+    "iloveyou1",
+    "11111111",
+    "00000000",
+    "87654321",
+];
+
+/// Rules for every new password: registration, bulk creation, changes and resets.
+pub fn check_password_strength(password: &str) -> Result<(), AppError> {
+    let weak = |reason: &str| Err(AppError::InvalidData(format!("Weak password: {}", reason)));
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return weak(&format!("use at least {} characters", MIN_PASSWORD_LENGTH));
+    }
+    if password.len() > MAX_PASSWORD_BYTES {
+        return weak(&format!("use at most {} bytes", MAX_PASSWORD_BYTES));
+    }
+    if !password.chars().any(char::is_alphabetic) || !password.chars().any(|c| c.is_ascii_digit()) {
+        return weak("use both letters and digits");
+    }
+    let lowercase = password.to_lowercase();
+    if COMMON_PASSWORDS.contains(&lowercase.as_str()) {
+        return weak("it is too common");
+    }
+    Ok(())
+}
+
+fn hash_reset_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+async fn send_password_changed_mail(app_state: &AppState, email: &str) {
+    let mail = Mail {
+        to: email.to_string(),
+        subject: "Your password was changed".to_string(),
+        text: "The password of your account was changed and you were signed out on other devices. \
+               If it was not you, reset your password and contact support."
+            .to_string(),
+    };
+    if let Err(e) = app_state.mailer.send(&mail).await {
+        log_event(
+            LogLevel::Warn,
+            format!("Password change notice not sent: {}", e),
+            Some(email),
+        );
+    }
+}
+
+/// Stores the new password after checking it, ends the other sessions of the
+/// user and lets them know by mail. `keep_session` stays signed in.
+pub async fn set_password(
+    app_state: &AppState,
+    email: &str,
+    password: &str,
+    keep_session: Option<&str>,
+) -> Result<(), AppError> {
+    check_password_strength(password)?;
+    let hashed_password = app_state.auth.hash_password(password)?;
+    app_state
+        .db
+        .change_user_password_hash(email, hashed_password)?;
+    match keep_session {
+        Some(keep) => {
+            for session in list_user_sessions(&app_state.db.sessions_tree, email)? {
+                if session.id != keep {
+                    revoke_session(app_state, &session.id)?;
+                }
+            }
+        }
+        None => {
+            revoke_user_sessions(app_state, email)?;
+        }
+    }
+    remove_password_resets(&app_state.db.password_resets_tree, |r| {
+        r.user_email == email
+    })?;
+    send_password_changed_mail(app_state, email).await;
+    Ok(())
+}
+
+/// Mails a reset token to the user, replacing earlier ones. Unknown emails
+/// are ignored so the response does not tell which accounts exist.
+pub async fn request_password_reset(app_state: &AppState, email: &str) -> Result<(), AppError> {
+    if app_state.db.find_user_by_email(email)?.is_none() {
+        log_event(
+            LogLevel::Info,
+            "Password reset requested for unknown account",
+            Some(email),
+        );
+        return Ok(());
+    }
+    remove_password_resets(&app_state.db.password_resets_tree, |r| {
+        r.user_email == email
+    })?;
+
+    let token = generate_random_id(48);
+    let now = Utc::now();
+    save_password_reset(
+        &app_state.db.password_resets_tree,
+        &hash_reset_token(&token),
+        &PasswordReset {
+            user_email: email.to_string(),
+            created_at: now,
+            expires_at: now + Duration::minutes(RESET_TOKEN_TTL_MINUTES),
+        },
+    )?;
+
+    let link = match &app_state.password_reset_url {
+        Some(url) => format!("{}?token={}", url, token),
+        None => format!("Reset token: {}", token),
+    };
+    app_state
+        .mailer
+        .send(&Mail {
+            to: email.to_string(),
+            subject: "Password reset".to_string(),
+            text: format!(
+                "Someone asked to reset the password of your account. If it was you, set a new one here \
+                 within {} minutes:\n\n{}\n\nOtherwise ignore this mail, your password stays the same.",
+                RESET_TOKEN_TTL_MINUTES, link
+            ),
+        })
+        .await?;
+    log_event(LogLevel::Info, "Password reset mail sent", Some(email));
+    Ok(())
+}
+
+/// Sets a new password with a token from the reset mail. Each token works once.
+pub async fn reset_password(
+    app_state: &AppState,
+    token: &str,
+    new_password: &str,
+) -> Result<(), AppError> {
+    let invalid = || AppError::InvalidData("Invalid or expired reset token".to_string());
+    let reset = take_password_reset(&app_state.db.password_resets_tree, &hash_reset_token(token))?
+        .ok_or_else(invalid)?;
+    if reset.expires_at < Utc::now() {
+        return Err(invalid());
+    }
+    if let Err(e) = check_password_strength(new_password) {
+        // A rejected password must not use up the token
+        save_password_reset(
+            &app_state.db.password_resets_tree,
+            &hash_reset_token(token),
+            &reset,
+        )?;
+        return Err(e);
+    }
+    set_password(app_state, &reset.user_email, new_password, None).await
+}
+
+/// Drops reset tokens that expired unused.
+pub fn cleanup_password_resets(app_state: &AppState) -> Result<(), AppError> {
+    let now = Utc::now();
+    remove_password_resets(&app_state.db.password_resets_tree, |r| r.expires_at < now)
+}
@@ -0,0 +1,97 @@
+use std::path::Path;
+
+use chrono::Utc;
+
+use crate::{
+    db::users::AppDb,
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::{
+        roles::{Permission, Role},
+        User,
+    },
+};
+
+const ADMINS_FILE_MIGRATION: &str = "admins_file_roles";
+
+pub fn has_permission(db: &AppDb, email: &str, permission: Permission) -> Result<bool, AppError> {
+    Ok(db
+        .find_user_by_email(email)?
+        .is_some_and(|user| user.has_permission(permission)))
+}
+
+/// For checks inside handlers, routes are guarded by `permission_middleware`.
+pub fn ensure_permission(db: &AppDb, email: &str, permission: Permission) -> Result<(), AppError> {
+    if has_permission(db, email, permission)? {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden)
+    }
+}
+
+/// Adds the roles granted to the email before the account was registered.
+pub fn apply_pending_roles(db: &AppDb, user: &mut User) -> Result<(), AppError> {
+    if let Some(pending) = db.role_grants_tree.remove(user.email.as_bytes())? {
+        let roles: Vec<Role> = serde_json::from_slice(&pending)?;
+        user.roles.extend(roles);
+        user.roles.sort();
+        user.roles.dedup();
+        db.role_grants_tree.flush()?;
+    }
+    Ok(())
+}
+
+/// Moves admin rights from `admins.txt` to user roles, once. Listed emails
+/// without an account get the role when they register. Existing accounts
+/// without roles become shop owners, as new accounts are.
+pub fn migrate_admins_file(db: &AppDb, admin_file_path: &Path) -> Result<(), AppError> {
+    if db.migrations_tree.contains_key(ADMINS_FILE_MIGRATION)? {
+        return Ok(());
+    }
+
+    let admins: Vec<String> = match std::fs::read_to_string(admin_file_path) {
+        Ok(content) => content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    for email in db.get_all_user_emails()? {
+        let Some(user) = db.find_user_by_email(&email)? else {
+            continue;
+        };
+        let mut roles = user.roles;
+        if roles.is_empty() {
+            roles.push(Role::ShopOwner);
+        }
+        if admins.contains(&email) {
+            roles.push(Role::Admin);
+        }
+        db.set_user_roles(&email, roles)?;
+    }
+    for email in &admins {
+        if db.find_user_by_email(email)?.is_none() {
+            db.role_grants_tree
+                .insert(email.as_bytes(), serde_json::to_vec(&[Role::Admin])?)?;
+        }
+    }
+    db.role_grants_tree.flush()?;
+
+    db.migrations_tree
+        .insert(ADMINS_FILE_MIGRATION, Utc::now().to_rfc3339().as_bytes())?;
+    db.migrations_tree.flush()?;
+    log_event(
+        LogLevel::Info,
+        format!(
+            "Admin rights of {} accounts moved from {} to roles",
+            admins.len(),
+            admin_file_path.display()
+        ),
+        None::<String>,
+    );
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use sled::IVec;
+
+use crate::{
+    auth::ACCESS_TOKEN_TTL,
+    db::sessions::{
+        cleanup_revoked_sessions, get_session_entry, insert_revoked_session, list_all_sessions,
+        list_user_sessions, remove_session, save_session, swap_session,
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::{
+        sessions::{RevokedSession, Session},
+        LoginResponse,
+    },
+    state::AppState,
+    utils::random::generate_random_id,
+};
+
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+/// Impersonation sessions cannot be refreshed past this.
+const IMPERSONATION_TTL_MINUTES: i64 = 60;
+/// A refresh token replaced this recently may still come from a parallel
+/// request of the same client, it is refused without revoking the session.
+const REFRESH_REUSE_GRACE_SECONDS: i64 = 30;
+
+fn hash_refresh_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Refresh tokens are `{session id}.{secret}`. With `stored`, the session is
+/// only rotated if nothing changed it since it was read.
+fn issue_tokens(
+    app_state: &AppState,
+    session: &mut Session,
+    stored: Option<IVec>,
+) -> Result<LoginResponse, AppError> {
+    let secret = generate_random_id(48);
+    session.previous_refresh_token_hash = Some(std::mem::replace(
+        &mut session.refresh_token_hash,
+        hash_refresh_secret(&secret),
+    ))
+    .filter(|hash| !hash.is_empty());
+    match stored {
+        // Refreshed in parallel or revoked meanwhile
+        Some(stored) => {
+            if !swap_session(&app_state.db.sessions_tree, stored, session)? {
+                return Err(AppError::Unauthorized);
+            }
+        }
+        None => save_session(&app_state.db.sessions_tree, session)?,
+    }
+    // Access tokens do not outlive their session
+    let now = Utc::now().timestamp();
+    let exp = (now + ACCESS_TOKEN_TTL as i64).min(session.expires_at.timestamp());
+    Ok(LoginResponse {
+        token: app_state.auth.create_token(
+            &session.user_email,
+            &session.id,
+            session.impersonated_by.as_deref(),
+            exp as usize,
+        )?,
+        refresh_token: format!("{}.{}", session.id, secret),
+        expires_in: (exp - now).max(0) as usize,
+    })
+}
+
+fn new_session(user_email: &str, user_agent: Option<String>, lifetime: Duration) -> Session {
+    let now = Utc::now();
+    Session {
+        id: generate_random_id(16),
+        user_email: user_email.to_string(),
+        refresh_token_hash: String::new(),
+        previous_refresh_token_hash: None,
+        user_agent,
+        two_factor: false,
+        impersonated_by: None,
+        created_at: now,
+        refreshed_at: now,
+        expires_at: now + lifetime,
+    }
+}
+
+/// Starts a session after a successful login, `two_factor` when the second
+/// factor was checked too.
+pub fn start_session(
+    app_state: &AppState,
+    user_email: &str,
+    user_agent: Option<String>,
+    two_factor: bool,
+) -> Result<LoginResponse, AppError> {
+    let mut session = new_session(
+        user_email,
+        user_agent,
+        Duration::days(REFRESH_TOKEN_TTL_DAYS),
+    );
+    session.two_factor = two_factor;
+    issue_tokens(app_state, &mut session, None)
+}
+
+/// Starts a session of `user_email` for an admin. Its tokens name the admin
+/// in the `act` claim and it ends after an hour.
+pub fn start_impersonation(
+    app_state: &AppState,
+    user_email: &str,
+    admin_email: &str,
+    user_agent: Option<String>,
+) -> Result<LoginResponse, AppError> {
+    let mut session = new_session(
+        user_email,
+        user_agent,
+        Duration::minutes(IMPERSONATION_TTL_MINUTES),
+    );
+    session.impersonated_by = Some(admin_email.to_string());
+    issue_tokens(app_state, &mut session, None)
+}
+
+/// Exchanges a refresh token for a new token pair. A token that was already
+/// used means it leaked, so the whole session is revoked.
+pub fn refresh_session(
+    app_state: &AppState,
+    refresh_token: &str,
+) -> Result<LoginResponse, AppError> {
+    let (id, secret) = refresh_token
+        .split_once('.')
+        .ok_or(AppError::Unauthorized)?;
+    let (stored, mut session) =
+        get_session_entry(&app_state.db.sessions_tree, id)?.ok_or(AppError::Unauthorized)?;
+    let now = Utc::now();
+    if session.expires_at < now {
+        revoke_session(app_state, &session.id)?;
+        return Err(AppError::Unauthorized);
+    }
+
+    let hash = hash_refresh_secret(secret);
+    if hash != session.refresh_token_hash {
+        let reused = session.previous_refresh_token_hash.as_deref() == Some(hash.as_str());
+        if reused && now - session.refreshed_at > Duration::seconds(REFRESH_REUSE_GRACE_SECONDS) {
+            log_event(
+                LogLevel::Warn,
+                format!("Refresh token reused, session {} revoked", session.id),
+                Some(session.user_email.as_str()),
+            );
+            revoke_session(app_state, &session.id)?;
+        }
+        return Err(AppError::Unauthorized);
+    }
+
+    session.refreshed_at = now;
+    issue_tokens(app_state, &mut session, Some(stored))
+}
+
+/// Ends a session, its access tokens are refused from now on.
+pub fn revoke_session(app_state: &AppState, id: &str) -> Result<(), AppError> {
+    let Some(session) = remove_session(&app_state.db.sessions_tree, id)? else {
+        return Ok(());
+    };
+    let now = Utc::now();
+    insert_revoked_session(
+        &app_state.db.revoked_sessions_tree,
+        id,
+        &RevokedSession {
+            user_email: session.user_email,
+            revoked_at: now,
+            expires_at: now + Duration::seconds(ACCESS_TOKEN_TTL as i64),
+        },
+    )
+}
+
+/// Ends all sessions of a user. Returns how many were ended.
+pub fn revoke_user_sessions(app_state: &AppState, user_email: &str) -> Result<usize, AppError> {
+    let sessions = list_user_sessions(&app_state.db.sessions_tree, user_email)?;
+    for session in &sessions {
+        revoke_session(app_state, &session.id)?;
+    }
+    log_event(
+        LogLevel::Info,
+        format!("{} sessions revoked", sessions.len()),
+        Some(user_email),
+    );
+    Ok(sessions.len())
+}
+
+/// Removes expired sessions and revocation list entries.
+pub fn cleanup_sessions(app_state: &AppState) -> Result<(), AppError> {
+    let now = Utc::now();
+    for session in list_all_sessions(&app_state.db.sessions_tree)? {
+        if session.expires_at < now {
+            remove_session(&app_state.db.sessions_tree, &session.id)?;
+        }
+    }
+    cleanup_revoked_sessions(&app_state.db.revoked_sessions_tree)
+}
@@ -0,0 +1,289 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+};
+
+use axum::http::HeaderMap;
+use chrono::{Duration, Utc};
+use sled::Tree;
+
+use crate::{
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::sessions::{LoginAttempts, LoginLockout},
+};
+
+/// Failed logins of one account before it is locked.
+const ACCOUNT_MAX_FAILURES: u32 = 5;
+/// Failed logins from one address before it is locked, it may try several accounts.
+const IP_MAX_FAILURES: u32 = 20;
+/// Failures older than this no longer count.
+const FAILURE_WINDOW_MINUTES: i64 = 15;
+const BASE_LOCKOUT_SECONDS: i64 = 60;
+const MAX_LOCKOUT_SECONDS: i64 = 24 * 60 * 60;
+/// A day without failures forgets earlier lockouts.
+const LOCKOUT_MEMORY_HOURS: i64 = 24;
+
+pub const ACCOUNT: &str = "account";
+pub const IP: &str = "ip";
+
+fn throttle_key(kind: &str, id: &str) -> String {
+    format!("{}:{}", kind, id)
+}
+
+/// The keys a login counts against, with their limits.
+fn limits(account: &str, ip: &IpAddr) -> [(&'static str, String, u32); 2] {
+    [
+        (ACCOUNT, account.to_string(), ACCOUNT_MAX_FAILURES),
+        (IP, ip.to_string(), IP_MAX_FAILURES),
+    ]
+}
+
+fn lockout_duration(lockouts: u32) -> Duration {
+    let seconds = BASE_LOCKOUT_SECONDS
+        .saturating_mul(1i64 << lockouts.saturating_sub(1).min(30))
+        .min(MAX_LOCKOUT_SECONDS);
+    Duration::seconds(seconds)
+}
+
+/// Counts failed logins per account and per IP address and locks them out for
+/// exponentially growing periods. Kept in memory; with a store the counts
+/// survive restarts.
+pub struct LoginThrottle {
+    attempts: Mutex<HashMap<String, LoginAttempts>>,
+    store: Option<Tree>,
+    /// Proxies in front of the server that append to `X-Forwarded-For`, 0 when
+    /// the header is not trusted.
+    trusted_proxy_hops: usize,
+}
+
+impl LoginThrottle {
+    pub fn new(store: Option<Tree>, trusted_proxy_hops: usize) -> Result<Self, AppError> {
+        let mut attempts = HashMap::new();
+        if let Some(tree) = &store {
+            for item_result in tree.iter() {
+                let (key, value) = item_result?;
+                attempts.insert(
+                    String::from_utf8_lossy(&key).to_string(),
+                    serde_json::from_slice(&value)?,
+                );
+            }
+        }
+        Ok(Self {
+            attempts: Mutex::new(attempts),
+            store,
+            trusted_proxy_hops,
+        })
+    }
+
+    /// The address the nearest trusted proxy saw. Each proxy appends the address
+    /// it got the request from, so entries left of that were sent by the client
+    /// and are ignored.
+    pub fn client_ip(&self, headers: &HeaderMap, peer: SocketAddr) -> IpAddr {
+        if self.trusted_proxy_hops > 0 {
+            let forwarded: Vec<&str> = headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .collect();
+            let ip = forwarded
+                .len()
+                .checked_sub(self.trusted_proxy_hops)
+                .and_then(|i| forwarded[i].trim().parse().ok());
+            if let Some(ip) = ip {
+                return ip;
+            }
+        }
+        peer.ip()
+    }
+
+    fn persist(&self, key: &str, attempts: Option<&LoginAttempts>) -> Result<(), AppError> {
+        if let Some(tree) = &self.store {
+            match attempts {
+                Some(attempts) => tree.insert(key.as_bytes(), serde_json::to_vec(attempts)?)?,
+                None => tree.remove(key.as_bytes())?,
+            };
+            tree.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Refuses the login while the account or the address is locked. Otherwise
+    /// the attempt is counted as a failure right away, under the same lock, so
+    /// concurrent requests cannot each get a guess; `record_success` and
+    /// `release` take it back.
+    pub fn check(&self, account: &str, ip: &IpAddr) -> Result<(), AppError> {
+        let now = Utc::now();
+        let limits = limits(account, ip);
+        let mut attempts = self.attempts.lock().unwrap();
+        let mut retry_at = None;
+        for (kind, id, limit) in &limits {
+            let Some(entry) = attempts.get_mut(&throttle_key(kind, id)) else {
+                continue;
+            };
+            if now - entry.last_failure > Duration::hours(LOCKOUT_MEMORY_HOURS) {
+                entry.lockouts = 0;
+            }
+            if now - entry.last_failure > Duration::minutes(FAILURE_WINDOW_MINUTES) {
+                entry.failures = 0;
+            }
+            let until = match entry.locked_until.filter(|until| *until > now) {
+                Some(until) => Some(until),
+                // The remaining attempts are taken by logins still running
+                None if entry.failures >= *limit => {
+                    Some(entry.last_failure + Duration::minutes(FAILURE_WINDOW_MINUTES))
+                }
+                None => None,
+            };
+            retry_at = retry_at.max(until);
+        }
+        if let Some(until) = retry_at {
+            return Err(AppError::TooManyRequests(
+                (until - now).num_seconds().max(1) as u64,
+            ));
+        }
+        let mut reserved = Vec::new();
+        for (kind, id, _) in &limits {
+            let key = throttle_key(kind, id);
+            let entry = attempts.entry(key.clone()).or_insert(LoginAttempts {
+                failures: 0,
+                last_failure: now,
+                lockouts: 0,
+                locked_until: None,
+            });
+            entry.failures += 1;
+            entry.last_failure = now;
+            reserved.push((key, entry.clone()));
+        }
+        drop(attempts);
+        for (key, entry) in reserved {
+            self.persist(&key, Some(&entry))?;
+        }
+        Ok(())
+    }
+
+    /// Takes back one attempt counted by `check`.
+    fn uncount(&self, key: &str) -> Result<(), AppError> {
+        let mut attempts = self.attempts.lock().unwrap();
+        let Some(entry) = attempts.get_mut(key) else {
+            return Ok(());
+        };
+        entry.failures = entry.failures.saturating_sub(1);
+        if entry.failures == 0 && entry.lockouts == 0 && entry.locked_until.is_none() {
+            attempts.remove(key);
+            drop(attempts);
+            return self.persist(key, None);
+        }
+        let entry = entry.clone();
+        drop(attempts);
+        self.persist(key, Some(&entry))
+    }
+
+    /// Logs a failed login, which `check` already counted, and locks the
+    /// account or the address once it used up its attempts.
+    pub fn record_failure(&self, account: &str, ip: &IpAddr) -> Result<(), AppError> {
+        log_event(
+            LogLevel::Warn,
+            format!("Failed login from {}", ip),
+            Some(account),
+        );
+        let now = Utc::now();
+        for (kind, id, limit) in limits(account, ip) {
+            let key = throttle_key(kind, &id);
+            let mut attempts = self.attempts.lock().unwrap();
+            let Some(entry) = attempts.get_mut(&key).filter(|e| e.failures >= limit) else {
+                continue;
+            };
+            entry.failures = 0;
+            entry.lockouts += 1;
+            let until = now + lockout_duration(entry.lockouts);
+            entry.locked_until = Some(until);
+            let entry = entry.clone();
+            drop(attempts);
+            self.persist(&key, Some(&entry))?;
+            log_event(
+                LogLevel::Warn,
+                format!(
+                    "Login locked for {} {} until {}",
+                    kind,
+                    id,
+                    until.to_rfc3339()
+                ),
+                Some(account),
+            );
+        }
+        Ok(())
+    }
+
+    /// Takes back the attempt of a login that neither failed nor finished yet,
+    /// like a right password still waiting for its second factor.
+    pub fn release(&self, account: &str, ip: &IpAddr) -> Result<(), AppError> {
+        for (kind, id, _) in limits(account, ip) {
+            self.uncount(&throttle_key(kind, &id))?;
+        }
+        Ok(())
+    }
+
+    /// Forgets the failures of the account after it logged in. Failures of the
+    /// address stay, a valid login must not hide guessing at other accounts;
+    /// only the attempt of this login is taken back.
+    pub fn record_success(&self, account: &str, ip: &IpAddr) -> Result<(), AppError> {
+        self.uncount(&throttle_key(IP, &ip.to_string()))?;
+        self.unlock(ACCOUNT, account).map(|_| ())
+    }
+
+    /// Lifts a lockout and forgets the failures. Returns whether there were any.
+    pub fn unlock(&self, kind: &str, id: &str) -> Result<bool, AppError> {
+        let key = throttle_key(kind, id);
+        let removed = self.attempts.lock().unwrap().remove(&key).is_some();
+        if removed {
+            self.persist(&key, None)?;
+        }
+        Ok(removed)
+    }
+
+    /// Accounts and addresses that are locked now.
+    pub fn lockouts(&self) -> Vec<LoginLockout> {
+        let now = Utc::now();
+        let mut lockouts: Vec<LoginLockout> = self
+            .attempts
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, a)| a.locked_until.is_some_and(|until| until > now))
+            .filter_map(|(key, attempts)| {
+                let (kind, id) = key.split_once(':')?;
+                Some(LoginLockout {
+                    kind: kind.to_string(),
+                    id: id.to_string(),
+                    attempts: attempts.clone(),
+                })
+            })
+            .collect();
+        lockouts.sort_by_key(|l| l.attempts.locked_until);
+        lockouts
+    }
+
+    /// Drops entries that no longer affect anything.
+    pub fn cleanup(&self) -> Result<(), AppError> {
+        let now = Utc::now();
+        let stale: Vec<String> = self
+            .attempts
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, a)| {
+                now - a.last_failure > Duration::hours(LOCKOUT_MEMORY_HOURS)
+                    && a.locked_until.is_none_or(|until| until < now)
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in stale {
+            self.attempts.lock().unwrap().remove(&key);
+            self.persist(&key, None)?;
+        }
+        Ok(())
+    }
+}
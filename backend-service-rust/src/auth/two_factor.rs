@@ -0,0 +1,262 @@
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{
+    db::{
+        sessions::{get_session_entry, swap_session},
+        two_factor::{
+            get_security_settings, remove_login_challenges, save_login_challenge,
+            take_login_challenge,
+        },
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::{
+        two_factor::{LoginChallenge, TwoFactor, TwoFactorEnrollment},
+        User,
+    },
+    state::AppState,
+    utils::random::generate_random_id,
+};
+
+/// Shown as the account issuer in authenticator apps.
+const TOTP_ISSUER: &str = "Autolab";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+/// Codes of the neighbouring time steps are accepted, for clock drift.
+const TOTP_SKEW_STEPS: u64 = 1;
+const RECOVERY_CODE_COUNT: usize = 10;
+const LOGIN_CHALLENGE_TTL_MINUTES: i64 = 5;
+
+fn hash_secret(value: &str) -> String {
+    format!("{:x}", Sha256::digest(value.as_bytes()))
+}
+
+/// Codes are typed with spaces and dashes, e.g. `123 456` or `ABCDE-FGHIJ`.
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+fn totp(secret: &str, email: &str) -> Result<TOTP, AppError> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| AppError::InternalServerError(format!("Invalid TOTP secret: {}", e)))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        email.to_string(),
+    )
+    .map_err(|e| AppError::InvalidData(format!("Cannot set up TOTP for {}: {}", email, e)))
+}
+
+/// Time step of a valid TOTP code that was not used yet.
+fn matching_step(two_factor: &TwoFactor, email: &str, code: &str) -> Result<Option<u64>, AppError> {
+    let totp = totp(&two_factor.secret, email)?;
+    let current = Utc::now().timestamp() as u64 / TOTP_STEP_SECONDS;
+    let step = (current - TOTP_SKEW_STEPS..=current + TOTP_SKEW_STEPS)
+        .filter(|step| two_factor.last_used_step < Some(*step))
+        .find(|step| totp.generate(step * TOTP_STEP_SECONDS) == code);
+    Ok(step)
+}
+
+/// New recovery codes and their hashes. Each code works once instead of a TOTP code.
+fn new_recovery_codes() -> (Vec<String>, Vec<String>) {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code = generate_random_id(10);
+            let hash = hash_secret(&code);
+            (format!("{}-{}", &code[..5], &code[5..]), hash)
+        })
+        .unzip()
+}
+
+fn enabled_two_factor(user: &User) -> Result<TwoFactor, AppError> {
+    user.two_factor
+        .clone()
+        .filter(|t| t.enabled)
+        .ok_or_else(|| AppError::Conflict("Two-factor authentication is not enabled".to_string()))
+}
+
+/// Checks a TOTP or recovery code of the user and uses it up.
+pub fn verify_code(app_state: &AppState, user: &User, code: &str) -> Result<bool, AppError> {
+    let mut two_factor = enabled_two_factor(user)?;
+    let code = normalize_code(code);
+    if code.len() == TOTP_DIGITS {
+        let Some(step) = matching_step(&two_factor, &user.email, &code)? else {
+            return Ok(false);
+        };
+        two_factor.last_used_step = Some(step);
+    } else {
+        let hash = hash_secret(&code);
+        let Some(index) = two_factor
+            .recovery_code_hashes
+            .iter()
+            .position(|h| *h == hash)
+        else {
+            return Ok(false);
+        };
+        two_factor.recovery_code_hashes.remove(index);
+        log_event(
+            LogLevel::Warn,
+            format!(
+                "Recovery code used, {} left",
+                two_factor.recovery_code_hashes.len()
+            ),
+            Some(user.email.as_str()),
+        );
+    }
+    app_state
+        .db
+        .set_user_two_factor(&user.email, Some(two_factor))?;
+    Ok(true)
+}
+
+/// Creates a new secret, which starts working once confirmed with a code.
+pub fn begin_enrollment(
+    app_state: &AppState,
+    user: &User,
+) -> Result<TwoFactorEnrollment, AppError> {
+    if user.has_two_factor() {
+        return Err(AppError::Conflict(
+            "Two-factor authentication is already enabled".to_string(),
+        ));
+    }
+    let Secret::Encoded(secret) = Secret::generate_secret().to_encoded() else {
+        unreachable!("to_encoded returns an encoded secret");
+    };
+    let otpauth_url = totp(&secret, &user.email)?.get_url();
+    app_state.db.set_user_two_factor(
+        &user.email,
+        Some(TwoFactor {
+            secret: secret.clone(),
+            enabled: false,
+            created_at: Utc::now(),
+            recovery_code_hashes: Vec::new(),
+            last_used_step: None,
+        }),
+    )?;
+    Ok(TwoFactorEnrollment {
+        secret,
+        otpauth_url,
+    })
+}
+
+/// Enables the second factor after the first valid code and returns the
+/// recovery codes, which are shown only this once.
+pub fn confirm_enrollment(
+    app_state: &AppState,
+    user: &User,
+    code: &str,
+) -> Result<Vec<String>, AppError> {
+    let mut two_factor = user
+        .two_factor
+        .clone()
+        .filter(|t| !t.enabled)
+        .ok_or_else(|| AppError::Conflict("No two-factor enrollment in progress".to_string()))?;
+    let Some(step) = matching_step(&two_factor, &user.email, &normalize_code(code))? else {
+        return Err(AppError::InvalidData("Invalid code".to_string()));
+    };
+    let (codes, hashes) = new_recovery_codes();
+    two_factor.enabled = true;
+    two_factor.last_used_step = Some(step);
+    two_factor.recovery_code_hashes = hashes;
+    app_state
+        .db
+        .set_user_two_factor(&user.email, Some(two_factor))?;
+    log_event(
+        LogLevel::Info,
+        "Two-factor authentication enabled",
+        Some(user.email.as_str()),
+    );
+    Ok(codes)
+}
+
+/// Replaces all recovery codes of the user.
+pub fn regenerate_recovery_codes(
+    app_state: &AppState,
+    user: &User,
+) -> Result<Vec<String>, AppError> {
+    let mut two_factor = enabled_two_factor(user)?;
+    let (codes, hashes) = new_recovery_codes();
+    two_factor.recovery_code_hashes = hashes;
+    app_state
+        .db
+        .set_user_two_factor(&user.email, Some(two_factor))?;
+    Ok(codes)
+}
+
+/// Marks a session as signed in with the second factor.
+pub fn mark_session_two_factor(app_state: &AppState, session_id: &str) -> Result<(), AppError> {
+    // Retried when a refresh rotated the session meanwhile, a revoked one stays gone
+    while let Some((stored, mut session)) =
+        get_session_entry(&app_state.db.sessions_tree, session_id)?
+    {
+        session.two_factor = true;
+        if swap_session(&app_state.db.sessions_tree, stored, &session)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Issued after the password of a user with two-factor authentication was
+/// checked, exchanged for tokens together with a code.
+pub fn start_login_challenge(
+    app_state: &AppState,
+    user_email: &str,
+    user_agent: Option<String>,
+) -> Result<(String, i64), AppError> {
+    let token = generate_random_id(48);
+    save_login_challenge(
+        &app_state.db.login_challenges_tree,
+        &hash_secret(&token),
+        &LoginChallenge {
+            user_email: user_email.to_string(),
+            user_agent,
+            expires_at: Utc::now() + Duration::minutes(LOGIN_CHALLENGE_TTL_MINUTES),
+        },
+    )?;
+    Ok((token, LOGIN_CHALLENGE_TTL_MINUTES * 60))
+}
+
+pub fn take_login_challenge_token(
+    app_state: &AppState,
+    token: &str,
+) -> Result<LoginChallenge, AppError> {
+    take_login_challenge(&app_state.db.login_challenges_tree, &hash_secret(token))?
+        .filter(|c| c.expires_at > Utc::now())
+        .ok_or(AppError::Unauthorized)
+}
+
+/// Puts back a challenge after a wrong code, so the user can retry without
+/// the password until the throttle locks the account.
+pub fn restore_login_challenge(
+    app_state: &AppState,
+    token: &str,
+    challenge: &LoginChallenge,
+) -> Result<(), AppError> {
+    save_login_challenge(
+        &app_state.db.login_challenges_tree,
+        &hash_secret(token),
+        challenge,
+    )
+}
+
+/// Whether admins turned on two-factor authentication for administration.
+pub fn is_required_for_admin(app_state: &AppState) -> Result<bool, AppError> {
+    Ok(get_security_settings(&app_state.db.settings_tree)?.require_admin_two_factor)
+}
+
+/// Drops login challenges that expired unanswered.
+pub fn cleanup_login_challenges(app_state: &AppState) -> Result<(), AppError> {
+    let now = Utc::now();
+    remove_login_challenges(&app_state.db.login_challenges_tree, |c| c.expires_at < now)
+}
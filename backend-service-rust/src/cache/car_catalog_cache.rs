@@ -0,0 +1,39 @@
+use moka::sync::Cache;
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use crate::{calc::car_catalog::CarCatalog, errors::AppError};
+
+// Catalog index per user. Entries are checked against the modification times of the
+// source yaml files on every access, so edits through any write path are picked up
+// without explicit invalidation.
+pub struct CarCatalogCache {
+    cache: Cache<String, Arc<CarCatalog>>, // Key: organization id
+    data_dir: PathBuf,
+}
+
+impl CarCatalogCache {
+    pub fn new(data_dir: PathBuf, max_size: u64) -> Arc<Self> {
+        let cache = Cache::builder()
+            .max_capacity(max_size)
+            .time_to_idle(Duration::from_secs(60 * 60))
+            .build();
+        Arc::new(Self { cache, data_dir })
+    }
+
+    pub async fn get(&self, email: &str) -> Result<Arc<CarCatalog>, AppError> {
+        if let Some(catalog) = self.cache.get(email) {
+            if !catalog.is_stale(&self.data_dir, email).await? {
+                return Ok(catalog);
+            }
+            log::debug!("Car catalog changed on disk, rebuilding index for {}", email);
+        }
+
+        let catalog = Arc::new(CarCatalog::load(&self.data_dir, email).await?);
+        self.cache.insert(email.to_string(), Arc::clone(&catalog));
+        Ok(catalog)
+    }
+
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
+}
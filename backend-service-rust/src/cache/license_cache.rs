@@ -1,7 +1,8 @@
 use moka::sync::Cache;
 use std::{sync::Arc, time::Duration};
 use crate::{
-    errors::AppError, exlogging::log_event, license_manager::LicenseData, state::AppState
+    auth::organizations::user_organization, db::users::AppDb, errors::AppError,
+    exlogging::log_event, license_keys::LicenseKeys, license_manager::LicenseData, state::AppState
 };
 use std::path::PathBuf;
 
@@ -15,24 +16,39 @@ use std::path::PathBuf;
 // The async part comes from awaiting `load_license_from_disk` on a cache miss.
 
 pub struct LicenseCache {
-    cache: Cache<String, LicenseData>, // Key: email
+    cache: Cache<String, LicenseData>, // Key: organization id
     data_dir: PathBuf,
-    jwt_license_secret: String
+    license_keys: Arc<LicenseKeys>,
+    db: AppDb,
 }
 
 impl LicenseCache {
-    pub fn new(data_dir: PathBuf, max_size: u64, secret: String) -> Arc<Self> {
+    pub fn new(
+        data_dir: PathBuf,
+        max_size: u64,
+        license_keys: Arc<LicenseKeys>,
+        db: AppDb,
+    ) -> Arc<Self> {
         let cache: Cache<String, LicenseData> = Cache::builder()
             .max_capacity(max_size)
             .time_to_live(Duration::from_secs(60 * 60))
             .build();
-        Arc::new(Self { cache, data_dir, jwt_license_secret: secret })
+        Arc::new(Self { cache, data_dir, license_keys, db })
     }
 
     // This function is async because it uses tokio::fs
     async fn load_license_from_disk(&self, email: &str) -> Result<LicenseData, AppError> {
         let token = crate::license_manager::read_latest_license_file(email, &self.data_dir).await?;
-        let data = crate::license_manager::decode_license_token(&token, self.jwt_license_secret.as_bytes())?;
+        let data = crate::license_manager::decode_license_token(&token, &self.license_keys)?;
+        // A license licenses one shop: the one its user is a member of now
+        if !matches!(user_organization(&self.db, &data.sub), Ok(o) if o == email) {
+            log_event(
+                crate::exlogging::LogLevel::Warn,
+                format!("License of {} not used, they are no longer a member", data.sub),
+                Some(email),
+            );
+            return Err(AppError::LicenseNotFound);
+        }
         let license_data = LicenseData::new(data);
         log_event(crate::exlogging::LogLevel::Trace, format!("License read from disk: {}", license_data.to_json_pretty()?), Some(email));
         Ok(license_data)
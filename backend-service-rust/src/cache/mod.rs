@@ -1 +1,2 @@
-pub mod license_cache;
\ No newline at end of file
+pub mod license_cache;
+pub mod car_catalog_cache;
@@ -0,0 +1,171 @@
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+use crate::models::calculations::{CalculationRow, CarCalcData};
+
+const EPSILON: f64 = 0.005;
+
+/// A processing row whose price or sum differs between two revisions.
+/// `from_*`/`to_*` are `None` for rows that exist in one revision only.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RowChange {
+    pub part: String,
+    pub processing: String,
+    pub row: String,
+    pub from_price: Option<f64>,
+    pub to_price: Option<f64>,
+    pub from_sum: Option<f64>,
+    pub to_sum: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CalculationDiff {
+    pub parts_added: Vec<String>,
+    pub parts_removed: Vec<String>,
+    pub price_changes: Vec<RowChange>,
+    pub total_from: f64,
+    pub total_to: f64,
+    pub total_delta: f64,
+}
+
+fn part_names(data: &CarCalcData) -> BTreeSet<&str> {
+    let selected = data
+        .parts
+        .iter()
+        .flat_map(|p| p.selected_parts.iter())
+        .map(|p| p.name.as_str());
+    let calculated = data
+        .calculations
+        .iter()
+        .flat_map(|c| c.keys())
+        .map(String::as_str);
+    selected.chain(calculated).collect()
+}
+
+type RowKey<'a> = (&'a str, &'a str, &'a str);
+
+/// Rows keyed by (part, processing, row) names, in document order.
+fn rows(data: &CarCalcData) -> Vec<(RowKey<'_>, &CalculationRow)> {
+    let mut rows = Vec::new();
+    for (part, processings) in data.calculations.iter().flatten() {
+        for processing in processings {
+            for row in &processing.result {
+                rows.push((
+                    (part.as_str(), processing.name.as_str(), row.name.as_str()),
+                    row,
+                ));
+            }
+        }
+    }
+    rows
+}
+
+fn find<'a>(rows: &[(RowKey, &'a CalculationRow)], key: RowKey) -> Option<&'a CalculationRow> {
+    rows.iter().find(|(k, _)| *k == key).map(|(_, r)| *r)
+}
+
+fn round_cents(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+fn differs(a: Option<f64>, b: Option<f64>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (a - b).abs() > EPSILON,
+        (a, b) => a.is_some() != b.is_some(),
+    }
+}
+
+pub fn diff_calculations(from: &CarCalcData, to: &CarCalcData) -> CalculationDiff {
+    let parts_from = part_names(from);
+    let parts_to = part_names(to);
+
+    let rows_from = rows(from);
+    let rows_to = rows(to);
+    let change = |(part, processing, row): RowKey,
+                  old: Option<&CalculationRow>,
+                  new: Option<&CalculationRow>| RowChange {
+        part: part.to_string(),
+        processing: processing.to_string(),
+        row: row.to_string(),
+        from_price: old.and_then(|r| r.price),
+        to_price: new.and_then(|r| r.price),
+        from_sum: old.and_then(CalculationRow::sum_value),
+        to_sum: new.and_then(CalculationRow::sum_value),
+    };
+
+    let mut price_changes = Vec::new();
+    for (key, old) in &rows_from {
+        let new = find(&rows_to, *key);
+        let changed = match new {
+            Some(new) => differs(old.price, new.price) || differs(old.sum_value(), new.sum_value()),
+            None => true,
+        };
+        if changed {
+            price_changes.push(change(*key, Some(old), new));
+        }
+    }
+    for (key, new) in &rows_to {
+        if find(&rows_from, *key).is_none() {
+            price_changes.push(change(*key, None, Some(new)));
+        }
+    }
+
+    let total_from = from.total();
+    let total_to = to.total();
+    CalculationDiff {
+        parts_added: parts_to
+            .difference(&parts_from)
+            .map(|s| s.to_string())
+            .collect(),
+        parts_removed: parts_from
+            .difference(&parts_to)
+            .map(|s| s.to_string())
+            .collect(),
+        price_changes,
+        total_from,
+        total_to,
+        total_delta: round_cents(total_to - total_from),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn calc(parts: serde_json::Value) -> CarCalcData {
+        serde_json::from_value(json!({
+            "car": {"year": "2020", "carClass": "C", "bodyType": "sedan"},
+            "calculations": parts,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_parts_prices_and_total() {
+        let from = calc(json!({
+            "hood": [{"name": "paint", "total": 3.0, "result": [
+                {"name": "prep", "price": 1, "sum": "1.00"},
+                {"name": "coat", "price": 2, "sum": "2.00"}
+            ]}],
+            "door": [{"name": "paint", "total": 2.0, "result": [{"name": "coat", "price": 2, "sum": "2.00"}]}]
+        }));
+        let to = calc(json!({
+            "hood": [{"name": "paint", "total": 4.5, "result": [
+                {"name": "prep", "price": 1, "sum": "1,00"},
+                {"name": "coat", "price": 3.5, "sum": "3.50"}
+            ]}],
+            "fender": [{"name": "paint", "total": 1.0, "result": [{"name": "coat", "price": 1, "sum": "1.00"}]}]
+        }));
+
+        let diff = diff_calculations(&from, &to);
+        assert_eq!(diff.parts_added, vec!["fender"]);
+        assert_eq!(diff.parts_removed, vec!["door"]);
+        assert_eq!(diff.price_changes.len(), 3);
+        assert_eq!(diff.price_changes[0].row, "coat");
+        assert_eq!(diff.price_changes[0].to_sum, Some(3.5));
+        assert_eq!(diff.price_changes[1].to_sum, None);
+        assert_eq!(diff.price_changes[2].from_sum, None);
+        assert_eq!(diff.total_delta, 0.5);
+    }
+}
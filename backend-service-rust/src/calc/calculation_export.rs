@@ -0,0 +1,422 @@
+use rust_xlsxwriter::{Format, Workbook};
+use serde::Deserialize;
+
+use crate::{
+    errors::AppError,
+    models::{
+        calculations::{CalculationIndexEntry, CarCalcData},
+        customers::Customer,
+    },
+    utils::money::Money,
+};
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Xlsx,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportLayout {
+    /// One row per processing row: part, action, hours, rate and total.
+    #[default]
+    LineItems,
+    /// One row per invoice line with net, tax and gross amounts.
+    Accounting,
+}
+
+impl ExportLayout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportLayout::LineItems => "line_items",
+            ExportLayout::Accounting => "accounting",
+        }
+    }
+
+    pub fn headers(&self) -> &'static [&'static str] {
+        match self {
+            ExportLayout::LineItems => &[
+                "calculation",
+                "title",
+                "date",
+                "status",
+                "customer",
+                "make",
+                "model",
+                "year",
+                "vin",
+                "license_plate",
+                "part",
+                "action",
+                "processing",
+                "operation",
+                "hours",
+                "rate",
+                "total",
+                "currency",
+            ],
+            ExportLayout::Accounting => &[
+                "invoice_number",
+                "invoice_date",
+                "customer",
+                "customer_email",
+                "vehicle",
+                "line",
+                "description",
+                "quantity",
+                "unit",
+                "unit_price",
+                "net_amount",
+                "tax_rate",
+                "tax_amount",
+                "gross_amount",
+                "currency",
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    /// Written with two decimals.
+    Money(Money),
+    Empty,
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<Option<&str>> for Cell {
+    fn from(value: Option<&str>) -> Self {
+        value.map_or(Cell::Empty, Cell::from)
+    }
+}
+
+impl From<Option<f64>> for Cell {
+    fn from(value: Option<f64>) -> Self {
+        value.map_or(Cell::Empty, Cell::Number)
+    }
+}
+
+impl From<Option<Money>> for Cell {
+    fn from(value: Option<Money>) -> Self {
+        value.map_or(Cell::Empty, Cell::Money)
+    }
+}
+
+/// A priced processing row of a calculation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineItem {
+    pub part: String,
+    pub action: Option<String>,
+    pub processing: String,
+    pub operation: String,
+    pub hours: Option<f64>,
+    pub rate: Option<f64>,
+    pub total: f64,
+}
+
+/// Rows without a sum (headers, failed evaluations) are left out.
+pub fn line_items(data: &CarCalcData) -> Vec<LineItem> {
+    let action = |part: &str| {
+        data.parts
+            .iter()
+            .flat_map(|p| p.selected_parts.iter())
+            .find(|p| p.name == part)
+            .and_then(|p| p.action.clone())
+    };
+    let mut items = Vec::new();
+    for (part, processings) in data.calculations.iter().flatten() {
+        for processing in processings {
+            for row in &processing.result {
+                let Some(total) = row.sum_value() else {
+                    continue;
+                };
+                items.push(LineItem {
+                    part: part.clone(),
+                    action: action(part),
+                    processing: processing.name.clone(),
+                    operation: row.name.clone(),
+                    hours: row.hours(),
+                    rate: row.price,
+                    total,
+                });
+            }
+        }
+    }
+    items
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct TaxSettings {
+    /// Percent, e.g. `20` for 20% VAT.
+    #[serde(default)]
+    pub tax_rate: f64,
+    /// Whether the calculated prices already include the tax.
+    #[serde(default)]
+    pub tax_included: bool,
+}
+
+fn round_to(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
+
+/// One stored calculation with everything the export rows are built from.
+pub struct ExportedCalculation<'a> {
+    pub entry: &'a CalculationIndexEntry,
+    pub data: &'a CarCalcData,
+    pub customer: Option<&'a Customer>,
+    pub currency: &'a str,
+}
+
+impl ExportedCalculation<'_> {
+    fn vehicle(&self) -> String {
+        let car = &self.data.car;
+        let mut words: Vec<&str> = [car.make.as_deref(), car.model.as_deref(), Some(&car.year)]
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .collect();
+        if let Some(plate) = car.license_plate.as_deref().filter(|p| !p.trim().is_empty()) {
+            words.push(plate);
+        }
+        words.join(" ")
+    }
+
+    pub fn rows(&self, layout: ExportLayout, tax: TaxSettings) -> Vec<Vec<Cell>> {
+        let items = line_items(self.data);
+        match layout {
+            ExportLayout::LineItems => items.iter().map(|item| self.line_item_row(item)).collect(),
+            ExportLayout::Accounting => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| self.accounting_row(i + 1, item, tax))
+                .collect(),
+        }
+    }
+
+    fn line_item_row(&self, item: &LineItem) -> Vec<Cell> {
+        let entry = self.entry;
+        vec![
+            Cell::from(entry.file_name.as_str()),
+            Cell::from(entry.title.as_str()),
+            Cell::Text(entry.updated_at.format("%Y-%m-%d").to_string()),
+            Cell::from(entry.status.as_str()),
+            Cell::from(self.customer.map(|c| c.name.as_str())),
+            Cell::from(entry.make.as_deref()),
+            Cell::from(entry.model.as_deref()),
+            Cell::from(entry.year.as_str()),
+            Cell::from(entry.vin.as_deref()),
+            Cell::from(entry.license_plate.as_deref()),
+            Cell::from(item.part.as_str()),
+            Cell::from(item.action.as_deref()),
+            Cell::from(item.processing.as_str()),
+            Cell::from(item.operation.as_str()),
+            Cell::from(item.hours),
+            Cell::from(item.rate.map(Money::from)),
+            Cell::Money(Money::from(item.total)),
+            Cell::from(self.currency),
+        ]
+    }
+
+    fn accounting_row(&self, line: usize, item: &LineItem, tax: TaxSettings) -> Vec<Cell> {
+        let rate = tax.tax_rate / 100.0;
+        let total = Money::from(item.total);
+        // Amounts are rounded to cents once, the tax is what is left so net + tax = gross
+        let (net, gross) = if tax.tax_included {
+            (total / (1.0 + rate), total)
+        } else {
+            (total, total * (1.0 + rate))
+        };
+        let (quantity, unit) = match item.hours.filter(|h| *h > 0.0) {
+            Some(hours) => (hours, "h"),
+            None => (1.0, "pcs"),
+        };
+        vec![
+            Cell::from(self.entry.name.as_str()),
+            Cell::Text(self.entry.updated_at.format("%Y-%m-%d").to_string()),
+            Cell::from(self.customer.map(|c| c.name.as_str())),
+            Cell::from(self.customer.and_then(|c| c.email.as_deref())),
+            Cell::Text(self.vehicle()),
+            Cell::Number(line as f64),
+            Cell::Text(format!("{} — {}: {}", item.part, item.processing, item.operation)),
+            Cell::Number(quantity),
+            Cell::from(unit),
+            Cell::Number(round_to(f64::from(net) / quantity, 4)),
+            Cell::Money(net),
+            Cell::Number(tax.tax_rate),
+            Cell::Money(gross - net),
+            Cell::Money(gross),
+            Cell::from(self.currency),
+        ]
+    }
+}
+
+/// Spreadsheets run text starting with one of these as a formula.
+const FORMULA_PREFIXES: [char; 4] = ['=', '+', '-', '@'];
+
+/// Text a spreadsheet would evaluate is written with a leading `'`, so a note like
+/// `=HYPERLINK(...)` stays text when the CSV is opened.
+fn csv_text(s: &str) -> String {
+    if s.starts_with(FORMULA_PREFIXES) {
+        format!("'{}", s)
+    } else {
+        s.to_string()
+    }
+}
+
+/// CSV records of the rows. Numbers are written with a dot as the decimal separator
+/// and at most four decimals, amounts with two.
+pub fn csv_chunk(rows: &[Vec<Cell>]) -> Result<Vec<u8>, AppError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer
+            .write_record(row.iter().map(|cell| match cell {
+                Cell::Text(s) => csv_text(s),
+                Cell::Number(n) => round_to(*n, 4).to_string(),
+                Cell::Money(m) => m.to_string(),
+                Cell::Empty => String::new(),
+            }))
+            .map_err(|e| AppError::InternalServerError(format!("CSV export failed: {}", e)))?;
+    }
+    writer
+        .into_inner()
+        .map_err(|e| AppError::InternalServerError(format!("CSV export failed: {}", e)))
+}
+
+pub fn xlsx_workbook(layout: ExportLayout, rows: &[Vec<Cell>]) -> Result<Vec<u8>, AppError> {
+    let xlsx_error = |e: rust_xlsxwriter::XlsxError| {
+        AppError::InternalServerError(format!("XLSX export failed: {}", e))
+    };
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let amount = Format::new().set_num_format("0.00");
+    let sheet = workbook.add_worksheet();
+    sheet.set_name(layout.name()).map_err(xlsx_error)?;
+    for (col, header) in layout.headers().iter().enumerate() {
+        sheet
+            .write_string_with_format(0, col as u16, *header, &bold)
+            .map_err(xlsx_error)?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let r = (i + 1) as u32;
+        for (col, cell) in row.iter().enumerate() {
+            let col = col as u16;
+            match cell {
+                Cell::Text(s) => sheet.write_string(r, col, s).map(|_| ()),
+                Cell::Number(n) => sheet.write_number(r, col, *n).map(|_| ()),
+                Cell::Money(m) => sheet
+                    .write_number_with_format(r, col, f64::from(*m), &amount)
+                    .map(|_| ()),
+                Cell::Empty => Ok(()),
+            }
+            .map_err(xlsx_error)?;
+        }
+    }
+    sheet.set_freeze_panes(1, 0).map_err(xlsx_error)?;
+    sheet.autofit();
+    workbook.save_to_buffer().map_err(xlsx_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn builds_line_items_and_accounting_rows() {
+        let data: CarCalcData = serde_json::from_value(json!({
+            "car": {"make": "skoda", "model": "octavia", "year": "2018", "carClass": "C", "bodyType": "sedan"},
+            "parts": {"selectedParts": [{"name": "hood", "action": "paint"}]},
+            "calculations": {"hood": [{"name": "painting", "result": [
+                {"name": "prep", "evaluate": "1,5", "price": 400, "sum": "600.00"},
+                {"name": "header only"}
+            ]}]}
+        }))
+        .unwrap();
+        let items = line_items(&data);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].action.as_deref(), Some("paint"));
+        assert_eq!(items[0].hours, Some(1.5));
+
+        let entry = CalculationIndexEntry::new("est.json", &data, chrono::Utc::now(), chrono::Utc::now());
+        let export = ExportedCalculation {
+            entry: &entry,
+            data: &data,
+            customer: None,
+            currency: "UAH",
+        };
+        let tax = TaxSettings {
+            tax_rate: 20.0,
+            tax_included: true,
+        };
+        let row = &export.rows(ExportLayout::Accounting, tax)[0];
+        assert_eq!(row.len(), ExportLayout::Accounting.headers().len());
+        assert_eq!(row[9], Cell::Number(333.3333));
+        assert_eq!(row[10], Cell::Money(Money::from_cents(50000)));
+        assert_eq!(row[12], Cell::Money(Money::from_cents(10000)));
+        assert_eq!(row[13], Cell::Money(Money::from_cents(60000)));
+
+        let csv = String::from_utf8(csv_chunk(&export.rows(ExportLayout::LineItems, tax)).unwrap()).unwrap();
+        assert!(csv.starts_with("est.json,skoda octavia 2018,"));
+        assert!(csv.trim_end().ends_with("hood,paint,painting,prep,1.5,400.00,600.00,UAH"));
+    }
+
+    #[test]
+    fn tax_is_gross_minus_net() {
+        let item = LineItem {
+            part: "hood".to_string(),
+            action: None,
+            processing: "painting".to_string(),
+            operation: "coat".to_string(),
+            hours: None,
+            rate: None,
+            total: 100.0,
+        };
+        let data: CarCalcData = serde_json::from_value(json!({
+            "car": {"year": "2018", "carClass": "C", "bodyType": "sedan"}
+        }))
+        .unwrap();
+        let entry = CalculationIndexEntry::new("est.json", &data, chrono::Utc::now(), chrono::Utc::now());
+        let export = ExportedCalculation {
+            entry: &entry,
+            data: &data,
+            customer: None,
+            currency: "UAH",
+        };
+        let tax = TaxSettings {
+            tax_rate: 20.0,
+            tax_included: true,
+        };
+        let row = export.accounting_row(1, &item, tax);
+        assert_eq!(row[10], Cell::Money(Money::from_cents(8333)));
+        assert_eq!(row[12], Cell::Money(Money::from_cents(1667)));
+        assert_eq!(row[13], Cell::Money(Money::from_cents(10000)));
+
+        let csv = String::from_utf8(csv_chunk(&[row]).unwrap()).unwrap();
+        assert!(csv.contains(",83.33,20,16.67,100.00,UAH"));
+    }
+
+    #[test]
+    fn csv_text_is_not_a_formula() {
+        let rows = vec![vec![
+            Cell::from("=HYPERLINK(\"http://x\")"),
+            Cell::from("@SUM(A1)"),
+            Cell::from("-1+2"),
+            Cell::from("hood"),
+            Cell::Number(-2.5),
+        ]];
+        let csv = String::from_utf8(csv_chunk(&rows).unwrap()).unwrap();
+        assert_eq!(csv.trim_end(), "\"'=HYPERLINK(\"\"http://x\"\")\",'@SUM(A1),'-1+2,hood,-2.5");
+    }
+}
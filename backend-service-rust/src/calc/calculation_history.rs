@@ -0,0 +1,422 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::{
+    calc::calculation_schema::upgrade_calculation_bytes,
+    errors::AppError,
+    transactionalfs::{GitTransactionalFs, TransactionalFs},
+    utils::{safe_join, safe_write, safe_write_overwrite, DataStorageCache},
+};
+
+/// Directory with stored calculations, relative to the user's personal directory.
+pub const CALCULATIONS: &str = "stored_calculations";
+/// Snapshots of every saved version, `<REVISIONS>/<file stem>/<number>.json`.
+pub const REVISIONS: &str = "calculation_revisions";
+/// Soft-deleted calculations, restorable until removed by hand.
+pub const DELETED: &str = "deleted_calculations";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionInfo {
+    /// Sequence number for snapshots, commit hash for the git backend.
+    pub id: String,
+    pub saved_at: Option<DateTime<Utc>>,
+    /// `None` for snapshots saved before authors were recorded.
+    pub author: Option<String>,
+    pub schema_version: Option<u32>,
+    pub total: Option<f64>,
+}
+
+/// Stored next to a snapshot as `<number>.meta.json`.
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotMeta {
+    author: String,
+    saved_at: DateTime<Utc>,
+}
+
+/// Keeps every saved version of a user's calculations. By default each version is
+/// stored as a snapshot file; with `use_git` the calculations directory is a git
+/// repository managed through [`GitTransactionalFs`] and every save is a commit.
+pub struct CalculationHistory<'a> {
+    user_dir: PathBuf,
+    author: String,
+    cache: &'a DataStorageCache,
+    use_git: bool,
+    /// Snapshots kept per calculation, `0` keeps all. Git history is never pruned.
+    keep_revisions: usize,
+}
+
+impl<'a> CalculationHistory<'a> {
+    pub fn new(
+        user_dir: PathBuf,
+        author: &str,
+        cache: &'a DataStorageCache,
+        use_git: bool,
+        keep_revisions: usize,
+    ) -> Self {
+        Self {
+            user_dir,
+            author: author.to_string(),
+            cache,
+            use_git,
+            keep_revisions,
+        }
+    }
+
+    fn calculations_dir(&self) -> PathBuf {
+        self.user_dir.join(CALCULATIONS)
+    }
+
+    fn snapshots_dir(&self, file_name: &str) -> Result<PathBuf, AppError> {
+        let stem = Path::new(file_name)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| AppError::InvalidData(format!("Invalid file name {}", file_name)))?;
+        Ok(safe_join(
+            self.user_dir.join(REVISIONS),
+            PathBuf::from(stem),
+        )?)
+    }
+
+    async fn git(&self) -> Result<GitTransactionalFs<'a>, AppError> {
+        let dir = self.calculations_dir();
+        fs::create_dir_all(&dir).await?;
+        Ok(GitTransactionalFs::new(dir, self.author.clone(), self.cache).await?)
+    }
+
+    async fn current_content(&self, file_name: &str) -> Result<Option<Vec<u8>>, AppError> {
+        let path = safe_join(self.calculations_dir(), PathBuf::from(file_name))?;
+        match fs::read(path).await {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Whether `content` is the same document as the one stored now.
+    pub async fn is_unchanged(&self, file_name: &str, content: &[u8]) -> Result<bool, AppError> {
+        Ok(self
+            .current_content(file_name)
+            .await?
+            .is_some_and(|current| same_document(&current, content)))
+    }
+
+    /// Writes the calculation and records it as a new revision. Returns the revision id,
+    /// or `None` if the content did not change since the last save.
+    pub async fn save(
+        &self,
+        file_name: &str,
+        content: Vec<u8>,
+    ) -> Result<Option<String>, AppError> {
+        let previous = self.current_content(file_name).await?;
+        if previous.as_deref().is_some_and(|p| same_document(p, &content)) {
+            return Ok(None);
+        }
+
+        if self.use_git {
+            let git = self.git().await?;
+            // Captures files saved before history was enabled as their own revision
+            git.commit_all_if_changed("Calculations saved before history was enabled")
+                .await?;
+            git.write_file(
+                content,
+                &PathBuf::from(file_name),
+                &format!("Save {}", file_name),
+            )
+            .await?;
+            let history = git.file_history(Path::new(file_name)).await?;
+            return Ok(history.into_iter().next().map(|r| r.hash));
+        }
+
+        let mut numbers = self.snapshot_numbers(file_name).await?;
+        if let (Some(previous), true) = (previous, numbers.is_empty()) {
+            // Keep the version saved before history was enabled, its author is unknown
+            self.write_snapshot(file_name, 1, previous, None).await?;
+            numbers.push(1);
+        }
+        let number = numbers.last().copied().unwrap_or(0) + 1;
+        let meta = SnapshotMeta {
+            author: self.author.clone(),
+            saved_at: Utc::now(),
+        };
+        self.write_snapshot(file_name, number, content.clone(), Some(meta))
+            .await?;
+        safe_write_overwrite(
+            self.calculations_dir(),
+            PathBuf::from(file_name),
+            content,
+            self.cache,
+        )
+        .await?;
+        numbers.push(number);
+        self.prune_snapshots(file_name, &numbers).await?;
+        Ok(Some(number.to_string()))
+    }
+
+    /// Removes the oldest snapshots beyond `keep_revisions`.
+    async fn prune_snapshots(&self, file_name: &str, numbers: &[u32]) -> Result<(), AppError> {
+        if self.keep_revisions == 0 || numbers.len() <= self.keep_revisions {
+            return Ok(());
+        }
+        let dir = self.snapshots_dir(file_name)?;
+        for number in &numbers[..numbers.len() - self.keep_revisions] {
+            for name in [format!("{}.json", number), format!("{}.meta.json", number)] {
+                let path = dir.join(name);
+                self.cache.invalidate(&path).await;
+                match fs::remove_file(&path).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_snapshot(
+        &self,
+        file_name: &str,
+        number: u32,
+        content: Vec<u8>,
+        meta: Option<SnapshotMeta>,
+    ) -> Result<(), AppError> {
+        let dir = self.snapshots_dir(file_name)?;
+        if let Some(meta) = meta {
+            safe_write(
+                dir.clone(),
+                PathBuf::from(format!("{}.meta.json", number)),
+                serde_json::to_vec(&meta)?,
+                self.cache,
+            )
+            .await?;
+        }
+        safe_write(
+            dir,
+            PathBuf::from(format!("{}.json", number)),
+            content,
+            self.cache,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Moves the calculation to the deleted calculations. Its revisions are kept.
+    pub async fn remove(&self, file_name: &str) -> Result<(), AppError> {
+        let path = safe_join(self.calculations_dir(), PathBuf::from(file_name))?;
+        let content = self
+            .current_content(file_name)
+            .await?
+            .ok_or(AppError::FileNotFound)?;
+        safe_write_overwrite(
+            self.user_dir.join(DELETED),
+            PathBuf::from(file_name),
+            content,
+            self.cache,
+        )
+        .await?;
+        if self.use_git {
+            self.git()
+                .await?
+                .delete_file(&PathBuf::from(file_name), &format!("Delete {}", file_name))
+                .await?;
+        } else {
+            self.cache.invalidate(&path).await;
+            fs::remove_file(&path).await?;
+        }
+        Ok(())
+    }
+
+    /// Moves a deleted calculation back.
+    pub async fn restore(&self, file_name: &str) -> Result<(), AppError> {
+        let deleted_path = safe_join(self.user_dir.join(DELETED), PathBuf::from(file_name))?;
+        let content = match fs::read(&deleted_path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(AppError::FileNotFound),
+            Err(e) => return Err(e.into()),
+        };
+        if self.current_content(file_name).await?.is_some() {
+            return Err(AppError::Conflict(format!(
+                "Calculation {} already exists",
+                file_name
+            )));
+        }
+        if self.use_git {
+            self.git()
+                .await?
+                .write_file(content, &PathBuf::from(file_name), &format!("Restore {}", file_name))
+                .await?;
+        } else {
+            safe_write(
+                self.calculations_dir(),
+                PathBuf::from(file_name),
+                content,
+                self.cache,
+            )
+            .await?;
+        }
+        self.cache.invalidate(&deleted_path).await;
+        fs::remove_file(&deleted_path).await?;
+        Ok(())
+    }
+
+    /// Sorted snapshot numbers of a calculation.
+    async fn snapshot_numbers(&self, file_name: &str) -> Result<Vec<u32>, AppError> {
+        let mut entries = match fs::read_dir(self.snapshots_dir(file_name)?).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut numbers = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(n) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+            {
+                numbers.push(n);
+            }
+        }
+        numbers.sort_unstable();
+        Ok(numbers)
+    }
+
+    /// Revisions of a calculation, newest first.
+    pub async fn list(&self, file_name: &str) -> Result<Vec<RevisionInfo>, AppError> {
+        let mut revisions = Vec::new();
+        if self.use_git {
+            let git = self.git().await?;
+            for revision in git.file_history(Path::new(file_name)).await? {
+                // Deleting the calculation is a commit without the file
+                let Ok(content) = git
+                    .read_file_at(&revision.hash, Path::new(file_name))
+                    .await
+                else {
+                    continue;
+                };
+                let saved_at = DateTime::parse_from_rfc3339(&revision.date)
+                    .ok()
+                    .map(|d| d.with_timezone(&Utc));
+                revisions.push(revision_info(
+                    revision.hash,
+                    saved_at,
+                    Some(revision.author),
+                    &content,
+                ));
+            }
+            return Ok(revisions);
+        }
+
+        let dir = self.snapshots_dir(file_name)?;
+        for number in self.snapshot_numbers(file_name).await?.into_iter().rev() {
+            let path = dir.join(format!("{}.json", number));
+            let meta = fs::read(dir.join(format!("{}.meta.json", number)))
+                .await
+                .ok()
+                .and_then(|m| serde_json::from_slice::<SnapshotMeta>(&m).ok());
+            let saved_at = match &meta {
+                Some(meta) => Some(meta.saved_at),
+                None => fs::metadata(&path)
+                    .await
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .map(DateTime::<Utc>::from),
+            };
+            let content = fs::read(&path).await?;
+            revisions.push(revision_info(
+                number.to_string(),
+                saved_at,
+                meta.map(|m| m.author),
+                &content,
+            ));
+        }
+        Ok(revisions)
+    }
+
+    /// Raw content of a revision as it was saved.
+    pub async fn read(&self, file_name: &str, revision: &str) -> Result<Vec<u8>, AppError> {
+        if self.use_git {
+            let git = self.git().await?;
+            return git
+                .read_file_at(revision, Path::new(file_name))
+                .await
+                .map_err(|_| AppError::NotFound);
+        }
+        let number: u32 = revision.parse().map_err(|_| AppError::NotFound)?;
+        let path = self
+            .snapshots_dir(file_name)?
+            .join(format!("{}.json", number));
+        match fs::read(path).await {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(AppError::NotFound),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Saved documents are compared as JSON: the flattened extra fields are serialized
+/// in no particular order, so equal documents are not always equal bytes.
+fn same_document(a: &[u8], b: &[u8]) -> bool {
+    if a == b {
+        return true;
+    }
+    match (
+        serde_json::from_slice::<serde_json::Value>(a),
+        serde_json::from_slice::<serde_json::Value>(b),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn revision_info(
+    id: String,
+    saved_at: Option<DateTime<Utc>>,
+    author: Option<String>,
+    content: &[u8],
+) -> RevisionInfo {
+    // A revision that no longer upgrades is still listed, just without the summary
+    let upgraded = upgrade_calculation_bytes(content).ok();
+    RevisionInfo {
+        id,
+        saved_at,
+        author,
+        schema_version: upgraded.as_ref().map(|u| u.from_version),
+        total: upgraded.as_ref().map(|u| u.data.total()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn keeps_the_newest_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DataStorageCache::new(10, 10, 10);
+        let history =
+            CalculationHistory::new(dir.path().to_path_buf(), "a@x.com", &cache, false, 2);
+
+        for n in 1..=4 {
+            let content = format!(r#"{{"n": {}, "extra": true}}"#, n).into_bytes();
+            assert_eq!(history.save("c.json", content).await.unwrap(), Some(n.to_string()));
+        }
+        // Same document, fields in another order
+        let reordered = br#"{"extra": true, "n": 4}"#.to_vec();
+        assert_eq!(history.save("c.json", reordered).await.unwrap(), None);
+
+        let ids: Vec<String> = history
+            .list("c.json")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|r| r.id)
+            .collect();
+        assert_eq!(ids, ["4", "3"]);
+        assert!(matches!(history.read("c.json", "1").await, Err(AppError::NotFound)));
+    }
+}
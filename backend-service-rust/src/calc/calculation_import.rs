@@ -0,0 +1,242 @@
+use std::io::{Cursor, Read};
+use std::path::{Component, Path};
+
+use serde::Serialize;
+
+use crate::{
+    calc::{calculation_history::CALCULATIONS, calculation_schema::upgrade_calculation_bytes},
+    errors::AppError,
+    models::{calculations::CarCalcData, CompanyInfo},
+    utils::CATALOG,
+};
+
+pub const COMPANY_FILE: &str = "company.json";
+
+/// Largest archive accepted by the import endpoints.
+pub const MAX_IMPORT_ARCHIVE_SIZE: usize = 64 * 1024 * 1024;
+/// Largest total size of the restored entries once extracted, whatever the
+/// entry headers claim.
+pub const MAX_IMPORT_EXTRACTED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// What a file of an exported user directory is restored as.
+#[derive(Debug)]
+pub enum ArchiveContent {
+    Calculation { file_name: String, data: Box<CarCalcData> },
+    Company(Box<CompanyInfo>),
+    /// Path relative to the user's catalog directory.
+    Catalog { path: String, content: Vec<u8> },
+    /// Licenses, revision snapshots, deleted calculations and git metadata are
+    /// not restored, they belong to the account the archive was made from.
+    Ignored,
+    Invalid(String),
+}
+
+#[derive(Debug)]
+pub struct ArchiveFile {
+    /// Path inside the archive.
+    pub path: String,
+    pub content: ArchiveContent,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportResult {
+    Imported,
+    /// Imported under another name because the original one was taken.
+    Renamed,
+    /// Already present with the same content, or not restorable.
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportedFile {
+    pub path: String,
+    pub result: ImportResult,
+    /// Where the file was restored to, relative to the user directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl ImportedFile {
+    pub fn new(path: &str, result: ImportResult, target: Option<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            result,
+            target,
+            error: None,
+        }
+    }
+
+    pub fn failed(path: &str, error: impl ToString) -> Self {
+        Self {
+            path: path.to_string(),
+            result: ImportResult::Failed,
+            target: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub renamed: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub files: Vec<ImportedFile>,
+}
+
+impl ImportReport {
+    pub fn push(&mut self, file: ImportedFile) {
+        match file.result {
+            ImportResult::Imported => self.imported += 1,
+            ImportResult::Renamed => self.renamed += 1,
+            ImportResult::Skipped => self.skipped += 1,
+            ImportResult::Failed => self.failed += 1,
+        }
+        self.files.push(file);
+    }
+}
+
+/// Kinds of entries that are restored.
+enum EntryKind {
+    Company,
+    Calculation(String),
+    Catalog(String),
+}
+
+fn entry_kind(path: &str) -> Option<EntryKind> {
+    let parts: Vec<&str> = path.split('/').collect();
+    if parts.iter().any(|p| p.starts_with('.')) {
+        return None;
+    }
+    match parts.as_slice() {
+        [COMPANY_FILE] => Some(EntryKind::Company),
+        [CALCULATIONS, file_name] if file_name.ends_with(".json") => {
+            Some(EntryKind::Calculation(file_name.to_string()))
+        }
+        [CATALOG, rest @ ..] if !rest.is_empty() => Some(EntryKind::Catalog(rest.join("/"))),
+        _ => None,
+    }
+}
+
+fn classify(kind: EntryKind, content: Vec<u8>) -> ArchiveContent {
+    match kind {
+        EntryKind::Company => match serde_json::from_slice::<CompanyInfo>(&content) {
+            Ok(company) => ArchiveContent::Company(Box::new(company)),
+            Err(e) => ArchiveContent::Invalid(format!("Invalid company info: {}", e)),
+        },
+        EntryKind::Calculation(file_name) => match upgrade_calculation_bytes(&content) {
+            Ok(upgraded) => ArchiveContent::Calculation {
+                file_name,
+                data: Box::new(upgraded.data),
+            },
+            Err(e) => ArchiveContent::Invalid(e.to_string()),
+        },
+        EntryKind::Catalog(path) => ArchiveContent::Catalog { path, content },
+    }
+}
+
+/// Reads an archive produced by the user data export. Entries pointing outside
+/// the archive root are reported as invalid instead of being extracted, entries
+/// that are not restored are not extracted at all.
+pub fn read_archive(bytes: &[u8]) -> Result<Vec<ArchiveFile>, AppError> {
+    read_archive_within(bytes, MAX_IMPORT_EXTRACTED_SIZE)
+}
+
+/// [`read_archive`], refusing the archive once the extracted entries exceed `budget` bytes.
+fn read_archive_within(bytes: &[u8], mut budget: u64) -> Result<Vec<ArchiveFile>, AppError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes))
+        .map_err(|e| AppError::InvalidData(format!("Invalid ZIP archive: {}", e)))?;
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| AppError::InvalidData(format!("Invalid ZIP archive: {}", e)))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let path = entry.name().to_string();
+        let enclosed = entry.enclosed_name().map(Path::to_path_buf);
+        let Some(enclosed) = enclosed.filter(|p| p.components().all(|c| matches!(c, Component::Normal(_)))) else {
+            files.push(ArchiveFile {
+                path,
+                content: ArchiveContent::Invalid("Path outside the archive root".to_string()),
+            });
+            continue;
+        };
+        let Some(kind) = entry_kind(&enclosed.to_string_lossy().replace('\\', "/")) else {
+            files.push(ArchiveFile {
+                path,
+                content: ArchiveContent::Ignored,
+            });
+            continue;
+        };
+        // The declared size is not trusted, reading stops one byte past the budget
+        let mut content = Vec::new();
+        let read = (&mut entry).take(budget + 1).read_to_end(&mut content);
+        if content.len() as u64 > budget {
+            return Err(AppError::InvalidData(format!(
+                "Archive extracts to more than {} MB",
+                MAX_IMPORT_EXTRACTED_SIZE / 1024 / 1024
+            )));
+        }
+        budget -= content.len() as u64;
+        let content = match read {
+            Ok(_) => classify(kind, content),
+            Err(e) => ArchiveContent::Invalid(format!("Cannot extract: {}", e)),
+        };
+        files.push(ArchiveFile { path, content });
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    #[test]
+    fn classifies_archive_entries() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let files: [(&str, &[u8]); 6] = [
+            ("stored_calculations/a.json", br#"{"car": {"year": "2018", "carClass": "C", "bodyType": "sedan"}}"#),
+            ("stored_calculations/b.json", b"{\"car\": 5}"),
+            ("catalog/tables/t1.csv", b"a,b"),
+            ("catalog/.git/HEAD", b"ref"),
+            ("licenses/x.license", b"token"),
+            ("../escape.txt", b"x"),
+        ];
+        for (name, content) in files {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+
+        let read = read_archive(&bytes).unwrap();
+        assert!(matches!(&read[0].content, ArchiveContent::Calculation { file_name, .. } if file_name == "a.json"));
+        assert!(matches!(read[1].content, ArchiveContent::Invalid(_)));
+        assert!(matches!(&read[2].content, ArchiveContent::Catalog { path, .. } if path == "tables/t1.csv"));
+        assert!(matches!(read[3].content, ArchiveContent::Ignored));
+        assert!(matches!(read[4].content, ArchiveContent::Ignored));
+        assert!(matches!(read[5].content, ArchiveContent::Invalid(_)));
+    }
+
+    #[test]
+    fn refuses_archives_over_the_budget() {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for name in ["catalog/a.csv", "catalog/b.csv", "licenses/ignored.jwt"] {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(&[0; 600]).unwrap();
+        }
+        let bytes = zip.finish().unwrap().into_inner();
+
+        assert!(read_archive_within(&bytes, 1200).is_ok());
+        assert!(matches!(read_archive_within(&bytes, 1000), Err(AppError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,491 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+use tokio::fs;
+
+use crate::{
+    calc::{calculation_history::CALCULATIONS, calculation_schema::upgrade_calculation_bytes},
+    db::{
+        calculations::{get_entry, list_user_entries, remove_entry, upsert_entry},
+        estimates::insert_transition,
+        organizations::list_organizations,
+        users::AppDb,
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::{
+        calculations::CalculationIndexEntry,
+        estimates::{EstimateStatus, StatusTransition},
+    },
+    utils::{organization_directory, safe_join},
+};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    UpdatedAt,
+    CreatedAt,
+    Total,
+    Make,
+    Year,
+    LicensePlate,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CalculationListQuery {
+    /// Full-text search, every word has to occur in one of the indexed fields.
+    pub q: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub year: Option<String>,
+    pub vin: Option<String>,
+    pub license_plate: Option<String>,
+    pub customer_id: Option<String>,
+    pub vehicle_id: Option<String>,
+    pub status: Option<EstimateStatus>,
+    pub total_min: Option<f64>,
+    pub total_max: Option<f64>,
+    pub updated_from: Option<DateTime<Utc>>,
+    pub updated_to: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: SortField,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+    /// Lists the deleted calculations instead.
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalculationPage {
+    pub items: Vec<CalculationIndexEntry>,
+    /// Number of calculations matching the filters, over all pages.
+    pub total_count: usize,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SortValue {
+    Number(f64),
+    Text(String),
+}
+
+/// Position after the last returned item: its sort value and file name as a tie-breaker.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor(SortValue, String);
+
+fn sort_value(entry: &CalculationIndexEntry, field: SortField) -> SortValue {
+    match field {
+        SortField::UpdatedAt => SortValue::Number(entry.updated_at.timestamp_micros() as f64),
+        SortField::CreatedAt => SortValue::Number(entry.created_at.timestamp_micros() as f64),
+        SortField::Total => SortValue::Number(entry.total),
+        SortField::Make => SortValue::Text(
+            format!(
+                "{} {}",
+                entry.make.as_deref().unwrap_or_default(),
+                entry.model.as_deref().unwrap_or_default()
+            )
+            .to_lowercase(),
+        ),
+        SortField::Year => SortValue::Text(entry.year.clone()),
+        SortField::LicensePlate => {
+            SortValue::Text(normalize_code(entry.license_plate.as_deref().unwrap_or_default()))
+        }
+    }
+}
+
+/// VINs and plates are compared without spaces, dashes and case.
+pub fn normalize_code(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+fn eq_ignore_case(value: Option<&str>, expected: &str) -> bool {
+    value.is_some_and(|v| v.trim().to_lowercase() == expected.trim().to_lowercase())
+}
+
+fn code_contains(value: Option<&str>, expected: &str) -> bool {
+    value.is_some_and(|v| normalize_code(v).contains(&normalize_code(expected)))
+}
+
+fn search_text(entry: &CalculationIndexEntry) -> String {
+    let fields = [
+        Some(entry.name.as_str()),
+        Some(entry.title.as_str()),
+        entry.make.as_deref(),
+        entry.model.as_deref(),
+        Some(entry.year.as_str()),
+        Some(entry.car_class.as_str()),
+        Some(entry.body_type.as_str()),
+        entry.notes.as_deref(),
+        Some(entry.status.as_str()),
+    ];
+    let mut text: Vec<String> = fields.iter().flatten().map(|s| s.to_lowercase()).collect();
+    text.extend(entry.parts.iter().map(|p| p.to_lowercase()));
+    text.extend(entry.vin.as_deref().map(normalize_code).map(|s| s.to_lowercase()));
+    text.extend(entry.license_plate.as_deref().map(normalize_code).map(|s| s.to_lowercase()));
+    text.join("\n")
+}
+
+impl CalculationListQuery {
+    fn matches(&self, entry: &CalculationIndexEntry) -> bool {
+        if entry.deleted_at.is_some() != self.deleted {
+            return false;
+        }
+        if let Some(q) = self.q.as_deref().filter(|q| !q.trim().is_empty()) {
+            let text = search_text(entry);
+            let all_found = q.split_whitespace().all(|word| {
+                let word = word.to_lowercase();
+                text.contains(&word) || text.contains(&normalize_code(&word).to_lowercase())
+            });
+            if !all_found {
+                return false;
+            }
+        }
+        self.make.as_deref().is_none_or(|m| eq_ignore_case(entry.make.as_deref(), m))
+            && self.model.as_deref().is_none_or(|m| eq_ignore_case(entry.model.as_deref(), m))
+            && self.year.as_deref().is_none_or(|y| entry.year.trim() == y.trim())
+            && self.vin.as_deref().is_none_or(|v| code_contains(entry.vin.as_deref(), v))
+            && self
+                .license_plate
+                .as_deref()
+                .is_none_or(|p| code_contains(entry.license_plate.as_deref(), p))
+            && self.customer_id.as_ref().is_none_or(|c| entry.customer_id.as_ref() == Some(c))
+            && self.vehicle_id.as_ref().is_none_or(|v| entry.vehicle_id.as_ref() == Some(v))
+            && self.status.is_none_or(|s| entry.status == s)
+            && self.total_min.is_none_or(|min| entry.total >= min)
+            && self.total_max.is_none_or(|max| entry.total <= max)
+            && self.updated_from.is_none_or(|from| entry.updated_at >= from)
+            && self.updated_to.is_none_or(|to| entry.updated_at <= to)
+    }
+
+    fn compare(&self, a: (&SortValue, &str), b: (&SortValue, &str)) -> Ordering {
+        let ordering = a
+            .0
+            .partial_cmp(b.0)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| a.1.cmp(b.1));
+        match self.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    }
+
+    /// Filters, sorts and pages the entries. Pages are keyset based, so saving
+    /// calculations between requests does not shift or repeat items.
+    pub fn apply(&self, entries: Vec<CalculationIndexEntry>) -> Result<CalculationPage, AppError> {
+        let cursor = match self.cursor.as_deref() {
+            Some(cursor) => Some(decode_cursor(cursor)?),
+            None => None,
+        };
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+
+        let mut matching: Vec<(SortValue, CalculationIndexEntry)> = entries
+            .into_iter()
+            .filter(|e| self.matches(e))
+            .map(|e| (sort_value(&e, self.sort), e))
+            .collect();
+        matching.sort_by(|a, b| self.compare((&a.0, &a.1.file_name), (&b.0, &b.1.file_name)));
+        let total_count = matching.len();
+
+        let start = match &cursor {
+            Some(Cursor(value, file_name)) => matching.partition_point(|(v, e)| {
+                self.compare((v, &e.file_name), (value, file_name)) != Ordering::Greater
+            }),
+            None => 0,
+        };
+        let mut page: Vec<(SortValue, CalculationIndexEntry)> =
+            matching.into_iter().skip(start).take(limit + 1).collect();
+        let next_cursor = if page.len() > limit {
+            page.truncate(limit);
+            page.last()
+                .map(|(v, e)| encode_cursor(&Cursor(v.clone(), e.file_name.clone())))
+                .transpose()?
+        } else {
+            None
+        };
+
+        Ok(CalculationPage {
+            items: page.into_iter().map(|(_, e)| e).collect(),
+            total_count,
+            next_cursor,
+        })
+    }
+}
+
+fn encode_cursor(cursor: &Cursor) -> Result<String, AppError> {
+    Ok(URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursor)?))
+}
+
+fn decode_cursor(cursor: &str) -> Result<Cursor, AppError> {
+    URL_SAFE_NO_PAD
+        .decode(cursor)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(|| AppError::InvalidData("Invalid cursor".to_string()))
+}
+
+async fn modified_at(path: &Path) -> Result<DateTime<Utc>, AppError> {
+    Ok(fs::metadata(path).await?.modified().map(DateTime::<Utc>::from)?)
+}
+
+/// Indexes a stored calculation file, keeping the creation time of an existing entry.
+/// A file that cannot be upgraded is indexed with its `migration_error`. The status
+/// comes from the document, so rebuilding the index or importing keeps it.
+pub async fn index_calculation_file(
+    db: &AppDb,
+    email: &str,
+    path: &Path,
+    previous: Option<&CalculationIndexEntry>,
+) -> Result<CalculationIndexEntry, AppError> {
+    let file_name = path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .ok_or_else(|| AppError::InvalidData(format!("Invalid file name {:?}", path)))?;
+    let content = fs::read(path).await?;
+    let updated_at = modified_at(path).await?;
+    let created_at = previous.map(|p| p.created_at).unwrap_or(updated_at);
+
+    let entry = match upgrade_calculation_bytes(&content) {
+        Ok(upgraded) => {
+            let mut entry =
+                CalculationIndexEntry::new(file_name, &upgraded.data, created_at, updated_at);
+            match previous {
+                // Statuses changed before they were kept in the document are only indexed
+                Some(previous) if upgraded.data.status_history.is_empty() => {
+                    entry.status = previous.status;
+                }
+                Some(_) => {}
+                None => {
+                    for transition in &upgraded.data.status_history {
+                        let transition = StatusTransition {
+                            file_name: file_name.to_string(),
+                            ..transition.clone()
+                        };
+                        insert_transition(&db.estimate_transitions_tree, email, &transition)?;
+                    }
+                }
+            }
+            entry
+        }
+        Err(e) => {
+            log_event(
+                LogLevel::Warn,
+                format!("Calculation {} needs migration: {}", file_name, e),
+                Some(email),
+            );
+            let mut entry = previous
+                .cloned()
+                .unwrap_or_else(|| CalculationIndexEntry::unreadable(file_name, created_at));
+            entry.updated_at = updated_at;
+            entry.deleted_at = None;
+            entry.migration_error = Some(e.to_string());
+            entry
+        }
+    };
+    upsert_entry(&db.calculations_tree, email, &entry)?;
+    Ok(entry)
+}
+
+/// Index entry of a single calculation, indexing the file first if it is not known yet.
+pub async fn indexed_calculation(
+    db: &AppDb,
+    email: &str,
+    calculations_dir: &Path,
+    file_name: &str,
+) -> Result<CalculationIndexEntry, AppError> {
+    if let Some(entry) = get_entry(&db.calculations_tree, email, file_name)? {
+        return match entry.deleted_at {
+            Some(_) => Err(AppError::FileNotFound),
+            None => Ok(entry),
+        };
+    }
+    let path = safe_join(calculations_dir, Path::new(file_name))?;
+    if !path.is_file() {
+        return Err(AppError::FileNotFound);
+    }
+    index_calculation_file(db, email, &path, None).await
+}
+
+/// Brings every organization's index in line with its calculations directory. Runs once
+/// on start; afterwards saving, deleting, restoring and importing keep the index current,
+/// so listing reads the index only.
+pub async fn sync_all_calculation_indexes(db: &AppDb, data_dir: &PathBuf) -> Result<(), AppError> {
+    for organization in list_organizations(&db.organizations_tree)? {
+        let calculations_dir =
+            organization_directory(data_dir, &organization.id)?.join(CALCULATIONS);
+        if let Err(e) = sync_calculation_index(db, &organization.id, &calculations_dir).await {
+            log_event(
+                LogLevel::Error,
+                format!("Calculation index of {} not synced: {}", organization.id, e),
+                None::<&str>,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Brings the user's index in line with the calculations directory: files saved before
+/// the index existed or changed outside `save_calculation` are (re)indexed, entries of
+/// removed files are dropped unless the calculation was soft-deleted. Only files whose
+/// modification time changed are read.
+async fn sync_calculation_index(
+    db: &AppDb,
+    email: &str,
+    calculations_dir: &Path,
+) -> Result<(), AppError> {
+    let mut indexed = list_user_entries(&db.calculations_tree, email)?;
+    let mut positions: HashMap<String, usize> = indexed
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (e.file_name.clone(), i))
+        .collect();
+    let mut present = HashSet::new();
+
+    let mut dir = match fs::read_dir(calculations_dir).await {
+        Ok(dir) => Some(dir),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = match dir.as_mut() {
+        Some(dir) => dir.next_entry().await?,
+        None => None,
+    } {
+        let path = entry.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") || !path.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        present.insert(file_name.clone());
+
+        let position = positions.get(&file_name).copied();
+        let modified = modified_at(&path).await?;
+        // Entries indexed before titles existed are refreshed once
+        if position.is_some_and(|i| indexed[i].updated_at == modified && !indexed[i].title.is_empty()) {
+            continue;
+        }
+        let previous = position.map(|i| indexed[i].clone());
+        match index_calculation_file(db, email, &path, previous.as_ref()).await {
+            Ok(updated) => match position {
+                Some(i) => indexed[i] = updated,
+                None => {
+                    positions.insert(file_name, indexed.len());
+                    indexed.push(updated);
+                }
+            },
+            Err(e) => log_event(
+                LogLevel::Warn,
+                format!("Calculation {} not indexed: {}", file_name, e),
+                Some(email),
+            ),
+        }
+    }
+
+    // Deleted calculations live outside the directory but keep their entries
+    let is_stale = |e: &CalculationIndexEntry| !present.contains(&e.file_name) && e.deleted_at.is_none();
+    for stale in indexed.iter().filter(|e| is_stale(e)) {
+        remove_entry(&db.calculations_tree, email, &stale.file_name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry(file_name: &str, make: &str, plate: &str, total: f64, day: u32) -> CalculationIndexEntry {
+        let date = Utc.with_ymd_and_hms(2026, 3, day, 12, 0, 0).unwrap();
+        CalculationIndexEntry {
+            file_name: file_name.to_string(),
+            name: file_name.trim_end_matches(".json").to_string(),
+            make: Some(make.to_string()),
+            model: Some("octavia".to_string()),
+            year: "2018".to_string(),
+            car_class: "C".to_string(),
+            body_type: "sedan".to_string(),
+            vin: None,
+            license_plate: Some(plate.to_string()),
+            notes: None,
+            title: String::new(),
+            customer_id: None,
+            vehicle_id: None,
+            parts: vec!["Капот".to_string()],
+            total,
+            status: EstimateStatus::Draft,
+            created_at: date,
+            updated_at: date,
+            deleted_at: None,
+            migration_error: None,
+        }
+    }
+
+    fn entries() -> Vec<CalculationIndexEntry> {
+        vec![
+            entry("a.json", "skoda", "AA 1234 BB", 100.0, 1),
+            entry("b.json", "skoda", "AA5678BB", 300.0, 2),
+            entry("c.json", "volkswagen", "KA-0001-AX", 200.0, 3),
+            entry("d.json", "skoda", "BC0002AB", 200.0, 4),
+        ]
+    }
+
+    #[test]
+    fn filters_and_full_text() {
+        let query = CalculationListQuery {
+            q: Some("skoda капот".to_string()),
+            total_min: Some(150.0),
+            ..Default::default()
+        };
+        let page = query.apply(entries()).unwrap();
+        let names: Vec<_> = page.items.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["d", "b"]);
+
+        let query = CalculationListQuery {
+            license_plate: Some("aa1234".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(query.apply(entries()).unwrap().items[0].name, "a");
+    }
+
+    #[test]
+    fn cursor_pagination_by_total() {
+        let mut query = CalculationListQuery {
+            sort: SortField::Total,
+            order: SortOrder::Asc,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let first = query.apply(entries()).unwrap();
+        assert_eq!(first.total_count, 4);
+        let names: Vec<_> = first.items.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "c"]);
+
+        query.cursor = first.next_cursor;
+        let second = query.apply(entries()).unwrap();
+        let names: Vec<_> = second.items.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["d", "b"]);
+        assert!(second.next_cursor.is_none());
+    }
+}
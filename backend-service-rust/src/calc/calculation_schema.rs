@@ -0,0 +1,349 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+    errors::AppError,
+    models::calculations::{
+        parse_decimal, CalculationData, CarCalcData, CALCULATION_SCHEMA_VERSION,
+    },
+};
+
+pub const SCHEMA_VERSION_FIELD: &str = "schemaVersion";
+
+type Document = Map<String, Value>;
+type Migration = fn(&mut Document) -> Result<(), String>;
+
+/// `MIGRATIONS[n]` upgrades a document from schema version `n` to `n + 1`.
+const MIGRATIONS: [Migration; CALCULATION_SCHEMA_VERSION as usize] = [legacy_to_v1, v1_to_v2];
+
+#[derive(Debug)]
+pub struct UpgradedCalculation {
+    pub data: CarCalcData,
+    pub from_version: u32,
+}
+
+impl UpgradedCalculation {
+    pub fn migrated(&self) -> bool {
+        self.from_version != CALCULATION_SCHEMA_VERSION
+    }
+}
+
+/// Files saved before versioning have no version field, the shape tells them apart:
+/// version 1 is the staged estimator format with a nested `car`, version 0 the flat
+/// [`CalculationData`] format.
+pub fn detect_version(doc: &Document) -> Result<u32, String> {
+    if let Some(version) = doc.get(SCHEMA_VERSION_FIELD) {
+        return version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("invalid {} {}", SCHEMA_VERSION_FIELD, version));
+    }
+    if doc.contains_key("car") {
+        Ok(1)
+    } else if doc.contains_key("body_type") || doc.contains_key("car_class") {
+        Ok(0)
+    } else {
+        Err("not a calculation document".to_string())
+    }
+}
+
+/// Brings a stored calculation of any known version to the current schema.
+pub fn upgrade_calculation(value: Value) -> Result<UpgradedCalculation, AppError> {
+    let Value::Object(mut doc) = value else {
+        return Err(AppError::InvalidData(
+            "Calculation must be a JSON object".to_string(),
+        ));
+    };
+    let from_version = detect_version(&doc).map_err(AppError::InvalidData)?;
+    if from_version > CALCULATION_SCHEMA_VERSION {
+        return Err(AppError::InvalidData(format!(
+            "Calculation has schema version {}, this server supports up to {}",
+            from_version, CALCULATION_SCHEMA_VERSION
+        )));
+    }
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(from_version as usize) {
+        migration(&mut doc).map_err(|e| {
+            AppError::InvalidData(format!(
+                "Failed to upgrade calculation from schema version {}: {}",
+                version, e
+            ))
+        })?;
+    }
+    doc.insert(
+        SCHEMA_VERSION_FIELD.to_string(),
+        json!(CALCULATION_SCHEMA_VERSION),
+    );
+    keep_unparsed_values(&mut doc);
+
+    let data = serde_json::from_value(Value::Object(doc)).map_err(|e| {
+        AppError::InvalidData(format!(
+            "Calculation does not match schema version {}: {}",
+            CALCULATION_SCHEMA_VERSION, e
+        ))
+    })?;
+    Ok(UpgradedCalculation { data, from_version })
+}
+
+pub fn upgrade_calculation_bytes(content: &[u8]) -> Result<UpgradedCalculation, AppError> {
+    let value = serde_json::from_slice(content)
+        .map_err(|e| AppError::InvalidData(format!("Calculation is not valid JSON: {}", e)))?;
+    upgrade_calculation(value)
+}
+
+/// The typed model reads numbers and texts in processing results leniently and drops
+/// what it cannot read. Such values are copied to a `raw<Field>` sibling first, which
+/// ends up in `additional_fields`, so saving or migrating the document does not lose them.
+fn keep_unparsed_values(doc: &mut Document) {
+    let Some(calculations) = doc.get_mut("calculations").and_then(Value::as_object_mut) else {
+        return;
+    };
+    let processings = calculations
+        .values_mut()
+        .filter_map(Value::as_array_mut)
+        .flatten()
+        .filter_map(Value::as_object_mut);
+    for processing in processings {
+        keep_unparsed(processing, "total", "rawTotal", is_number);
+        keep_unparsed(processing, "error", "rawError", |error| match error {
+            Value::Object(error) => match error.len() {
+                0 => true,
+                1 => error.get("message").is_some_and(Value::is_string),
+                _ => false,
+            },
+            _ => true,
+        });
+        let rows = processing
+            .get_mut("result")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_object_mut);
+        for row in rows {
+            keep_unparsed(row, "estimation", "rawEstimation", is_number);
+            keep_unparsed(row, "price", "rawPrice", is_number);
+            keep_unparsed(row, "evaluate", "rawEvaluate", is_text);
+            keep_unparsed(row, "sum", "rawSum", is_text);
+        }
+    }
+}
+
+fn keep_unparsed(
+    object: &mut Document,
+    field: &str,
+    raw_field: &str,
+    readable: fn(&Value) -> bool,
+) {
+    if let Some(value) = object.get(field).filter(|v| !v.is_null() && !readable(v)) {
+        object.insert(raw_field.to_string(), value.clone());
+    }
+}
+
+fn is_number(value: &Value) -> bool {
+    match value {
+        Value::Number(n) => n.as_f64().is_some(),
+        Value::String(s) => parse_decimal(s).is_some(),
+        _ => false,
+    }
+}
+
+fn is_text(value: &Value) -> bool {
+    value.is_string() || value.is_number()
+}
+
+fn legacy_to_v1(doc: &mut Document) -> Result<(), String> {
+    let legacy: CalculationData =
+        serde_json::from_value(Value::Object(std::mem::take(doc))).map_err(|e| e.to_string())?;
+    let (make, model) = match legacy.model {
+        Some(m) => (Some(m.brand), Some(m.model)),
+        None => (None, None),
+    };
+    // Legacy body parts were `{brand, model}` pairs with the part name in `model`
+    let selected_parts: Vec<Value> = legacy
+        .body_parts
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| json!({ "name": p.model }))
+        .collect();
+
+    doc.insert(
+        "car".to_string(),
+        json!({
+            "make": make,
+            "model": model,
+            "year": legacy.year,
+            "carClass": legacy.car_class,
+            "bodyType": legacy.body_type,
+            "licensePlate": legacy.license_plate,
+            "vin": legacy.vin,
+            "notes": legacy.notes,
+            "storeFileName": legacy.saved_file_name,
+        }),
+    );
+    doc.insert(
+        "paint".to_string(),
+        json!({ "color": legacy.color, "paintType": legacy.paint_type }),
+    );
+    doc.insert(
+        "parts".to_string(),
+        json!({ "selectedParts": selected_parts }),
+    );
+    if let Some(timestamp) = legacy.timestamp {
+        doc.insert("timestamp".to_string(), json!(timestamp));
+    }
+    Ok(())
+}
+
+fn v1_to_v2(doc: &mut Document) -> Result<(), String> {
+    let car = doc
+        .get_mut("car")
+        .and_then(Value::as_object_mut)
+        .ok_or("`car` must be an object")?;
+
+    // The car form posted the VIN as `VIN`, which the typed model dropped on save
+    if let Some(vin) = car.remove("VIN") {
+        if car.get("vin").is_none_or(Value::is_null) {
+            car.insert("vin".to_string(), vin);
+        }
+    }
+    match car.get("year") {
+        Some(Value::Number(n)) => {
+            let year = n.to_string();
+            car.insert("year".to_string(), json!(year));
+        }
+        Some(Value::String(_)) => {}
+        _ => {
+            car.insert("year".to_string(), json!(""));
+        }
+    }
+    for field in ["carClass", "bodyType"] {
+        if car.get(field).is_none_or(Value::is_null) {
+            car.insert(field.to_string(), json!(""));
+        }
+    }
+
+    // Free-form `calculations` not keyed by part are kept aside instead of failing the upgrade
+    let keyed_by_part = doc.get("calculations").is_none_or(|c| {
+        c.is_null() || c.as_object().is_some_and(|parts| parts.values().all(Value::is_array))
+    });
+    if !keyed_by_part {
+        if let Some(calculations) = doc.remove("calculations") {
+            doc.insert("legacyCalculations".to_string(), calculations);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrades_legacy_document() {
+        let legacy = json!({
+            "model": {"brand": "skoda", "model": "octavia"},
+            "year": "2015",
+            "body_type": "sedan",
+            "car_class": "C",
+            "color": "white",
+            "paint_type": "metallic",
+            "body_parts": [{"brand": "skoda", "model": "hood"}],
+            "timestamp": null,
+            "notes": null,
+            "saved_file_name": "octavia.json",
+            "vin": null,
+            "license_plate": "AA1234BB"
+        });
+        let upgraded = upgrade_calculation(legacy).unwrap();
+        assert_eq!(upgraded.from_version, 0);
+        let data = upgraded.data;
+        assert_eq!(data.schema_version, CALCULATION_SCHEMA_VERSION);
+        assert_eq!(data.car.make.as_deref(), Some("skoda"));
+        assert_eq!(data.car.store_file_name.as_deref(), Some("octavia.json"));
+        assert_eq!(data.paint.unwrap().paint_type.as_deref(), Some("metallic"));
+        assert_eq!(data.parts.unwrap().selected_parts[0].name, "hood");
+    }
+
+    #[test]
+    fn upgrades_unversioned_document() {
+        let v1 = json!({
+            "car": {"make": null, "year": 2020, "carClass": "C", "bodyType": null, "VIN": "TMB123"},
+            "calculations": {
+                "hood": [{"name": "paint", "error": null, "total": "1,5", "result": [
+                    {"name": "prep", "estimation": 0.5, "evaluate": "0,5", "price": 1, "sum": "0.50"},
+                    {"name": "coat", "estimation": "1", "evaluate": 1, "price": "1", "sum": 1}
+                ]}]
+            },
+            "totalTables": {"kept": true}
+        });
+        let upgraded = upgrade_calculation(v1).unwrap();
+        assert!(upgraded.migrated());
+        let data = upgraded.data;
+        assert_eq!(data.car.year, "2020");
+        assert_eq!(data.car.vin.as_deref(), Some("TMB123"));
+        let processing = &data.calculations.as_ref().unwrap()["hood"][0];
+        assert_eq!(processing.total, Some(1.5));
+        let rows = &processing.result;
+        assert_eq!(rows[1].estimation, Some(1.0));
+        assert_eq!(rows[1].sum.as_deref(), Some("1"));
+        assert!(data.additional_fields.contains_key("totalTables"));
+
+        let again = upgrade_calculation(serde_json::to_value(&data).unwrap()).unwrap();
+        assert!(!again.migrated());
+    }
+
+    #[test]
+    fn keeps_failed_processors() {
+        let doc = json!({
+            "car": {"make": "skoda", "year": 2018, "carClass": "C", "bodyType": null},
+            "calculations": {
+                "hood": [
+                    {"name": "paint", "result": null, "text": "ReferenceError", "error": {}},
+                    {"name": "prep", "result": null, "error": {"message": "t2 is null"}},
+                    {"name": "coat", "result": [], "error": "failed"}
+                ]
+            }
+        });
+        let upgraded = upgrade_calculation(doc).unwrap();
+        let hood = &upgraded.data.calculations.as_ref().unwrap()["hood"];
+        assert!(hood[0].result.is_empty());
+        assert_eq!(hood[0].error.as_deref(), Some("{}"));
+        assert_eq!(hood[1].error.as_deref(), Some("t2 is null"));
+        assert_eq!(hood[2].error.as_deref(), Some("failed"));
+    }
+
+    #[test]
+    fn keeps_unparsed_values() {
+        let doc = json!({
+            "car": {"make": "skoda", "year": "2018", "carClass": "C", "bodyType": "sedan"},
+            "calculations": {
+                "hood": [{
+                    "name": "paint",
+                    "total": "n/a",
+                    "error": {"message": "t2 is null", "stack": "at t2"},
+                    "result": [{"name": "prep", "estimation": "~1", "price": 2, "sum": true}]
+                }]
+            }
+        });
+        let data = upgrade_calculation(doc).unwrap().data;
+        let processing = &data.calculations.as_ref().unwrap()["hood"][0];
+        assert_eq!(processing.total, None);
+        assert_eq!(processing.additional_fields["rawTotal"], json!("n/a"));
+        assert_eq!(processing.error.as_deref(), Some("t2 is null"));
+        assert_eq!(processing.additional_fields["rawError"]["stack"], json!("at t2"));
+        let row = &processing.result[0];
+        assert_eq!(row.price, Some(2.0));
+        assert_eq!(row.additional_fields["rawEstimation"], json!("~1"));
+        assert_eq!(row.additional_fields["rawSum"], json!(true));
+
+        let saved = serde_json::to_value(&data).unwrap();
+        assert_eq!(saved["calculations"]["hood"][0]["rawTotal"], json!("n/a"));
+        let again = upgrade_calculation(saved).unwrap().data;
+        let row = &again.calculations.as_ref().unwrap()["hood"][0].result[0];
+        assert_eq!(row.additional_fields["rawEstimation"], json!("~1"));
+    }
+
+    #[test]
+    fn rejects_unknown_documents() {
+        assert!(upgrade_calculation(json!({"foo": 1})).is_err());
+        assert!(upgrade_calculation(json!({"schemaVersion": 99, "car": {}})).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+use serde::Serialize;
+use std::{
+    ffi::OsStr,
+    path::PathBuf,
+    time::SystemTime,
+};
+
+use crate::{
+    calc::cars::{parse_car_yaml, CarData},
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    utils::all_files_with_extension,
+};
+
+/// Directory with per-make car YAML files, relative to the catalog root.
+pub const CARS: &str = "cars";
+
+/// Minimal score for a model to be reported by [`CarCatalog::search`].
+const SEARCH_THRESHOLD: f64 = 0.6;
+
+/// A production period of a model, parsed from entries like `"2003-2012"` or `"2020-"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Generation {
+    /// Zero-based position of the generation in the model's `gen` list.
+    pub index: usize,
+    pub from: u16,
+    /// `None` for a generation that is still in production.
+    pub to: Option<u16>,
+    pub label: String,
+}
+
+impl Generation {
+    pub fn parse(index: usize, label: &str) -> Option<Self> {
+        let (from, to) = label.split_once('-')?;
+        let from = from.trim().parse().ok()?;
+        let to = match to.trim() {
+            "" => None,
+            to => Some(to.parse().ok()?),
+        };
+        Some(Self {
+            index,
+            from,
+            to,
+            label: label.to_string(),
+        })
+    }
+
+    pub fn contains(&self, year: u16) -> bool {
+        year >= self.from && self.to.map(|to| year <= to).unwrap_or(true)
+    }
+}
+
+impl CarData {
+    pub fn generations(&self) -> Vec<Generation> {
+        self.gen
+            .iter()
+            .enumerate()
+            .filter_map(|(i, label)| Generation::parse(i, label))
+            .collect()
+    }
+
+    /// Generation produced in `year`. Boundary years belong to both adjacent
+    /// generations in the catalog, the newer one wins.
+    pub fn generation_for_year(&self, year: u16) -> Option<Generation> {
+        self.generations()
+            .into_iter()
+            .rev()
+            .find(|g| g.contains(year))
+    }
+
+    /// Models without any `gen` info are treated as available in every year.
+    pub fn produced_in(&self, year: u16) -> bool {
+        self.gen.is_empty() || self.generation_for_year(year).is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CatalogEntry {
+    pub make: String,
+    pub model: String,
+    pub data: CarData,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogMatch {
+    pub make: String,
+    pub model: String,
+    pub score: f64,
+    pub generation: Option<Generation>,
+    #[serde(flatten)]
+    pub data: CarData,
+}
+
+/// In-memory index over all `cars/*.yaml` files visible to a user
+/// (user catalog overrides common files with the same name).
+#[derive(Debug, Default)]
+pub struct CarCatalog {
+    entries: Vec<CatalogEntry>,
+    sources: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+async fn catalog_sources(
+    data_dir: &PathBuf,
+    organization: &str,
+) -> Result<Vec<(PathBuf, Option<SystemTime>)>, AppError> {
+    let mut files = all_files_with_extension(data_dir, organization, CARS, OsStr::new("yaml")).await?;
+    files.sort();
+    let mut sources = Vec::with_capacity(files.len());
+    for file in files {
+        let modified = tokio::fs::metadata(&file)
+            .await
+            .and_then(|m| m.modified())
+            .ok();
+        sources.push((file, modified));
+    }
+    Ok(sources)
+}
+
+impl CarCatalog {
+    pub async fn load(data_dir: &PathBuf, organization: &str) -> Result<Self, AppError> {
+        let sources = catalog_sources(data_dir, organization).await?;
+        let mut entries = Vec::new();
+        for (path, _) in sources.iter() {
+            let make = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(make) => make.to_string(),
+                None => continue,
+            };
+            // A broken make file must not take down search for every other make
+            let models = match parse_car_yaml(path) {
+                Ok(models) => models,
+                Err(e) => {
+                    log_event(
+                        LogLevel::Error,
+                        format!("Car catalog file {} skipped: {}", path.display(), e),
+                        Some(organization),
+                    );
+                    continue;
+                }
+            };
+            entries.extend(models.into_iter().map(|(model, data)| CatalogEntry {
+                make: make.clone(),
+                model,
+                data,
+            }));
+        }
+        entries.sort_by(|a, b| (&a.make, &a.model).cmp(&(&b.make, &b.model)));
+        Ok(Self { entries, sources })
+    }
+
+    /// Whether any of the source files was added, removed or modified since loading.
+    pub async fn is_stale(&self, data_dir: &PathBuf, organization: &str) -> Result<bool, AppError> {
+        Ok(catalog_sources(data_dir, organization).await? != self.sources)
+    }
+
+    pub fn get(&self, make: &str, model: &str) -> Option<&CatalogEntry> {
+        let make = normalize(make);
+        let model = normalize(model);
+        self.entries
+            .iter()
+            .find(|e| normalize(&e.make) == make && normalize(&e.model) == model)
+    }
+
+    /// All models of a make, optionally restricted to the ones produced in `year`.
+    pub fn models_of(&self, make: &str, year: Option<u16>) -> Vec<CatalogMatch> {
+        let make = normalize(make);
+        self.entries
+            .iter()
+            .filter(|e| normalize(&e.make) == make)
+            .filter(|e| year.map(|y| e.data.produced_in(y)).unwrap_or(true))
+            .map(|e| to_match(e, 1.0, year))
+            .collect()
+    }
+
+    /// Fuzzy search by make and model. Every word of the query has to match either
+    /// the make or the model, typos are tolerated. A four-digit word is treated as
+    /// the production year if `year` is not given explicitly.
+    pub fn search(&self, query: &str, year: Option<u16>, limit: usize) -> Vec<CatalogMatch> {
+        let mut year = year;
+        let mut words = Vec::new();
+        for word in query.split_whitespace().map(normalize).filter(|w| !w.is_empty()) {
+            match word.parse::<u16>() {
+                Ok(y) if year.is_none() && (1900..=2100).contains(&y) => year = Some(y),
+                _ => words.push(word),
+            }
+        }
+
+        let mut found: Vec<CatalogMatch> = self
+            .entries
+            .iter()
+            .filter(|e| year.map(|y| e.data.produced_in(y)).unwrap_or(true))
+            .filter_map(|e| {
+                let score = entry_score(e, &words)?;
+                Some(to_match(e, score, year))
+            })
+            .collect();
+        found.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| (&a.make, &a.model).cmp(&(&b.make, &b.model)))
+        });
+        found.truncate(limit);
+        found
+    }
+}
+
+fn to_match(entry: &CatalogEntry, score: f64, year: Option<u16>) -> CatalogMatch {
+    CatalogMatch {
+        make: entry.make.clone(),
+        model: entry.model.clone(),
+        score,
+        generation: year.and_then(|y| entry.data.generation_for_year(y)),
+        data: entry.data.clone(),
+    }
+}
+
+fn normalize(s: &str) -> String {
+    s.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn word_score(word: &str, candidate: &str) -> f64 {
+    if candidate == word {
+        1.0
+    } else if candidate.starts_with(word) {
+        0.9
+    } else if candidate.contains(word) {
+        0.75
+    } else {
+        strsim::jaro_winkler(word, candidate) * 0.8
+    }
+}
+
+fn entry_score(entry: &CatalogEntry, words: &[String]) -> Option<f64> {
+    if words.is_empty() {
+        return Some(1.0);
+    }
+    let mut candidates = vec![normalize(&entry.make), normalize(&entry.model)];
+    candidates.extend(entry.model.split_whitespace().map(normalize));
+
+    let mut total = 0.0;
+    for word in words {
+        let best = candidates
+            .iter()
+            .map(|c| word_score(word, c))
+            .fold(0.0, f64::max);
+        if best < SEARCH_THRESHOLD {
+            return None;
+        }
+        total += best;
+    }
+    Some(total / words.len() as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn car(gen: &[&str]) -> CarData {
+        CarData {
+            body: vec![],
+            class: None,
+            gen: gen.iter().map(|s| s.to_string()).collect(),
+            is_suv: false,
+            estimated_price: 0,
+            euro_class: "C".into(),
+            euro_body_types: vec![],
+        }
+    }
+
+    fn catalog() -> CarCatalog {
+        let entry = |make: &str, model: &str, gen: &[&str]| CatalogEntry {
+            make: make.into(),
+            model: model.into(),
+            data: car(gen),
+        };
+        CarCatalog {
+            entries: vec![
+                entry("skoda", "octavia", &["1996-2004", "2004-2013", "2013-2020", "2020-"]),
+                entry("volkswagen", "golf", &["1997-2003", "2003-2008", "2008-2012", "2012-"]),
+                entry("volkswagen", "polo", &["2009-2017"]),
+            ],
+            sources: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_broken_make_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let data_dir = dir.path().to_path_buf();
+        let cars = data_dir.join(crate::utils::COMMON).join(CARS);
+        std::fs::create_dir_all(&cars).unwrap();
+        std::fs::write(
+            cars.join("skoda.yaml"),
+            "octavia:\n  body: [\"sedan\"]\n  gen: [\"2013-2020\"]\n  is_suv: false\n  estimated_price: 20000\n  euro_class: \"C\"\n  euro_body_types: [\"sedan\"]\n",
+        )
+        .unwrap();
+        std::fs::write(cars.join("broken.yaml"), "octavia: [unclosed").unwrap();
+
+        let catalog = CarCatalog::load(&data_dir, "org").await.unwrap();
+        assert!(catalog.get("skoda", "octavia").is_some());
+        assert!(catalog.models_of("broken", None).is_empty());
+    }
+
+    #[test]
+    fn generation_ranges() {
+        let data = car(&["2003-2012", "2012-2020", "2020-"]);
+        assert_eq!(data.generation_for_year(2005).unwrap().index, 0);
+        assert_eq!(data.generation_for_year(2012).unwrap().index, 1);
+        assert_eq!(data.generation_for_year(2026).unwrap().to, None);
+        assert!(data.generation_for_year(1999).is_none());
+        assert!(Generation::parse(0, "bogus").is_none());
+    }
+
+    #[test]
+    fn fuzzy_search_tolerates_typos() {
+        let catalog = catalog();
+        let found = catalog.search("octavai", None, 10);
+        assert_eq!(found[0].model, "octavia");
+
+        let found = catalog.search("volkswagen", None, 10);
+        assert_eq!(found.len(), 2);
+
+        let found = catalog.search("zzz polo", None, 10);
+        assert!(found.is_empty(), "every word has to match");
+
+        let found = catalog.search("polo 2020", None, 10);
+        assert!(found.is_empty());
+
+        let found = catalog.search("golf 2010", None, 10);
+        assert_eq!(found[0].generation.as_ref().unwrap().label, "2008-2012");
+    }
+}
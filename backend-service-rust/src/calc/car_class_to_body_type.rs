@@ -74,3 +74,66 @@ pub fn serialize_to_yaml<T: Serialize, P: AsRef<Path>>(data: &T, file_path: P) -
 
     Ok(())
 }
+
+/// Class/body pairs that appeared or disappeared between two mappings.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct MappingDiff {
+    pub added: Vec<(String, String)>,
+    pub removed: Vec<(String, String)>,
+}
+
+impl MappingDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn mapping_pairs(mapping: &HashMap<String, Vec<String>>) -> HashSet<(String, String)> {
+    mapping
+        .iter()
+        .flat_map(|(class, bodies)| bodies.iter().map(move |b| (class.clone(), b.clone())))
+        .collect()
+}
+
+pub fn diff_mappings(
+    old: &HashMap<String, Vec<String>>,
+    new: &HashMap<String, Vec<String>>,
+) -> MappingDiff {
+    let old_pairs = mapping_pairs(old);
+    let new_pairs = mapping_pairs(new);
+    let mut added: Vec<_> = new_pairs.difference(&old_pairs).cloned().collect();
+    let mut removed: Vec<_> = old_pairs.difference(&new_pairs).cloned().collect();
+    added.sort();
+    removed.sort();
+    MappingDiff { added, removed }
+}
+
+/// Reads a previously generated mapping, `None` if it is missing or unreadable.
+pub fn read_mapping_yaml<P: AsRef<Path>>(file_path: P) -> Option<HashMap<String, Vec<String>>> {
+    let content = fs::read_to_string(file_path).ok()?;
+    serde_yaml::from_str(&content).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_reports_pairs() {
+        let old: HashMap<String, Vec<String>> = [
+            ("A".to_string(), vec!["sedan".to_string(), "wagon".to_string()]),
+            ("B".to_string(), vec!["sedan".to_string()]),
+        ]
+        .into();
+        let new: HashMap<String, Vec<String>> = [
+            ("A".to_string(), vec!["sedan".to_string()]),
+            ("C".to_string(), vec!["coupe".to_string()]),
+            ("B".to_string(), vec!["sedan".to_string()]),
+        ]
+        .into();
+        let diff = diff_mappings(&old, &new);
+        assert_eq!(diff.added, vec![("C".to_string(), "coupe".to_string())]);
+        assert_eq!(diff.removed, vec![("A".to_string(), "wagon".to_string())]);
+        assert!(diff_mappings(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,339 @@
+use serde::Serialize;
+use serde_yaml::Value;
+use std::collections::HashSet;
+
+use crate::calc::{car_catalog::Generation, cars::CarData, constants::BODY_TYPES};
+
+const REQUIRED_FIELDS: [&str; 4] = ["body", "estimated_price", "euro_class", "euro_body_types"];
+
+// Descriptive fields kept in the catalog files, not used by calculations
+const OPTIONAL_FIELDS: [&str; 7] = [
+    "class",
+    "gen",
+    "is_suv",
+    "powertrain",
+    "price",
+    "variants",
+    "models",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModelIssue {
+    /// `None` for problems with the file as a whole.
+    pub model: Option<String>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl ModelIssue {
+    fn file(message: impl Into<String>) -> Self {
+        Self {
+            model: None,
+            field: None,
+            message: message.into(),
+        }
+    }
+
+    fn field(model: &str, field: &str, message: impl Into<String>) -> Self {
+        Self {
+            model: Some(model.to_string()),
+            field: Some(field.to_string()),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CarFileReport {
+    pub make: String,
+    pub models_total: usize,
+    pub models_valid: usize,
+    pub issues: Vec<ModelIssue>,
+}
+
+fn closest_field(name: &str) -> Option<&'static str> {
+    REQUIRED_FIELDS
+        .iter()
+        .chain(OPTIONAL_FIELDS.iter())
+        .map(|f| (*f, strsim::levenshtein(name, f)))
+        .filter(|(_, distance)| *distance <= 2)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(f, _)| f)
+}
+
+fn string_list<'a>(
+    model: &str,
+    field: &str,
+    value: &'a Value,
+    issues: &mut Vec<ModelIssue>,
+) -> Vec<&'a str> {
+    let Some(items) = value.as_sequence() else {
+        issues.push(ModelIssue::field(model, field, "expected a list of strings"));
+        return vec![];
+    };
+    items
+        .iter()
+        .filter_map(|item| {
+            let s = item.as_str();
+            if s.is_none() {
+                issues.push(ModelIssue::field(
+                    model,
+                    field,
+                    format!("expected a string, found {:?}", item),
+                ));
+            }
+            s
+        })
+        .collect()
+}
+
+/// Checks a single model entry. `known_classes` holds the classes from the class/body
+/// mapping; `None` skips the `euro_class` check (mapping not built yet).
+pub fn validate_model(
+    model: &str,
+    value: &Value,
+    known_classes: Option<&HashSet<String>>,
+) -> Vec<ModelIssue> {
+    let mut issues = Vec::new();
+    let Some(fields) = value.as_mapping() else {
+        issues.push(ModelIssue {
+            model: Some(model.to_string()),
+            field: None,
+            message: "model entry must be a mapping of fields".to_string(),
+        });
+        return issues;
+    };
+
+    for key in fields.keys() {
+        let Some(key) = key.as_str() else {
+            issues.push(ModelIssue::field(model, "", format!("non-string field name {:?}", key)));
+            continue;
+        };
+        if REQUIRED_FIELDS.contains(&key) || OPTIONAL_FIELDS.contains(&key) {
+            continue;
+        }
+        let message = match closest_field(key) {
+            Some(suggestion) => format!("unknown field, did you mean `{}`?", suggestion),
+            None => "unknown field".to_string(),
+        };
+        issues.push(ModelIssue::field(model, key, message));
+    }
+
+    for field in REQUIRED_FIELDS {
+        if !fields.contains_key(field) {
+            issues.push(ModelIssue::field(model, field, "missing required field"));
+        }
+    }
+
+    if let Some(body) = fields.get("body") {
+        string_list(model, "body", body, &mut issues);
+    }
+
+    if let Some(price) = fields.get("estimated_price") {
+        if price.as_u64().is_none_or(|p| p > u32::MAX as u64) {
+            issues.push(ModelIssue::field(
+                model,
+                "estimated_price",
+                "expected a non-negative integer",
+            ));
+        }
+    }
+
+    if let Some(is_suv) = fields.get("is_suv") {
+        if !is_suv.is_bool() {
+            issues.push(ModelIssue::field(model, "is_suv", "expected true or false"));
+        }
+    }
+
+    if let Some(gen) = fields.get("gen") {
+        for (i, label) in string_list(model, "gen", gen, &mut issues).into_iter().enumerate() {
+            match Generation::parse(i, label) {
+                Some(g) if g.to.is_some_and(|to| to < g.from) => issues.push(ModelIssue::field(
+                    model,
+                    "gen",
+                    format!("generation `{}` ends before it starts", label),
+                )),
+                Some(_) => {}
+                None => issues.push(ModelIssue::field(
+                    model,
+                    "gen",
+                    format!("`{}` is not a year range like `2003-2012` or `2020-`", label),
+                )),
+            }
+        }
+    }
+
+    match fields.get("euro_class") {
+        Some(Value::String(class)) => {
+            if let Some(classes) = known_classes {
+                if !classes.contains(class) {
+                    issues.push(ModelIssue::field(
+                        model,
+                        "euro_class",
+                        format!("class `{}` is not present in the class/body mapping", class),
+                    ));
+                }
+            }
+        }
+        Some(_) => issues.push(ModelIssue::field(model, "euro_class", "expected a string")),
+        None => {}
+    }
+
+    if let Some(body_types) = fields.get("euro_body_types") {
+        for body_type in string_list(model, "euro_body_types", body_types, &mut issues) {
+            if !BODY_TYPES.contains(&body_type) {
+                issues.push(ModelIssue::field(
+                    model,
+                    "euro_body_types",
+                    format!(
+                        "unknown body type `{}`, expected one of: {}",
+                        body_type,
+                        BODY_TYPES.join(", ")
+                    ),
+                ));
+            }
+        }
+    }
+
+    // Anything the checks above missed still has to deserialize
+    if issues.is_empty() {
+        if let Err(e) = serde_yaml::from_value::<CarData>(value.clone()) {
+            issues.push(ModelIssue {
+                model: Some(model.to_string()),
+                field: None,
+                message: e.to_string(),
+            });
+        }
+    }
+    issues
+}
+
+/// Validates the content of a `cars/<make>.yaml` file model by model,
+/// so one broken entry does not hide problems (or valid models) elsewhere.
+pub fn validate_car_file(
+    make: &str,
+    content: &str,
+    known_classes: Option<&HashSet<String>>,
+) -> CarFileReport {
+    let mut report = CarFileReport {
+        make: make.to_string(),
+        models_total: 0,
+        models_valid: 0,
+        issues: vec![],
+    };
+    let doc: Value = match serde_yaml::from_str(content) {
+        Ok(doc) => doc,
+        Err(e) => {
+            report.issues.push(ModelIssue::file(format!("invalid YAML: {}", e)));
+            return report;
+        }
+    };
+    let Some(models) = doc.as_mapping() else {
+        if !doc.is_null() {
+            report
+                .issues
+                .push(ModelIssue::file("expected a mapping of model names to model data"));
+        }
+        return report;
+    };
+    for (name, value) in models {
+        report.models_total += 1;
+        let name = match name {
+            Value::String(s) => s.clone(),
+            other => {
+                report
+                    .issues
+                    .push(ModelIssue::file(format!("model name {:?} must be a string", other)));
+                continue;
+            }
+        };
+        let issues = validate_model(&name, value, known_classes);
+        if issues.is_empty() {
+            report.models_valid += 1;
+        }
+        report.issues.extend(issues);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_issues_per_model() {
+        let yaml = r#"
+"golf":
+  body: ["hatchback"]
+  gen: ["2012-", "20x0-2010"]
+  estimated_price: 25000
+  euro_class: "C"
+  euro_body_types: ["hatchback 5 doors", "limousine"]
+"polo":
+  body: ["hatchback"]
+  estimated_price: 18000
+  euro_clas: "B"
+  euro_body_types: ["hatchback 5 doors"]
+"passat":
+  body: ["sedan"]
+  estimated_price: 35000
+  euro_class: "D"
+  euro_body_types: ["sedan", "wagon"]
+"#;
+        let classes: HashSet<String> = ["B", "C"].iter().map(|s| s.to_string()).collect();
+        let report = validate_car_file("volkswagen", yaml, Some(&classes));
+        assert_eq!(report.models_total, 3);
+        assert_eq!(report.models_valid, 0);
+
+        let for_model = |m: &str| -> Vec<&ModelIssue> {
+            report
+                .issues
+                .iter()
+                .filter(|i| i.model.as_deref() == Some(m))
+                .collect()
+        };
+        assert_eq!(for_model("golf").len(), 2);
+        let polo = for_model("polo");
+        assert_eq!(polo.len(), 2);
+        assert!(polo[0].message.contains("`euro_class`"));
+        let passat = for_model("passat");
+        assert_eq!(passat.len(), 1);
+        assert_eq!(passat[0].field.as_deref(), Some("euro_class"));
+    }
+
+    #[test]
+    fn shipped_catalog_is_valid() {
+        let common = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../common");
+        let mapping = std::fs::read_to_string(
+            common.join(crate::calc::car_class_to_body_type::CLASS_TYPE_MAPPING_FILE),
+        )
+        .unwrap();
+        let mapping: serde_yaml::Mapping = serde_yaml::from_str(&mapping).unwrap();
+        let classes: HashSet<String> = mapping
+            .keys()
+            .filter_map(|k| k.as_str().map(str::to_string))
+            .collect();
+
+        let mut checked = 0;
+        for entry in std::fs::read_dir(common.join("cars")).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("yaml") {
+                continue;
+            }
+            let make = path.file_stem().unwrap().to_string_lossy().to_string();
+            let report =
+                validate_car_file(&make, &std::fs::read_to_string(&path).unwrap(), Some(&classes));
+            assert!(report.issues.is_empty(), "{}: {:?}", make, report.issues);
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn broken_yaml_is_a_file_issue() {
+        let report = validate_car_file("seat", "\"ibiza\": [unclosed", None);
+        assert_eq!(report.models_total, 0);
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.issues[0].model.is_none());
+    }
+}
@@ -15,6 +15,11 @@ use crate::calc::constants::*;
 pub struct CarData {
     pub body: Vec<String>,
     #[serde(default)]
+    pub class: Option<String>,
+    // Production years per generation, e.g. "1996-2003" or "2020-" for a running one
+    #[serde(default)]
+    pub gen: Vec<String>,
+    #[serde(default)]
     pub is_suv: bool,
     pub estimated_price: u32,
     pub euro_class: String,
@@ -22,11 +27,27 @@ pub struct CarData {
 }
 
 
+// Broken model entries are skipped (and logged) instead of failing the whole file,
+// see car_schema for the detailed report
 pub fn parse_car_yaml<P: AsRef<Path>>(path: P) -> Result<HashMap<String, CarData>, Box<dyn Error>> {
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);    
-    let car_data: HashMap<String, CarData> = serde_yaml::from_reader(reader)?;
-    
+    let file = File::open(&path)?;
+    let reader = BufReader::new(file);
+    let raw: Option<HashMap<String, serde_yaml::Value>> = serde_yaml::from_reader(reader)?;
+
+    let mut car_data = HashMap::new();
+    for (model, value) in raw.unwrap_or_default() {
+        match serde_yaml::from_value::<CarData>(value) {
+            Ok(data) => {
+                car_data.insert(model, data);
+            }
+            Err(e) => log::warn!(
+                "Skipping model {} in {:?}: {}",
+                model,
+                path.as_ref(),
+                e
+            ),
+        }
+    }
     Ok(car_data)
 }
 
@@ -8,6 +8,20 @@ pub const BODY_TYPE_HATCHBACK_3_DOORS: &str = "hatchback 3 doors";
 pub const BODY_TYPE_SUV_3_DOORS: &str = "suv 3 doors";
 pub const BODY_TYPE_SUV_5_DOORS: &str = "suv 5 doors";
 pub const BODY_TYPE_COUPE: &str = "coupe";
+pub const BODY_TYPE_CABRIOLET: &str = "cabriolet";
+
+pub const BODY_TYPES: [&str; 10] = [
+    BODY_TYPE_WAGON,
+    BODY_TYPE_PICKUP,
+    BODY_TYPE_SEDAN,
+    BODY_TYPE_LIFTBACK,
+    BODY_TYPE_HATCHBACK_5_DOORS,
+    BODY_TYPE_HATCHBACK_3_DOORS,
+    BODY_TYPE_SUV_3_DOORS,
+    BODY_TYPE_SUV_5_DOORS,
+    BODY_TYPE_COUPE,
+    BODY_TYPE_CABRIOLET,
+];
 
 // T1 entry mappings - Russian to English
 pub const T1_ENTRY_WAGON: &str = "УНИВЕРСАЛ";
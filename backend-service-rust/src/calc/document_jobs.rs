@@ -0,0 +1,361 @@
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    calc::{document_numbering::release_document_number, templating::GeneratePdfInternalRequest},
+    db::{
+        attachment::{
+            insert_attachment, list_all_attachments_for_user, AttachmentHandle,
+            AttachmentLifecycle,
+        },
+        document_jobs::{get_job, list_all_jobs, list_user_jobs, remove_job, save_job},
+        users::AppDb,
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::documents::{AssignedNumber, DocumentFormat, DocumentJob, DocumentJobStatus},
+    state::AppState,
+    utils::{random::generate_random_id, safe_write, organization_directory, ATTACHMENTS},
+};
+
+/// Attempts before a job is marked as failed.
+pub const MAX_ATTEMPTS: u32 = 5;
+const RETRY_BASE_SECONDS: i64 = 10;
+const RETRY_MAX_SECONDS: i64 = 30 * 60;
+/// How often the worker looks for retries that became due.
+const WORKER_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// Finished jobs are kept this long for status queries, their documents stay.
+const FINISHED_JOB_RETENTION_DAYS: i64 = 7;
+
+/// Delay before the next attempt: 10s, 20s, 40s... up to 30 minutes.
+pub fn retry_delay(attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    Duration::seconds((RETRY_BASE_SECONDS << exponent).min(RETRY_MAX_SECONDS))
+}
+
+/// Identifies a document by everything it is rendered from.
+pub fn content_hash(
+    format: DocumentFormat,
+    request: &GeneratePdfInternalRequest,
+) -> Result<String, AppError> {
+    let mut hasher = Sha256::new();
+    hasher.update(format.extension().as_bytes());
+    hasher.update(serde_json::to_vec(request)?);
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// Cyrillic letters used on Ukrainian plates have Latin look-alikes
+fn transliterate_plate_char(c: char) -> Option<char> {
+    let latin = match c.to_uppercase().next().unwrap_or(c) {
+        'А' => 'A',
+        'В' => 'B',
+        'Е' => 'E',
+        'І' => 'I',
+        'К' => 'K',
+        'М' => 'M',
+        'Н' => 'H',
+        'О' => 'O',
+        'Р' => 'P',
+        'С' => 'C',
+        'Т' => 'T',
+        'Х' => 'X',
+        c if c.is_ascii_alphanumeric() || c == '-' => c,
+        _ => return None,
+    };
+    Some(latin)
+}
+
+fn file_name_part(value: Option<&Value>) -> Option<String> {
+    let part: String = value?.as_str()?.chars().filter_map(transliterate_plate_char).collect();
+    (!part.is_empty()).then_some(part)
+}
+
+/// File name from the estimate number, plate and order date, e.g.
+/// `estimate_034423_AA1234BB_2025-09-18.pdf`. Only ASCII is used so the name
+/// fits into a `Content-Disposition` header.
+pub fn document_file_name(request: &GeneratePdfInternalRequest, format: DocumentFormat) -> String {
+    let calculation = &request.calculation;
+    let number = request
+        .metadata
+        .order_number
+        .as_deref()
+        .map(|n| Value::String(n.to_string()));
+    let number = file_name_part(number.as_ref())
+        .or_else(|| file_name_part(calculation.pointer("/order/orderNumber")));
+    let plate = file_name_part(calculation.pointer("/car/licensePlate"));
+    let date = calculation
+        .pointer("/order/orderDate")
+        .and_then(Value::as_str)
+        .and_then(|d| d.get(..10))
+        .filter(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| Utc::now().format("%Y-%m-%d").to_string());
+
+    let mut parts = vec!["estimate".to_string()];
+    parts.extend(number);
+    parts.extend(plate);
+    parts.push(date);
+    format!("{}.{}", parts.join("_"), format.extension())
+}
+
+fn find_document(db: &AppDb, organization: &str, hash: &str) -> Result<Option<AttachmentHandle>, AppError> {
+    Ok(list_all_attachments_for_user(&db.attachments_tree, organization)?
+        .into_iter()
+        .find(|a| {
+            a.content_hash.as_deref() == Some(hash)
+                && std::path::Path::new(&a.file_path).exists()
+        }))
+}
+
+/// Queues a document for generation. A document already generated from the
+/// same input is reused, and a job already queued for it is returned instead
+/// of a new one.
+pub fn enqueue_document_job(
+    db: &AppDb,
+    organization: &str,
+    format: DocumentFormat,
+    calculation: Option<String>,
+    request: GeneratePdfInternalRequest,
+    number: Option<AssignedNumber>,
+) -> Result<DocumentJob, AppError> {
+    let hash = content_hash(format, &request)?;
+    let pending = list_user_jobs(&db.document_jobs_tree, organization)?
+        .into_iter()
+        .find(|j| {
+            j.content_hash == hash
+                && matches!(j.status, DocumentJobStatus::Queued | DocumentJobStatus::Running)
+        });
+    if let Some(job) = pending {
+        return Ok(job);
+    }
+
+    let now = Utc::now();
+    let mut job = DocumentJob {
+        id: generate_random_id(12),
+        owner: organization.to_string(),
+        format,
+        status: DocumentJobStatus::Queued,
+        calculation,
+        content_hash: hash,
+        file_name: document_file_name(&request, format),
+        attempts: 0,
+        created_at: now,
+        updated_at: now,
+        next_attempt_at: now,
+        last_error: None,
+        attachment_id: None,
+        deduplicated: false,
+        number,
+        request: Some(request),
+    };
+    if let Some(existing) = find_document(db, organization, &job.content_hash)? {
+        job.status = DocumentJobStatus::Done;
+        job.attachment_id = Some(existing.id);
+        job.file_name = existing.file_name;
+        job.deduplicated = true;
+        job.request = None;
+    }
+    save_job(&db.document_jobs_tree, &job)?;
+    Ok(job)
+}
+
+async fn store_document(
+    app_state: &AppState,
+    job: &DocumentJob,
+    content: Vec<u8>,
+) -> Result<AttachmentHandle, AppError> {
+    let organization_dir = organization_directory(&app_state.data_dir_path, &job.owner)?;
+    let size = content.len();
+    let written = safe_write(
+        organization_dir.join(ATTACHMENTS),
+        format!("{}__{}", job.id, job.file_name).into(),
+        content,
+        &app_state.cache,
+    )
+    .await?;
+    let mut handle = AttachmentHandle::new(
+        job.owner.clone(),
+        written.to_string_lossy().to_string(),
+        job.file_name.clone(),
+        Some(size),
+    );
+    handle.lifecycle = AttachmentLifecycle::GeneratedDocument;
+    handle.calculation = job.calculation.clone();
+    handle.content_hash = Some(job.content_hash.clone());
+    insert_attachment(&app_state.db.attachments_tree, &handle)?;
+    Ok(handle)
+}
+
+async fn render(app_state: &AppState, job: &DocumentJob) -> Result<Vec<u8>, AppError> {
+    let request = job
+        .request
+        .as_ref()
+        .ok_or_else(|| AppError::InvalidData("The job has no renderer input".to_string()))?;
+    app_state
+        .document_renderer
+        .render(request, &job.owner, job.format)
+        .await
+}
+
+async fn run_job(app_state: &AppState, mut job: DocumentJob) -> Result<(), AppError> {
+    job.status = DocumentJobStatus::Running;
+    job.attempts += 1;
+    job.updated_at = Utc::now();
+    save_job(&app_state.db.document_jobs_tree, &job)?;
+
+    let result = match render(app_state, &job).await {
+        Ok(content) => store_document(app_state, &job, content).await,
+        Err(e) => Err(e),
+    };
+    job.updated_at = Utc::now();
+    match result {
+        Ok(handle) => {
+            job.status = DocumentJobStatus::Done;
+            job.attachment_id = Some(handle.id);
+            job.last_error = None;
+            job.request = None;
+            log_event(
+                LogLevel::Info,
+                format!("Document {} generated by job {}", job.file_name, job.id),
+                Some(job.owner.as_str()),
+            );
+        }
+        Err(e) => {
+            // Template and input errors fail the same way on every attempt
+            let permanent = matches!(e, AppError::InvalidData(_));
+            job.last_error = Some(e.to_string());
+            if permanent || job.attempts >= MAX_ATTEMPTS {
+                job.status = DocumentJobStatus::Failed;
+                job.request = None;
+                if let Some(number) = job.number.clone() {
+                    release_document_number(&app_state.db, &job.owner, number, &e.to_string())?;
+                }
+            } else {
+                job.status = DocumentJobStatus::Queued;
+                job.next_attempt_at = job.updated_at + retry_delay(job.attempts);
+            }
+            log_event(
+                LogLevel::Warn,
+                format!(
+                    "Document job {} attempt {} failed ({:?}): {}",
+                    job.id, job.attempts, job.status, e
+                ),
+                Some(job.owner.as_str()),
+            );
+        }
+    }
+    save_job(&app_state.db.document_jobs_tree, &job)
+}
+
+async fn run_due_jobs(app_state: &AppState) -> Result<(), AppError> {
+    let now = Utc::now();
+    let mut due: Vec<DocumentJob> = list_all_jobs(&app_state.db.document_jobs_tree)?
+        .into_iter()
+        .filter(|j| j.status == DocumentJobStatus::Queued && j.next_attempt_at <= now)
+        .collect();
+    due.sort_by_key(|j| j.next_attempt_at);
+    for job in due {
+        // The job may have been picked up or removed since the listing
+        match get_job(&app_state.db.document_jobs_tree, &job.owner, &job.id)? {
+            Some(current) if current.status == DocumentJobStatus::Queued => {
+                run_job(app_state, current).await?
+            }
+            _ => continue,
+        }
+    }
+    Ok(())
+}
+
+/// Jobs left running by a previous process are queued again.
+fn requeue_interrupted_jobs(db: &AppDb) -> Result<(), AppError> {
+    for mut job in list_all_jobs(&db.document_jobs_tree)? {
+        if job.status == DocumentJobStatus::Running {
+            job.status = DocumentJobStatus::Queued;
+            job.next_attempt_at = Utc::now();
+            save_job(&db.document_jobs_tree, &job)?;
+        }
+    }
+    Ok(())
+}
+
+/// Processes queued jobs one at a time, woken by new jobs or by the poll interval.
+pub fn spawn_document_worker(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        if let Err(e) = requeue_interrupted_jobs(&state.db) {
+            log_event(
+                LogLevel::Error,
+                format!("Failed to requeue document jobs: {}", e),
+                None::<String>,
+            );
+        }
+        loop {
+            if let Err(e) = run_due_jobs(&state).await {
+                log_event(
+                    LogLevel::Error,
+                    format!("Document worker error: {}", e),
+                    None::<String>,
+                );
+            }
+            tokio::select! {
+                _ = state.document_jobs_notify.notified() => {}
+                _ = tokio::time::sleep(WORKER_POLL_INTERVAL) => {}
+            }
+        }
+    });
+}
+
+/// Removes finished jobs past their retention period.
+pub fn cleanup_finished_jobs(db: &AppDb) -> Result<(), AppError> {
+    let cutoff = Utc::now() - Duration::days(FINISHED_JOB_RETENTION_DAYS);
+    for job in list_all_jobs(&db.document_jobs_tree)? {
+        let finished = matches!(job.status, DocumentJobStatus::Done | DocumentJobStatus::Failed);
+        if finished && job.updated_at < cutoff {
+            remove_job(&db.document_jobs_tree, &job.owner, &job.id)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc::templating::Metadata;
+    use serde_json::json;
+
+    #[test]
+    fn names_documents_and_backs_off() {
+        let request = GeneratePdfInternalRequest {
+            custom_template_content: None,
+            company_info: serde_json::from_value(json!({
+                "email": "a@b.c",
+                "license": null,
+                "company_name": "Test",
+                "current_time": "2025-01-01T00:00:00Z"
+            }))
+            .unwrap(),
+            calculation: json!({
+                "car": {"licensePlate": "АА 1234 ВВ"},
+                "order": {"orderDate": "2025-09-18T20:38:15.091Z"}
+            }),
+            metadata: Metadata {
+                order_number: Some("034423".to_string()),
+                order_notes: None,
+            },
+        };
+        assert_eq!(
+            document_file_name(&request, DocumentFormat::Pdf),
+            "estimate_034423_AA1234BB_2025-09-18.pdf"
+        );
+        assert_ne!(
+            content_hash(DocumentFormat::Pdf, &request).unwrap(),
+            content_hash(DocumentFormat::Html, &request).unwrap()
+        );
+
+        assert_eq!(retry_delay(1), Duration::seconds(10));
+        assert_eq!(retry_delay(3), Duration::seconds(40));
+        assert_eq!(retry_delay(30), Duration::seconds(RETRY_MAX_SECONDS));
+    }
+}
@@ -0,0 +1,222 @@
+use chrono::{Datelike, Utc};
+
+use crate::{
+    calc::{document_jobs::document_file_name, templating::GeneratePdfInternalRequest},
+    db::{
+        document_numbers::{
+            find_calculation_assignment, next_sequence, remove_assignment, rollback_sequence,
+            save_assignment,
+        },
+        users::AppDb,
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::documents::{AssignedNumber, DocumentFormat, NumberAssignment, NumberAssignmentStatus},
+};
+
+/// Fills a number pattern: `{year}` is the year, `{seq}` the sequence and
+/// `{seq:05}` the sequence padded with zeros to 5 digits.
+pub fn format_document_number(pattern: &str, year: i32, sequence: u64) -> Result<String, AppError> {
+    let invalid = |reason: &str| {
+        AppError::InvalidData(format!(
+            "Invalid document number format {:?}: {}",
+            pattern, reason
+        ))
+    };
+    let mut number = String::new();
+    let mut has_sequence = false;
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        number.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| invalid("unclosed `{`"))?
+            + start;
+        match &rest[start + 1..end] {
+            "year" => number.push_str(&year.to_string()),
+            "seq" => {
+                has_sequence = true;
+                number.push_str(&sequence.to_string());
+            }
+            placeholder => {
+                let width = placeholder
+                    .strip_prefix("seq:")
+                    .and_then(|w| w.parse::<usize>().ok())
+                    .filter(|w| *w <= 20)
+                    .ok_or_else(|| {
+                        invalid(&format!("unknown placeholder `{{{}}}`", placeholder))
+                    })?;
+                has_sequence = true;
+                number.push_str(&format!("{:0width$}", sequence, width = width));
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    number.push_str(rest);
+    if !has_sequence {
+        return Err(invalid("`{seq}` is required"));
+    }
+    Ok(number)
+}
+
+/// Gives the document the next number of the organization's sequence and
+/// records it.
+/// A stored calculation keeps the number of its first document, so printing it
+/// again does not use up numbers.
+pub fn assign_document_number(
+    db: &AppDb,
+    organization: &str,
+    user_email: &str,
+    request: &mut GeneratePdfInternalRequest,
+    format: DocumentFormat,
+    calculation: Option<&str>,
+) -> Result<AssignedNumber, AppError> {
+    if let Some(calculation) = calculation {
+        if let Some(assignment) =
+            find_calculation_assignment(&db.document_numbers_tree, organization, calculation)?
+        {
+            request.metadata.order_number = Some(assignment.number.clone());
+            return Ok(AssignedNumber {
+                assignment,
+                reused: true,
+            });
+        }
+    }
+
+    let pattern = request.company_info.document_numbering.format.clone();
+    // Checked before taking a number, a broken pattern must not leave a gap
+    format_document_number(&pattern, 0, 0)?;
+    let year = Utc::now().year();
+    let sequence = next_sequence(&db.document_counters_tree, organization, year)?;
+    let number = format_document_number(&pattern, year, sequence)?;
+    request.metadata.order_number = Some(number.clone());
+    let assignment = NumberAssignment {
+        number,
+        year,
+        sequence,
+        calculation: calculation.map(str::to_string),
+        format,
+        document: document_file_name(request, format),
+        actor: user_email.to_string(),
+        assigned_at: Utc::now(),
+        status: NumberAssignmentStatus::Issued,
+        void_reason: None,
+    };
+    save_assignment(&db.document_numbers_tree, organization, &assignment)?;
+    log_event(
+        LogLevel::Info,
+        format!(
+            "Document number {} assigned to {}",
+            assignment.number, assignment.document
+        ),
+        Some(user_email),
+    );
+    Ok(AssignedNumber {
+        assignment,
+        reused: false,
+    })
+}
+
+/// Takes a number back after the document could not be generated. If later
+/// numbers were issued meanwhile the number stays in the audit as void.
+pub fn release_document_number(
+    db: &AppDb,
+    organization: &str,
+    assigned: AssignedNumber,
+    reason: &str,
+) -> Result<(), AppError> {
+    if assigned.reused {
+        return Ok(());
+    }
+    let mut assignment = assigned.assignment;
+    // The assignment goes first: once the sequence is rolled back the number can be
+    // issued again, and its new assignment must not be removed with ours
+    if !remove_assignment(&db.document_numbers_tree, organization, &assignment)? {
+        return Ok(());
+    }
+    if rollback_sequence(
+        &db.document_counters_tree,
+        organization,
+        assignment.year,
+        assignment.sequence,
+    )? {
+        return Ok(());
+    }
+    log_event(
+        LogLevel::Warn,
+        format!("Document number {} voided: {}", assignment.number, reason),
+        Some(assignment.actor.as_str()),
+    );
+    assignment.status = NumberAssignmentStatus::Void;
+    assignment.void_reason = Some(reason.to_string());
+    save_assignment(&db.document_numbers_tree, organization, &assignment)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_document_numbers() {
+        assert_eq!(
+            format_document_number("EST-{year}-{seq:05}", 2026, 42).unwrap(),
+            "EST-2026-00042"
+        );
+        assert_eq!(
+            format_document_number("{seq}/{year}", 2026, 7).unwrap(),
+            "7/2026"
+        );
+        assert!(format_document_number("EST-{year}", 2026, 1).is_err());
+        assert!(format_document_number("EST-{month}-{seq}", 2026, 1).is_err());
+        assert!(format_document_number("EST-{seq", 2026, 1).is_err());
+    }
+
+    #[test]
+    fn counter_is_gap_free_after_rollback() {
+        use crate::db::document_numbers::{next_sequence, rollback_sequence};
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = db.open_tree("counters").unwrap();
+        assert_eq!(next_sequence(&tree, "a@b.c", 2026).unwrap(), 1);
+        assert_eq!(next_sequence(&tree, "a@b.c", 2026).unwrap(), 2);
+        assert_eq!(next_sequence(&tree, "x@y.z", 2026).unwrap(), 1);
+        assert!(!rollback_sequence(&tree, "a@b.c", 2026, 1).unwrap());
+        assert!(rollback_sequence(&tree, "a@b.c", 2026, 2).unwrap());
+        assert_eq!(next_sequence(&tree, "a@b.c", 2026).unwrap(), 2);
+        assert_eq!(next_sequence(&tree, "a@b.c", 2027).unwrap(), 1);
+    }
+
+    #[test]
+    fn release_keeps_a_reissued_number() {
+        use crate::db::document_numbers::list_assignments;
+        let dir = tempfile::tempdir().unwrap();
+        let db = AppDb::new(dir.path().join("db").to_str().unwrap()).unwrap();
+        let assign = |calculation: &str| {
+            let mut request: GeneratePdfInternalRequest = serde_json::from_value(serde_json::json!({
+                "custom_template_content": null,
+                "company_info": {
+                    "email": "u@x.com", "license": null, "company_name": "Garage",
+                    "current_time": "2026-03-01T12:00:00Z"
+                },
+                "calculation": {"car": {"year": "2018", "carClass": "C", "bodyType": "sedan"}},
+                "metadata": {}
+            }))
+            .unwrap();
+            assign_document_number(&db, "org", "u@x.com", &mut request, DocumentFormat::Pdf, Some(calculation))
+                .unwrap()
+        };
+
+        let first = assign("a.json");
+        let stale = first.clone();
+        release_document_number(&db, "org", first, "failed").unwrap();
+        let second = assign("b.json");
+        assert_eq!(second.assignment.sequence, stale.assignment.sequence);
+
+        // A late release of the first document must leave the new owner of the number alone
+        release_document_number(&db, "org", stale, "failed").unwrap();
+        let assignments = list_assignments(&db.document_numbers_tree, "org").unwrap();
+        assert_eq!(assignments.len(), 1);
+        assert_eq!(assignments[0].calculation.as_deref(), Some("b.json"));
+        assert_eq!(assignments[0].status, NumberAssignmentStatus::Issued);
+    }
+}
@@ -0,0 +1,235 @@
+use async_trait::async_trait;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::{
+    calc::{
+        estimate_documents::{render_estimate_docx, render_estimate_markdown, render_estimate_xlsx},
+        estimate_pdf::render_estimate_pdf,
+        templating::{render_html_template, send_gen_doc_request, GeneratePdfInternalRequest},
+    },
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::documents::DocumentFormat,
+};
+
+/// Font used by the native PDF renderer unless `PDF_FONT_PATH` is set. It has to
+/// cover Cyrillic, the built-in PDF fonts do not.
+pub const DEFAULT_PDF_FONT: &str = "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf";
+
+/// PDF service used when `PDF_GEN_URL_POST` is not set.
+pub const DEFAULT_PDF_GEN_URL: &str = "http://localhost:5000/generate";
+
+/// Turns a print request into a document.
+#[async_trait]
+pub trait DocumentRenderer: Send + Sync {
+    /// Renders the request's template as HTML.
+    async fn render_html(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        user_email: &str,
+    ) -> Result<String, AppError>;
+
+    /// Renders the estimate as a PDF.
+    async fn render_pdf(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        user_email: &str,
+    ) -> Result<Vec<u8>, AppError>;
+
+    /// Renders the request in the given format. Office documents and the text
+    /// summary are always built in-process from the estimate data.
+    async fn render(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        user_email: &str,
+        format: DocumentFormat,
+    ) -> Result<Vec<u8>, AppError> {
+        match format {
+            DocumentFormat::Pdf => self.render_pdf(request, user_email).await,
+            DocumentFormat::Html => Ok(self.render_html(request, user_email).await?.into_bytes()),
+            DocumentFormat::Docx => render_estimate_docx(request),
+            DocumentFormat::Xlsx => render_estimate_xlsx(request),
+            DocumentFormat::Markdown => Ok(render_estimate_markdown(request).into_bytes()),
+        }
+    }
+}
+
+/// Renders in-process: HTML through the template engine, PDF through a direct
+/// layout of the estimate table.
+pub struct NativeRenderer {
+    font_path: PathBuf,
+    font: Option<Arc<Vec<u8>>>,
+}
+
+impl NativeRenderer {
+    pub fn new(font_path: PathBuf) -> Self {
+        let font = match std::fs::read(&font_path) {
+            Ok(font) => Some(Arc::new(font)),
+            Err(e) => {
+                log_event(
+                    LogLevel::Warn,
+                    format!("PDF font {:?} not loaded, PDF rendering is unavailable: {}", font_path, e),
+                    None::<&str>,
+                );
+                None
+            }
+        };
+        Self { font_path, font }
+    }
+}
+
+#[async_trait]
+impl DocumentRenderer for NativeRenderer {
+    async fn render_html(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        _user_email: &str,
+    ) -> Result<String, AppError> {
+        let template = request
+            .custom_template_content
+            .as_deref()
+            .ok_or_else(|| AppError::InvalidData("No template to render".to_string()))?;
+        render_html_template(template, request)
+    }
+
+    async fn render_pdf(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        _user_email: &str,
+    ) -> Result<Vec<u8>, AppError> {
+        let font = self.font.as_ref().ok_or_else(|| {
+            AppError::InternalServerError(format!(
+                "PDF font {:?} is not available, set PDF_FONT_PATH",
+                self.font_path
+            ))
+        })?;
+        render_estimate_pdf(request, font)
+    }
+}
+
+/// The external PDF service. When it cannot be reached or fails, rendering fails
+/// too, unless a native fallback is configured. Native documents look different,
+/// so the fallback is opt-in and every use of it is logged as a warning.
+pub struct ExternalRenderer {
+    url: String,
+    fallback: Option<NativeRenderer>,
+}
+
+impl ExternalRenderer {
+    pub fn new(url: String, fallback: Option<NativeRenderer>) -> Self {
+        Self { url, fallback }
+    }
+
+    /// The inner `Err` says why the service is unavailable.
+    async fn request(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        user_email: &str,
+        format: &str,
+    ) -> Result<Result<Vec<u8>, String>, AppError> {
+        let response = match send_gen_doc_request(request, &self.url, user_email, format).await {
+            Ok(response) => response,
+            Err(e) => return Ok(Err(e.to_string())),
+        };
+        let status = response.status();
+        if status.is_server_error() {
+            return Ok(Err(format!("it returned {}", status)));
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+        if !status.is_success() {
+            return Err(AppError::InvalidData(format!(
+                "Document service error: {}",
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        Ok(Ok(body.to_vec()))
+    }
+
+    fn fallback(&self, reason: &str, user_email: &str) -> Result<&NativeRenderer, AppError> {
+        let Some(fallback) = &self.fallback else {
+            return Err(AppError::InternalServerError(format!(
+                "Document service {} unavailable: {}",
+                self.url, reason
+            )));
+        };
+        log_event(
+            LogLevel::Warn,
+            format!("Document service {} unavailable, rendering natively: {}", self.url, reason),
+            Some(user_email),
+        );
+        Ok(fallback)
+    }
+}
+
+#[async_trait]
+impl DocumentRenderer for ExternalRenderer {
+    async fn render_html(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        user_email: &str,
+    ) -> Result<String, AppError> {
+        match self.request(request, user_email, "html").await? {
+            Ok(body) => Ok(String::from_utf8_lossy(&body).into_owned()),
+            Err(reason) => {
+                self.fallback(&reason, user_email)?
+                    .render_html(request, user_email)
+                    .await
+            }
+        }
+    }
+
+    async fn render_pdf(
+        &self,
+        request: &GeneratePdfInternalRequest,
+        user_email: &str,
+    ) -> Result<Vec<u8>, AppError> {
+        match self.request(request, user_email, "pdf").await? {
+            Ok(body) => Ok(body),
+            Err(reason) => {
+                self.fallback(&reason, user_email)?
+                    .render_pdf(request, user_email)
+                    .await
+            }
+        }
+    }
+}
+
+/// Picks the renderer from configuration. `kind` is `external` (the default, the
+/// PDF service) or `native`. With `native_fallback` the PDF service falls back to
+/// native rendering when it is unavailable.
+pub fn document_renderer(
+    kind: Option<&str>,
+    pdf_gen_api_url_post: Option<String>,
+    font_path: PathBuf,
+    native_fallback: bool,
+) -> Result<Arc<dyn DocumentRenderer>, String> {
+    match kind.unwrap_or("external") {
+        "native" => {
+            log_event(
+                LogLevel::Info,
+                "Documents are rendered natively, the PDF service is not used",
+                None::<&str>,
+            );
+            Ok(Arc::new(NativeRenderer::new(font_path)))
+        }
+        "external" => {
+            let url = pdf_gen_api_url_post.unwrap_or_else(|| DEFAULT_PDF_GEN_URL.to_string());
+            log_event(
+                LogLevel::Info,
+                if native_fallback {
+                    format!("Documents are rendered by {}, natively when it is unavailable", url)
+                } else {
+                    format!("Documents are rendered by {}", url)
+                },
+                None::<&str>,
+            );
+            let fallback = native_fallback.then(|| NativeRenderer::new(font_path));
+            Ok(Arc::new(ExternalRenderer::new(url, fallback)))
+        }
+        other => Err(format!("Unknown DOCUMENT_RENDERER {:?}", other)),
+    }
+}
@@ -0,0 +1,398 @@
+use std::io::{Cursor, Write};
+
+use rust_xlsxwriter::{Format, Workbook};
+use zip::write::FileOptions;
+
+use crate::{
+    calc::{
+        estimate_pdf::{estimate_sections, labels, order_date, vehicle_description, EstimateSection, Labels},
+        templating::GeneratePdfInternalRequest,
+    },
+    errors::AppError,
+};
+
+/// What every estimate document starts with.
+struct EstimateHeader<'a> {
+    labels: Labels,
+    currency: &'a str,
+    order: String,
+    vehicle: Option<String>,
+    notes: Option<&'a str>,
+    order_date: String,
+}
+
+impl<'a> EstimateHeader<'a> {
+    fn new(request: &'a GeneratePdfInternalRequest) -> Self {
+        let company = &request.company_info;
+        let labels = labels(&company.lang_output);
+        let order = format!(
+            "{}{}",
+            labels.order,
+            request.metadata.order_number.as_deref().unwrap_or_default()
+        );
+        Self {
+            labels,
+            currency: &company.pricing_preferences.preferred_currency,
+            order,
+            vehicle: vehicle_description(request),
+            notes: request.metadata.order_notes.as_deref().filter(|n| !n.trim().is_empty()),
+            order_date: order_date(request),
+        }
+    }
+
+    fn vehicle_line(&self) -> Option<String> {
+        self.vehicle
+            .as_ref()
+            .map(|v| format!("{} {}", self.labels.vehicle, v))
+    }
+
+    fn date_line(&self) -> String {
+        format!("{} {}", self.labels.order_date, self.order_date)
+    }
+
+    fn money(&self, caption: &str, amount: f64) -> String {
+        format!("{} {:.2} {}", caption, amount, self.currency)
+    }
+}
+
+fn section_total(section: &EstimateSection) -> f64 {
+    section.groups.iter().filter_map(|g| g.total).sum()
+}
+
+/// A short summary for messaging apps: vehicle, one line per part with its
+/// total, and the grand total.
+pub fn render_estimate_markdown(request: &GeneratePdfInternalRequest) -> String {
+    let header = EstimateHeader::new(request);
+    let company = &request.company_info;
+    let mut lines = vec![format!("**{}** — {}", header.order, company.company_name)];
+    lines.extend(header.vehicle_line());
+    if !header.order_date.is_empty() {
+        lines.push(header.date_line());
+    }
+    lines.extend(header.notes.map(|n| format!("_{}_", n.trim())));
+    lines.push(String::new());
+
+    let mut grand_total = 0.0;
+    for section in estimate_sections(&request.calculation) {
+        let total = section_total(&section);
+        grand_total += total;
+        lines.push(format!("- {}: {:.2} {}", section.name, total, header.currency));
+    }
+    lines.push(String::new());
+    lines.push(format!("**{}**", header.money(header.labels.grand_total, grand_total)));
+    lines.join("\n") + "\n"
+}
+
+/// The estimate as a spreadsheet, one table per work group. Numeric cells are
+/// written as numbers so they can be recalculated.
+pub fn render_estimate_xlsx(request: &GeneratePdfInternalRequest) -> Result<Vec<u8>, AppError> {
+    let xlsx_error = |e: rust_xlsxwriter::XlsxError| {
+        AppError::InternalServerError(format!("XLSX rendering failed: {}", e))
+    };
+    let header = EstimateHeader::new(request);
+    let bold = Format::new().set_bold();
+    let title = Format::new().set_bold().set_font_size(14);
+    let amount = Format::new().set_num_format("0.00");
+    let bold_amount = Format::new().set_bold().set_num_format("0.00");
+
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+    sheet.set_name("Estimate").map_err(xlsx_error)?;
+    sheet.set_column_width(0, 60).map_err(xlsx_error)?;
+    for col in 1..4 {
+        sheet.set_column_width(col, 14).map_err(xlsx_error)?;
+    }
+
+    let mut row = 0;
+    sheet
+        .write_string_with_format(row, 0, &request.company_info.company_name, &title)
+        .map_err(xlsx_error)?;
+    sheet
+        .write_string_with_format(row, 3, &header.order, &bold)
+        .map_err(xlsx_error)?;
+    row += 1;
+    let info = [header.vehicle_line(), Some(header.date_line()), header.notes.map(str::to_string)];
+    for line in info.into_iter().flatten() {
+        sheet.write_string(row, 0, &line).map_err(xlsx_error)?;
+        row += 1;
+    }
+
+    let mut grand_total = 0.0;
+    for section in estimate_sections(&request.calculation) {
+        row += 1;
+        sheet
+            .write_string_with_format(row, 0, section.name.to_uppercase(), &bold)
+            .map_err(xlsx_error)?;
+        row += 1;
+        for group in section.groups {
+            if let Some(name) = &group.name {
+                sheet.write_string(row, 0, name).map_err(xlsx_error)?;
+                row += 1;
+            }
+            if !group.rows.is_empty() {
+                for (col, caption) in header.labels.columns.iter().enumerate() {
+                    sheet
+                        .write_string_with_format(row, col as u16, *caption, &bold)
+                        .map_err(xlsx_error)?;
+                }
+                row += 1;
+            }
+            for cells in &group.rows {
+                for (col, cell) in cells.iter().enumerate() {
+                    let col = col as u16;
+                    match cell.trim().parse::<f64>() {
+                        Ok(n) if col > 0 => sheet.write_number(row, col, n).map(|_| ()),
+                        _ => sheet.write_string(row, col, cell).map(|_| ()),
+                    }
+                    .map_err(xlsx_error)?;
+                }
+                row += 1;
+            }
+            if let Some(total) = group.total {
+                grand_total += total;
+                sheet
+                    .write_string_with_format(row, 2, header.labels.total, &bold)
+                    .map_err(xlsx_error)?;
+                sheet
+                    .write_number_with_format(row, 3, total, &amount)
+                    .map_err(xlsx_error)?;
+                row += 1;
+            }
+        }
+    }
+    row += 1;
+    sheet
+        .write_string_with_format(row, 2, header.labels.grand_total, &bold)
+        .map_err(xlsx_error)?;
+    sheet
+        .write_number_with_format(row, 3, grand_total, &bold_amount)
+        .map_err(xlsx_error)?;
+    sheet
+        .write_string(row, 4, header.currency)
+        .map_err(xlsx_error)?;
+    workbook.save_to_buffer().map_err(xlsx_error)
+}
+
+const DOCX_CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/></Types>"#;
+
+const DOCX_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/></Relationships>"#;
+
+/// A4 with 15mm margins, in twentieths of a point.
+const DOCX_SECTION: &str = r#"<w:sectPr><w:pgSz w:w="11906" w:h="16838"/><w:pgMar w:top="850" w:right="850" w:bottom="850" w:left="850" w:header="0" w:footer="0" w:gutter="0"/></w:sectPr>"#;
+const DOCX_TEXT_WIDTH: u32 = 11906 - 2 * 850;
+/// Share of the text width taken by the name, estimation, price and sum columns.
+const DOCX_COLUMNS: [u32; 4] = [50, 15, 15, 20];
+
+/// Control characters other than tab and line breaks are not allowed in XML 1.0,
+/// even escaped, and would make Word refuse the document. They are dropped.
+fn xml_escape(text: &str) -> String {
+    text.replace(|c: char| c < ' ' && !matches!(c, '\t' | '\n' | '\r'), "")
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// A paragraph with a single run. `size` is in points.
+fn docx_paragraph(text: &str, size: u32, bold: bool, right: bool) -> String {
+    format!(
+        r#"<w:p><w:pPr>{}<w:spacing w:after="60"/></w:pPr><w:r><w:rPr>{}<w:sz w:val="{}"/></w:rPr><w:t xml:space="preserve">{}</w:t></w:r></w:p>"#,
+        if right { r#"<w:jc w:val="right"/>"# } else { "" },
+        if bold { "<w:b/>" } else { "" },
+        size * 2,
+        xml_escape(text)
+    )
+}
+
+fn docx_table(header: &[&str; 4], rows: &[[String; 4]]) -> String {
+    let widths = DOCX_COLUMNS.map(|share| DOCX_TEXT_WIDTH * share / 100);
+    let row = |cells: [&str; 4], is_header: bool| {
+        let cells: String = cells
+            .iter()
+            .zip(widths)
+            .enumerate()
+            .map(|(col, (text, width))| {
+                format!(
+                    r#"<w:tc><w:tcPr><w:tcW w:w="{}" w:type="dxa"/>{}</w:tcPr>{}</w:tc>"#,
+                    width,
+                    if is_header { r#"<w:shd w:val="clear" w:color="auto" w:fill="E6E6E6"/>"# } else { "" },
+                    docx_paragraph(text, 9, is_header, col > 0 && !is_header)
+                )
+            })
+            .collect();
+        let header_row = if is_header { "<w:trPr><w:tblHeader/></w:trPr>" } else { "" };
+        format!("<w:tr>{}{}</w:tr>", header_row, cells)
+    };
+    let grid: String = widths
+        .iter()
+        .map(|w| format!(r#"<w:gridCol w:w="{}"/>"#, w))
+        .collect();
+    let body: String = rows
+        .iter()
+        .map(|cells| row([&cells[0], &cells[1], &cells[2], &cells[3]].map(String::as_str), false))
+        .collect();
+    format!(
+        r#"<w:tbl><w:tblPr><w:tblW w:w="{}" w:type="dxa"/><w:tblBorders><w:top w:val="single" w:sz="4" w:color="000000"/><w:left w:val="single" w:sz="4" w:color="000000"/><w:bottom w:val="single" w:sz="4" w:color="000000"/><w:right w:val="single" w:sz="4" w:color="000000"/><w:insideH w:val="single" w:sz="4" w:color="000000"/><w:insideV w:val="single" w:sz="4" w:color="000000"/></w:tblBorders></w:tblPr><w:tblGrid>{}</w:tblGrid>{}{}</w:tbl>"#,
+        DOCX_TEXT_WIDTH,
+        grid,
+        row(*header, true),
+        body
+    )
+}
+
+/// The estimate as an editable Word document with the same layout as the PDF.
+pub fn render_estimate_docx(request: &GeneratePdfInternalRequest) -> Result<Vec<u8>, AppError> {
+    let header = EstimateHeader::new(request);
+    let company = &request.company_info;
+    let mut body = vec![
+        docx_paragraph(&header.order, 12, true, true),
+        docx_paragraph(&company.company_name, 14, true, false),
+    ];
+    if !company.company_addr.is_empty() {
+        body.push(docx_paragraph(&company.company_addr, 9, false, false));
+    }
+    body.push(docx_paragraph(&format!("Email: {}", company.email), 9, false, false));
+    body.extend(header.vehicle_line().map(|v| docx_paragraph(&v, 9, false, false)));
+    body.push(docx_paragraph(&header.date_line(), 9, false, false));
+    body.extend(header.notes.map(|n| docx_paragraph(n, 9, false, false)));
+
+    let mut grand_total = 0.0;
+    for section in estimate_sections(&request.calculation) {
+        body.push(docx_paragraph(&section.name.to_uppercase(), 11, true, false));
+        for group in section.groups {
+            if let Some(name) = &group.name {
+                body.push(docx_paragraph(name, 9, false, false));
+            }
+            if !group.rows.is_empty() {
+                body.push(docx_table(&header.labels.columns, &group.rows));
+            }
+            if let Some(total) = group.total {
+                grand_total += total;
+                body.push(docx_paragraph(&header.money(header.labels.total, total), 9, false, true));
+            }
+        }
+    }
+    body.push(docx_paragraph(&header.money(header.labels.grand_total, grand_total), 12, true, true));
+
+    let document = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:body>{}{}</w:body></w:document>"#,
+        body.concat(),
+        DOCX_SECTION
+    );
+
+    let zip_error = |e: zip::result::ZipError| {
+        AppError::InternalServerError(format!("DOCX rendering failed: {}", e))
+    };
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in [
+        ("[Content_Types].xml", DOCX_CONTENT_TYPES),
+        ("_rels/.rels", DOCX_RELS),
+        ("word/document.xml", document.as_str()),
+    ] {
+        zip.start_file(name, FileOptions::default()).map_err(zip_error)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish().map_err(zip_error)?.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calc::templating::Metadata;
+    use serde_json::json;
+
+    #[test]
+    fn summarizes_estimate_as_markdown() {
+        let request = GeneratePdfInternalRequest {
+            custom_template_content: None,
+            company_info: serde_json::from_value(json!({
+                "email": "a@b.c",
+                "license": null,
+                "company_name": "Autolab",
+                "current_time": "2025-01-01T00:00:00Z",
+                "lang_output": "en"
+            }))
+            .unwrap(),
+            calculation: json!({
+                "car": {"make": "Skoda", "licensePlate": "AA1234BB"},
+                "order": {"orderDate": "2025-09-18T20:38:15.091Z"},
+                "calc": {
+                    "Door": [{"name": "Paint", "result": [], "total": 100.5}, {"result": [], "total": 20}],
+                    "Hood": [{"result": [], "total": 10}]
+                }
+            }),
+            metadata: Metadata {
+                order_number: Some("7".to_string()),
+                order_notes: None,
+            },
+        };
+        let currency = &request.company_info.pricing_preferences.preferred_currency;
+        assert_eq!(
+            render_estimate_markdown(&request),
+            format!(
+                "**ORDER #7** — Autolab\nVehicle: Skoda AA1234BB\nOrder date: 2025-09-18\n\n\
+                 - Door: 120.50 {c}\n- Hood: 10.00 {c}\n\n**Grand total: 130.50 {c}**\n",
+                c = currency
+            )
+        );
+        assert!(render_estimate_docx(&request).unwrap().starts_with(b"PK"));
+    }
+
+    fn xml_parts(document: Vec<u8>) -> Vec<(String, String)> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(document)).unwrap();
+        let mut parts = vec![];
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i).unwrap();
+            let name = file.name().to_string();
+            if name.ends_with(".xml") || name.ends_with(".rels") {
+                let mut content = String::new();
+                std::io::Read::read_to_string(&mut file, &mut content).unwrap();
+                parts.push((name, content));
+            }
+        }
+        parts
+    }
+
+    #[test]
+    fn office_documents_are_well_formed() {
+        let request = GeneratePdfInternalRequest {
+            custom_template_content: None,
+            company_info: serde_json::from_value(json!({
+                "email": "a@b.c",
+                "license": null,
+                "company_name": "Auto\u{1}lab <&> \"Kyiv\"",
+                "current_time": "2025-01-01T00:00:00Z",
+                "lang_output": "en"
+            }))
+            .unwrap(),
+            calculation: json!({
+                "car": {"make": "Skoda\u{0}\u{b}", "licensePlate": "AA1234BB"},
+                "calc": {
+                    "Door\u{1f}": [{"name": "Paint\ttwo", "result": [
+                        {"name": "prep\u{8}", "estimation": 1, "price": 2, "sum": "2"}
+                    ], "total": 2}]
+                }
+            }),
+            metadata: Metadata {
+                order_number: Some("7".to_string()),
+                order_notes: Some("line\nbreak\u{7}".to_string()),
+            },
+        };
+
+        let docx = xml_parts(render_estimate_docx(&request).unwrap());
+        let (_, document) = docx.iter().find(|(name, _)| name == "word/document.xml").unwrap();
+        let parsed = roxmltree::Document::parse(document).unwrap();
+        let text: String = parsed.descendants().filter_map(|n| n.text()).collect();
+        assert!(text.contains("Autolab <&> \"Kyiv\""));
+        assert!(text.contains("DOOR"));
+
+        let xlsx = xml_parts(render_estimate_xlsx(&request).unwrap());
+        assert!(xlsx.iter().any(|(name, _)| name == "xl/workbook.xml"));
+        for (name, content) in &xlsx {
+            assert!(roxmltree::Document::parse(content).is_ok(), "{} is not well-formed", name);
+        }
+    }
+}
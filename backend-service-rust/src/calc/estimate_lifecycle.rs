@@ -0,0 +1,62 @@
+use crate::{errors::AppError, models::estimates::EstimateStatus};
+
+use EstimateStatus::*;
+
+/// Statuses reachable from `status`. Moving back to `Draft` reopens the estimate for
+/// edits; `Invoiced` is final.
+pub fn allowed_transitions(status: EstimateStatus) -> &'static [EstimateStatus] {
+    match status {
+        Draft => &[Sent, Approved, Cancelled],
+        Sent => &[Approved, Draft, Cancelled],
+        Approved => &[InRepair, Draft, Cancelled],
+        InRepair => &[Completed, Draft, Cancelled],
+        Completed => &[Invoiced],
+        Invoiced => &[],
+        Cancelled => &[Draft],
+    }
+}
+
+/// The calculation can only be changed until the customer approves it.
+pub fn is_editable(status: EstimateStatus) -> bool {
+    matches!(status, Draft | Sent)
+}
+
+pub fn check_transition(from: EstimateStatus, to: EstimateStatus) -> Result<(), AppError> {
+    if allowed_transitions(from).contains(&to) {
+        Ok(())
+    } else {
+        Err(AppError::Conflict(format!(
+            "Estimate cannot move from {} to {}",
+            from, to
+        )))
+    }
+}
+
+pub fn check_editable(status: EstimateStatus) -> Result<(), AppError> {
+    if is_editable(status) {
+        Ok(())
+    } else {
+        Err(AppError::Conflict(format!(
+            "Estimate is {}, reopen it as a draft to make changes",
+            status
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_repair_flow() {
+        let flow = [Draft, Sent, Approved, InRepair, Completed, Invoiced];
+        for pair in flow.windows(2) {
+            assert!(check_transition(pair[0], pair[1]).is_ok());
+        }
+        assert!(check_transition(Draft, Invoiced).is_err());
+        assert!(check_transition(Invoiced, Draft).is_err());
+        assert!(check_transition(Approved, Draft).is_ok());
+        assert!(!is_editable(Approved));
+        assert!(is_editable(Sent));
+    }
+}
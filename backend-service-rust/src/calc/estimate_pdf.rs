@@ -0,0 +1,479 @@
+use printpdf::{
+    path::PaintMode, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference,
+    PdfLayerReference, Point, Rect, Rgb,
+};
+use serde_json::Value;
+
+use crate::{calc::templating::GeneratePdfInternalRequest, errors::AppError};
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const FOOTER_HEIGHT: f32 = 10.0;
+const PT_TO_MM: f32 = 0.3528;
+const TEXT_SIZE: f32 = 9.0;
+const CELL_PADDING: f32 = 1.5;
+/// Share of the content width taken by the name, estimation, price and sum columns.
+const COLUMNS: [f32; 4] = [0.5, 0.15, 0.15, 0.2];
+
+/// Captions of the estimate documents in the company's output language.
+pub struct Labels {
+    pub order: &'static str,
+    pub vehicle: &'static str,
+    pub columns: [&'static str; 4],
+    pub total: &'static str,
+    pub grand_total: &'static str,
+    pub order_date: &'static str,
+}
+
+pub fn labels(lang: &str) -> Labels {
+    match lang {
+        "ua" => Labels {
+            order: "ЗАМОВЛЕННЯ №",
+            vehicle: "Автомобіль:",
+            columns: ["Найменування", "Оцінка", "Вартість", "Сумма"],
+            total: "Всього:",
+            grand_total: "Разом:",
+            order_date: "Дата замовлення:",
+        },
+        _ => Labels {
+            order: "ORDER #",
+            vehicle: "Vehicle:",
+            columns: ["Description", "Estimate", "Price", "Amount"],
+            total: "Total:",
+            grand_total: "Grand total:",
+            order_date: "Order date:",
+        },
+    }
+}
+
+/// A work group of the estimate, e.g. one processing of a part.
+#[derive(Debug, Default, PartialEq)]
+pub struct WorkGroup {
+    pub name: Option<String>,
+    /// Name, estimation, price and sum of each row.
+    pub rows: Vec<[String; 4]>,
+    pub total: Option<f64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct EstimateSection {
+    pub name: String,
+    pub groups: Vec<WorkGroup>,
+}
+
+/// Numbers as a person would write them: no float noise, no trailing zeros.
+pub fn value_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => i.to_string(),
+            (None, Some(f)) => {
+                let rounded = format!("{:.4}", f);
+                rounded
+                    .trim_end_matches('0')
+                    .trim_end_matches('.')
+                    .to_string()
+            }
+            _ => n.to_string(),
+        },
+        other => other.to_string(),
+    }
+}
+
+/// Sections of the `calc` object the frontend sends for printing: part name to a
+/// list of work groups with `name`, `result` rows and a `total`. Entries that are
+/// not tables, such as evaluation errors, are left out.
+pub fn estimate_sections(calculation: &Value) -> Vec<EstimateSection> {
+    let Some(calc) = calculation.get("calc").and_then(Value::as_object) else {
+        return Vec::new();
+    };
+    calc.iter()
+        .filter_map(|(name, groups)| {
+            let groups: Vec<WorkGroup> = groups
+                .as_array()?
+                .iter()
+                .filter(|g| g.is_object())
+                .map(|group| WorkGroup {
+                    name: group
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .filter(|n| !n.trim().is_empty())
+                        .map(str::to_string),
+                    rows: group
+                        .get("result")
+                        .and_then(Value::as_array)
+                        .into_iter()
+                        .flatten()
+                        .map(|row| {
+                            ["name", "estimation", "price", "sum"]
+                                .map(|key| row.get(key).map(value_text).unwrap_or_default())
+                        })
+                        .collect(),
+                    total: group.get("total").and_then(Value::as_f64),
+                })
+                .collect();
+            Some(EstimateSection {
+                name: name.clone(),
+                groups,
+            })
+        })
+        .collect()
+}
+
+/// Make, model, year, plate and VIN of the estimated car, if any are known.
+pub fn vehicle_description(request: &GeneratePdfInternalRequest) -> Option<String> {
+    let car = request.calculation.get("car")?;
+    let vehicle: Vec<String> = ["make", "model", "year", "licensePlate", "vin"]
+        .iter()
+        .filter_map(|key| car.get(*key).map(value_text))
+        .filter(|v| !v.trim().is_empty())
+        .collect();
+    (!vehicle.is_empty()).then(|| vehicle.join(" "))
+}
+
+/// Date part of the order date, empty if the calculation has none.
+pub fn order_date(request: &GeneratePdfInternalRequest) -> String {
+    request
+        .calculation
+        .pointer("/order/orderDate")
+        .and_then(Value::as_str)
+        .map(|d| d.chars().take(10).collect::<String>())
+        .unwrap_or_default()
+}
+
+struct FontMetrics<'a> {
+    face: ttf_parser::Face<'a>,
+}
+
+impl FontMetrics<'_> {
+    fn text_width(&self, text: &str, size: f32) -> f32 {
+        let units: u32 = text
+            .chars()
+            .map(|c| {
+                self.face
+                    .glyph_index(c)
+                    .and_then(|g| self.face.glyph_hor_advance(g))
+                    .unwrap_or(self.face.units_per_em() / 2) as u32
+            })
+            .sum();
+        units as f32 / self.face.units_per_em() as f32 * size * PT_TO_MM
+    }
+
+    /// Greedy word wrap; words wider than the line are split by characters.
+    fn wrap(&self, text: &str, size: f32, width: f32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        for word in text.split_whitespace() {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+            if self.text_width(&candidate, size) <= width {
+                line = candidate;
+                continue;
+            }
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            for c in word.chars() {
+                line.push(c);
+                if self.text_width(&line, size) > width && line.chars().count() > 1 {
+                    line.pop();
+                    lines.push(std::mem::replace(&mut line, c.to_string()));
+                }
+            }
+        }
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+fn draw_rule(layer: &PdfLayerReference, top: f32, thickness: f32) {
+    layer.set_outline_thickness(thickness);
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(MARGIN), Mm(PAGE_HEIGHT - top)), false),
+            (Point::new(Mm(PAGE_WIDTH - MARGIN), Mm(PAGE_HEIGHT - top)), false),
+        ],
+        is_closed: false,
+    });
+}
+
+fn line_height(size: f32) -> f32 {
+    size * 1.3 * PT_TO_MM
+}
+
+struct PageWriter<'a> {
+    doc: PdfDocumentReference,
+    font: IndirectFontRef,
+    metrics: FontMetrics<'a>,
+    layers: Vec<PdfLayerReference>,
+    /// Distance of the cursor from the top edge of the page.
+    y: f32,
+}
+
+impl PageWriter<'_> {
+    fn layer(&self) -> &PdfLayerReference {
+        self.layers.last().expect("document has a page")
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self
+            .doc
+            .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "estimate");
+        self.layers.push(self.doc.get_page(page).get_layer(layer));
+        self.y = MARGIN;
+    }
+
+    /// Starts a new page unless `height` fits above the footer.
+    fn reserve(&mut self, height: f32) -> bool {
+        if self.y + height > PAGE_HEIGHT - MARGIN - FOOTER_HEIGHT {
+            self.new_page();
+            return true;
+        }
+        false
+    }
+
+    fn text_at(&self, text: &str, size: f32, x: f32, top: f32) {
+        let baseline = PAGE_HEIGHT - top - size * 0.8 * PT_TO_MM;
+        self.layer()
+            .use_text(text, size, Mm(x), Mm(baseline), &self.font);
+    }
+
+    fn text_right(&self, text: &str, size: f32, right: f32, top: f32) {
+        let width = self.metrics.text_width(text, size);
+        self.text_at(text, size, right - width, top);
+    }
+
+    fn paragraph(&mut self, text: &str, size: f32) {
+        for line in self.metrics.wrap(text, size, PAGE_WIDTH - 2.0 * MARGIN) {
+            self.reserve(line_height(size));
+            self.text_at(&line, size, MARGIN, self.y);
+            self.y += line_height(size);
+        }
+    }
+
+    fn rule(&self, top: f32, thickness: f32) {
+        draw_rule(self.layer(), top, thickness);
+    }
+
+    fn column_bounds(&self) -> [(f32, f32); 4] {
+        let content = PAGE_WIDTH - 2.0 * MARGIN;
+        let mut x = MARGIN;
+        COLUMNS.map(|share| {
+            let start = x;
+            x += share * content;
+            (start, x)
+        })
+    }
+
+    fn wrap_cells(&self, cells: &[String; 4], size: f32) -> Vec<Vec<String>> {
+        cells
+            .iter()
+            .zip(self.column_bounds())
+            .map(|(cell, (start, end))| {
+                self.metrics
+                    .wrap(cell, size, end - start - 2.0 * CELL_PADDING)
+            })
+            .collect()
+    }
+
+    fn row_height(&self, cells: &[String; 4], size: f32) -> f32 {
+        let lines = self
+            .wrap_cells(cells, size)
+            .iter()
+            .map(Vec::len)
+            .max()
+            .unwrap_or(1);
+        lines as f32 * line_height(size) + 2.0 * CELL_PADDING
+    }
+
+    fn table_row(&mut self, cells: &[String; 4], header: bool) {
+        let size = if header { TEXT_SIZE - 1.0 } else { TEXT_SIZE };
+        let bounds = self.column_bounds();
+        let wrapped = self.wrap_cells(cells, size);
+        let height = self.row_height(cells, size);
+        let top = self.y;
+        let layer = self.layer();
+        layer.set_outline_thickness(0.5);
+        for (col, (start, end)) in bounds.iter().enumerate() {
+            let rect = Rect::new(
+                Mm(*start),
+                Mm(PAGE_HEIGHT - top - height),
+                Mm(*end),
+                Mm(PAGE_HEIGHT - top),
+            );
+            if header {
+                layer.set_fill_color(Color::Rgb(Rgb::new(0.94, 0.94, 0.94, None)));
+                layer.add_rect(rect.with_mode(PaintMode::Fill));
+                layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+            }
+            layer.add_rect(rect.with_mode(PaintMode::Stroke));
+            for (i, line) in wrapped[col].iter().enumerate() {
+                let line_top = top + CELL_PADDING + i as f32 * line_height(size);
+                // Numbers are right aligned, like the number cells of the HTML template
+                if col == 0 || header {
+                    self.text_at(line, size, start + CELL_PADDING, line_top);
+                } else {
+                    self.text_right(line, size, end - CELL_PADDING, line_top);
+                }
+            }
+        }
+        self.y += height;
+    }
+}
+
+/// Lays out the estimate table of a print request as an A4 PDF, without going
+/// through HTML. `font` must be a TrueType font covering the document's script.
+pub fn render_estimate_pdf(
+    request: &GeneratePdfInternalRequest,
+    font: &[u8],
+) -> Result<Vec<u8>, AppError> {
+    let pdf_error = |e: printpdf::Error| AppError::InternalServerError(format!("PDF rendering failed: {}", e));
+    let face = ttf_parser::Face::parse(font, 0)
+        .map_err(|e| AppError::InternalServerError(format!("Invalid PDF font: {}", e)))?;
+    let company = &request.company_info;
+    let labels = labels(&company.lang_output);
+    let currency = &company.pricing_preferences.preferred_currency;
+    let order_number = request.metadata.order_number.clone().unwrap_or_default();
+
+    let (doc, page, layer) = PdfDocument::new(
+        format!("{}{}", labels.order, order_number),
+        Mm(PAGE_WIDTH),
+        Mm(PAGE_HEIGHT),
+        "estimate",
+    );
+    let font = doc.add_external_font(font).map_err(pdf_error)?;
+    let first_layer = doc.get_page(page).get_layer(layer);
+    let mut writer = PageWriter {
+        doc,
+        font,
+        metrics: FontMetrics { face },
+        layers: vec![first_layer],
+        y: MARGIN,
+    };
+
+    writer.text_right(
+        &format!("{}{}", labels.order, order_number),
+        12.0,
+        PAGE_WIDTH - MARGIN,
+        writer.y,
+    );
+    writer.paragraph(&company.company_name, 14.0);
+    if !company.company_addr.is_empty() {
+        writer.paragraph(&company.company_addr, TEXT_SIZE);
+    }
+    writer.paragraph(&format!("Email: {}", company.email), TEXT_SIZE);
+    writer.y += 2.0;
+    writer.rule(writer.y, 1.5);
+    writer.y += 4.0;
+
+    if let Some(vehicle) = vehicle_description(request) {
+        writer.paragraph(&format!("{} {}", labels.vehicle, vehicle), TEXT_SIZE);
+    }
+    if let Some(notes) = request.metadata.order_notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        writer.paragraph(notes, TEXT_SIZE);
+    }
+
+    let header = labels.columns.map(str::to_string);
+    let mut grand_total = 0.0;
+    for section in estimate_sections(&request.calculation) {
+        writer.y += 4.0;
+        writer.reserve(4.0 * line_height(TEXT_SIZE));
+        writer.paragraph(&section.name.to_uppercase(), 11.0);
+        for group in section.groups {
+            if let Some(name) = &group.name {
+                writer.y += 1.0;
+                writer.paragraph(name, TEXT_SIZE);
+            }
+            if group.rows.is_empty() {
+                continue;
+            }
+            writer.y += 1.0;
+            let first_row = writer.row_height(&group.rows[0], TEXT_SIZE);
+            writer.reserve(writer.row_height(&header, TEXT_SIZE - 1.0) + first_row);
+            writer.table_row(&header, true);
+            for row in &group.rows {
+                if writer.reserve(writer.row_height(row, TEXT_SIZE)) {
+                    writer.table_row(&header, true);
+                }
+                writer.table_row(row, false);
+            }
+            if let Some(total) = group.total {
+                grand_total += total;
+                writer.y += 1.5;
+                writer.reserve(line_height(TEXT_SIZE));
+                writer.text_right(
+                    &format!("{} {:.2} {}", labels.total, total, currency),
+                    TEXT_SIZE,
+                    PAGE_WIDTH - MARGIN,
+                    writer.y,
+                );
+                writer.y += line_height(TEXT_SIZE) + 2.0;
+            }
+        }
+    }
+    writer.y += 4.0;
+    writer.reserve(line_height(12.0));
+    writer.text_right(
+        &format!("{} {:.2} {}", labels.grand_total, grand_total, currency),
+        12.0,
+        PAGE_WIDTH - MARGIN,
+        writer.y,
+    );
+
+    let order_date = order_date(request);
+    let pages = writer.layers.len();
+    for (i, layer) in writer.layers.iter().enumerate() {
+        let footer_top = PAGE_HEIGHT - MARGIN - FOOTER_HEIGHT + 3.0;
+        draw_rule(layer, footer_top - 1.5, 0.5);
+        let baseline = PAGE_HEIGHT - footer_top - 4.0;
+        layer.use_text(
+            format!("{} {}", labels.order_date, order_date),
+            TEXT_SIZE - 1.0,
+            Mm(MARGIN),
+            Mm(baseline),
+            &writer.font,
+        );
+        let page_number = format!("{}/{}", i + 1, pages);
+        let width = writer.metrics.text_width(&page_number, TEXT_SIZE - 1.0);
+        layer.use_text(
+            page_number,
+            TEXT_SIZE - 1.0,
+            Mm(PAGE_WIDTH - MARGIN - width),
+            Mm(baseline),
+            &writer.font,
+        );
+    }
+    writer.doc.save_to_bytes().map_err(pdf_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_sections_from_print_request() {
+        let calculation = json!({
+            "calc": {
+                "Двері передні праві": [{
+                    "name": "РАБОТЫ АРМАТУРНЫЕ",
+                    "result": [{"name": "Зняти", "estimation": 0.4, "price": 1, "sum": "0.40"}],
+                    "total": 1.2000000000000002
+                }],
+                "broken part": "evaluation error"
+            }
+        });
+        let sections = estimate_sections(&calculation);
+        assert_eq!(sections.len(), 1);
+        let group = &sections[0].groups[0];
+        assert_eq!(group.rows[0], ["Зняти", "0.4", "1", "0.40"].map(str::to_string));
+        assert_eq!(group.total, Some(1.2000000000000002));
+    }
+}
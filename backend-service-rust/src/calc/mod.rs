@@ -4,4 +4,20 @@ pub mod car_class_to_body_type;
 pub mod table_processing;
 pub mod constants;
 pub mod templating;
+pub mod estimate_pdf;
+pub mod estimate_documents;
+pub mod document_renderer;
+pub mod document_jobs;
+pub mod document_numbering;
 pub mod t2;
+pub mod car_catalog;
+pub mod vin;
+pub mod car_schema;
+pub mod calculation_schema;
+pub mod calculation_history;
+pub mod calculation_diff;
+pub mod calculation_index;
+pub mod calculation_export;
+pub mod calculation_import;
+pub mod estimate_lifecycle;
+pub mod registry;
@@ -0,0 +1,150 @@
+use serde::Serialize;
+use sled::Tree;
+
+use crate::{
+    calc::calculation_index::normalize_code,
+    db::{customers::get_customer, vehicles::get_vehicle},
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    models::{
+        calculations::{CalculationIndexEntry, CarCalcData},
+        customers::{Customer, Vehicle},
+    },
+};
+
+/// Checks the customer and vehicle an estimate refers to. References to records that
+/// were deleted, or never existed, are dropped so the estimate can still be saved; the
+/// car fields keep what was shown for the vehicle. An estimate for a known vehicle
+/// without a customer is linked to the vehicle's customer.
+pub fn link_registry(
+    customers_tree: &Tree,
+    vehicles_tree: &Tree,
+    email: &str,
+    data: &mut CarCalcData,
+) -> Result<(), AppError> {
+    if let Some(vehicle_id) = data.car.vehicle_id.clone() {
+        match get_vehicle(vehicles_tree, email, &vehicle_id)? {
+            Some(vehicle) => {
+                if data.customer_id.is_none() {
+                    data.customer_id = vehicle.customer_id;
+                }
+            }
+            None => {
+                log_event(
+                    LogLevel::Warn,
+                    format!("Unlinked unknown vehicle {} from the estimate", vehicle_id),
+                    Some(email),
+                );
+                data.car.vehicle_id = None;
+            }
+        }
+    }
+    if let Some(customer_id) = data.customer_id.clone() {
+        if get_customer(customers_tree, email, &customer_id)?.is_none() {
+            log_event(
+                LogLevel::Warn,
+                format!("Unlinked unknown customer {} from the estimate", customer_id),
+                Some(email),
+            );
+            data.customer_id = None;
+        }
+    }
+    Ok(())
+}
+
+fn same_code(value: Option<&str>, expected: Option<&str>) -> bool {
+    match (value.map(normalize_code), expected.map(normalize_code)) {
+        (Some(value), Some(expected)) => !expected.is_empty() && value == expected,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RepairHistory {
+    pub vehicles: Vec<Vehicle>,
+    pub customers: Vec<Customer>,
+    /// Oldest first.
+    pub calculations: Vec<CalculationIndexEntry>,
+}
+
+/// Registered vehicles and saved calculations matching a VIN or a license plate.
+/// Calculations count if they reference a matching vehicle or carry the same VIN or
+/// plate themselves, so estimates made before the vehicle was registered are found too.
+pub fn repair_history(
+    vin: Option<&str>,
+    plate: Option<&str>,
+    vehicles: Vec<Vehicle>,
+    customers: Vec<Customer>,
+    entries: Vec<CalculationIndexEntry>,
+) -> RepairHistory {
+    let vehicles: Vec<Vehicle> = vehicles
+        .into_iter()
+        .filter(|v| same_code(v.vin.as_deref(), vin) || same_code(v.license_plate.as_deref(), plate))
+        .collect();
+    let customers = customers
+        .into_iter()
+        .filter(|c| vehicles.iter().any(|v| v.customer_id.as_ref() == Some(&c.id)))
+        .collect();
+
+    let mut calculations: Vec<CalculationIndexEntry> = entries
+        .into_iter()
+        .filter(|e| e.deleted_at.is_none())
+        .filter(|e| {
+            e.vehicle_id
+                .as_ref()
+                .is_some_and(|id| vehicles.iter().any(|v| &v.id == id))
+                || same_code(e.vin.as_deref(), vin)
+                || same_code(e.license_plate.as_deref(), plate)
+                || vehicles.iter().any(|v| {
+                    same_code(e.vin.as_deref(), v.vin.as_deref())
+                        || same_code(e.license_plate.as_deref(), v.license_plate.as_deref())
+                })
+        })
+        .collect();
+    calculations.sort_by_key(|e| e.created_at);
+
+    RepairHistory {
+        vehicles,
+        customers,
+        calculations,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(file_name: &str, vin: Option<&str>, plate: &str, vehicle_id: Option<&str>) -> CalculationIndexEntry {
+        serde_json::from_value(json!({
+            "file_name": file_name, "name": file_name, "make": null, "model": null,
+            "year": "2018", "car_class": "C", "body_type": "sedan", "vin": vin,
+            "license_plate": plate, "notes": null, "vehicle_id": vehicle_id, "parts": [],
+            "total": 0.0, "created_at": "2026-03-01T12:00:00Z", "updated_at": "2026-03-01T12:00:00Z"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn finds_calculations_by_vehicle_vin_and_plate() {
+        let vehicle: Vehicle = serde_json::from_value(json!({
+            "id": "V1", "owner": "u@x.com", "customerId": "C1",
+            "vin": "TMBJJ7NE1F0000000", "licensePlate": "AA 1234 BB"
+        }))
+        .unwrap();
+        let customer: Customer =
+            serde_json::from_value(json!({"id": "C1", "owner": "u@x.com", "name": "Ivan"})).unwrap();
+        let entries = vec![
+            entry("linked.json", None, "", Some("V1")),
+            entry("old_plate.json", None, "aa-1234-bb", None),
+            entry("vin.json", Some("tmbjj7ne1f0000000"), "", None),
+            entry("other.json", Some("WVWZZZ1KZ9W123456"), "BC0002AB", None),
+        ];
+
+        let history = repair_history(Some("TMBJJ7NE1F0000000"), None, vec![vehicle], vec![customer], entries);
+        assert_eq!(history.vehicles.len(), 1);
+        assert_eq!(history.customers[0].name, "Ivan");
+        let files: Vec<&str> = history.calculations.iter().map(|e| e.file_name.as_str()).collect();
+        assert_eq!(files, vec!["linked.json", "old_plate.json", "vin.json"]);
+    }
+}
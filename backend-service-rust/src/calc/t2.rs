@@ -18,25 +18,25 @@ static CSV_EXT: LazyLock<&'static OsStr> = LazyLock::new(|| OsStr::new("csv"));
 pub const TABLE_T2: &str = "tables/t2.csv";
 
 pub async fn t2_rows_all(
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
     cache: &DataStorageCache,
 ) -> Result<Vec<IndexMap<String, String>>, AppError> {
     log_event(
         LogLevel::Debug,
-        format!("t2_rows_all: Loading T2 table for user: {}", user_email),
-        Some(user_email),
+        format!("t2_rows_all: Loading T2 table for organization: {}", organization),
+        Some(organization),
     );
 
     let path_in_userspace =
-        crate::utils::get_file_path_user_common(data_dir, user_email, &TABLE_T2.to_string())
+        crate::utils::get_file_path_user_common(data_dir, organization, &TABLE_T2.to_string())
             .await
             .map_err(AppError::IoError)?;
 
     log_event(
         LogLevel::Debug,
         format!("t2_rows_all: Resolved path: {:?}", path_in_userspace),
-        Some(user_email),
+        Some(organization),
     );
 
     let data = parse_csv_file_async_safe(data_dir, &path_in_userspace, cache).await?;
@@ -44,14 +44,14 @@ pub async fn t2_rows_all(
     log_event(
         LogLevel::Debug,
         format!("t2_rows_all: Loaded {} rows from T2 table", data.len()),
-        Some(user_email),
+        Some(organization),
     );
 
     if let Some(first_row) = data.first() {
         log_event(
             LogLevel::Trace,
             format!("t2_rows_all: First row keys: {:?}", first_row.keys().collect::<Vec<_>>()),
-            Some(user_email),
+            Some(organization),
         );
     }
 
@@ -60,25 +60,25 @@ pub async fn t2_rows_all(
 
 pub async fn t2_rows_by_body_type(
     car_type: &str,
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
     cache: &DataStorageCache,
 ) -> Result<Vec<IndexMap<String, String>>, AppError> {
     log_event(
         LogLevel::Debug,
-        format!("t2_rows_by_body_type: Filtering for car_type='{}', user='{}'", car_type, user_email),
-        Some(user_email),
+        format!("t2_rows_by_body_type: Filtering for car_type='{}', organization='{}'", car_type, organization),
+        Some(organization),
     );
 
     let path_in_userspace =
-        crate::utils::get_file_path_user_common(data_dir, user_email, &TABLE_T2.to_string())
+        crate::utils::get_file_path_user_common(data_dir, organization, &TABLE_T2.to_string())
             .await
             .map_err(AppError::IoError)?;
 
     log_event(
         LogLevel::Debug,
         format!("t2_rows_by_body_type: Resolved path: {:?}", path_in_userspace),
-        Some(user_email),
+        Some(organization),
     );
 
     let data = parse_csv_file_async_safe(data_dir, &path_in_userspace, cache).await?;
@@ -86,7 +86,7 @@ pub async fn t2_rows_by_body_type(
     log_event(
         LogLevel::Debug,
         format!("t2_rows_by_body_type: Loaded {} total rows before filtering", data.len()),
-        Some(user_email),
+        Some(organization),
     );
 
     let filtered_by_class: Vec<_> = data
@@ -97,7 +97,7 @@ pub async fn t2_rows_by_body_type(
                     log_event(
                         LogLevel::Trace,
                         format!("t2_rows_by_body_type: Row matched - column='{}', value='{}'", k, v),
-                        Some(user_email),
+                        Some(organization),
                     );
                     return true;
                 }
@@ -110,14 +110,14 @@ pub async fn t2_rows_by_body_type(
     log_event(
         LogLevel::Debug,
         format!("t2_rows_by_body_type: Filtered to {} rows matching car_type='{}'", filtered_by_class.len(), car_type),
-        Some(user_email),
+        Some(organization),
     );
 
     if filtered_by_class.is_empty() {
         log_event(
             LogLevel::Warn,
             format!("t2_rows_by_body_type: No rows matched car_type='{}'. Check T2_BODY constant='{}' and CSV column names", car_type, T2_BODY),
-            Some(user_email),
+            Some(organization),
         );
     }
 
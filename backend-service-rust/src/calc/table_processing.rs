@@ -25,9 +25,9 @@ pub const TABLES: &str = "tables";
 
 pub async fn all_tables_list(
     data_dir: &PathBuf,
-    user_email: &str,
+    organization: &str,
 ) -> Result<Vec<PathBuf>, AppError> {
-    utils::all_files_with_extension(data_dir, user_email, TABLES, &CSV_EXT).await
+    utils::all_files_with_extension(data_dir, organization, TABLES, &CSV_EXT).await
 }
 
 pub async fn lookup(
@@ -4,8 +4,10 @@ use crate::{
     exlogging::{log_event, LogLevel},
     utils::list_catalog_files_user_common, // Import the new CompanyInfo struct
 };
+use minijinja::{context, AutoEscape, Environment, ErrorKind, UndefinedBehavior};
 use reqwest::{Client, Response};
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::path::PathBuf;
 
 /// Directory name for document templates.
@@ -20,6 +22,9 @@ pub const TEMPLATES: &str = "doc_templates";
 /// or shared sample documents inside the data directory.
 pub const SAMPLES: &str = "samples";
 
+/// Template used when a request names neither a template nor custom content.
+pub const DEFAULT_TEMPLATE: &str = "calculation_ua.html";
+
 /// List all available templates for a given user.
 ///
 /// This function will collect template file names that are either
@@ -34,10 +39,10 @@ pub const SAMPLES: &str = "samples";
 /// * `Ok(Vec<String>)` - A list of template file names accessible to the user.
 /// * `Err(AppError)` - An error if the file system lookup or access fails.
 pub async fn list_templates(
-    user_email: &str,
+    organization: &str,
     data_dir_path: &PathBuf,
 ) -> Result<Vec<String>, AppError> {
-    let data = list_catalog_files_user_common(data_dir_path, user_email, &TEMPLATES)
+    let data = list_catalog_files_user_common(data_dir_path, organization, &TEMPLATES)
         .await
         .map_err(AppError::IoError)?;
     Ok(data)
@@ -57,10 +62,10 @@ pub async fn list_templates(
 /// * `Ok(Vec<String>)` - A list of sample file names accessible to the user.
 /// * `Err(AppError)` - An error if the file system lookup or access fails.
 pub async fn list_samples(
-    user_email: &str,
+    organization: &str,
     data_dir_path: &PathBuf,
 ) -> Result<Vec<String>, AppError> {
-    let data = list_catalog_files_user_common(data_dir_path, user_email, &SAMPLES)
+    let data = list_catalog_files_user_common(data_dir_path, organization, &SAMPLES)
         .await
         .map_err(AppError::IoError)?;
     Ok(data)
@@ -73,10 +78,10 @@ pub async fn list_samples(
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Metadata {
     /// Optional order number for the document (e.g., invoice or job ID).
-    order_number: Option<String>,
+    pub order_number: Option<String>,
 
     /// Optional notes or comments associated with the order.
-    order_notes: Option<String>,
+    pub order_notes: Option<String>,
 }
 
 /// Internal request payload for PDF generation.
@@ -115,7 +120,7 @@ pub struct GeneratePdfInternalRequest {
 /// * `Ok(Response)` - The HTTP response from the PDF generation API.
 /// * `Err(AppError)` - An error if the HTTP request fails or if the API is unreachable.
 pub async fn send_gen_doc_request(
-    internal_request: GeneratePdfInternalRequest,
+    internal_request: &GeneratePdfInternalRequest,
     pdf_gen_api_url_post: &str,
     user_email: &str,
     format: &str
@@ -123,7 +128,7 @@ pub async fn send_gen_doc_request(
     let client = Client::new();
     client
         .post(format!("{}/{}", pdf_gen_api_url_post, format))
-        .json(&internal_request)
+        .json(internal_request)
         .send()
         .await
         .map_err(|err| {
@@ -135,3 +140,236 @@ pub async fn send_gen_doc_request(
             AppError::InternalServerError(err.to_string())
         })
 }
+
+/// Create a template environment compatible with the Jinja2 templates written
+/// for the external PDF service.
+///
+/// Python methods such as `dict.items()` are provided by the `pycompat` layer of
+/// `minijinja-contrib`, and values are HTML-escaped unless marked `|safe`.
+///
+/// # Returns
+/// * `Environment` - A template environment without any templates loaded.
+pub fn template_environment() -> Environment<'static> {
+    let mut env = Environment::new();
+    minijinja_contrib::add_to_environment(&mut env);
+    env.set_unknown_method_callback(minijinja_contrib::pycompat::unknown_method_callback);
+    env.set_auto_escape_callback(|_| AutoEscape::Html);
+    env
+}
+
+/// Render an HTML document template in-process.
+///
+/// The template receives the same context as the external PDF service: the
+/// whole request is available as `data`, e.g. `data.company_info.company_name`
+/// or `data.calculation.calc`.
+///
+/// # Arguments
+/// * `template` - The template source.
+/// * `request` - The request payload with company info, calculation data and metadata.
+///
+/// # Returns
+/// * `Ok(String)` - The rendered HTML.
+/// * `Err(AppError)` - An [`AppError::InvalidData`] describing the template error and its line.
+pub fn render_html_template(
+    template: &str,
+    request: &GeneratePdfInternalRequest,
+) -> Result<String, AppError> {
+    let env = template_environment();
+    env.render_str(template, context! { data => request })
+        .map_err(|e| AppError::InvalidData(format!("Template error: {}", e)))
+}
+
+/// A template error located in the template source.
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct TemplateError {
+    /// Error category, e.g. `syntax_error` or `undefined_variable`.
+    pub kind: &'static str,
+    pub message: String,
+    /// 1-based line of the error, if known.
+    pub line: Option<usize>,
+    /// 1-based column of the error, if known.
+    pub column: Option<usize>,
+    /// The undefined expression, e.g. `data.metadata.order_date`.
+    pub undefined_variable: Option<String>,
+}
+
+impl TemplateError {
+    fn from_minijinja(source: &str, err: &minijinja::Error) -> Self {
+        let kind = match err.kind() {
+            ErrorKind::SyntaxError => "syntax_error",
+            ErrorKind::UndefinedError => "undefined_variable",
+            ErrorKind::UnknownFilter | ErrorKind::UnknownTest | ErrorKind::UnknownFunction => {
+                "unknown_function"
+            }
+            _ => "render_error",
+        };
+        let mut range = err.range().filter(|r| r.end <= source.len());
+        if let (ErrorKind::UndefinedError, Some(r)) = (err.kind(), range.as_mut()) {
+            // The span of an attribute lookup starts after the object it is looked up
+            // on, extend it to the whole dotted path
+            let path_start = source[..r.start]
+                .trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.')
+                .len();
+            r.start = path_start;
+        }
+        let column = range.as_ref().map(|r| {
+            let line_start = source[..r.start].rfind('\n').map_or(0, |i| i + 1);
+            source[line_start..r.start].chars().count() + 1
+        });
+        let undefined_variable = match (err.kind(), &range) {
+            (ErrorKind::UndefinedError, Some(r)) => Some(source[r.clone()].trim().to_string()),
+            _ => None,
+        };
+        Self {
+            kind,
+            message: err.detail().unwrap_or(&err.kind().to_string()).to_string(),
+            line: err.line(),
+            column,
+            undefined_variable,
+        }
+    }
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "line {}, column {}: ", line, column)?,
+            (Some(line), None) => write!(f, "line {}: ", line)?,
+            _ => {}
+        }
+        match &self.undefined_variable {
+            Some(variable) => write!(f, "undefined variable `{}`", variable),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Check that a template compiles, without rendering it.
+///
+/// # Arguments
+/// * `template` - The template source.
+///
+/// # Returns
+/// * `Ok(())` - The template is syntactically valid.
+/// * `Err(TemplateError)` - The first syntax error and its location.
+pub fn validate_template(template: &str) -> Result<(), TemplateError> {
+    let env = template_environment();
+    env.template_from_str(template)
+        .map(|_| ())
+        .map_err(|e| TemplateError::from_minijinja(template, &e))
+}
+
+/// Check an uploaded catalog file if it is a document template, e.g.
+/// `doc_templates/invoice.html`. Other files are accepted as they are.
+///
+/// # Arguments
+/// * `path` - The catalog path the file is uploaded to.
+/// * `content` - The uploaded file content.
+///
+/// # Returns
+/// * `Ok(())` - The file is not a template or the template compiles.
+/// * `Err(AppError)` - An [`AppError::InvalidData`] with the error location.
+pub fn check_uploaded_template(path: &str, content: &[u8]) -> Result<(), AppError> {
+    let path = std::path::Path::new(path);
+    let is_template = path.extension().is_some_and(|ext| ext == "html")
+        && path.components().any(|c| c.as_os_str() == TEMPLATES);
+    if !is_template {
+        return Ok(());
+    }
+    let template = std::str::from_utf8(content)
+        .map_err(|_| AppError::InvalidData("Template is not valid UTF-8".to_string()))?;
+    validate_template(template)
+        .map_err(|e| AppError::InvalidData(format!("Template error: {}", e)))
+}
+
+/// Result of rendering a template against sample data.
+#[derive(Debug, Serialize)]
+pub struct TemplatePreview {
+    /// The rendered HTML, absent if the template could not be rendered at all.
+    pub html: Option<String>,
+    pub error: Option<TemplateError>,
+}
+
+/// Render a template for preview, reporting undefined variables.
+///
+/// Documents are rendered leniently, so a misspelled variable silently prints
+/// nothing. The preview renders strictly first to point at it, and then
+/// leniently so the HTML can still be shown next to the error.
+///
+/// # Arguments
+/// * `template` - The template source.
+/// * `request` - The request payload built from the sample.
+///
+/// # Returns
+/// * `TemplatePreview` - The HTML and the first error found, if any.
+pub fn preview_template(template: &str, request: &GeneratePdfInternalRequest) -> TemplatePreview {
+    let mut env = template_environment();
+    env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    let error = match env.render_str(template, context! { data => request }) {
+        Ok(html) => return TemplatePreview { html: Some(html), error: None },
+        Err(e) => TemplateError::from_minijinja(template, &e),
+    };
+    let html = match error.kind {
+        "undefined_variable" => render_html_template(template, request).ok(),
+        _ => None,
+    };
+    TemplatePreview {
+        html,
+        error: Some(error),
+    }
+}
+
+/// Convert a stored sample, a saved calculation, into the `calculation` value
+/// the print dialog sends. Samples already in that shape are used as they are.
+/// Like the print dialog, samples saved without an order are dated today.
+pub fn sample_calculation(sample: Value) -> Value {
+    match sample.get("calculations") {
+        Some(calc) => json!({
+            "car": sample.get("car"),
+            "paint": sample.get("paint"),
+            "order": sample.get("order").cloned().unwrap_or_else(|| json!({
+                "orderDate": chrono::Utc::now().to_rfc3339(),
+                "orderNumber": "000001",
+            })),
+            "calc": calc,
+        }),
+        None => sample,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_template_errors() {
+        let error = validate_template("<p>\n  {% if data.x %}\n</p>").unwrap_err();
+        assert_eq!(error.kind, "syntax_error");
+        assert_eq!(error.line, Some(2));
+
+        let request = GeneratePdfInternalRequest {
+            custom_template_content: None,
+            company_info: serde_json::from_value(json!({
+                "email": "a@b.c",
+                "license": null,
+                "company_name": "Test",
+                "current_time": "2025-01-01T00:00:00Z"
+            }))
+            .unwrap(),
+            calculation: json!({"car": {"make": "Skoda"}}),
+            metadata: Metadata {
+                order_number: Some("7".to_string()),
+                order_notes: None,
+            },
+        };
+        let preview = preview_template(
+            "{{ data.calculation.car.make }}\n  {{ data.calculation.car.modle }}!",
+            &request,
+        );
+        let error = preview.error.unwrap();
+        assert_eq!(error.kind, "undefined_variable");
+        assert_eq!((error.line, error.column), (Some(2), Some(6)));
+        assert_eq!(error.undefined_variable.as_deref(), Some("data.calculation.car.modle"));
+        assert_eq!(preview.html.as_deref(), Some("Skoda\n  !"));
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Serialize;
+
+use crate::errors::AppError;
+
+/// World manufacturer identifiers (VIN positions 1-3) mapped to catalog make names,
+/// i.e. the `cars/<make>.yaml` file stems.
+const WMI_MAKES: &[(&str, &str)] = &[
+    ("WAU", "audi"),
+    ("WA1", "audi"),
+    ("WUA", "audi"),
+    ("TRU", "audi"),
+    ("VSS", "seat"),
+    ("VSE", "seat"),
+    ("TMB", "skoda"),
+    ("WVW", "volkswagen"),
+    ("WVG", "volkswagen"),
+    ("WV1", "volkswagen"),
+    ("WV2", "volkswagen"),
+    ("WV3", "volkswagen"),
+    ("1VW", "volkswagen"),
+    ("3VW", "volkswagen"),
+    ("9BW", "volkswagen"),
+    ("AAV", "volkswagen"),
+    ("LFV", "volkswagen"),
+    ("LSV", "volkswagen"),
+];
+
+/// Model year codes (VIN position 10), starting from 1980. The cycle repeats every 30 years.
+const YEAR_CODES: &str = "ABCDEFGHJKLMNPRSTVWXY123456789";
+const YEAR_CODES_BASE: u16 = 1980;
+
+const CHECK_DIGIT_WEIGHTS: [u32; 17] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedVin {
+    pub vin: String,
+    pub wmi: String,
+    pub make: Option<String>,
+    pub model_year: Option<u16>,
+    /// ISO 3779 check digit (position 9). Mandatory in North America only,
+    /// so an invalid one is reported but not rejected.
+    pub check_digit_valid: bool,
+}
+
+fn transliterate(c: char) -> Option<u32> {
+    let value = match c {
+        '0'..='9' => return c.to_digit(10),
+        'A' | 'J' => 1,
+        'B' | 'K' | 'S' => 2,
+        'C' | 'L' | 'T' => 3,
+        'D' | 'M' | 'U' => 4,
+        'E' | 'N' | 'V' => 5,
+        'F' | 'W' => 6,
+        'G' | 'P' | 'X' => 7,
+        'H' | 'Y' => 8,
+        'R' | 'Z' => 9,
+        _ => return None,
+    };
+    Some(value)
+}
+
+fn check_digit(vin: &str) -> Option<char> {
+    let mut sum = 0;
+    for (c, weight) in vin.chars().zip(CHECK_DIGIT_WEIGHTS) {
+        sum += transliterate(c)? * weight;
+    }
+    match sum % 11 {
+        10 => Some('X'),
+        n => char::from_digit(n, 10),
+    }
+}
+
+/// Model year encoded in the 10th position. Each code stands for two years 30 years
+/// apart, the latest one not later than `max_year` is taken.
+pub fn model_year(code: char, max_year: u16) -> Option<u16> {
+    let offset = YEAR_CODES.find(code)? as u16;
+    let mut year = YEAR_CODES_BASE + offset;
+    while year + 30 <= max_year {
+        year += 30;
+    }
+    Some(year)
+}
+
+pub fn make_by_wmi(wmi: &str) -> Option<&'static str> {
+    WMI_MAKES
+        .iter()
+        .find(|(code, _)| *code == wmi)
+        .map(|(_, make)| *make)
+}
+
+/// Offline VIN decoding: manufacturer from the WMI and model year from position 10.
+/// `current_year` bounds the model year (next year's models are sold already).
+pub fn decode_vin(vin: &str, current_year: u16) -> Result<DecodedVin, AppError> {
+    let vin: String = vin
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .flat_map(char::to_uppercase)
+        .collect();
+    if vin.len() != 17 || !vin.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(AppError::InvalidData(
+            "VIN must consist of 17 latin letters and digits".to_string(),
+        ));
+    }
+    if vin.contains(['I', 'O', 'Q']) {
+        return Err(AppError::InvalidData(
+            "VIN cannot contain letters I, O or Q".to_string(),
+        ));
+    }
+
+    let wmi = vin[0..3].to_string();
+    let year_code = vin.chars().nth(9).unwrap_or('0');
+    Ok(DecodedVin {
+        make: make_by_wmi(&wmi).map(str::to_string),
+        model_year: model_year(year_code, current_year + 1),
+        check_digit_valid: check_digit(&vin) == vin.chars().nth(8),
+        wmi,
+        vin,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_year_cycle() {
+        assert_eq!(model_year('A', 2027), Some(2010));
+        assert_eq!(model_year('Y', 2027), Some(2000));
+        assert_eq!(model_year('9', 2027), Some(2009));
+        assert_eq!(model_year('T', 2027), Some(2026));
+        assert_eq!(model_year('V', 2027), Some(2027));
+        assert_eq!(model_year('W', 2027), Some(1998));
+        assert_eq!(model_year('U', 2027), None);
+    }
+
+    #[test]
+    fn decodes_known_vins() {
+        let decoded = decode_vin("wvwzzz1kz9w 123456", 2026).unwrap();
+        assert_eq!(decoded.vin, "WVWZZZ1KZ9W123456");
+        assert_eq!(decoded.make.as_deref(), Some("volkswagen"));
+        assert_eq!(decoded.model_year, Some(2009));
+
+        let decoded = decode_vin("1M8GDM9AXKP042788", 2026).unwrap();
+        assert!(decoded.check_digit_valid);
+        assert_eq!(decoded.make, None);
+
+        assert!(decode_vin("TMBJJ7NE1F0O00000", 2026).is_err());
+        assert!(decode_vin("TMB123", 2026).is_err());
+    }
+}
@@ -1,6 +1,11 @@
 use std::sync::Arc;
 
 use crate::{
+    auth::{
+        passwords::cleanup_password_resets, sessions::cleanup_sessions,
+        two_factor::cleanup_login_challenges,
+    },
+    calc::document_jobs::cleanup_finished_jobs,
     db::{attachment, notifications},
     exlogging::{log_event, LogLevel},
     state::AppState,
@@ -28,4 +33,44 @@ pub async fn cleanup_task(state: Arc<AppState>) {
             None::<String>,
         );
     }
-}
\ No newline at end of file
+
+    if let Err(e) = cleanup_finished_jobs(&state.db) {
+        log_event(
+            LogLevel::Error,
+            format!("Error during document jobs cleanup: {}", e),
+            None::<String>,
+        );
+    }
+
+    if let Err(e) = cleanup_sessions(&state) {
+        log_event(
+            LogLevel::Error,
+            format!("Error during sessions cleanup: {}", e),
+            None::<String>,
+        );
+    }
+
+    if let Err(e) = cleanup_password_resets(&state) {
+        log_event(
+            LogLevel::Error,
+            format!("Error during password resets cleanup: {}", e),
+            None::<String>,
+        );
+    }
+
+    if let Err(e) = cleanup_login_challenges(&state) {
+        log_event(
+            LogLevel::Error,
+            format!("Error during login challenges cleanup: {}", e),
+            None::<String>,
+        );
+    }
+
+    if let Err(e) = state.login_throttle.cleanup() {
+        log_event(
+            LogLevel::Error,
+            format!("Error during login attempts cleanup: {}", e),
+            None::<String>,
+        );
+    }
+}
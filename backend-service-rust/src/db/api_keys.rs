@@ -0,0 +1,35 @@
+use sled::Tree;
+
+use crate::errors::AppError;
+use crate::models::api_keys::ApiKey;
+
+pub fn save_api_key(api_keys_tree: &Tree, api_key: &ApiKey) -> Result<(), AppError> {
+    api_keys_tree.insert(api_key.id.as_bytes(), serde_json::to_vec(api_key)?)?;
+    api_keys_tree.flush()?;
+    Ok(())
+}
+
+pub fn get_api_key(api_keys_tree: &Tree, id: &str) -> Result<Option<ApiKey>, AppError> {
+    match api_keys_tree.get(id.as_bytes())? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn list_user_api_keys(api_keys_tree: &Tree, user_email: &str) -> Result<Vec<ApiKey>, AppError> {
+    let mut api_keys = Vec::new();
+    for item_result in api_keys_tree.iter() {
+        let (_, value_ivec) = item_result?;
+        let api_key: ApiKey = serde_json::from_slice(&value_ivec)?;
+        if api_key.user_email == user_email {
+            api_keys.push(api_key);
+        }
+    }
+    Ok(api_keys)
+}
+
+pub fn remove_api_key(api_keys_tree: &Tree, id: &str) -> Result<(), AppError> {
+    api_keys_tree.remove(id.as_bytes())?;
+    api_keys_tree.flush()?;
+    Ok(())
+}
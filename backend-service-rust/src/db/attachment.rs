@@ -19,6 +19,8 @@ pub enum AttachmentLifecycle {
     TempJustUploaded,
     SupportRequestActive,
     MarkedForDeletionAfter(Duration),
+    /// Output of a document generation job, kept with its calculation.
+    GeneratedDocument,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,11 +34,19 @@ pub struct AttachmentHandle {
     #[serde(default)]
     pub file_name: String,
     pub id: String,
-    pub owner_user_email: String,
+    /// Organization the file belongs to.
+    #[serde(alias = "ownerUserEmail")]
+    pub owner: String,
     #[serde(skip_serializing_if = "Option::is_none", default)]
     size: Option<usize>,
     #[serde(default)]
     pub public: bool,
+    /// Stored calculation a generated document belongs to.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub calculation: Option<String>,
+    /// Hash of the input a generated document was rendered from.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub content_hash: Option<String>,
 }
 
 impl Default for AttachmentHandle {
@@ -47,16 +57,18 @@ impl Default for AttachmentHandle {
             file_path: String::new(),
             file_name: String::new(),
             id: generate_random_id(12),
-            owner_user_email: String::new(),
+            owner: String::new(),
             size: Option::default(),
             public: false,
+            calculation: None,
+            content_hash: None,
         }
     }
 }
 
 impl AttachmentHandle {
     pub fn new(
-        owner_user_email: String,
+        owner: String,
         file_path: String,
         file_name: String,
         size: Option<usize>,
@@ -67,9 +79,11 @@ impl AttachmentHandle {
             file_path,
             file_name,
             id: generate_random_id(12),
-            owner_user_email,
+            owner,
             size,
             public: false,
+            calculation: None,
+            content_hash: None,
         }
     }
 }
@@ -110,7 +124,7 @@ pub fn try_get_by_id_checked(
     match att {
         Some(data) => {
             if let Some(user_to_check_access) = user_email {
-                if data.owner_user_email != user_to_check_access {
+                if data.owner != user_to_check_access {
                     log_event(
                         LogLevel::Warn,
                         "Forbidden: attempt to access attachment owned by another user",
@@ -169,6 +183,7 @@ fn should_be_cleaned(att: &AttachmentHandle) -> bool {
         AttachmentLifecycle::TempJustUploaded => now - created > Duration::minutes(30),
         AttachmentLifecycle::SupportRequestActive => false,
         AttachmentLifecycle::MarkedForDeletionAfter(time_delta) => now - created > time_delta,
+        AttachmentLifecycle::GeneratedDocument => false,
     }
 }
 
@@ -184,7 +199,7 @@ pub fn try_get_by_id_checked_or_public(
                 return  Ok(Some(data));
             }
             if let Some(user_to_check_access) = user_email {
-                if data.owner_user_email != user_to_check_access {
+                if data.owner != user_to_check_access {
                     log_event(
                         LogLevel::Warn,
                         "Forbidden: attempt to access attachment owned by another user",
@@ -221,7 +236,7 @@ pub fn list_all_attachments_for_user(
     for item_result in attachments_tree.iter() {
         let (_key, value_ivec) = item_result?;
         let a: AttachmentHandle = serde_json::from_slice(&value_ivec)?;
-        if a.owner_user_email == user_email {
+        if a.owner == user_email {
             all.push(a);
         }
     }
@@ -260,14 +275,14 @@ pub fn handle_attachment(
     Ok(handle)
 }
 
-fn insert_attachment(requests_tree: &Tree, a: &AttachmentHandle) -> Result<(), AppError> {
+pub fn insert_attachment(requests_tree: &Tree, a: &AttachmentHandle) -> Result<(), AppError> {
     let key = a.id.clone();
     let value = serde_json::to_vec(&a)?;
 
     log_event(
         LogLevel::Debug,
         format!("New attachment handle {:?}", &a),
-        Some(a.owner_user_email.as_str()),
+        Some(a.owner.as_str()),
     );
 
     if requests_tree.contains_key(&key)? {
@@ -279,7 +294,7 @@ fn insert_attachment(requests_tree: &Tree, a: &AttachmentHandle) -> Result<(), A
     Ok(())
 }
 
-fn update_attachment(requests_tree: &Tree, a: &AttachmentHandle) -> Result<(), AppError> {
+pub fn update_attachment(requests_tree: &Tree, a: &AttachmentHandle) -> Result<(), AppError> {
     let key = a.id.clone();
     let value = serde_json::to_vec(&a)?;
 
@@ -289,7 +304,7 @@ fn update_attachment(requests_tree: &Tree, a: &AttachmentHandle) -> Result<(), A
     log_event(
         LogLevel::Info,
         format!("Updated support request: {}", a.id),
-        Some(a.owner_user_email.as_str()),
+        Some(a.owner.as_str()),
     );
     Ok(())
 }
@@ -0,0 +1,45 @@
+use sled::Tree;
+
+use crate::db::keys::{owner_key, owner_prefix};
+use crate::errors::AppError;
+use crate::models::calculations::CalculationIndexEntry;
+
+pub fn upsert_entry(
+    calculations_tree: &Tree,
+    email: &str,
+    entry: &CalculationIndexEntry,
+) -> Result<(), AppError> {
+    let key = owner_key(email, &entry.file_name);
+    calculations_tree.insert(key, serde_json::to_vec(entry)?)?;
+    calculations_tree.flush()?;
+    Ok(())
+}
+
+pub fn get_entry(
+    calculations_tree: &Tree,
+    email: &str,
+    file_name: &str,
+) -> Result<Option<CalculationIndexEntry>, AppError> {
+    match calculations_tree.get(owner_key(email, file_name))? {
+        Some(ivec) => Ok(Some(serde_json::from_slice(&ivec)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn remove_entry(calculations_tree: &Tree, email: &str, file_name: &str) -> Result<bool, AppError> {
+    let removed = calculations_tree.remove(owner_key(email, file_name))?;
+    calculations_tree.flush()?;
+    Ok(removed.is_some())
+}
+
+pub fn list_user_entries(
+    calculations_tree: &Tree,
+    email: &str,
+) -> Result<Vec<CalculationIndexEntry>, AppError> {
+    let mut entries = Vec::new();
+    for item_result in calculations_tree.scan_prefix(owner_prefix(email)) {
+        let (_key, value_ivec) = item_result?;
+        entries.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(entries)
+}
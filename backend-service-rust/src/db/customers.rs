@@ -0,0 +1,53 @@
+use sled::Tree;
+
+use crate::db::keys::{owner_key, owner_prefix};
+use crate::errors::AppError;
+use crate::exlogging::{log_event, LogLevel};
+use crate::models::customers::Customer;
+
+pub fn insert_customer(customers_tree: &Tree, customer: &Customer) -> Result<(), AppError> {
+    let key = owner_key(&customer.owner, &customer.id);
+    if customers_tree.contains_key(&key)? {
+        return Err(AppError::Conflict(format!("Customer {} already exists", customer.id)));
+    }
+    customers_tree.insert(key, serde_json::to_vec(customer)?)?;
+    customers_tree.flush()?;
+    log_event(
+        LogLevel::Info,
+        format!("New customer {}", customer.id),
+        Some(customer.owner.as_str()),
+    );
+    Ok(())
+}
+
+pub fn update_customer(customers_tree: &Tree, customer: &Customer) -> Result<(), AppError> {
+    let key = owner_key(&customer.owner, &customer.id);
+    customers_tree.insert(key, serde_json::to_vec(customer)?)?;
+    customers_tree.flush()?;
+    Ok(())
+}
+
+pub fn remove_customer(customers_tree: &Tree, email: &str, id: &str) -> Result<bool, AppError> {
+    let removed = customers_tree.remove(owner_key(email, id))?;
+    customers_tree.flush()?;
+    if removed.is_some() {
+        log_event(LogLevel::Info, format!("Removed customer {}", id), Some(email));
+    }
+    Ok(removed.is_some())
+}
+
+pub fn get_customer(customers_tree: &Tree, email: &str, id: &str) -> Result<Option<Customer>, AppError> {
+    match customers_tree.get(owner_key(email, id))? {
+        Some(ivec) => Ok(Some(serde_json::from_slice(&ivec)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn list_user_customers(customers_tree: &Tree, email: &str) -> Result<Vec<Customer>, AppError> {
+    let mut customers = Vec::new();
+    for item_result in customers_tree.scan_prefix(owner_prefix(email)) {
+        let (_key, value_ivec) = item_result?;
+        customers.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(customers)
+}
@@ -0,0 +1,44 @@
+use sled::Tree;
+
+use crate::db::keys::{owner_key, owner_prefix};
+use crate::errors::AppError;
+use crate::models::documents::DocumentJob;
+
+/// Inserts or replaces the job.
+pub fn save_job(jobs_tree: &Tree, job: &DocumentJob) -> Result<(), AppError> {
+    let key = owner_key(&job.owner, &job.id);
+    jobs_tree.insert(key, serde_json::to_vec(job)?)?;
+    jobs_tree.flush()?;
+    Ok(())
+}
+
+pub fn get_job(jobs_tree: &Tree, email: &str, id: &str) -> Result<Option<DocumentJob>, AppError> {
+    match jobs_tree.get(owner_key(email, id))? {
+        Some(ivec) => Ok(Some(serde_json::from_slice(&ivec)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn remove_job(jobs_tree: &Tree, email: &str, id: &str) -> Result<(), AppError> {
+    jobs_tree.remove(owner_key(email, id))?;
+    Ok(())
+}
+
+pub fn list_user_jobs(jobs_tree: &Tree, email: &str) -> Result<Vec<DocumentJob>, AppError> {
+    let mut jobs = Vec::new();
+    for item_result in jobs_tree.scan_prefix(owner_prefix(email)) {
+        let (_key, value_ivec) = item_result?;
+        jobs.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(jobs)
+}
+
+/// Jobs of all users, used by the worker and the cleanup.
+pub fn list_all_jobs(jobs_tree: &Tree) -> Result<Vec<DocumentJob>, AppError> {
+    let mut jobs = Vec::new();
+    for item_result in jobs_tree.iter() {
+        let (_key, value_ivec) = item_result?;
+        jobs.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(jobs)
+}
@@ -0,0 +1,114 @@
+use sled::Tree;
+
+use crate::db::keys::{owner_key, owner_prefix};
+use crate::errors::AppError;
+use crate::models::documents::{NumberAssignment, NumberAssignmentStatus};
+
+fn counter_key(email: &str, year: i32) -> Vec<u8> {
+    owner_key(email, &year.to_string())
+}
+
+// Assignments sort by year and sequence
+fn assignment_key(email: &str, year: i32, sequence: u64) -> Vec<u8> {
+    owner_key(email, &format!("{:04}/{:012}", year, sequence))
+}
+
+fn read_counter(value: Option<&[u8]>) -> Result<u64, AppError> {
+    match value {
+        Some(bytes) => {
+            let bytes: [u8; 8] = bytes.try_into().map_err(|_| {
+                AppError::InternalServerError("Corrupt document counter".to_string())
+            })?;
+            Ok(u64::from_be_bytes(bytes))
+        }
+        None => Ok(0),
+    }
+}
+
+/// Takes the next number of the year's sequence. Concurrent callers retry the
+/// compare-and-swap until each gets its own number.
+pub fn next_sequence(counters_tree: &Tree, email: &str, year: i32) -> Result<u64, AppError> {
+    let key = counter_key(email, year);
+    loop {
+        let current = counters_tree.get(&key)?;
+        let next = read_counter(current.as_deref())? + 1;
+        let swapped =
+            counters_tree.compare_and_swap(&key, current, Some(&next.to_be_bytes()[..]))?;
+        if swapped.is_ok() {
+            counters_tree.flush()?;
+            return Ok(next);
+        }
+    }
+}
+
+/// Gives `sequence` back if no later number was taken. Returns whether it did.
+pub fn rollback_sequence(
+    counters_tree: &Tree,
+    email: &str,
+    year: i32,
+    sequence: u64,
+) -> Result<bool, AppError> {
+    let previous = sequence.saturating_sub(1).to_be_bytes();
+    let swapped = counters_tree.compare_and_swap(
+        counter_key(email, year),
+        Some(&sequence.to_be_bytes()[..]),
+        Some(&previous[..]),
+    )?;
+    counters_tree.flush()?;
+    Ok(swapped.is_ok())
+}
+
+pub fn save_assignment(
+    numbers_tree: &Tree,
+    email: &str,
+    assignment: &NumberAssignment,
+) -> Result<(), AppError> {
+    let key = assignment_key(email, assignment.year, assignment.sequence);
+    numbers_tree.insert(key, serde_json::to_vec(assignment)?)?;
+    numbers_tree.flush()?;
+    Ok(())
+}
+
+/// Removes the assignment if it is still stored as `assignment`, not replaced by
+/// another one of the same number. Returns whether it did.
+pub fn remove_assignment(
+    numbers_tree: &Tree,
+    email: &str,
+    assignment: &NumberAssignment,
+) -> Result<bool, AppError> {
+    let swapped = numbers_tree.compare_and_swap(
+        assignment_key(email, assignment.year, assignment.sequence),
+        Some(serde_json::to_vec(assignment)?),
+        None as Option<&[u8]>,
+    )?;
+    numbers_tree.flush()?;
+    Ok(swapped.is_ok())
+}
+
+/// Assignments of the user's documents in sequence order.
+pub fn list_assignments(
+    numbers_tree: &Tree,
+    email: &str,
+) -> Result<Vec<NumberAssignment>, AppError> {
+    let mut assignments = Vec::new();
+    for item_result in numbers_tree.scan_prefix(owner_prefix(email)) {
+        let (_key, value_ivec) = item_result?;
+        assignments.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(assignments)
+}
+
+/// The number issued for a stored calculation, if it has one.
+pub fn find_calculation_assignment(
+    numbers_tree: &Tree,
+    email: &str,
+    calculation: &str,
+) -> Result<Option<NumberAssignment>, AppError> {
+    Ok(list_assignments(numbers_tree, email)?
+        .into_iter()
+        .rev()
+        .find(|a| {
+            a.status == NumberAssignmentStatus::Issued
+                && a.calculation.as_deref() == Some(calculation)
+        }))
+}
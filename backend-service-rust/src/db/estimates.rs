@@ -0,0 +1,54 @@
+use sled::Tree;
+
+use crate::db::keys::{owner_key, owner_prefix};
+use crate::errors::AppError;
+use crate::models::estimates::StatusTransition;
+
+// Transitions of one estimate share the `<file name>/` prefix and sort by time
+fn transition_uid(transition: &StatusTransition) -> String {
+    format!(
+        "{}/{:020}",
+        transition.file_name,
+        transition.at.timestamp_nanos_opt().unwrap_or_default()
+    )
+}
+
+pub fn insert_transition(
+    transitions_tree: &Tree,
+    email: &str,
+    transition: &StatusTransition,
+) -> Result<(), AppError> {
+    let key = owner_key(email, &transition_uid(transition));
+    transitions_tree.insert(key, serde_json::to_vec(transition)?)?;
+    transitions_tree.flush()?;
+    Ok(())
+}
+
+/// Transitions of one estimate, oldest first.
+pub fn list_estimate_transitions(
+    transitions_tree: &Tree,
+    email: &str,
+    file_name: &str,
+) -> Result<Vec<StatusTransition>, AppError> {
+    let prefix = owner_key(email, &format!("{}/", file_name));
+    let mut transitions = Vec::new();
+    for item_result in transitions_tree.scan_prefix(prefix) {
+        let (_key, value_ivec) = item_result?;
+        transitions.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(transitions)
+}
+
+/// All transitions of the user's estimates, oldest first.
+pub fn list_user_transitions(
+    transitions_tree: &Tree,
+    email: &str,
+) -> Result<Vec<StatusTransition>, AppError> {
+    let mut transitions: Vec<StatusTransition> = Vec::new();
+    for item_result in transitions_tree.scan_prefix(owner_prefix(email)) {
+        let (_key, value_ivec) = item_result?;
+        transitions.push(serde_json::from_slice(&value_ivec)?);
+    }
+    transitions.sort_by_key(|t| t.at);
+    Ok(transitions)
+}
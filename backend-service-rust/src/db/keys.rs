@@ -0,0 +1,20 @@
+use byteorder::{BigEndian, WriteBytesExt};
+
+/// Prefix of the records of one owner in trees keyed by owner and record id: the
+/// length of the owner as big-endian u16 and the owner itself, so an owner that is
+/// a prefix of another one does not match its records.
+pub fn owner_prefix(owner: &str) -> Vec<u8> {
+    let mut prefix_bytes = Vec::new();
+    prefix_bytes
+        .write_u16::<BigEndian>(owner.len() as u16)
+        .expect("Failed to write owner length");
+    prefix_bytes.extend_from_slice(owner.as_bytes());
+    prefix_bytes
+}
+
+/// Key of the record `uid` of `owner`.
+pub fn owner_key(owner: &str, uid: &str) -> Vec<u8> {
+    let mut key_bytes = owner_prefix(owner);
+    key_bytes.extend_from_slice(uid.as_bytes());
+    key_bytes
+}
@@ -1,4 +1,16 @@
 pub mod users;
 pub mod requests;
 pub mod notifications;
-pub mod attachment;
\ No newline at end of file
+pub mod attachment;
+pub mod calculations;
+pub mod estimates;
+pub mod customers;
+pub mod vehicles;
+pub mod document_jobs;
+pub mod document_numbers;
+pub mod sessions;
+pub mod organizations;
+pub mod password_resets;
+pub mod two_factor;
+pub mod api_keys;
+pub mod keys;
@@ -0,0 +1,138 @@
+use sled::Tree;
+
+use crate::db::keys::{owner_key, owner_prefix};
+use crate::errors::AppError;
+use crate::models::organizations::{Organization, OrganizationInvitation};
+
+pub fn save_organization(
+    organizations_tree: &Tree,
+    organization: &Organization,
+) -> Result<(), AppError> {
+    organizations_tree.insert(
+        organization.id.as_bytes(),
+        serde_json::to_vec(organization)?,
+    )?;
+    organizations_tree.flush()?;
+    Ok(())
+}
+
+pub fn get_organization(
+    organizations_tree: &Tree,
+    id: &str,
+) -> Result<Option<Organization>, AppError> {
+    match organizations_tree.get(id.as_bytes())? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn remove_organization(organizations_tree: &Tree, id: &str) -> Result<(), AppError> {
+    organizations_tree.remove(id.as_bytes())?;
+    organizations_tree.flush()?;
+    Ok(())
+}
+
+pub fn list_organizations(organizations_tree: &Tree) -> Result<Vec<Organization>, AppError> {
+    let mut organizations = Vec::new();
+    for item_result in organizations_tree.iter() {
+        let (_key, value_ivec) = item_result?;
+        organizations.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(organizations)
+}
+
+pub fn save_invitation(
+    invitations_tree: &Tree,
+    invitation: &OrganizationInvitation,
+) -> Result<(), AppError> {
+    invitations_tree.insert(
+        owner_key(&invitation.email, &invitation.organization),
+        serde_json::to_vec(invitation)?,
+    )?;
+    invitations_tree.flush()?;
+    Ok(())
+}
+
+pub fn get_invitation(
+    invitations_tree: &Tree,
+    email: &str,
+    organization: &str,
+) -> Result<Option<OrganizationInvitation>, AppError> {
+    match invitations_tree.get(owner_key(email, organization))? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn remove_invitation(
+    invitations_tree: &Tree,
+    email: &str,
+    organization: &str,
+) -> Result<Option<OrganizationInvitation>, AppError> {
+    let removed = invitations_tree.remove(owner_key(email, organization))?;
+    invitations_tree.flush()?;
+    match removed {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Invitations sent to a user.
+pub fn list_user_invitations(
+    invitations_tree: &Tree,
+    email: &str,
+) -> Result<Vec<OrganizationInvitation>, AppError> {
+    let mut invitations = Vec::new();
+    for item_result in invitations_tree.scan_prefix(owner_prefix(email)) {
+        let (_key, value_ivec) = item_result?;
+        invitations.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(invitations)
+}
+
+/// Invitations sent by a shop.
+pub fn list_organization_invitations(
+    invitations_tree: &Tree,
+    organization: &str,
+) -> Result<Vec<OrganizationInvitation>, AppError> {
+    let mut invitations = Vec::new();
+    for item_result in invitations_tree.iter() {
+        let (_key, value_ivec) = item_result?;
+        let invitation: OrganizationInvitation = serde_json::from_slice(&value_ivec)?;
+        if invitation.organization == organization {
+            invitations.push(invitation);
+        }
+    }
+    Ok(invitations)
+}
+
+/// Whether a tree keyed by owner has records of the owner.
+pub fn has_owner_entries(tree: &Tree, owner: &str) -> Result<bool, AppError> {
+    Ok(tree.scan_prefix(owner_prefix(owner)).next().transpose()?.is_some())
+}
+
+/// Re-keys the records of a tree keyed by owner and record id from one owner
+/// to another, along with the `owner` field of the records that have one.
+pub fn move_owner_entries(tree: &Tree, from: &str, to: &str) -> Result<usize, AppError> {
+    let from_prefix = owner_prefix(from);
+    let to_prefix = owner_prefix(to);
+    let mut moved = 0;
+    for item_result in tree.scan_prefix(&from_prefix) {
+        let (key, value) = item_result?;
+        let mut new_key = to_prefix.clone();
+        new_key.extend_from_slice(&key[from_prefix.len()..]);
+
+        let mut new_value = value.to_vec();
+        if let Ok(mut record) = serde_json::from_slice::<serde_json::Value>(&value) {
+            if record.get("owner").and_then(|o| o.as_str()) == Some(from) {
+                record["owner"] = serde_json::Value::String(to.to_string());
+                new_value = serde_json::to_vec(&record)?;
+            }
+        }
+        tree.insert(new_key, new_value)?;
+        tree.remove(key)?;
+        moved += 1;
+    }
+    tree.flush()?;
+    Ok(moved)
+}
@@ -0,0 +1,43 @@
+use sled::Tree;
+
+use crate::errors::AppError;
+use crate::models::sessions::PasswordReset;
+
+pub fn save_password_reset(
+    password_resets_tree: &Tree,
+    token_hash: &str,
+    reset: &PasswordReset,
+) -> Result<(), AppError> {
+    password_resets_tree.insert(token_hash.as_bytes(), serde_json::to_vec(reset)?)?;
+    password_resets_tree.flush()?;
+    Ok(())
+}
+
+/// Removes the reset and returns it, so a token works only once.
+pub fn take_password_reset(
+    password_resets_tree: &Tree,
+    token_hash: &str,
+) -> Result<Option<PasswordReset>, AppError> {
+    let removed = password_resets_tree.remove(token_hash.as_bytes())?;
+    password_resets_tree.flush()?;
+    match removed {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+/// Drops the pending resets of a user, or expired ones of all users.
+pub fn remove_password_resets(
+    password_resets_tree: &Tree,
+    matches: impl Fn(&PasswordReset) -> bool,
+) -> Result<(), AppError> {
+    for item_result in password_resets_tree.iter() {
+        let (key, value_ivec) = item_result?;
+        let reset: PasswordReset = serde_json::from_slice(&value_ivec)?;
+        if matches(&reset) {
+            password_resets_tree.remove(key)?;
+        }
+    }
+    password_resets_tree.flush()?;
+    Ok(())
+}
@@ -0,0 +1,105 @@
+use sled::{IVec, Tree};
+
+use crate::errors::AppError;
+use crate::models::sessions::{RevokedSession, Session};
+
+pub fn save_session(sessions_tree: &Tree, session: &Session) -> Result<(), AppError> {
+    sessions_tree.insert(session.id.as_bytes(), serde_json::to_vec(session)?)?;
+    sessions_tree.flush()?;
+    Ok(())
+}
+
+pub fn get_session(sessions_tree: &Tree, id: &str) -> Result<Option<Session>, AppError> {
+    match sessions_tree.get(id.as_bytes())? {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+/// The session with its stored bytes, to be replaced with `swap_session`.
+pub fn get_session_entry(
+    sessions_tree: &Tree,
+    id: &str,
+) -> Result<Option<(IVec, Session)>, AppError> {
+    match sessions_tree.get(id.as_bytes())? {
+        Some(value) => {
+            let session = serde_json::from_slice(&value)?;
+            Ok(Some((value, session)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Replaces the session only if it is still stored as `current`. Returns false
+/// when it was changed or removed in between.
+pub fn swap_session(
+    sessions_tree: &Tree,
+    current: IVec,
+    session: &Session,
+) -> Result<bool, AppError> {
+    let swapped = sessions_tree
+        .compare_and_swap(
+            session.id.as_bytes(),
+            Some(current),
+            Some(serde_json::to_vec(session)?),
+        )?
+        .is_ok();
+    sessions_tree.flush()?;
+    Ok(swapped)
+}
+
+pub fn remove_session(sessions_tree: &Tree, id: &str) -> Result<Option<Session>, AppError> {
+    let removed = sessions_tree.remove(id.as_bytes())?;
+    sessions_tree.flush()?;
+    match removed {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn list_all_sessions(sessions_tree: &Tree) -> Result<Vec<Session>, AppError> {
+    let mut sessions = Vec::new();
+    for item_result in sessions_tree.iter() {
+        let (_key, value_ivec) = item_result?;
+        sessions.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(sessions)
+}
+
+pub fn list_user_sessions(
+    sessions_tree: &Tree,
+    user_email: &str,
+) -> Result<Vec<Session>, AppError> {
+    Ok(list_all_sessions(sessions_tree)?
+        .into_iter()
+        .filter(|s| s.user_email == user_email)
+        .collect())
+}
+
+pub fn insert_revoked_session(
+    revoked_tree: &Tree,
+    id: &str,
+    revoked: &RevokedSession,
+) -> Result<(), AppError> {
+    revoked_tree.insert(id.as_bytes(), serde_json::to_vec(revoked)?)?;
+    revoked_tree.flush()?;
+    Ok(())
+}
+
+pub fn is_session_revoked(revoked_tree: &Tree, id: &str) -> Result<bool, AppError> {
+    Ok(revoked_tree.contains_key(id.as_bytes())?)
+}
+
+/// Drops revocation list entries whose tokens have all expired.
+pub fn cleanup_revoked_sessions(revoked_tree: &Tree) -> Result<(), AppError> {
+    let now = chrono::Utc::now();
+    for item_result in revoked_tree.iter() {
+        let (key, value_ivec) = item_result?;
+        let revoked: RevokedSession = serde_json::from_slice(&value_ivec)?;
+        if revoked.expires_at < now {
+            revoked_tree.remove(key)?;
+        }
+    }
+    revoked_tree.flush()?;
+    Ok(())
+}
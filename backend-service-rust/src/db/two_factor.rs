@@ -0,0 +1,60 @@
+use sled::Tree;
+
+use crate::errors::AppError;
+use crate::models::two_factor::{LoginChallenge, SecuritySettings};
+
+const SECURITY_SETTINGS_KEY: &str = "security";
+
+pub fn save_login_challenge(
+    login_challenges_tree: &Tree,
+    token_hash: &str,
+    challenge: &LoginChallenge,
+) -> Result<(), AppError> {
+    login_challenges_tree.insert(token_hash.as_bytes(), serde_json::to_vec(challenge)?)?;
+    login_challenges_tree.flush()?;
+    Ok(())
+}
+
+/// Removes the challenge and returns it, so a token works only once.
+pub fn take_login_challenge(
+    login_challenges_tree: &Tree,
+    token_hash: &str,
+) -> Result<Option<LoginChallenge>, AppError> {
+    let removed = login_challenges_tree.remove(token_hash.as_bytes())?;
+    login_challenges_tree.flush()?;
+    match removed {
+        Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn remove_login_challenges(
+    login_challenges_tree: &Tree,
+    matches: impl Fn(&LoginChallenge) -> bool,
+) -> Result<(), AppError> {
+    for item_result in login_challenges_tree.iter() {
+        let (key, value_ivec) = item_result?;
+        let challenge: LoginChallenge = serde_json::from_slice(&value_ivec)?;
+        if matches(&challenge) {
+            login_challenges_tree.remove(key)?;
+        }
+    }
+    login_challenges_tree.flush()?;
+    Ok(())
+}
+
+pub fn get_security_settings(settings_tree: &Tree) -> Result<SecuritySettings, AppError> {
+    match settings_tree.get(SECURITY_SETTINGS_KEY)? {
+        Some(value) => Ok(serde_json::from_slice(&value)?),
+        None => Ok(SecuritySettings::default()),
+    }
+}
+
+pub fn save_security_settings(
+    settings_tree: &Tree,
+    settings: &SecuritySettings,
+) -> Result<(), AppError> {
+    settings_tree.insert(SECURITY_SETTINGS_KEY, serde_json::to_vec(settings)?)?;
+    settings_tree.flush()?;
+    Ok(())
+}
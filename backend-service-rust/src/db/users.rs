@@ -1,6 +1,6 @@
 use std::string::FromUtf8Error;
 
-use crate::{errors::AppError, exlogging::{log_event, LogLevel}, models::User};
+use crate::{errors::AppError, exlogging::{log_event, LogLevel}, models::{roles::Role, two_factor::TwoFactor, User}};
 use serde_json;
 use sled::{Tree};
 
@@ -8,6 +8,24 @@ const USERS_TREE_NAME: &str = "users";
 const REQUESTS_TREE_NAME: &str = "support_requests";
 const NOTIFICATIONS_TREE_NAME: &str = "notifications";
 const ATTACHMENTS_TREE_NAME: &str = "attachments";
+const CALCULATIONS_TREE_NAME: &str = "calculations";
+const ESTIMATE_TRANSITIONS_TREE_NAME: &str = "estimate_transitions";
+const CUSTOMERS_TREE_NAME: &str = "customers";
+const VEHICLES_TREE_NAME: &str = "vehicles";
+const DOCUMENT_JOBS_TREE_NAME: &str = "document_jobs";
+const DOCUMENT_COUNTERS_TREE_NAME: &str = "document_counters";
+const DOCUMENT_NUMBERS_TREE_NAME: &str = "document_numbers";
+const SESSIONS_TREE_NAME: &str = "sessions";
+const REVOKED_SESSIONS_TREE_NAME: &str = "revoked_sessions";
+const ROLE_GRANTS_TREE_NAME: &str = "role_grants";
+const MIGRATIONS_TREE_NAME: &str = "migrations";
+const ORGANIZATIONS_TREE_NAME: &str = "organizations";
+const ORGANIZATION_INVITATIONS_TREE_NAME: &str = "organization_invitations";
+const PASSWORD_RESETS_TREE_NAME: &str = "password_resets";
+const LOGIN_ATTEMPTS_TREE_NAME: &str = "login_attempts";
+const LOGIN_CHALLENGES_TREE_NAME: &str = "login_challenges";
+const SETTINGS_TREE_NAME: &str = "settings";
+const API_KEYS_TREE_NAME: &str = "api_keys";
 
 #[derive(Clone)]
 pub struct AppDb {
@@ -15,7 +33,33 @@ pub struct AppDb {
     pub users_tree: Tree,
     pub requests_tree: Tree,
     pub notifications_tree: Tree,
-    pub attachments_tree: Tree
+    pub attachments_tree: Tree,
+    pub calculations_tree: Tree,
+    pub estimate_transitions_tree: Tree,
+    pub customers_tree: Tree,
+    pub vehicles_tree: Tree,
+    pub document_jobs_tree: Tree,
+    pub document_counters_tree: Tree,
+    pub document_numbers_tree: Tree,
+    pub sessions_tree: Tree,
+    pub revoked_sessions_tree: Tree,
+    /// Roles for emails that have no account yet
+    pub role_grants_tree: Tree,
+    /// One-time data migrations that already ran
+    pub migrations_tree: Tree,
+    pub organizations_tree: Tree,
+    /// Invitations to join a shop, by invited email and organization id
+    pub organization_invitations_tree: Tree,
+    /// Pending password reset tokens, by token hash
+    pub password_resets_tree: Tree,
+    /// Failed logins, only used when lockouts are kept across restarts
+    pub login_attempts_tree: Tree,
+    /// Password logins waiting for the second factor, by token hash
+    pub login_challenges_tree: Tree,
+    /// Settings changed at runtime by admins
+    pub settings_tree: Tree,
+    /// API keys, by key id
+    pub api_keys_tree: Tree,
 }
 
 impl AppDb {
@@ -25,7 +69,48 @@ impl AppDb {
         let requests_tree = db.open_tree(REQUESTS_TREE_NAME)?;
         let notifications_tree = db.open_tree(NOTIFICATIONS_TREE_NAME)?;
         let attachments_tree = db.open_tree(ATTACHMENTS_TREE_NAME)?;
-        Ok(Self { users_tree, requests_tree, notifications_tree, attachments_tree })
+        let calculations_tree = db.open_tree(CALCULATIONS_TREE_NAME)?;
+        let estimate_transitions_tree = db.open_tree(ESTIMATE_TRANSITIONS_TREE_NAME)?;
+        let customers_tree = db.open_tree(CUSTOMERS_TREE_NAME)?;
+        let vehicles_tree = db.open_tree(VEHICLES_TREE_NAME)?;
+        let document_jobs_tree = db.open_tree(DOCUMENT_JOBS_TREE_NAME)?;
+        let document_counters_tree = db.open_tree(DOCUMENT_COUNTERS_TREE_NAME)?;
+        let document_numbers_tree = db.open_tree(DOCUMENT_NUMBERS_TREE_NAME)?;
+        let sessions_tree = db.open_tree(SESSIONS_TREE_NAME)?;
+        let revoked_sessions_tree = db.open_tree(REVOKED_SESSIONS_TREE_NAME)?;
+        let role_grants_tree = db.open_tree(ROLE_GRANTS_TREE_NAME)?;
+        let migrations_tree = db.open_tree(MIGRATIONS_TREE_NAME)?;
+        let organizations_tree = db.open_tree(ORGANIZATIONS_TREE_NAME)?;
+        let organization_invitations_tree = db.open_tree(ORGANIZATION_INVITATIONS_TREE_NAME)?;
+        let password_resets_tree = db.open_tree(PASSWORD_RESETS_TREE_NAME)?;
+        let login_attempts_tree = db.open_tree(LOGIN_ATTEMPTS_TREE_NAME)?;
+        let login_challenges_tree = db.open_tree(LOGIN_CHALLENGES_TREE_NAME)?;
+        let settings_tree = db.open_tree(SETTINGS_TREE_NAME)?;
+        let api_keys_tree = db.open_tree(API_KEYS_TREE_NAME)?;
+        Ok(Self {
+            users_tree,
+            requests_tree,
+            notifications_tree,
+            attachments_tree,
+            calculations_tree,
+            estimate_transitions_tree,
+            customers_tree,
+            vehicles_tree,
+            document_jobs_tree,
+            document_counters_tree,
+            document_numbers_tree,
+            sessions_tree,
+            revoked_sessions_tree,
+            role_grants_tree,
+            migrations_tree,
+            organizations_tree,
+            organization_invitations_tree,
+            password_resets_tree,
+            login_attempts_tree,
+            login_challenges_tree,
+            settings_tree,
+            api_keys_tree,
+        })
     }
 
     pub fn insert_user(&self, user: &User) -> Result<(), AppError> {
@@ -100,4 +185,63 @@ impl AppDb {
 
         Ok(())
     }
+
+    pub fn set_user_roles(&self, email: &str, mut roles: Vec<Role>) -> Result<(), AppError> {
+        let key = email.as_bytes();
+        let mut user = self
+            .find_user_by_email(email)?
+            .ok_or(AppError::UserNotFound)?;
+
+        roles.sort();
+        roles.dedup();
+        log_event(LogLevel::Info, format!("Set roles {:?}", roles), Some(email));
+        user.roles = roles;
+
+        self.users_tree.insert(key, serde_json::to_vec(&user)?)?;
+        self.users_tree.flush()?;
+        Ok(())
+    }
+
+    pub fn set_user_organization(&self, email: &str, organization: &str) -> Result<(), AppError> {
+        let key = email.as_bytes();
+        let mut user = self
+            .find_user_by_email(email)?
+            .ok_or(AppError::UserNotFound)?;
+
+        log_event(LogLevel::Info, format!("Set organization {}", organization), Some(email));
+        user.organization = Some(organization.to_string());
+
+        self.users_tree.insert(key, serde_json::to_vec(&user)?)?;
+        self.users_tree.flush()?;
+        Ok(())
+    }
+
+    pub fn set_user_two_factor(
+        &self,
+        email: &str,
+        two_factor: Option<TwoFactor>,
+    ) -> Result<(), AppError> {
+        let key = email.as_bytes();
+        let mut user = self
+            .find_user_by_email(email)?
+            .ok_or(AppError::UserNotFound)?;
+
+        user.two_factor = two_factor;
+
+        self.users_tree.insert(key, serde_json::to_vec(&user)?)?;
+        self.users_tree.flush()?;
+        Ok(())
+    }
+
+    pub fn list_organization_members(&self, organization: &str) -> Result<Vec<User>, AppError> {
+        let mut members = Vec::new();
+        for item in self.users_tree.iter() {
+            let (_, value) = item?;
+            let user: User = serde_json::from_slice(&value)?;
+            if user.organization.as_deref() == Some(organization) {
+                members.push(user);
+            }
+        }
+        Ok(members)
+    }
 }
@@ -0,0 +1,53 @@
+use sled::Tree;
+
+use crate::db::keys::{owner_key, owner_prefix};
+use crate::errors::AppError;
+use crate::exlogging::{log_event, LogLevel};
+use crate::models::customers::Vehicle;
+
+pub fn insert_vehicle(vehicles_tree: &Tree, vehicle: &Vehicle) -> Result<(), AppError> {
+    let key = owner_key(&vehicle.owner, &vehicle.id);
+    if vehicles_tree.contains_key(&key)? {
+        return Err(AppError::Conflict(format!("Vehicle {} already exists", vehicle.id)));
+    }
+    vehicles_tree.insert(key, serde_json::to_vec(vehicle)?)?;
+    vehicles_tree.flush()?;
+    log_event(
+        LogLevel::Info,
+        format!("New vehicle {}", vehicle.id),
+        Some(vehicle.owner.as_str()),
+    );
+    Ok(())
+}
+
+pub fn update_vehicle(vehicles_tree: &Tree, vehicle: &Vehicle) -> Result<(), AppError> {
+    let key = owner_key(&vehicle.owner, &vehicle.id);
+    vehicles_tree.insert(key, serde_json::to_vec(vehicle)?)?;
+    vehicles_tree.flush()?;
+    Ok(())
+}
+
+pub fn remove_vehicle(vehicles_tree: &Tree, email: &str, id: &str) -> Result<bool, AppError> {
+    let removed = vehicles_tree.remove(owner_key(email, id))?;
+    vehicles_tree.flush()?;
+    if removed.is_some() {
+        log_event(LogLevel::Info, format!("Removed vehicle {}", id), Some(email));
+    }
+    Ok(removed.is_some())
+}
+
+pub fn get_vehicle(vehicles_tree: &Tree, email: &str, id: &str) -> Result<Option<Vehicle>, AppError> {
+    match vehicles_tree.get(owner_key(email, id))? {
+        Some(ivec) => Ok(Some(serde_json::from_slice(&ivec)?)),
+        None => Ok(None),
+    }
+}
+
+pub fn list_user_vehicles(vehicles_tree: &Tree, email: &str) -> Result<Vec<Vehicle>, AppError> {
+    let mut vehicles = Vec::new();
+    for item_result in vehicles_tree.scan_prefix(owner_prefix(email)) {
+        let (_key, value_ivec) = item_result?;
+        vehicles.push(serde_json::from_slice(&value_ivec)?);
+    }
+    Ok(vehicles)
+}
@@ -1,4 +1,4 @@
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
 use serde::Serialize;
@@ -34,6 +34,8 @@ pub enum AppError {
     Unauthorized,
     #[error("Forbidden")]
     Forbidden,
+    #[error("Two-factor authentication required")]
+    TwoFactorRequired,
     #[error("License expired")]
     LicenseExpired,
     #[error("License not found")]
@@ -59,7 +61,12 @@ pub enum AppError {
     #[error("Internal server error: {0}")]
     InternalServerError(String),
     #[error("Bad request: {0}")]
-    BadRequest(String)
+    BadRequest(String),
+    #[error("Conflict: {0}")]
+    Conflict(String),
+    /// Seconds until the caller may try again.
+    #[error("Too many attempts, try again in {0} seconds")]
+    TooManyRequests(u64)
 }
 
 // Implement the From trait for TransactionalFsError to AppError
@@ -97,11 +104,14 @@ impl IntoResponse for AppError {
         let status_code = match &self {
             AppError::Unauthorized => StatusCode::UNAUTHORIZED,
             AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::TwoFactorRequired => StatusCode::FORBIDDEN,
             AppError::LicenseExpired => StatusCode::FORBIDDEN,
             AppError::LicenseNotFound => StatusCode::FORBIDDEN,
             AppError::UserNotFound => StatusCode::NOT_FOUND,
             AppError::InvalidCredentials => StatusCode::UNAUTHORIZED,
             AppError::UserExists => StatusCode::CONFLICT,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
             AppError::AdminCheckFailed => StatusCode::NOT_FOUND, // As requested for admin check
             AppError::FileNotFound => StatusCode::NOT_FOUND,
             AppError::NotFound => StatusCode::NOT_FOUND,
@@ -118,6 +128,9 @@ impl IntoResponse for AppError {
         log::warn!("Error response sent: {}", self);
         exlogging::log_event(exlogging::LogLevel::Warn, format!("Error response: {:?}", self.to_string()), None::<&str>);
 
+        if let AppError::TooManyRequests(retry_after) = self {
+            return (status_code, [(header::RETRY_AFTER, retry_after.to_string())], body).into_response();
+        }
         (status_code, body).into_response()
     }
 }
\ No newline at end of file
@@ -0,0 +1,131 @@
+use std::{fs, path::PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use clap::{Parser, Subcommand};
+use jsonwebtoken::decode_header;
+
+use crate::{
+    errors::AppError,
+    license_keys::{generate_key_pair, LicenseKeys},
+    license_manager::{decode_license_token_no_validation, generate_license_token, LicenseData},
+};
+
+/// Without a command the server is started.
+#[derive(Parser)]
+#[command(version, about = "Autolab backend")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Work with license keys and license files, without a running server
+    #[command(subcommand)]
+    License(LicenseCommand),
+}
+
+#[derive(Subcommand)]
+pub enum LicenseCommand {
+    /// Create an Ed25519 key pair, <KID>.key.pem and <KID>.pub.pem
+    Keygen {
+        #[arg(long)]
+        kid: String,
+        #[arg(long, env = "LICENSE_KEYS_DIR")]
+        keys_dir: PathBuf,
+    },
+    /// Sign a license with a private key of the keys directory
+    Generate {
+        #[arg(long, env = "LICENSE_KEYS_DIR")]
+        keys_dir: PathBuf,
+        /// Key to sign with
+        #[arg(long, env = "LICENSE_SIGNING_KEY_ID")]
+        kid: String,
+        #[arg(long)]
+        email: String,
+        /// Days until the license expires
+        #[arg(long, required_unless_present = "expires", conflicts_with = "expires")]
+        days: Option<i64>,
+        /// Expiry date, e.g. 2027-01-01T00:00:00Z
+        #[arg(long)]
+        expires: Option<DateTime<Utc>>,
+        /// License level, Basic by default
+        #[arg(long)]
+        level: Option<String>,
+        /// File to write the license to instead of stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Show the header and claims of a license file, without checking it
+    Inspect { file: PathBuf },
+    /// Check the signature and expiry of a license file with the public keys
+    Verify {
+        file: PathBuf,
+        #[arg(long, env = "LICENSE_KEYS_DIR")]
+        keys_dir: PathBuf,
+    },
+}
+
+fn read_token(file: &PathBuf) -> Result<String, AppError> {
+    Ok(fs::read_to_string(file)?.trim().to_string())
+}
+
+pub fn run(command: LicenseCommand) -> Result<(), AppError> {
+    match command {
+        LicenseCommand::Keygen { kid, keys_dir } => {
+            let (private_path, public_path) = generate_key_pair(&keys_dir, &kid)?;
+            println!("Private key: {}", private_path.display());
+            println!("Public key:  {}", public_path.display());
+        }
+        LicenseCommand::Generate {
+            keys_dir,
+            kid,
+            email,
+            days,
+            expires,
+            level,
+            out,
+        } => {
+            let license_keys = LicenseKeys::load(Some(&keys_dir), Some(&kid), None)?;
+            let expiry_date = match (days, expires) {
+                (_, Some(expires)) => expires,
+                (days, None) => Utc::now() + Duration::days(days.unwrap_or_default()),
+            };
+            let token = generate_license_token(&email, expiry_date, level, &license_keys)?;
+            match out {
+                Some(out) => {
+                    fs::write(&out, &token)?;
+                    println!("License written to {}", out.display());
+                }
+                None => println!("{}", token),
+            }
+        }
+        LicenseCommand::Inspect { file } => {
+            let token = read_token(&file)?;
+            let header = decode_header(&token)?;
+            let claims = decode_license_token_no_validation(&token)?;
+            let inspected = serde_json::json!({
+                "alg": header.alg,
+                "kid": header.kid,
+                "license": LicenseData::new(claims),
+            });
+            println!("{}", serde_json::to_string_pretty(&inspected)?);
+        }
+        LicenseCommand::Verify { file, keys_dir } => {
+            let token = read_token(&file)?;
+            let license_keys = LicenseKeys::load(Some(&keys_dir), None, None)?;
+            let claims = license_keys.verify(&token).map_err(|e| match e {
+                AppError::Unauthorized => AppError::InvalidData(
+                    "License is not signed by any of the public keys".to_string(),
+                ),
+                e => e,
+            })?;
+            let license = LicenseData::new(claims);
+            println!(
+                "Valid {} license of {}, expires {} ({} days left)",
+                license.level, license.claims.sub, license.expiration_date, license.days_left
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,399 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use ed25519_dalek::{
+    pkcs8::{spki::der::pem::LineEnding, EncodePrivateKey, EncodePublicKey, KeypairBytes},
+    SigningKey,
+};
+use jsonwebtoken::{
+    decode, decode_header, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header,
+    Validation,
+};
+
+use crate::{errors::AppError, license_manager::LicenseClaims};
+
+/// Public keys are `{kid}.pub.pem`, enough to verify licenses.
+const PUBLIC_KEY_SUFFIX: &str = ".pub.pem";
+/// Private keys are `{kid}.key.pem`, needed only where licenses are issued.
+const PRIVATE_KEY_SUFFIX: &str = ".key.pem";
+
+/// HS256 secret of licenses from before key pairs, accepted until a cutoff.
+struct LegacyLicenseKey {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    until: DateTime<Utc>,
+}
+
+impl LegacyLicenseKey {
+    fn active(&self) -> bool {
+        Utc::now() < self.until
+    }
+}
+
+struct SigningLicenseKey {
+    kid: String,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+/// Keys of license tokens. Licenses are signed with an Ed25519 or RSA key
+/// named in the `kid` header, so keys can be rotated while older licenses
+/// stay valid as long as their public key is kept.
+pub struct LicenseKeys {
+    signing: Option<SigningLicenseKey>,
+    verifying: HashMap<String, (Algorithm, DecodingKey)>,
+    /// `LICENSE_JWT_SECRET`, only with `LICENSE_LEGACY_UNTIL`.
+    legacy: Option<LegacyLicenseKey>,
+}
+
+fn check_key_id(kid: &str) -> Result<(), AppError> {
+    if kid.is_empty()
+        || !kid
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(AppError::ConfigError(format!(
+            "Invalid license key id {:?}, use letters, digits, - and _",
+            kid
+        )));
+    }
+    Ok(())
+}
+
+/// Parses `LICENSE_LEGACY_UNTIL`, an RFC 3339 time or a date (midnight UTC).
+pub fn parse_legacy_cutoff(value: &str) -> Result<DateTime<Utc>, AppError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|date| date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
+        })
+        .map_err(|_| {
+            AppError::ConfigError(format!(
+                "Invalid LICENSE_LEGACY_UNTIL {:?}, use a date like 2026-12-31",
+                value
+            ))
+        })
+}
+
+fn read_key(path: &Path) -> Result<Vec<u8>, AppError> {
+    fs::read(path).map_err(|e| AppError::ConfigError(format!("Cannot read {:?}: {}", path, e)))
+}
+
+fn public_key(path: &Path) -> Result<(Algorithm, DecodingKey), AppError> {
+    let pem = read_key(path)?;
+    if let Ok(key) = DecodingKey::from_ed_pem(&pem) {
+        Ok((Algorithm::EdDSA, key))
+    } else if let Ok(key) = DecodingKey::from_rsa_pem(&pem) {
+        Ok((Algorithm::RS256, key))
+    } else {
+        Err(AppError::ConfigError(format!(
+            "{:?} is not an Ed25519 or RSA public key",
+            path
+        )))
+    }
+}
+
+fn private_key(path: &Path) -> Result<(Algorithm, EncodingKey), AppError> {
+    let pem = read_key(path)?;
+    let (algorithm, key) = if let Ok(key) = EncodingKey::from_ed_pem(&pem) {
+        (Algorithm::EdDSA, key)
+    } else if let Ok(key) = EncodingKey::from_rsa_pem(&pem) {
+        (Algorithm::RS256, key)
+    } else {
+        return Err(AppError::ConfigError(format!(
+            "{:?} is not an Ed25519 or RSA private key",
+            path
+        )));
+    };
+    // Parsing is lazy, fail at startup rather than at the first license
+    encode(&Header::new(algorithm), &serde_json::json!({}), &key)
+        .map_err(|e| AppError::ConfigError(format!("Cannot sign with {:?}: {}", path, e)))?;
+    Ok((algorithm, key))
+}
+
+impl LicenseKeys {
+    /// Loads all public keys of `dir` and the private key `signing_kid`, if
+    /// this instance issues licenses. `legacy` is the HMAC secret and the time
+    /// licenses without a key id stop being accepted.
+    pub fn load(
+        dir: Option<&Path>,
+        signing_kid: Option<&str>,
+        legacy: Option<(&str, DateTime<Utc>)>,
+    ) -> Result<Self, AppError> {
+        let mut verifying = HashMap::new();
+        if let Some(dir) = dir {
+            let entries = fs::read_dir(dir)
+                .map_err(|e| AppError::ConfigError(format!("Cannot read {:?}: {}", dir, e)))?;
+            for entry in entries {
+                let path = entry?.path();
+                let Some(kid) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(PUBLIC_KEY_SUFFIX))
+                else {
+                    continue;
+                };
+                check_key_id(kid)?;
+                verifying.insert(kid.to_string(), public_key(&path)?);
+            }
+        }
+
+        let signing = match (dir, signing_kid) {
+            (Some(dir), Some(kid)) => {
+                check_key_id(kid)?;
+                let (algorithm, key) =
+                    private_key(&dir.join(format!("{}{}", kid, PRIVATE_KEY_SUFFIX)))?;
+                // Licenses we issue must pass our own verification
+                if verifying.get(kid).map(|(a, _)| *a) != Some(algorithm) {
+                    return Err(AppError::ConfigError(format!(
+                        "Public key {}{} of the signing key is missing",
+                        kid, PUBLIC_KEY_SUFFIX
+                    )));
+                }
+                Some(SigningLicenseKey {
+                    kid: kid.to_string(),
+                    algorithm,
+                    key,
+                })
+            }
+            (None, Some(_)) => {
+                return Err(AppError::ConfigError(
+                    "A license signing key id needs a keys directory".to_string(),
+                ))
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            signing,
+            verifying,
+            legacy: legacy.map(|(secret, until)| LegacyLicenseKey {
+                encoding: EncodingKey::from_secret(secret.as_bytes()),
+                decoding: DecodingKey::from_secret(secret.as_bytes()),
+                until,
+            }),
+        })
+    }
+
+    /// Id of the key new licenses are signed with, `None` for the legacy secret.
+    pub fn signing_kid(&self) -> Option<&str> {
+        self.signing.as_ref().map(|s| s.kid.as_str())
+    }
+
+    pub fn key_ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.verifying.keys().map(String::as_str).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Cutoff of HMAC licenses, when they are configured.
+    pub fn legacy_until(&self) -> Option<DateTime<Utc>> {
+        self.legacy.as_ref().map(|legacy| legacy.until)
+    }
+
+    pub fn sign(&self, claims: &LicenseClaims) -> Result<String, AppError> {
+        if let Some(signing) = &self.signing {
+            let mut header = Header::new(signing.algorithm);
+            header.kid = Some(signing.kid.clone());
+            return encode(&header, claims, &signing.key).map_err(AppError::JwtError);
+        }
+        let legacy = self
+            .legacy
+            .as_ref()
+            .filter(|legacy| legacy.active())
+            .ok_or_else(|| AppError::ConfigError("No license signing key configured".to_string()))?;
+        encode(&Header::default(), claims, &legacy.encoding).map_err(AppError::JwtError)
+    }
+
+    /// Checks the signature and expiry of a license. Tokens of unknown keys
+    /// and of other algorithms than their key's are refused, as are tokens
+    /// without a key id once the legacy cutoff passed.
+    pub fn verify(&self, token: &str) -> Result<LicenseClaims, AppError> {
+        let header = decode_header(token).map_err(|_| AppError::Unauthorized)?;
+        let (algorithm, key) = match (&header.kid, &self.legacy) {
+            (Some(kid), _) => self
+                .verifying
+                .get(kid)
+                .map(|(algorithm, key)| (*algorithm, key))
+                .ok_or(AppError::Unauthorized)?,
+            (None, Some(legacy)) if legacy.active() => (Algorithm::HS256, &legacy.decoding),
+            (None, _) => return Err(AppError::Unauthorized),
+        };
+        decode::<LicenseClaims>(token, key, &Validation::new(algorithm))
+            .map(|data| data.claims)
+            .map_err(|e| {
+                if let ErrorKind::ExpiredSignature = e.kind() {
+                    AppError::LicenseExpired
+                } else {
+                    AppError::Unauthorized
+                }
+            })
+    }
+}
+
+/// Writes a new Ed25519 key pair to `dir` and returns the paths of the
+/// private and public key. Existing keys are never overwritten.
+pub fn generate_key_pair(dir: &Path, kid: &str) -> Result<(PathBuf, PathBuf), AppError> {
+    check_key_id(kid)?;
+    let private_path = dir.join(format!("{}{}", kid, PRIVATE_KEY_SUFFIX));
+    let public_path = dir.join(format!("{}{}", kid, PUBLIC_KEY_SUFFIX));
+    if private_path.exists() || public_path.exists() {
+        return Err(AppError::Conflict(format!(
+            "License key {} already exists",
+            kid
+        )));
+    }
+
+    let signing_key = SigningKey::from_bytes(&rand::random::<[u8; 32]>());
+    // PKCS#8 v1, without the public key, which is the form the JWT library reads
+    let private_pem = KeypairBytes {
+        secret_key: signing_key.to_bytes(),
+        public_key: None,
+    }
+    .to_pkcs8_pem(LineEnding::LF)
+    .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+    let public_pem = signing_key
+        .verifying_key()
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(|e| AppError::InternalServerError(e.to_string()))?;
+
+    fs::create_dir_all(dir)?;
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(&private_path)?, private_pem.as_bytes())?;
+    fs::write(&public_path, public_pem)?;
+    Ok((private_path, public_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn claims() -> LicenseClaims {
+        LicenseClaims {
+            sub: "user@example.com".to_string(),
+            exp: (Utc::now() + Duration::days(30)).timestamp() as usize,
+            level: "pro".to_string(),
+        }
+    }
+
+    fn keys(dir: &Path, kid: &str) -> LicenseKeys {
+        LicenseKeys::load(Some(dir), Some(kid), None).unwrap()
+    }
+
+    fn legacy(until: DateTime<Utc>) -> LicenseKeys {
+        LicenseKeys::load(None, None, Some(("legacy-secret", until))).unwrap()
+    }
+
+    #[test]
+    fn generated_key_pair_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let (private_path, public_path) = generate_key_pair(dir.path(), "2026-01").unwrap();
+        assert!(private_path.ends_with("2026-01.key.pem"));
+        assert!(public_path.ends_with("2026-01.pub.pem"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&private_path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        assert!(matches!(
+            generate_key_pair(dir.path(), "2026-01"),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            generate_key_pair(dir.path(), "../2026-01"),
+            Err(AppError::ConfigError(_))
+        ));
+
+        let keys = keys(dir.path(), "2026-01");
+        assert_eq!(keys.signing_kid(), Some("2026-01"));
+        let token = keys.sign(&claims()).unwrap();
+        let header = decode_header(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::EdDSA);
+        assert_eq!(header.kid.as_deref(), Some("2026-01"));
+        assert_eq!(keys.verify(&token).unwrap().sub, "user@example.com");
+    }
+
+    #[test]
+    fn verifies_with_the_key_named_by_kid() {
+        let dir = tempfile::tempdir().unwrap();
+        generate_key_pair(dir.path(), "old").unwrap();
+        generate_key_pair(dir.path(), "new").unwrap();
+        let old = keys(dir.path(), "old");
+        let new = keys(dir.path(), "new");
+        assert_eq!(new.key_ids(), ["new", "old"]);
+
+        // Rotated keys keep older licenses valid
+        let token = old.sign(&claims()).unwrap();
+        assert_eq!(new.verify(&token).unwrap().sub, "user@example.com");
+
+        // The signature has to match the key the kid names
+        let mut header = decode_header(&token).unwrap();
+        header.kid = Some("new".to_string());
+        let forged = encode(&header, &claims(), &old.signing.as_ref().unwrap().key).unwrap();
+        assert!(matches!(new.verify(&forged), Err(AppError::Unauthorized)));
+
+        let other = tempfile::tempdir().unwrap();
+        generate_key_pair(other.path(), "other").unwrap();
+        assert!(matches!(
+            keys(other.path(), "other").verify(&token),
+            Err(AppError::Unauthorized)
+        ));
+    }
+
+    #[test]
+    fn refuses_other_algorithms_than_the_key() {
+        let dir = tempfile::tempdir().unwrap();
+        generate_key_pair(dir.path(), "main").unwrap();
+        let keys = LicenseKeys::load(
+            Some(dir.path()),
+            Some("main"),
+            Some(("legacy-secret", Utc::now() + Duration::days(1))),
+        )
+        .unwrap();
+
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("main".to_string());
+        // The public key is no secret, it must not work as an HMAC key
+        let public_pem = fs::read(dir.path().join("main.pub.pem")).unwrap();
+        let token = encode(&header, &claims(), &EncodingKey::from_secret(&public_pem)).unwrap();
+        assert!(matches!(keys.verify(&token), Err(AppError::Unauthorized)));
+
+        // Nor does the legacy secret once a token names a key
+        let secret = EncodingKey::from_secret(b"legacy-secret");
+        let token = encode(&header, &claims(), &secret).unwrap();
+        assert!(matches!(keys.verify(&token), Err(AppError::Unauthorized)));
+    }
+
+    #[test]
+    fn legacy_licenses_stop_at_the_cutoff() {
+        let token = legacy(Utc::now() + Duration::days(1)).sign(&claims()).unwrap();
+        assert_eq!(decode_header(&token).unwrap().kid, None);
+        let active = legacy(Utc::now() + Duration::days(1));
+        assert_eq!(active.verify(&token).unwrap().sub, "user@example.com");
+
+        let expired = legacy(Utc::now() - Duration::seconds(1));
+        assert!(matches!(expired.verify(&token), Err(AppError::Unauthorized)));
+        assert!(matches!(
+            expired.sign(&claims()),
+            Err(AppError::ConfigError(_))
+        ));
+        let none = LicenseKeys::load(None, None, None).unwrap();
+        assert!(matches!(none.verify(&token), Err(AppError::Unauthorized)));
+
+        assert_eq!(
+            parse_legacy_cutoff("2026-12-31").unwrap().to_rfc3339(),
+            "2026-12-31T00:00:00+00:00"
+        );
+        assert!(parse_legacy_cutoff("end of year").is_err());
+    }
+}
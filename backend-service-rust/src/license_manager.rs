@@ -1,11 +1,9 @@
 use crate::errors::AppError;
 use crate::exlogging::{log_event, LogLevel};
-use crate::utils::{self, user_personal_directory_from_email};
+use crate::license_keys::LicenseKeys;
+use crate::utils::{self, organization_directory};
 use base64::Engine;
 use chrono::{DateTime, Utc};
-use jsonwebtoken::{
-    decode, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation,
-};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::SystemTime;
@@ -131,7 +129,7 @@ pub fn generate_license_token(
     email: &str,
     expiry_date: DateTime<Utc>,
     level: Option<String>,
-    jwt_secret: &[u8],
+    license_keys: &LicenseKeys,
 ) -> Result<String, AppError> {
     let expiration = expiry_date.timestamp() as usize;
     let license_level = level.unwrap_or_else(default_license_level); // Use default if not provided
@@ -142,35 +140,28 @@ pub fn generate_license_token(
         level: license_level,
     };
 
-    let header = Header::default();
-    let encoding_key = EncodingKey::from_secret(jwt_secret);
+    let token = license_keys.sign(&claims)?;
 
     log_event(
         LogLevel::Info,
         format!(
-            "License generated: {:?}; expiry date: {:?}",
-            claims, expiry_date
+            "License generated: {:?}; expiry date: {:?}; key: {}",
+            claims,
+            expiry_date,
+            license_keys.signing_kid().unwrap_or("legacy secret")
         ),
         None::<&str>,
     );
 
-    encode(&header, &claims, &encoding_key).map_err(AppError::JwtError)
+    Ok(token)
 }
 
 // Function to decode and validate a JWT license token
-pub fn decode_license_token(token: &str, jwt_secret: &[u8]) -> Result<LicenseClaims, AppError> {
-    let decoding_key = DecodingKey::from_secret(jwt_secret);
-    let validation = Validation::default();
-
-    decode::<LicenseClaims>(token, &decoding_key, &validation)
-        .map(|data| data.claims)
-        .map_err(|e| {
-            if let ErrorKind::ExpiredSignature = e.kind() {
-                AppError::LicenseExpired
-            } else {
-                AppError::Unauthorized
-            }
-        })
+pub fn decode_license_token(
+    token: &str,
+    license_keys: &LicenseKeys,
+) -> Result<LicenseClaims, AppError> {
+    license_keys.verify(token)
 }
 
 pub fn decode_license_token_no_validation(token: &str) -> Result<LicenseClaims, AppError> {
@@ -204,12 +195,12 @@ pub fn decode_license_token_no_validation(token: &str) -> Result<LicenseClaims,
 
 // Function to save a valid license token to a file
 pub async fn save_license_file(
-    user_email: &str,
+    organization: &str,
     token: &str,
     data_dir: &PathBuf,
 ) -> Result<(), AppError> {
     // Get the user's directory
-    let user_data_dir = ensure_license_path(user_email, data_dir)?;
+    let user_data_dir = ensure_license_path(organization, data_dir)?;
 
     // Use a unique filename, perhaps based on a timestamp or hash,
     // but for simplicity here, we'll use email + a counter or just email.
@@ -232,10 +223,10 @@ pub async fn save_license_file(
 }
 
 pub async fn find_latest_license_file(
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
 ) -> Result<PathBuf, AppError> {
-    let user_data_dir = ensure_license_path(user_email, data_dir)?;
+    let user_data_dir = ensure_license_path(organization, data_dir)?;
 
     // Find the latest license file based on file metadata timestamp
     let mut entries = fs::read_dir(&user_data_dir)
@@ -274,12 +265,12 @@ pub async fn read_license_file(license_path: &PathBuf) -> Result<String, AppErro
 }
 
 pub async fn read_license_file_by_name(
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
     file_name: &str,
 ) -> Result<String, AppError> {
     // Ensure user license directory exists
-    let user_data_dir = ensure_license_path(user_email, data_dir)?;
+    let user_data_dir = ensure_license_path(organization, data_dir)?;
 
     // Construct the full path to the license file
     let license_path = user_data_dir.join(file_name);
@@ -301,10 +292,10 @@ pub async fn read_license_file_by_name(
 
 // Convenience function that combines the two operations
 pub async fn read_latest_license_file(
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
 ) -> Result<String, AppError> {
-    let license_path = find_latest_license_file(user_email, data_dir).await?;
+    let license_path = find_latest_license_file(organization, data_dir).await?;
     read_license_file(&license_path).await
 }
 
@@ -321,18 +312,18 @@ pub async fn read_latest_license_file(
 //     }
 // }
 
-pub fn ensure_license_path(user_email: &str, data_dir: &PathBuf) -> Result<PathBuf, AppError> {
-    let user_data_dir = user_personal_directory_from_email(data_dir, user_email)?;
-    let licenses_dir = user_data_dir.join("licenses");
+pub fn ensure_license_path(organization: &str, data_dir: &PathBuf) -> Result<PathBuf, AppError> {
+    let organization_dir = organization_directory(data_dir, organization)?;
+    let licenses_dir = organization_dir.join("licenses");
     std::fs::create_dir_all(&licenses_dir)?;
     Ok(licenses_dir)
 }
 
 pub async fn list_license_files(
-    user_email: &str,
+    organization: &str,
     data_dir: &PathBuf,
 ) -> Result<Vec<String>, AppError> {
-    let user_data_dir = ensure_license_path(user_email, data_dir)?;
+    let user_data_dir = ensure_license_path(organization, data_dir)?;
 
     // Check if the user directory exists, return empty list if not
     if !user_data_dir.exists() {
@@ -362,11 +353,11 @@ pub async fn list_license_files(
 
 // New function to delete a specific license file for a user
 pub async fn delete_license_file(
-    user_email: &str,
+    organization: &str,
     license_filename: &str,
     data_dir: &PathBuf,
 ) -> Result<(), AppError> {
-    let user_data_dir = ensure_license_path(user_email, data_dir)?;
+    let user_data_dir = ensure_license_path(organization, data_dir)?;
     let filepath = user_data_dir.join(license_filename);
 
     // Ensure the file exists and is within the user's directory to prevent directory traversal attacks
@@ -381,7 +372,7 @@ pub async fn delete_license_file(
         Ok(_) => fs::remove_file(&filepath)
             .await
             .map_err(AppError::IoError)?,
-        Err(e) => log_event(LogLevel::Error, e.to_string(), Some(user_email)),
+        Err(e) => log_event(LogLevel::Error, e.to_string(), Some(organization)),
     }
 
     Ok(())
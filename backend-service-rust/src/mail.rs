@@ -0,0 +1,127 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::{
+    errors::AppError,
+    exlogging::{log_event, LogLevel},
+    utils::random::generate_random_id,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+}
+
+/// Delivers mail to users, e.g. password reset links.
+#[async_trait]
+pub trait MailTransport: Send + Sync {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError>;
+}
+
+/// Writes mail to the application log, for local development.
+pub struct LogTransport;
+
+#[async_trait]
+impl MailTransport for LogTransport {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        log_event(
+            LogLevel::Info,
+            format!("Mail \"{}\":\n{}", mail.subject, mail.text),
+            Some(mail.to.as_str()),
+        );
+        Ok(())
+    }
+}
+
+/// Stores every mail as an `.eml` file in a directory.
+pub struct FileTransport {
+    dir: PathBuf,
+    from: String,
+}
+
+#[async_trait]
+impl MailTransport for FileTransport {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let now = Utc::now();
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            self.from,
+            mail.to,
+            mail.subject,
+            now.to_rfc2822(),
+            mail.text
+        );
+        let file_name = format!(
+            "{}_{}.eml",
+            now.format("%Y%m%dT%H%M%S"),
+            generate_random_id(6)
+        );
+        tokio::fs::write(self.dir.join(file_name), message).await?;
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct HttpMail<'a> {
+    from: &'a str,
+    #[serde(flatten)]
+    mail: &'a Mail,
+}
+
+/// Posts mail as JSON (`from`, `to`, `subject`, `text`) to a mail relay.
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    from: String,
+}
+
+#[async_trait]
+impl MailTransport for HttpTransport {
+    async fn send(&self, mail: &Mail) -> Result<(), AppError> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&HttpMail {
+                from: &self.from,
+                mail,
+            })
+            .send()
+            .await
+            .map_err(|e| AppError::InternalServerError(format!("Mail relay unreachable: {}", e)))?;
+        if !response.status().is_success() {
+            return Err(AppError::InternalServerError(format!(
+                "Mail relay returned {}",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Picks the transport named by `MAIL_TRANSPORT`: `log` (default), `file` or `http`.
+pub fn mail_transport(
+    kind: Option<&str>,
+    from: String,
+    mail_dir: Option<String>,
+    mail_http_url: Option<String>,
+) -> Result<Arc<dyn MailTransport>, String> {
+    match kind {
+        None | Some("log") => Ok(Arc::new(LogTransport)),
+        Some("file") => Ok(Arc::new(FileTransport {
+            dir: mail_dir.unwrap_or_else(|| "mail".to_string()).into(),
+            from,
+        })),
+        Some("http") => Ok(Arc::new(HttpTransport {
+            client: reqwest::Client::new(),
+            url: mail_http_url.ok_or("MAIL_HTTP_URL is required for the http transport")?,
+            from,
+        })),
+        Some(other) => Err(format!("Unknown MAIL_TRANSPORT {:?}", other)),
+    }
+}
@@ -3,26 +3,45 @@ use api::v1::admin::{
     list_user_licenses_handler,
 };
 use axum::{
+    extract::DefaultBodyLimit,
     http::StatusCode,
     middleware::from_fn_with_state,
     response::IntoResponse,
-    routing::{delete, get, post},
+    routing::{delete, get, post, put},
     Router,
 };
 use exlogging::{configure_log_event, log_event, LogLevel, LoggerConfig};
 
 use crate::{
     api::v1::admin::{generate_invite_handler, list_archived_invite_handler, list_invite_handler},
-    auth::Auth,
-    cache::license_cache::LicenseCache,
+    auth::{
+        organizations::migrate_to_organizations, roles::migrate_admins_file, throttle::LoginThrottle,
+        Auth,
+    },
+    cache::{car_catalog_cache::CarCatalogCache, license_cache::LicenseCache},
+    calc::calculation_import::MAX_IMPORT_ARCHIVE_SIZE,
+    calc::{
+        calculation_index::sync_all_calculation_indexes,
+        document_jobs::spawn_document_worker,
+        document_renderer::{document_renderer, DEFAULT_PDF_FONT},
+    },
     cleanup::cleanup_task,
     db::users::AppDb,
-    middleware::{admin_check_middleware, jwt_auth_middleware, license_expiry_middleware},
+    license_cli::{Cli, Command},
+    license_keys::{parse_legacy_cutoff, LicenseKeys},
+    middleware::{jwt_auth_middleware, license_expiry_middleware, permission_middleware, two_factor_middleware},
+    models::roles::Permission,
     state::AppState,
     utils::DataStorageCache,
 };
+use clap::Parser;
 use dotenv::dotenv;
-use std::{env, path::PathBuf, sync::Arc};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::Notify;
 use tower_http::{services::ServeDir, trace::TraceLayer};
 
 mod api;
@@ -33,7 +52,10 @@ mod cleanup;
 mod db;
 mod errors;
 mod exlogging;
+mod license_cli;
+mod license_keys;
 mod license_manager;
+mod mail;
 mod middleware;
 mod models;
 mod state;
@@ -54,21 +76,43 @@ fn spawn_periodic_cleanup(state: Arc<AppState>) {
     });
 }
 
+fn env_flag(name: &str) -> bool {
+    env::var(name)
+        .map(|v| v == "true" || v == "1")
+        .unwrap_or(false)
+}
+
 #[tokio::main]
 async fn main() -> tokio::io::Result<()> {
     dotenv().ok();
 
+    if let Some(Command::License(command)) = Cli::parse().command {
+        if let Err(e) = license_cli::run(command) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("debug,tower_http=warn,sled=warn"));
 
     let database_url = env::var("DATABASE_URL").unwrap_or_else(|_| "data/sled_db".to_string());
     let jwt_secret = env::var("JWT_SECRET").unwrap_or_else(|_| "supersecretjwtkey".to_string());
-    let jwt_license_secret =
-        env::var("LICENSE_JWT_SECRET").unwrap_or_else(|_| "licensejwtsecretkey".to_string());
+    let license_keys_dir = env::var("LICENSE_KEYS_DIR").ok().map(PathBuf::from);
+    // HMAC-signed licenses are only accepted when explicitly enabled with a cutoff
+    let legacy_license = env::var("LICENSE_JWT_SECRET").ok().map(|secret| {
+        let until = env::var("LICENSE_LEGACY_UNTIL")
+            .expect("LICENSE_JWT_SECRET needs LICENSE_LEGACY_UNTIL, the date HMAC-signed licenses stop being accepted");
+        (secret, parse_legacy_cutoff(&until).expect("Invalid LICENSE_LEGACY_UNTIL"))
+    });
     let admin_file_path = env::var("ADMIN_FILE_PATH").unwrap_or_else(|_| "admins.txt".to_string());
     let log_file_path = env::var("LOG_FILE_PATH").unwrap_or_else(|_| "application.log".to_string());
     let data_dir_path = env::var("DATA_DIR_PATH").unwrap_or_else(|_| "data".to_string());
-    let pdf_gen_api_url_post =
-        env::var("PDF_GEN_URL_POST").unwrap_or_else(|_| "http://localhost:5000/generate".to_string());
+    let calculation_history_git = env_flag("CALCULATION_HISTORY_GIT");
+    let calculation_revisions_keep: usize = env::var("CALCULATION_REVISIONS_KEEP")
+        .unwrap_or_else(|_| "50".to_string())
+        .parse()
+        .expect("CALCULATION_REVISIONS_KEEP must be a number");
     let license_cache_size: u64 = env::var("LICENSE_CACHE_SIZE")
         .unwrap_or_else(|_| "100".to_string())
         .parse()
@@ -79,28 +123,105 @@ async fn main() -> tokio::io::Result<()> {
 
     std::fs::create_dir_all(&data_dir_path)?;
 
-    check_admin_file(&admin_file_path);
-
     let db = AppDb::new(&database_url).expect("Failed to initialize database");
+    migrate_admins_file(&db, Path::new(&admin_file_path))
+        .expect("Failed to migrate admins file to user roles");
+    migrate_to_organizations(&db, Path::new(&data_dir_path))
+        .expect("Failed to move user data to organizations");
+    sync_all_calculation_indexes(&db, &PathBuf::from(&data_dir_path))
+        .await
+        .expect("Failed to sync calculation indexes");
     let auth = Auth::new(jwt_secret.as_bytes());
+    let login_throttle = LoginThrottle::new(
+        env_flag("LOGIN_THROTTLE_PERSIST").then(|| db.login_attempts_tree.clone()),
+        // One proxy (the ingress) unless configured otherwise
+        if env_flag("TRUST_PROXY_HEADERS") {
+            env::var("TRUSTED_PROXY_HOPS")
+                .unwrap_or_else(|_| "1".to_string())
+                .parse()
+                .expect("TRUSTED_PROXY_HOPS must be a number")
+        } else {
+            0
+        },
+    )
+    .expect("Failed to load login attempts");
+    let license_keys = Arc::new(
+        LicenseKeys::load(
+            license_keys_dir.as_deref(),
+            env::var("LICENSE_SIGNING_KEY_ID").ok().as_deref(),
+            legacy_license
+                .as_ref()
+                .map(|(secret, until)| (secret.as_str(), *until)),
+        )
+        .expect("Failed to load license keys"),
+    );
+    match license_keys.signing_kid() {
+        Some(kid) => log::info!("Signing licenses with key {}", kid),
+        None if license_keys.legacy_until().is_some() => log_event(
+            LogLevel::Warn,
+            "Signing licenses with LICENSE_JWT_SECRET, configure LICENSE_KEYS_DIR and LICENSE_SIGNING_KEY_ID",
+            None::<&str>,
+        ),
+        None => log::info!("No license signing key, licenses can only be verified"),
+    }
+    if let Some(until) = license_keys.legacy_until() {
+        log_event(
+            LogLevel::Warn,
+            if until > chrono::Utc::now() {
+                format!("HMAC-signed licenses are accepted until {}", until.to_rfc3339())
+            } else {
+                format!("HMAC-signed licenses are refused since {}", until.to_rfc3339())
+            },
+            None::<&str>,
+        );
+    }
+    log::info!("License verification keys: {:?}", license_keys.key_ids());
     let license_cache = LicenseCache::new(
         PathBuf::from(data_dir_path.clone()),
         license_cache_size,
-        jwt_license_secret.clone(),
+        license_keys.clone(),
+        db.clone(),
     );
 
+    let car_catalog = CarCatalogCache::new(PathBuf::from(data_dir_path.clone()), 100);
+
+    let document_renderer = document_renderer(
+        env::var("DOCUMENT_RENDERER").ok().as_deref(),
+        env::var("PDF_GEN_URL_POST").ok(),
+        env::var("PDF_FONT_PATH")
+            .unwrap_or_else(|_| DEFAULT_PDF_FONT.to_string())
+            .into(),
+        env_flag("DOCUMENT_RENDERER_FALLBACK"),
+    )
+    .expect("DOCUMENT_RENDERER must be native or external");
+
+    let mailer = mail::mail_transport(
+        env::var("MAIL_TRANSPORT").ok().as_deref(),
+        env::var("MAIL_FROM").unwrap_or_else(|_| "no-reply@localhost".to_string()),
+        env::var("MAIL_DIR").ok(),
+        env::var("MAIL_HTTP_URL").ok(),
+    )
+    .expect("MAIL_TRANSPORT must be log, file or http");
+
     let shared_state = Arc::new(AppState {
         db,
         auth,
+        login_throttle,
         license_cache,
-        pdf_gen_api_url_post,
-        jwt_license_secret,
+        car_catalog,
+        document_renderer,
+        document_jobs_notify: Notify::new(),
+        calculation_history_git,
+        calculation_revisions_keep,
+        mailer,
+        password_reset_url: env::var("PASSWORD_RESET_URL").ok(),
+        license_keys,
         data_dir_path: PathBuf::from(data_dir_path),
-        admin_file_path: PathBuf::from(admin_file_path),
         cache: Arc::new(DataStorageCache::new(10, 10, 50)),
     });
 
     spawn_periodic_cleanup(shared_state.clone());
+    spawn_document_worker(shared_state.clone());
 
     let init_result = api::v1::admin_editor_endpoints::run_list_class_body_types_rebuild(
         &shared_state.data_dir_path,
@@ -132,12 +253,55 @@ async fn main() -> tokio::io::Result<()> {
     let public_router = Router::new()
         .route("/health", get(api::v1::auth::health))
         .route("/register", post(api::v1::auth::register))
-        .route("/login", post(api::v1::auth::login));
+        .route("/login", post(api::v1::auth::login))
+        .route("/login/two_factor", post(api::v1::auth::login_two_factor))
+        .route("/refresh", post(api::v1::auth::refresh))
+        .route(
+            "/password_reset/request",
+            post(api::v1::auth::request_password_reset),
+        )
+        .route(
+            "/password_reset/confirm",
+            post(api::v1::auth::confirm_password_reset),
+        );
 
     // Define the API router with built-in error handling through Result returns
     let api_router = Router::new()
         .route("/license", get(api::v1::license::get_license))
+        .route("/logout", post(api::v1::auth::logout))
+        .route("/logout_all", post(api::v1::auth::logout_all))
+        .route("/change_password", post(api::v1::auth::change_password))
+        .route("/sessions", get(api::v1::auth::list_sessions))
+        .route("/sessions/{id}", delete(api::v1::auth::delete_session))
+        .route("/impersonation", get(api::v1::auth::get_impersonation))
+        .route("/impersonation/end", post(api::v1::auth::end_impersonation))
+        .route(
+            "/api_keys",
+            get(api::v1::api_keys::list_api_keys).post(api::v1::api_keys::create_api_key),
+        )
+        .route("/api_keys/{id}", delete(api::v1::api_keys::revoke_api_key))
+        .route(
+            "/two_factor",
+            get(api::v1::two_factor::get_two_factor_status),
+        )
+        .route(
+            "/two_factor/enroll",
+            post(api::v1::two_factor::enroll_two_factor),
+        )
+        .route(
+            "/two_factor/confirm",
+            post(api::v1::two_factor::confirm_two_factor),
+        )
+        .route(
+            "/two_factor/recovery_codes",
+            post(api::v1::two_factor::regenerate_two_factor_recovery_codes),
+        )
+        .route(
+            "/two_factor/disable",
+            post(api::v1::two_factor::disable_two_factor),
+        )
         .route("/getlicenses", get(api::v1::license::get_license_list))
+        .route("/roles", get(api::v1::user::get_my_roles))
         .nest(
             "/admin",
             Router::new()
@@ -148,11 +312,6 @@ async fn main() -> tokio::io::Result<()> {
                 .route("/listusers", get(api::v1::admin::list_users))
                 .route("/logs", get(api::v1::admin::get_n_logs))
                 .route("/logs_frontend", get(api::v1::admin::get_n_logs_frontend))
-                .route("/support_message", post(api::v1::support::support_add_message))
-                .route("/support_all", get(api::v1::support::support_get_all_requests))
-                .route("/support_unresponded", get(api::v1::support::support_get_unresponded))
-                .route("/support_delete", delete(api::v1::support::support_delete))
-                .route("/support_get", get(api::v1::support::support_get))
                 .route(
                     "/notifications",
                     get(api::v1::notifications::admin_list_all_notifications)
@@ -162,10 +321,32 @@ async fn main() -> tokio::io::Result<()> {
                 .route("/cache_clear_all", post(api::v1::admin::clear_all_cache))
                 .route("/manageuser", post(api::v1::admin::manage_user))
                 .route("/users/bulk", post(api::v1::admin::bulk_create_users))
+                .route(
+                    "/calculations/migrate",
+                    post(api::v1::admin::migrate_calculations_handler),
+                )
                 .route("/impersonate", post(api::v1::auth::impersonate))
                 .route(
-                    "/attachment_list",
-                    get(api::v1::attachments::admin_all_attachments),
+                    "/users/{email}/revoke_sessions",
+                    post(api::v1::auth::admin_revoke_sessions),
+                )
+                .route("/lockouts", get(api::v1::auth::admin_list_lockouts))
+                .route(
+                    "/lockouts/{kind}/{id}",
+                    delete(api::v1::auth::admin_unlock),
+                )
+                .route("/roles", get(api::v1::admin::list_roles))
+                .route(
+                    "/organizations",
+                    get(api::v1::organizations::admin_list_organizations),
+                )
+                .route(
+                    "/users/{email}/roles",
+                    get(api::v1::admin::get_user_roles).post(api::v1::admin::grant_role),
+                )
+                .route(
+                    "/users/{email}/roles/{role}",
+                    delete(api::v1::admin::revoke_role),
                 )
                 .route(
                     "/license/invalidate/{email}",
@@ -192,32 +373,84 @@ async fn main() -> tokio::io::Result<()> {
                     get(api::v1::admin::export_user_data_handler),
                 )
                 .route(
-                    "/trigger_list_class_body_types_rebuild_global",
-                    post(api::v1::admin_editor_endpoints::trigger_list_class_body_types_rebuild_global),
+                    "/import_user_data/{user_email}",
+                    post(api::v1::calc::import_endpoints::admin_import_user_data)
+                        .layer(DefaultBodyLimit::max(MAX_IMPORT_ARCHIVE_SIZE)),
                 )
-                .nest(
-                    "/editor",
-                    Router::new()                        .route(
-                            "/list_files",
-                            get(api::v1::admin_editor_endpoints::get_file_list),
-                        )
+                .route(
+                    "/security",
+                    get(api::v1::two_factor::admin_get_security_settings)
+                        .put(api::v1::two_factor::admin_update_security_settings),
+                )
+                .layer(from_fn_with_state(shared_state.clone(), two_factor_middleware))
+                .layer(from_fn_with_state(
+                    (shared_state.clone(), Permission::Administration),
+                    permission_middleware,
+                ))
+                // Support agents answer tickets without full admin rights
+                .merge(
+                    Router::new()
+                        .route("/support_message", post(api::v1::support::support_add_message))
+                        .route("/support_all", get(api::v1::support::support_get_all_requests))
+                        .route("/support_unresponded", get(api::v1::support::support_get_unresponded))
+                        .route("/support_delete", delete(api::v1::support::support_delete))
+                        .route("/support_get", get(api::v1::support::support_get))
                         .route(
-                            "/upload_file/{path}",
-                            post(api::v1::admin_editor_endpoints::upload_file),
+                            "/attachment_list",
+                            get(api::v1::attachments::admin_all_attachments),
                         )
+                        .layer(from_fn_with_state(
+                            (shared_state.clone(), Permission::AnswerSupport),
+                            permission_middleware,
+                        )),
+                )
+                // Catalog editors maintain the common data shared by all users
+                .merge(
+                    Router::new()
                         .route(
-                            "/read_file/{path}",
-                            get(api::v1::admin_editor_endpoints::read_file),
+                            "/trigger_list_class_body_types_rebuild_global",
+                            post(api::v1::admin_editor_endpoints::trigger_list_class_body_types_rebuild_global),
                         )
-                        .route(
-                            "/delete_file/{path}",
-                            delete(api::v1::admin_editor_endpoints::delete_file),
-                        ),
-                )
-                .layer(from_fn_with_state(
-                    shared_state.clone(),
-                    admin_check_middleware,
-                )),
+                        .nest(
+                            "/editor",
+                            Router::new()
+                                .route(
+                                    "/list_files",
+                                    get(api::v1::admin_editor_endpoints::get_file_list),
+                                )
+                                .route(
+                                    "/upload_file/{path}",
+                                    post(api::v1::admin_editor_endpoints::upload_file),
+                                )
+                                .route(
+                                    "/read_file/{path}",
+                                    get(api::v1::admin_editor_endpoints::read_file),
+                                )
+                                .route(
+                                    "/delete_file/{path}",
+                                    delete(api::v1::admin_editor_endpoints::delete_file),
+                                )
+                                .route(
+                                    "/carcatalog/validate",
+                                    get(api::v1::car_catalog_endpoints::validate_common_car_catalog),
+                                )
+                                .route(
+                                    "/carcatalog/{make}",
+                                    get(api::v1::car_catalog_endpoints::get_common_car_make)
+                                        .post(api::v1::car_catalog_endpoints::create_common_car_make)
+                                        .delete(api::v1::car_catalog_endpoints::delete_common_car_make),
+                                )
+                                .route(
+                                    "/carcatalog/{make}/{model}",
+                                    put(api::v1::car_catalog_endpoints::put_common_car_model)
+                                        .delete(api::v1::car_catalog_endpoints::delete_common_car_model),
+                                ),
+                        )
+                        .layer(from_fn_with_state(
+                            (shared_state.clone(), Permission::EditCommonCatalog),
+                            permission_middleware,
+                        )),
+                ),
         )
         .route("/license_upload", post(api::v1::user::upload_license))
         .route("/getactivelicense", get(api::v1::user::get_active_license))
@@ -231,6 +464,35 @@ async fn main() -> tokio::io::Result<()> {
             "/updatecompanyinfo",
             post(api::v1::user::update_company_info),
         )
+        .route(
+            "/organization",
+            get(api::v1::organizations::get_current_organization)
+                .put(api::v1::organizations::rename_organization),
+        )
+        .route(
+            "/organization/members",
+            post(api::v1::organizations::add_organization_member),
+        )
+        .route(
+            "/organization/members/{email}",
+            delete(api::v1::organizations::remove_organization_member),
+        )
+        .route(
+            "/organization/invitations/{email}",
+            delete(api::v1::organizations::cancel_organization_invitation),
+        )
+        .route(
+            "/organization_invitations",
+            get(api::v1::organizations::list_my_invitations),
+        )
+        .route(
+            "/organization_invitations/{organization}",
+            delete(api::v1::organizations::decline_organization_invitation),
+        )
+        .route(
+            "/organization_invitations/{organization}/accept",
+            post(api::v1::organizations::accept_organization_invitation),
+        )
         .route("/notifications", get(api::v1::notifications::list_notifications))
         .route(
             "/notifications/unread-count",
@@ -311,10 +573,80 @@ async fn main() -> tokio::io::Result<()> {
                     "/calculationstore",
                     post(api::v1::calc::persistence_endpoints::save_calculation),
                 )
+                .route(
+                    "/calculationstore",
+                    delete(api::v1::calc::persistence_endpoints::delete_calculation),
+                )
+                .route(
+                    "/calculationstore/restore",
+                    post(api::v1::calc::persistence_endpoints::restore_calculation),
+                )
+                .route(
+                    "/calculationstore/rename",
+                    post(api::v1::calc::persistence_endpoints::rename_calculation),
+                )
+                .route(
+                    "/calculationstore/duplicate",
+                    post(api::v1::calc::persistence_endpoints::duplicate_calculation),
+                )
+                .route(
+                    "/calculationstore/revisions",
+                    get(api::v1::calc::persistence_endpoints::list_calculation_revisions),
+                )
+                .route(
+                    "/calculationstore/revision",
+                    get(api::v1::calc::persistence_endpoints::get_calculation_revision),
+                )
+                .route(
+                    "/calculationstore/diff",
+                    get(api::v1::calc::persistence_endpoints::diff_calculation_revisions),
+                )
                 .route(
                     "/calculationstore/list",
                     get(api::v1::calc::persistence_endpoints::get_calculations_list),
                 )
+                .route(
+                    "/calculationstore/status",
+                    get(api::v1::calc::estimate_endpoints::get_estimate_status),
+                )
+                .route(
+                    "/calculationstore/status",
+                    post(api::v1::calc::estimate_endpoints::change_estimate_status),
+                )
+                .route(
+                    "/calculationstore/export",
+                    get(api::v1::calc::export_endpoints::export_calculations),
+                )
+                .route(
+                    "/calculationstore/transitions",
+                    get(api::v1::calc::estimate_endpoints::list_estimate_transitions_handler),
+                )
+                .route(
+                    "/calculationstore/import",
+                    post(api::v1::calc::import_endpoints::import_user_data)
+                        .layer(DefaultBodyLimit::max(MAX_IMPORT_ARCHIVE_SIZE)),
+                )
+                .route(
+                    "/customers",
+                    get(api::v1::customers::list_customers).post(api::v1::customers::create_customer),
+                )
+                .route(
+                    "/customers/{id}",
+                    get(api::v1::customers::get_customer)
+                        .put(api::v1::customers::update_customer)
+                        .delete(api::v1::customers::delete_customer),
+                )
+                .route(
+                    "/vehicles",
+                    get(api::v1::customers::list_vehicles).post(api::v1::customers::create_vehicle),
+                )
+                .route("/vehicles/history", get(api::v1::customers::vehicle_history))
+                .route(
+                    "/vehicles/{id}",
+                    get(api::v1::customers::get_vehicle)
+                        .put(api::v1::customers::update_vehicle)
+                        .delete(api::v1::customers::delete_vehicle),
+                )
                 .route("/get_calc_details", get(api::v1::user::get_calc_details))
                 .route(
                     "/generate_pdf_table",
@@ -323,6 +655,26 @@ async fn main() -> tokio::io::Result<()> {
                 .route(
                     "/generate_html_table",
                     post(api::v1::calc::output_endpoints::gen_html),
+                )
+                .route(
+                    "/generate_document",
+                    post(api::v1::calc::output_endpoints::gen_document),
+                )
+                .route(
+                    "/documents",
+                    get(api::v1::calc::output_endpoints::list_calculation_documents),
+                )
+                .route(
+                    "/documents/jobs",
+                    post(api::v1::calc::output_endpoints::create_document_job),
+                )
+                .route(
+                    "/documents/jobs/{id}",
+                    get(api::v1::calc::output_endpoints::get_document_job),
+                )
+                .route(
+                    "/document_numbers",
+                    get(api::v1::calc::output_endpoints::list_document_numbers),
                 ).route(
                     "/list_templates",
                     get(api::v1::templating_endpoints::list_templates),
@@ -336,10 +688,26 @@ async fn main() -> tokio::io::Result<()> {
                     "/get_sample/{path}",
                     get(api::v1::templating_endpoints::get_sample),
                 )
+                .route(
+                    "/preview_template",
+                    post(api::v1::templating_endpoints::preview_template_with_sample),
+                )
                 .route(
                     "/carmakes",
                     get(api::v1::calc::data_endpoints::list_car_makes),
                 )
+                .route(
+                    "/carcatalog/search",
+                    get(api::v1::calc::data_endpoints::search_car_catalog),
+                )
+                .route(
+                    "/carcatalog/generation",
+                    get(api::v1::calc::data_endpoints::get_car_generation),
+                )
+                .route(
+                    "/decode_vin/{vin}",
+                    get(api::v1::calc::data_endpoints::decode_vin),
+                )
                 .route(
                     "/list_class_body_types",
                     get(api::v1::calc::data_endpoints::list_class_body_types),
@@ -403,6 +771,21 @@ async fn main() -> tokio::io::Result<()> {
                 )
                 .route("/list_commits", get(api::v1::editor_endpoints::list_commits))
                 .route("/revert_commit", post(api::v1::editor_endpoints::revert_commit))
+                .route(
+                    "/carcatalog/validate",
+                    get(api::v1::car_catalog_endpoints::validate_user_car_catalog),
+                )
+                .route(
+                    "/carcatalog/{make}",
+                    get(api::v1::car_catalog_endpoints::get_user_car_make)
+                        .post(api::v1::car_catalog_endpoints::create_user_car_make)
+                        .delete(api::v1::car_catalog_endpoints::delete_user_car_make),
+                )
+                .route(
+                    "/carcatalog/{make}/{model}",
+                    put(api::v1::car_catalog_endpoints::put_user_car_model)
+                        .delete(api::v1::car_catalog_endpoints::delete_user_car_model),
+                )
                 .route(
                     "/read_common_file/{path}",
                     get(api::v1::editor_endpoints::read_common_file),
@@ -447,20 +830,12 @@ async fn main() -> tokio::io::Result<()> {
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8080").await?;
     log::info!("Starting server at http://0.0.0.0:8080");
     log_event(LogLevel::Info, "Application started", None::<&str>);
-    axum::serve(listener, app).await?;
+    // Login throttling needs the peer address
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
-
-// check if admins file is available, if not - print error into the log, if yes - print info with admins file path
-fn check_admin_file(path: &str) {
-    let admin_file_path = PathBuf::from(path);
-    if admin_file_path.exists() {
-        log::info!("Admin file found at: {}", admin_file_path.display());
-    } else {
-        log::error!(
-            "Admin file NOT found at: {}. Admin functionality might be limited.",
-            admin_file_path.display()
-        );
-    }
-}
@@ -1,14 +1,14 @@
 // src/middleware/mod.rs
 use axum::{
     body::Body, // Explicitly use axum's Body type
-    extract::{FromRequestParts, State},
+    extract::{FromRequestParts, OriginalUri, State},
     http::{request::Parts, Request},
     middleware::Next, // Import Next without generic
     response::Response,
 };
 // Removed: use tower_http::handle_error::HandleErrorLayer; // Not used in this file
 
-use crate::{auth::admin_check::is_admin_async, cache::license_cache::get_license_cache, errors::AppError, state::AppState};
+use crate::{auth::{api_keys::{authenticate_api_key, required_scope, API_KEY_HEADER}, organizations::user_organization, roles::has_permission, two_factor::is_required_for_admin}, cache::license_cache::get_license_cache, db::sessions::{get_session, is_session_revoked}, errors::AppError, exlogging::{log_event, LogLevel}, models::roles::Permission, state::AppState};
 
 use std::sync::Arc;
 // Removed: use async_trait::async_trait; // Not needed for native async traits
@@ -34,6 +34,63 @@ where
     }
 }
 
+// Session id of the token making the request, set by jwt_auth_middleware
+#[derive(Clone)]
+pub struct CurrentSession(pub String);
+
+impl<S> FromRequestParts<S> for CurrentSession
+where
+    S: Send + Sync + 'static,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CurrentSession>()
+            .cloned()
+            .ok_or(AppError::MissingExtension("session".to_string()))
+    }
+}
+
+// Organization whose data the request works with, set by jwt_auth_middleware
+#[derive(Clone)]
+pub struct CurrentOrganization(pub String);
+
+impl<S> FromRequestParts<S> for CurrentOrganization
+where
+    S: Send + Sync + 'static,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CurrentOrganization>()
+            .cloned()
+            .ok_or(AppError::MissingExtension("organization".to_string()))
+    }
+}
+
+// Admin acting as the user on impersonation tokens, set by jwt_auth_middleware
+#[derive(Clone)]
+pub struct Impersonator(pub Option<String>);
+
+impl<S> FromRequestParts<S> for Impersonator
+where
+    S: Send + Sync + 'static,
+{
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Impersonator>()
+            .cloned()
+            .ok_or(AppError::MissingExtension("impersonator".to_string()))
+    }
+}
+
 // Middleware function signatures remain correct for use with from_fn_with_state
 // They list extractors first, then Request<Body>, then Next.
 
@@ -53,6 +110,28 @@ pub async fn jwt_auth_middleware(
         return Ok(next.run(req).await);
     }
 
+    // Scripts authenticate with an API key instead, limited to the routes of its scopes
+    if let Some(key) = parts.headers.get(API_KEY_HEADER).and_then(|header| header.to_str().ok()) {
+        let api_key = authenticate_api_key(&app_state, key)?;
+        let path = parts
+            .extensions
+            .get::<OriginalUri>()
+            .map_or(parts.uri.path(), |uri| uri.path());
+        match required_scope(&parts.method, path) {
+            Some(scope) if api_key.scopes.contains(&scope) => {}
+            _ => return Err(AppError::Forbidden),
+        }
+        let organization = match user_organization(&app_state.db, &api_key.user_email) {
+            Err(AppError::UserNotFound) => return Err(AppError::Unauthorized),
+            other => other?,
+        };
+        parts.extensions.insert(api_key.user_email);
+        parts.extensions.insert(Impersonator(None));
+        parts.extensions.insert(CurrentOrganization(organization));
+        let req = Request::from_parts(parts, body);
+        return Ok(next.run(req).await);
+    }
+
     let auth_header = parts
         .headers
         .get("Authorization")
@@ -65,7 +144,32 @@ pub async fn jwt_auth_middleware(
 
     match app_state.auth.decode_token(&token) {
         Ok(claims) => {
+            // Tokens issued before sessions existed cannot be revoked
+            let session_id = claims.sid.ok_or(AppError::Unauthorized)?;
+            if is_session_revoked(&app_state.db.revoked_sessions_tree, &session_id)? {
+                return Err(AppError::Unauthorized);
+            }
+            let organization = match user_organization(&app_state.db, &claims.sub) {
+                Err(AppError::UserNotFound) => return Err(AppError::Unauthorized),
+                other => other?,
+            };
+            let impersonator = claims.act.map(|act| act.sub);
+            if let Some(admin) = &impersonator {
+                // Everything done in the name of another user is audited
+                let path = parts
+                    .extensions
+                    .get::<OriginalUri>()
+                    .map_or(parts.uri.path(), |uri| uri.path());
+                log_event(
+                    LogLevel::Info,
+                    format!("Impersonating {}: {} {}", claims.sub, parts.method, path),
+                    Some(admin.as_str()),
+                );
+            }
             parts.extensions.insert(claims.sub);
+            parts.extensions.insert(CurrentSession(session_id));
+            parts.extensions.insert(Impersonator(impersonator));
+            parts.extensions.insert(CurrentOrganization(organization));
             let req = Request::from_parts(parts, body);
             Ok(next.run(req).await)
         }
@@ -76,21 +180,51 @@ pub async fn jwt_auth_middleware(
     }
 }
 
-// Middleware to check if the authenticated user is an admin
-// Signature: (AuthenticatedUser, State<Arc<AppState>>, Request<Body>, Next)
-pub async fn admin_check_middleware(
+// Middleware to check that the authenticated user has a permission through one of their roles
+// Signature: (AuthenticatedUser, State<(Arc<AppState>, Permission)>, Request<Body>, Next)
+pub async fn permission_middleware(
     AuthenticatedUser(user_email): AuthenticatedUser, // Extractor 1
-    State(app_state): State<Arc<AppState>>,           // Extractor 2
+    State((app_state, permission)): State<(Arc<AppState>, Permission)>, // Extractor 2
     req: Request<Body>,                               // Request<Body>
     next: Next,                                       // Next
 ) -> Result<Response, AppError> {
-    if is_admin_async(&user_email, &app_state.admin_file_path).await? {
+    if has_permission(&app_state.db, &user_email, permission)? {
         Ok(next.run(req).await)
     } else {
         Err(AppError::AdminCheckFailed)
     }
 }
 
+/// Admin paths open to sessions without the second factor, so the frontend
+/// can still tell admins apart.
+const TWO_FACTOR_EXEMPT_PATHS: &[&str] = &["/api/v1/admin/check_admin_status"];
+
+// Middleware to require a session signed in with the second factor, once admins turned it on
+pub async fn two_factor_middleware(
+    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentSession(session_id): CurrentSession,
+    OriginalUri(uri): OriginalUri, // The admin router is nested, its own uri lacks the prefix
+    State(app_state): State<Arc<AppState>>,
+    req: Request<Body>,
+    next: Next,
+) -> Result<Response, AppError> {
+    if TWO_FACTOR_EXEMPT_PATHS.contains(&uri.path()) || !is_required_for_admin(&app_state)? {
+        return Ok(next.run(req).await);
+    }
+    let session_two_factor = get_session(&app_state.db.sessions_tree, &session_id)?
+        .is_some_and(|s| s.two_factor);
+    // The flag of the session no longer counts once the user turned 2FA off
+    let user_two_factor = app_state
+        .db
+        .find_user_by_email(&user_email)?
+        .is_some_and(|u| u.has_two_factor());
+    if session_two_factor && user_two_factor {
+        Ok(next.run(req).await)
+    } else {
+        Err(AppError::TwoFactorRequired)
+    }
+}
+
 /// Paths that require JWT but must not be license-protected.
 const LICENSE_EXEMPT_PATHS: &[&str] = &[
     "/notifications/unread-count",
@@ -102,7 +236,7 @@ const LICENSE_EXEMPT_PATHS: &[&str] = &[
 ];
 
 pub async fn license_expiry_middleware(
-    AuthenticatedUser(user_email): AuthenticatedUser,
+    CurrentOrganization(organization): CurrentOrganization,
     State(app_state): State<Arc<AppState>>,
     req: Request<Body>, // Request<Body>
     next: Next,         // Next
@@ -112,12 +246,12 @@ pub async fn license_expiry_middleware(
     }
     let license_cache = get_license_cache(&app_state);
 
-    match license_cache.get_license(&user_email).await {
+    match license_cache.get_license(&organization).await {
         Ok(license_data) => {
             if !license_data.is_expired() {
                 Ok(next.run(req).await)
             } else {
-                license_cache.invalidate_license(&user_email);
+                license_cache.invalidate_license(&organization);
                 Err(AppError::LicenseExpired)
             }
         }
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// What an API key may do, see `auth::api_keys::required_scope`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApiKeyScope {
+    /// Read the shop's tables and car catalog.
+    ReadCatalog,
+    /// Upload, fix and delete tables and car catalog entries.
+    WriteCatalog,
+    /// Read stored calculations and estimates.
+    ReadCalculations,
+}
+
+/// A key for scripts acting as the user, kept by the hash of its secret.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub user_email: String,
+    pub name: String,
+    pub key_hash: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeySummary {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiKey> for ApiKeySummary {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            scopes: api_key.scopes,
+            created_at: api_key.created_at,
+            last_used_at: api_key.last_used_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<ApiKeyScope>,
+    /// Current password of the user, keys outlive sessions.
+    pub password: String,
+    /// TOTP or recovery code, required when two-factor authentication is on.
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedApiKey {
+    /// The key itself, shown only this once.
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKeySummary,
+}
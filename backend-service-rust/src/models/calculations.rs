@@ -1,20 +1,47 @@
 use std::collections::HashMap;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use chrono::{DateTime, Utc};
+use indexmap::IndexMap;
 use serde_json::Value;
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::models::estimates::{EstimateStatus, StatusTransition};
+
+/// Version written into every saved calculation. Bump it together with a new step
+/// in `calc::calculation_schema::MIGRATIONS`.
+pub const CALCULATION_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CarCalcData {
+    /// Missing in files saved before versioning was introduced.
+    #[serde(default)]
+    pub schema_version: u32,
+    /// Name given by the user, the file name is only a storage key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// Changed only through the estimate status endpoint, saving keeps the stored one.
+    #[serde(default, skip_serializing_if = "EstimateStatus::is_draft")]
+    pub status: EstimateStatus,
+    /// Status changes, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status_history: Vec<StatusTransition>,
+    /// Customer from the `customers` registry the estimate is made for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub customer_id: Option<String>,
     pub car: Car,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub calculations: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub paint: Option<Paint>,
+    /// Processing results per part name, in the order the parts were selected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calculations: Option<IndexMap<String, Vec<PartProcessing>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parts: Option<Parts>,
     #[serde(flatten)]
     pub additional_fields: HashMap<String, Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Car {
     pub make: Option<String>,
@@ -23,9 +50,296 @@ pub struct Car {
     pub car_class: String,
     pub body_type: String,
     pub license_plate: Option<String>,
+    // The car form posts `VIN`
+    #[serde(alias = "VIN")]
     pub vin: Option<String>,
     pub notes: Option<String>,
     pub store_file_name: Option<String>,
+    /// Vehicle from the `vehicles` registry.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vehicle_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Paint {
+    pub color: Option<String>,
+    pub paint_type: Option<String>,
+    #[serde(flatten)]
+    pub additional_fields: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Parts {
+    #[serde(default)]
+    pub selected_parts: Vec<SelectedPart>,
+    /// Layout of the part picker, owned by the frontend.
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub parts_visual: Value,
+    #[serde(flatten)]
+    pub additional_fields: HashMap<String, Value>,
+}
+
+/// A damaged part together with the chosen repair action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SelectedPart {
+    pub name: String,
+    #[serde(default)]
+    pub action: Option<String>,
+    #[serde(default)]
+    pub damage_level: Option<i64>,
+    #[serde(default)]
+    pub replace: bool,
+    #[serde(default)]
+    pub original: bool,
+    #[serde(default)]
+    pub outside_repair_zone: Option<Value>,
+    #[serde(default, skip_serializing_if = "Value::is_null")]
+    pub grid: Value,
+    #[serde(default)]
+    pub table_data: Vec<Value>,
+    #[serde(flatten)]
+    pub additional_fields: HashMap<String, Value>,
+}
+
+/// One processing table evaluated for a part, e.g. disassembly or painting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PartProcessing {
+    #[serde(default)]
+    pub name: String,
+    /// Failed processors store the thrown error, which may be an object.
+    #[serde(default, deserialize_with = "lenient_error")]
+    pub error: Option<String>,
+    /// `null` for failed processors.
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub result: Vec<CalculationRow>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub total: Option<f64>,
+    #[serde(flatten)]
+    pub additional_fields: HashMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalculationRow {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub estimation: Option<f64>,
+    /// The expression as typed by the user, e.g. `"0,4"`.
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub evaluate: Option<String>,
+    #[serde(default, deserialize_with = "lenient_f64")]
+    pub price: Option<f64>,
+    #[serde(default, deserialize_with = "lenient_string")]
+    pub sum: Option<String>,
+    #[serde(default)]
+    pub tooltip: Option<String>,
+    #[serde(flatten)]
+    pub additional_fields: HashMap<String, Value>,
+}
+
+/// Searchable summary of a stored calculation, kept in the `calculations` tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalculationIndexEntry {
+    pub file_name: String,
+    /// File name without extension, used as the calculation id by the frontend.
+    pub name: String,
+    /// Title as shown in the calculation lists.
+    #[serde(default)]
+    pub title: String,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub year: String,
+    pub car_class: String,
+    pub body_type: String,
+    pub vin: Option<String>,
+    pub license_plate: Option<String>,
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub customer_id: Option<String>,
+    #[serde(default)]
+    pub vehicle_id: Option<String>,
+    pub parts: Vec<String>,
+    pub total: f64,
+    #[serde(default)]
+    pub status: EstimateStatus,
+    pub created_at: DateTime<Utc>,
+    /// Modification time of the file, also used to detect changes made outside `save_calculation`.
+    pub updated_at: DateTime<Utc>,
+    /// Set while the calculation is in the user's deleted calculations.
+    #[serde(default)]
+    pub deleted_at: Option<DateTime<Utc>>,
+    /// Why the file cannot be upgraded to the current schema. Such a calculation
+    /// needs migration and is listed with the fields it was last indexed with.
+    #[serde(default)]
+    pub migration_error: Option<String>,
+}
+
+impl CalculationIndexEntry {
+    pub fn new(
+        file_name: &str,
+        data: &CarCalcData,
+        created_at: DateTime<Utc>,
+        updated_at: DateTime<Utc>,
+    ) -> Self {
+        let mut parts: Vec<String> = data
+            .parts
+            .iter()
+            .flat_map(|p| p.selected_parts.iter().map(|s| s.name.clone()))
+            .collect();
+        for part in data.calculations.iter().flat_map(|c| c.keys()) {
+            if !parts.contains(part) {
+                parts.push(part.clone());
+            }
+        }
+        Self {
+            file_name: file_name.to_string(),
+            name: file_name.strip_suffix(".json").unwrap_or(file_name).to_string(),
+            title: data.display_title(),
+            make: data.car.make.clone(),
+            model: data.car.model.clone(),
+            year: data.car.year.clone(),
+            car_class: data.car.car_class.clone(),
+            body_type: data.car.body_type.clone(),
+            vin: data.car.vin.clone(),
+            license_plate: data.car.license_plate.clone(),
+            notes: data.car.notes.clone(),
+            customer_id: data.customer_id.clone(),
+            vehicle_id: data.car.vehicle_id.clone(),
+            parts,
+            total: data.total(),
+            status: data.status,
+            created_at,
+            updated_at,
+            deleted_at: None,
+            migration_error: None,
+        }
+    }
+
+    /// Entry of a file that was never indexed and cannot be upgraded.
+    pub fn unreadable(file_name: &str, created_at: DateTime<Utc>) -> Self {
+        let name = file_name.strip_suffix(".json").unwrap_or(file_name);
+        Self {
+            file_name: file_name.to_string(),
+            name: name.to_string(),
+            title: name.to_string(),
+            make: None,
+            model: None,
+            year: String::new(),
+            car_class: String::new(),
+            body_type: String::new(),
+            vin: None,
+            license_plate: None,
+            notes: None,
+            customer_id: None,
+            vehicle_id: None,
+            parts: vec![],
+            total: 0.0,
+            status: EstimateStatus::default(),
+            created_at,
+            updated_at: created_at,
+            deleted_at: None,
+            migration_error: None,
+        }
+    }
+}
+
+impl CarCalcData {
+    /// "Make model year", or body type and class for cars picked without a make.
+    pub fn default_title(&self) -> String {
+        let car = &self.car;
+        let words = match car.make.as_deref().filter(|m| !m.trim().is_empty()) {
+            Some(make) => vec![make, car.model.as_deref().unwrap_or_default(), car.year.as_str()],
+            None => vec![car.body_type.as_str(), car.car_class.as_str(), car.year.as_str()],
+        };
+        let title: Vec<&str> = words
+            .into_iter()
+            .map(str::trim)
+            .filter(|w| !w.is_empty())
+            .collect();
+        title.join(" ")
+    }
+
+    pub fn display_title(&self) -> String {
+        match self.title.as_deref().map(str::trim) {
+            Some(title) if !title.is_empty() => title.to_string(),
+            _ => self.default_title(),
+        }
+    }
+
+    /// Sum of all processing totals over all parts, rounded to cents.
+    pub fn total(&self) -> f64 {
+        let total = self
+            .calculations
+            .iter()
+            .flat_map(|c| c.values())
+            .flatten()
+            .filter_map(|p| p.total)
+            .fold(0.0, |acc, t| acc + t);
+        (total * 100.0).round() / 100.0
+    }
+}
+
+impl CalculationRow {
+    pub fn sum_value(&self) -> Option<f64> {
+        parse_decimal(self.sum.as_deref()?)
+    }
+
+    /// Hours as entered by the user, the estimated value otherwise.
+    pub fn hours(&self) -> Option<f64> {
+        self.evaluate
+            .as_deref()
+            .and_then(parse_decimal)
+            .or(self.estimation)
+    }
+}
+
+pub(crate) fn parse_decimal(s: &str) -> Option<f64> {
+    s.trim().replace(',', ".").parse().ok()
+}
+
+// Numbers typed in by users end up in the documents both as numbers and as
+// strings, with either a dot or a comma as the decimal separator.
+fn lenient_f64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => parse_decimal(&s),
+        _ => None,
+    })
+}
+
+fn lenient_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::String(s)) => Some(s),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None,
+    })
+}
+
+fn lenient_error<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        None | Some(Value::Null) => None,
+        Some(Value::String(s)) => Some(s),
+        Some(Value::Object(error)) => match error.get("message") {
+            Some(Value::String(message)) => Some(message.clone()),
+            _ => Some(Value::Object(error).to_string()),
+        },
+        Some(other) => Some(other.to_string()),
+    })
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + Default,
+{
+    Ok(Option::<T>::deserialize(deserializer)?.unwrap_or_default())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -46,6 +360,7 @@ pub struct Estimation {
     pub sections: Option<Vec<String>>
 }
 
+/// Format posted by the first version of the estimator, schema version 0.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CalculationData {
     pub model: Option<CarModel>,
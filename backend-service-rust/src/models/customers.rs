@@ -0,0 +1,96 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+fn generate_random_id() -> String {
+    crate::utils::random::generate_random_id(8)
+}
+
+fn utc_now() -> DateTime<Utc> {
+    Utc::now()
+}
+
+/// A customer of the shop, kept in the `customers` tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Customer {
+    #[serde(default = "generate_random_id")]
+    pub id: String,
+    /// Organization owning the record.
+    #[serde(default)]
+    pub owner: String,
+    pub name: String,
+    #[serde(default)]
+    pub phone: Option<String>,
+    /// Contact email of the customer.
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default = "utc_now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "utc_now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomerInput {
+    pub name: String,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
+
+/// A vehicle serviced by the shop, kept in the `vehicles` tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Vehicle {
+    #[serde(default = "generate_random_id")]
+    pub id: String,
+    #[serde(default)]
+    pub owner: String,
+    #[serde(default)]
+    pub customer_id: Option<String>,
+    #[serde(default)]
+    pub vin: Option<String>,
+    #[serde(default)]
+    pub license_plate: Option<String>,
+    #[serde(default)]
+    pub make: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub year: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default = "utc_now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "utc_now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VehicleInput {
+    #[serde(default)]
+    pub customer_id: Option<String>,
+    #[serde(default)]
+    pub vin: Option<String>,
+    #[serde(default)]
+    pub license_plate: Option<String>,
+    #[serde(default)]
+    pub make: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub year: Option<String>,
+    #[serde(default)]
+    pub notes: Option<String>,
+}
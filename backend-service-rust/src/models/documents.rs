@@ -0,0 +1,127 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::calc::templating::GeneratePdfInternalRequest;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentFormat {
+    #[default]
+    Pdf,
+    Html,
+    Docx,
+    Xlsx,
+    /// Short text summary for messaging apps.
+    Markdown,
+}
+
+impl DocumentFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocumentFormat::Pdf => "pdf",
+            DocumentFormat::Html => "html",
+            DocumentFormat::Docx => "docx",
+            DocumentFormat::Xlsx => "xlsx",
+            DocumentFormat::Markdown => "md",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DocumentFormat::Pdf => "application/pdf",
+            DocumentFormat::Html => "text/html; charset=utf-8",
+            DocumentFormat::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            DocumentFormat::Xlsx => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            DocumentFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentJobStatus {
+    Queued,
+    Running,
+    Done,
+    /// Gave up after the last attempt.
+    Failed,
+}
+
+/// A document generation job, kept in the `document_jobs` tree until it is
+/// cleaned up.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentJob {
+    pub id: String,
+    pub owner: String,
+    pub format: DocumentFormat,
+    pub status: DocumentJobStatus,
+    /// Stored calculation the document is generated for, if any.
+    #[serde(default)]
+    pub calculation: Option<String>,
+    /// Hash of the format and the renderer input, equal inputs give equal documents.
+    pub content_hash: String,
+    pub file_name: String,
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// When a queued job is due, later than `created_at` for retries.
+    pub next_attempt_at: DateTime<Utc>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+    /// The generated document, set once the job is done.
+    #[serde(default)]
+    pub attachment_id: Option<String>,
+    /// The job reused a document generated earlier from the same input.
+    #[serde(default)]
+    pub deduplicated: bool,
+    /// Document number assigned when the job was created.
+    #[serde(default)]
+    pub number: Option<AssignedNumber>,
+    /// Renderer input, dropped once the job is finished and from API responses.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<GeneratePdfInternalRequest>,
+}
+
+/// Default pattern of document numbers, e.g. `EST-2026-00042`.
+pub const DEFAULT_NUMBER_FORMAT: &str = "EST-{year}-{seq:05}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NumberAssignmentStatus {
+    Issued,
+    /// The document was not generated and the number could not be given back
+    /// because later numbers were already issued.
+    Void,
+}
+
+/// Audit record of a document number, kept in the `document_numbers` tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NumberAssignment {
+    pub number: String,
+    pub year: i32,
+    /// Position in the company's gap-free sequence of the year.
+    pub sequence: u64,
+    #[serde(default)]
+    pub calculation: Option<String>,
+    pub format: DocumentFormat,
+    /// File name of the document the number was printed on.
+    pub document: String,
+    /// Email of the user who generated the document.
+    pub actor: String,
+    pub assigned_at: DateTime<Utc>,
+    pub status: NumberAssignmentStatus,
+    #[serde(default)]
+    pub void_reason: Option<String>,
+}
+
+/// A number given to a document being generated.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignedNumber {
+    pub assignment: NumberAssignment,
+    /// The calculation already had this number from an earlier document.
+    pub reused: bool,
+}
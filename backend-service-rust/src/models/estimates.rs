@@ -0,0 +1,64 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Lifecycle of a stored calculation once it is used as an estimate for a customer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EstimateStatus {
+    #[default]
+    Draft,
+    Sent,
+    Approved,
+    InRepair,
+    Completed,
+    Invoiced,
+    Cancelled,
+}
+
+impl EstimateStatus {
+    pub fn is_draft(&self) -> bool {
+        *self == EstimateStatus::Draft
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EstimateStatus::Draft => "draft",
+            EstimateStatus::Sent => "sent",
+            EstimateStatus::Approved => "approved",
+            EstimateStatus::InRepair => "in_repair",
+            EstimateStatus::Completed => "completed",
+            EstimateStatus::Invoiced => "invoiced",
+            EstimateStatus::Cancelled => "cancelled",
+        }
+    }
+}
+
+impl fmt::Display for EstimateStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A recorded status change, kept in the estimate's document and in the
+/// `estimate_transitions` tree.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusTransition {
+    pub file_name: String,
+    pub from: EstimateStatus,
+    pub to: EstimateStatus,
+    /// Email of the user who made the change.
+    pub actor: String,
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StatusChangeRequest {
+    pub filename: String,
+    pub status: EstimateStatus,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
@@ -1,11 +1,23 @@
+pub mod api_keys;
 pub mod calculations;
+pub mod customers;
+pub mod documents;
+pub mod estimates;
 pub mod invite;
 pub mod notifications;
+pub mod organizations;
 pub mod requests;
+pub mod roles;
+pub mod sessions;
 pub mod table_validation;
+pub mod two_factor;
 
 use crate::{
     license_manager::{GenerateLicenseByDateRequest, GenerateLicenseByDaysRequest},
+    models::{
+        roles::{Permission, Role},
+        two_factor::TwoFactor,
+    },
     utils::money::MoneyWithCurrency,
 };
 use chrono::{DateTime, Utc};
@@ -17,6 +29,38 @@ pub struct User {
     pub id: Uuid,
     pub email: String,
     pub password_hash: String,
+    /// Sorted, without duplicates.
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    /// Shop the user works in, set at registration.
+    #[serde(default)]
+    pub organization: Option<String>,
+    #[serde(default)]
+    pub two_factor: Option<TwoFactor>,
+}
+
+impl User {
+    /// A new account owns its shop.
+    pub fn new(email: String, password_hash: String) -> Self {
+        User {
+            id: Uuid::new_v4(),
+            email,
+            password_hash,
+            roles: vec![Role::ShopOwner],
+            organization: None,
+            two_factor: None,
+        }
+    }
+
+    pub fn has_two_factor(&self) -> bool {
+        self.two_factor.as_ref().is_some_and(|t| t.enabled)
+    }
+
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.roles
+            .iter()
+            .any(|role| role.permissions().contains(&permission))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,12 +86,60 @@ pub struct ImpersonateRequest {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginResponse {
     pub token: String,
+    /// Exchanged for a new token pair at `/refresh`, single use.
+    pub refresh_token: String,
+    /// Lifetime of `token` in seconds.
+    pub expires_in: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    /// Token from the reset mail.
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
     pub exp: usize,
+    /// Session the token was issued for, checked against revoked sessions.
+    #[serde(default)]
+    pub sid: Option<String>,
+    /// Admin acting as the user, on impersonation tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>,
+}
+
+/// `act` claim of RFC 8693: who is really making the requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Actor {
+    pub sub: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonationStatus {
+    pub impersonating: bool,
+    pub user: String,
+    /// The admin acting as `user`.
+    pub admin: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 fn default_lang() -> String {
@@ -58,6 +150,10 @@ fn default_currency() -> String {
     MoneyWithCurrency::default().currency
 }
 
+fn default_number_format() -> String {
+    documents::DEFAULT_NUMBER_FORMAT.to_string()
+}
+
 fn default_empty_string() -> String {
     "".to_string()
 }
@@ -71,6 +167,22 @@ pub struct PricingPreferences {
     pub norm_price: MoneyWithCurrency,
 }
 
+/// How numbers assigned to the company's documents look.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentNumbering {
+    /// Pattern with `{year}` and `{seq}` placeholders, `{seq:05}` pads the sequence to 5 digits.
+    #[serde(default = "default_number_format")]
+    pub format: String,
+}
+
+impl Default for DocumentNumbering {
+    fn default() -> Self {
+        Self {
+            format: default_number_format(),
+        }
+    }
+}
+
 impl Default for PricingPreferences {
     fn default() -> Self {
         Self {
@@ -100,6 +212,9 @@ pub struct CompanyInfo {
     // money stuff
     #[serde(default)]
     pub pricing_preferences: PricingPreferences,
+
+    #[serde(default)]
+    pub document_numbering: DocumentNumbering,
 }
 
 #[derive(Debug, Serialize)]
@@ -114,6 +229,9 @@ pub enum ManageUserRequest {
     Delete { email: String },
     #[serde(rename = "change_pass")]
     ChangePassword { email: String, data: String },
+    /// For users who lost both their authenticator and recovery codes.
+    #[serde(rename = "reset_two_factor")]
+    ResetTwoFactor { email: String },
 }
 
 #[derive(Debug, Deserialize)]
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::roles::Role;
+
+/// A shop. It owns the catalog, company info, licenses, calculations and
+/// everything else its members work on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrganizationMember {
+    pub email: String,
+    /// `shop_owner` or `shop_employee`.
+    pub role: Option<Role>,
+}
+
+/// An invitation to join a shop. The user only moves once they accept it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationInvitation {
+    pub organization: String,
+    pub organization_name: String,
+    pub email: String,
+    pub role: Role,
+    pub invited_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrganizationView {
+    #[serde(flatten)]
+    pub organization: Organization,
+    pub members: Vec<OrganizationMember>,
+    /// Pending invitations of the shop.
+    pub invitations: Vec<OrganizationInvitation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenameOrganizationRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MemberRequest {
+    pub email: String,
+    #[serde(default = "default_member_role")]
+    pub role: Role,
+}
+
+fn default_member_role() -> Role {
+    Role::ShopEmployee
+}
@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    SupportAgent,
+    CatalogEditor,
+    ShopOwner,
+    ShopEmployee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    /// Users, licenses, invites, logs, caches and everything else under `/admin`.
+    Administration,
+    /// Read and answer support tickets of all users.
+    AnswerSupport,
+    /// Read attachments of all users, e.g. those sent with tickets.
+    ViewAllAttachments,
+    /// Edit the common data files and car catalog shared by all users.
+    EditCommonCatalog,
+    /// Change the company profile.
+    ManageCompany,
+}
+
+impl Role {
+    pub const ALL: [Role; 5] = [
+        Role::Admin,
+        Role::SupportAgent,
+        Role::CatalogEditor,
+        Role::ShopOwner,
+        Role::ShopEmployee,
+    ];
+
+    pub fn permissions(self) -> &'static [Permission] {
+        match self {
+            Role::Admin => &[
+                Permission::Administration,
+                Permission::AnswerSupport,
+                Permission::ViewAllAttachments,
+                Permission::EditCommonCatalog,
+                Permission::ManageCompany,
+            ],
+            Role::SupportAgent => &[Permission::AnswerSupport, Permission::ViewAllAttachments],
+            Role::CatalogEditor => &[Permission::EditCommonCatalog],
+            Role::ShopOwner => &[Permission::ManageCompany],
+            Role::ShopEmployee => &[],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleRequest {
+    pub role: Role,
+}
+
+/// Roles of a user and the permissions they add up to.
+#[derive(Debug, Serialize)]
+pub struct UserRoles {
+    pub email: String,
+    pub roles: Vec<Role>,
+    pub permissions: Vec<Permission>,
+}
+
+impl UserRoles {
+    pub fn new(email: String, roles: &[Role]) -> Self {
+        let mut permissions: Vec<Permission> = roles
+            .iter()
+            .flat_map(|r| r.permissions())
+            .copied()
+            .collect();
+        permissions.sort();
+        permissions.dedup();
+        Self {
+            email,
+            roles: roles.to_vec(),
+            permissions,
+        }
+    }
+}
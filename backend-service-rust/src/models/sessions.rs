@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A login on one device, kept in the `sessions` tree until it expires or is
+/// revoked. Only hashes of refresh tokens are stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    pub user_email: String,
+    pub refresh_token_hash: String,
+    /// Hash of the token replaced by the last refresh, to recognize reuse.
+    #[serde(default)]
+    pub previous_refresh_token_hash: Option<String>,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    /// Signed in with the second factor, see `auth::two_factor`.
+    #[serde(default)]
+    pub two_factor: bool,
+    /// Admin impersonating the user, such sessions end within an hour.
+    #[serde(default)]
+    pub impersonated_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub refreshed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A password reset link sent by mail, kept by the hash of its token until it
+/// is used or expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordReset {
+    pub user_email: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Failed logins of one account or IP address, see `auth::throttle`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginAttempts {
+    /// Failures since the last lockout or success.
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+    /// Lockouts in a row, each one lasts twice as long as the previous.
+    pub lockouts: u32,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+/// A locked account or address as listed to admins.
+#[derive(Debug, Serialize)]
+pub struct LoginLockout {
+    /// `account` or `ip`.
+    pub kind: String,
+    pub id: String,
+    #[serde(flatten)]
+    pub attempts: LoginAttempts,
+}
+
+/// A session as shown to its owner.
+#[derive(Debug, Serialize)]
+pub struct SessionSummary {
+    pub id: String,
+    pub user_agent: Option<String>,
+    pub two_factor: bool,
+    pub impersonated_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub refreshed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// The session of the token making the request.
+    pub current: bool,
+}
+
+impl SessionSummary {
+    pub fn new(session: Session, current_session: &str) -> Self {
+        Self {
+            current: session.id == current_session,
+            id: session.id,
+            user_agent: session.user_agent,
+            two_factor: session.two_factor,
+            impersonated_by: session.impersonated_by,
+            created_at: session.created_at,
+            refreshed_at: session.refreshed_at,
+            expires_at: session.expires_at,
+        }
+    }
+}
+
+/// Entry of the revocation list. Access tokens of the session are refused
+/// until `expires_at`, when the last of them has expired anyway.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RevokedSession {
+    pub user_email: String,
+    pub revoked_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// TOTP second factor of a user, see `auth::two_factor`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TwoFactor {
+    /// Base32 shared secret, as shown to the authenticator app.
+    pub secret: String,
+    /// False until the user confirmed enrollment with a first code.
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    /// Hashes of the unused recovery codes.
+    #[serde(default)]
+    pub recovery_code_hashes: Vec<String>,
+    /// Time step of the last accepted code, so a code works only once.
+    #[serde(default)]
+    pub last_used_step: Option<u64>,
+}
+
+/// A password login waiting for the second factor, kept by the hash of its token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoginChallenge {
+    pub user_email: String,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Answer of `/login` for users with two-factor authentication.
+#[derive(Debug, Serialize)]
+pub struct TwoFactorChallengeResponse {
+    pub two_factor_required: bool,
+    pub challenge: String,
+    /// Lifetime of `challenge` in seconds.
+    pub expires_in: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorLoginRequest {
+    pub challenge: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorEnrollment {
+    pub secret: String,
+    /// `otpauth://` provisioning URI, rendered as a QR code by the frontend.
+    pub otpauth_url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorEnrollRequest {
+    /// Current password of the user, a stolen session must not add its own factor.
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorCodeRequest {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorDisableRequest {
+    pub password: String,
+    pub code: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TwoFactorStatus {
+    pub enabled: bool,
+    pub recovery_codes_left: usize,
+    /// Administration needs a session signed in with the second factor.
+    pub required_for_admin: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SecuritySettings {
+    #[serde(default)]
+    pub require_admin_two_factor: bool,
+}
@@ -1,13 +1,27 @@
-use crate::{auth::Auth, cache::license_cache::LicenseCache, db::users::AppDb, utils};
+use crate::{auth::{throttle::LoginThrottle, Auth}, mail::MailTransport, calc::document_renderer::DocumentRenderer, cache::{car_catalog_cache::CarCatalogCache, license_cache::LicenseCache}, db::users::AppDb, license_keys::LicenseKeys, utils};
 use std::{path::{PathBuf}, sync::Arc};
+use tokio::sync::Notify;
 
 pub struct AppState {
     pub db: AppDb,
     pub auth: Auth,
+    pub login_throttle: LoginThrottle,
     pub license_cache: Arc<LicenseCache>,
-    pub admin_file_path: PathBuf,
+    pub car_catalog: Arc<CarCatalogCache>,
     pub data_dir_path: PathBuf,
-    pub jwt_license_secret: String,
-    pub pdf_gen_api_url_post: String,
+    /// Signs new licenses and verifies uploaded ones.
+    pub license_keys: Arc<LicenseKeys>,
+    /// Renders printable documents, in-process or through the external PDF service.
+    pub document_renderer: Arc<dyn DocumentRenderer>,
+    /// Wakes the document worker when a job is queued.
+    pub document_jobs_notify: Notify,
+    /// Keep calculation revisions as git commits instead of snapshot files.
+    pub calculation_history_git: bool,
+    /// Snapshot revisions kept per calculation, `0` keeps all.
+    pub calculation_revisions_keep: usize,
+    /// Sends account mail such as password resets.
+    pub mailer: Arc<dyn MailTransport>,
+    /// Page of the frontend where a reset token is entered, linked from reset mails.
+    pub password_reset_url: Option<String>,
     pub cache: Arc<utils::DataStorageCache>
 }
\ No newline at end of file
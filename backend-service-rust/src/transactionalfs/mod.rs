@@ -6,10 +6,16 @@ use tokio::fs;
 use tokio::process::Command;
 
 use crate::{
+    calc::car_class_to_body_type::CLASS_TYPE_MAPPING_FILE,
     exlogging::{log_event, LogLevel},
     utils::{safe_join, safe_write, DataStorageCache, SafeFsError},
 };
 
+/// Files regenerated from other files of the repository. They are kept out of
+/// the history, so reverting a commit of their source is never undone by a
+/// later commit of the regenerated file.
+const DERIVED_FILES: &[&str] = &[CLASS_TYPE_MAPPING_FILE];
+
 #[derive(Error, Debug)]
 pub enum TransactionalFsError {
     #[error("IO error: {0}")]
@@ -45,6 +51,16 @@ pub struct CommitInfo {
     pub files: Vec<String>,
 }
 
+/// A commit that touched a particular file.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize)]
+pub struct FileRevision {
+    pub hash: String,
+    pub author: String,
+    /// Commit date in RFC 3339 format.
+    pub date: String,
+    pub message: String,
+}
+
 #[async_trait]
 pub trait TransactionalFs {
     /// Writes a new file or updates an existing one, and commits the change.
@@ -60,6 +76,18 @@ pub trait TransactionalFs {
         git_message: &str,
     ) -> Result<(), TransactionalFsError>;
 
+    /// Writes several files and commits them as one change. If a write fails, the
+    /// files written so far are put back as they were and nothing is committed.
+    ///
+    /// # Arguments
+    /// * `files` - Paths relative to the `root_path` and their new content.
+    /// * `git_message` - The Git commit message.
+    async fn write_files(
+        &self,
+        files: Vec<(PathBuf, Vec<u8>)>,
+        git_message: &str,
+    ) -> Result<(), TransactionalFsError>;
+
     async fn commit_all_if_changed(&self, message: &str) -> Result<(), TransactionalFsError>;
 
     /// Deletes a file and commits the deletion.
@@ -89,6 +117,26 @@ pub trait TransactionalFs {
     async fn list_commits(&self) -> Result<Vec<CommitInfo>, TransactionalFsError>;
 
     async fn list_files(&self) -> Result<FsEntry, TransactionalFsError>;
+
+    /// Lists the commits that changed a file, newest first.
+    ///
+    /// # Arguments
+    /// * `file_path_relative_to_root` - The path of the file relative to the `root_path`.
+    async fn file_history(
+        &self,
+        file_path_relative_to_root: &Path,
+    ) -> Result<Vec<FileRevision>, TransactionalFsError>;
+
+    /// Reads the content of a file as it was in a specific commit.
+    ///
+    /// # Arguments
+    /// * `commit_hash` - The hash of the commit.
+    /// * `file_path_relative_to_root` - The path of the file relative to the `root_path`.
+    async fn read_file_at(
+        &self,
+        commit_hash: &str,
+        file_path_relative_to_root: &Path,
+    ) -> Result<Vec<u8>, TransactionalFsError>;
 }
 
 pub struct GitTransactionalFs<'a> {
@@ -188,6 +236,57 @@ impl<'a> TransactionalFs for GitTransactionalFs<'a> {
         Ok(())
     }
 
+    async fn write_files(
+        &self,
+        files: Vec<(PathBuf, Vec<u8>)>,
+        git_message: &str,
+    ) -> Result<(), TransactionalFsError> {
+        log_event(
+            LogLevel::Info,
+            format!("Update {} files, root: {:?}", files.len(), self.root_path),
+            Some(self.author_email.as_str()),
+        );
+
+        // Content before the write, `None` for new files
+        let mut written: Vec<(PathBuf, Option<Vec<u8>>)> = Vec::new();
+        let mut failure = None;
+        for (relative, content) in files {
+            let previous = match safe_join(&self.root_path, &relative) {
+                Ok(full_path) => fs::read(&full_path).await.ok(),
+                Err(e) => {
+                    failure = Some(e.into());
+                    break;
+                }
+            };
+            match safe_write(&self.root_path, &relative, content, self.cache).await {
+                Ok(_) => written.push((relative, previous)),
+                Err(e) => {
+                    failure = Some(e.into());
+                    break;
+                }
+            }
+        }
+
+        if let Some(failure) = failure {
+            for (relative, previous) in written.into_iter().rev() {
+                let full_path = safe_join(&self.root_path, &relative)?;
+                match previous {
+                    Some(previous) => {
+                        safe_write(&self.root_path, &relative, previous, self.cache).await?;
+                    }
+                    None => {
+                        self.cache.invalidate(&full_path).await;
+                        fs::remove_file(&full_path).await?;
+                    }
+                }
+            }
+            return Err(failure);
+        }
+
+        self.perform_git_commit(git_message).await?;
+        Ok(())
+    }
+
     async fn delete_file(
         &self,
         file_path_relative_to_root: &PathBuf,
@@ -312,6 +411,66 @@ impl<'a> TransactionalFs for GitTransactionalFs<'a> {
         }
         Ok(commits)
     }
+
+    async fn file_history(
+        &self,
+        file_path_relative_to_root: &Path,
+    ) -> Result<Vec<FileRevision>, TransactionalFsError> {
+        safe_join(self.root_path.as_path(), file_path_relative_to_root)?;
+        if !self.root_path.join(".git").exists() {
+            return Ok(Vec::new());
+        }
+        let path = file_path_relative_to_root.to_string_lossy();
+        let output = Self::run_git_command_with_output_fallible(
+            &self.root_path,
+            &["log", "--pretty=format:%H%x09%an%x09%aI%x09%s", "--", &path],
+        )
+        .await?;
+
+        output
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let mut fields = line.splitn(4, '\t');
+                match (fields.next(), fields.next(), fields.next(), fields.next()) {
+                    (Some(hash), Some(author), Some(date), message) => Ok(FileRevision {
+                        hash: hash.to_string(),
+                        author: author.to_string(),
+                        date: date.to_string(),
+                        message: message.unwrap_or_default().to_string(),
+                    }),
+                    _ => Err(TransactionalFsError::GitLogParseError(line.to_string())),
+                }
+            })
+            .collect()
+    }
+
+    async fn read_file_at(
+        &self,
+        commit_hash: &str,
+        file_path_relative_to_root: &Path,
+    ) -> Result<Vec<u8>, TransactionalFsError> {
+        safe_join(self.root_path.as_path(), file_path_relative_to_root)?;
+        if !commit_hash.chars().all(|c| c.is_ascii_hexdigit()) || commit_hash.is_empty() {
+            return Err(TransactionalFsError::CommitNotFound(commit_hash.to_string()));
+        }
+        // Paths in `git show <rev>:<path>` are relative to the repository root
+        let object = format!(
+            "{}:{}",
+            commit_hash,
+            file_path_relative_to_root.to_string_lossy()
+        );
+        let output = Command::new("git")
+            .current_dir(&self.root_path)
+            .args(["show", &object])
+            .output()
+            .await?;
+        if !output.status.success() {
+            return Err(TransactionalFsError::CommitNotFound(commit_hash.to_string()));
+        }
+        Ok(output.stdout)
+    }
 }
 
 impl<'a> GitTransactionalFs<'a> {
@@ -332,6 +491,7 @@ impl<'a> GitTransactionalFs<'a> {
         if !root_path.join(".git").exists() {
             Self::run_git_command(&root_path, &["init"]).await?;
         }
+        Self::exclude_derived_files(&root_path).await?;
 
         Ok(Self {
             root_path,
@@ -340,6 +500,32 @@ impl<'a> GitTransactionalFs<'a> {
         })
     }
 
+    /// Lists the derived files in `.git/info/exclude`, which is not part of the history itself.
+    async fn exclude_derived_files(root_path: &Path) -> Result<(), TransactionalFsError> {
+        let exclude_path = root_path.join(".git").join("info").join("exclude");
+        let mut exclude = fs::read_to_string(&exclude_path).await.unwrap_or_default();
+        let missing: Vec<&str> = DERIVED_FILES
+            .iter()
+            .copied()
+            .filter(|file| !exclude.lines().any(|line| line == *file))
+            .collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        if !exclude.is_empty() && !exclude.ends_with('\n') {
+            exclude.push('\n');
+        }
+        for file in missing {
+            exclude.push_str(file);
+            exclude.push('\n');
+        }
+        if let Some(info_dir) = exclude_path.parent() {
+            fs::create_dir_all(info_dir).await?;
+        }
+        fs::write(&exclude_path, exclude).await?;
+        Ok(())
+    }
+
     // Helper function to run Git commands
     async fn run_git_command(cwd: &Path, args: &[&str]) -> Result<(), TransactionalFsError> {
         let output = Command::new("git")
@@ -474,6 +660,16 @@ impl<'a> GitTransactionalFs<'a> {
         )
         .await?; // Name is also email as per requirement
 
+        // Derived files committed before they were excluded leave the history
+        // with this commit rather than with a commit of their own
+        for file in DERIVED_FILES {
+            Self::run_git_command(
+                &self.root_path,
+                &["rm", "--cached", "--quiet", "--ignore-unmatch", file],
+            )
+            .await?;
+        }
+
         // Add all changes to staging
         Self::run_git_command(&self.root_path, &["add", "."]).await?;
 
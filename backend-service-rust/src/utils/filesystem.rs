@@ -1,7 +1,5 @@
-use chrono::{DateTime, Duration, Utc};
 use lexiclean::Lexiclean;
 use percent_encoding::{percent_encode, NON_ALPHANUMERIC};
-use serde::Serialize;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::ffi::OsStr;
@@ -10,13 +8,12 @@ use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::fs;
-use tokio::fs::read_dir;
 use tokio::io;
 
 use crate::errors::AppError;
 use crate::exlogging::log_event;
 
-use super::{DataStorageCache, CATALOG, COMMON, USERS, USERS_DELETED};
+use super::{DataStorageCache, CATALOG, COMMON, ORGANIZATIONS, ORGANIZATIONS_DELETED, USERS};
 
 #[derive(Debug, Error)]
 pub enum SafeFsError {
@@ -137,34 +134,34 @@ pub fn sanitize_email_for_path(email: &str) -> String {
     percent_encode(email.as_bytes(), NON_ALPHANUMERIC).to_string()
 }
 
-pub fn user_catalog_directory_from_email(
+pub fn organization_catalog_directory(
     data_dir: &PathBuf,
-    email: &str,
+    organization: &str,
 ) -> Result<PathBuf, std::io::Error> {
-    let full_path = user_personal_directory_from_email(data_dir, email)?.join(CATALOG);
+    let full_path = organization_directory(data_dir, organization)?.join(CATALOG);
     std::fs::create_dir_all(&full_path)?;
     Ok(full_path)
 }
 
-pub async fn delete_user_data_gracefully(
+pub async fn delete_organization_data_gracefully(
     data_dir: &PathBuf,
-    email: &str,
+    organization: &str,
     cache: &DataStorageCache,
 ) -> Result<(), std::io::Error> {
-    let user_catalog = user_personal_directory_from_email(data_dir, email)?;
-    let deleted_user_catalog = user_deleted_directory_from_email(data_dir, email)?;
+    let organization_dir = organization_directory(data_dir, organization)?;
+    let deleted_organization_dir = organization_deleted_directory(data_dir, organization)?;
 
-    if let Some(parent) = deleted_user_catalog.parent() {
+    if let Some(parent) = deleted_organization_dir.parent() {
         fs::create_dir_all(parent).await?;
     }
-    fs::create_dir_all(&deleted_user_catalog).await?;
+    fs::create_dir_all(&deleted_organization_dir).await?;
 
-    let mut entries = fs::read_dir(&user_catalog).await?;
+    let mut entries = fs::read_dir(&organization_dir).await?;
 
     while let Some(entry) = entries.next_entry().await? {
         let entry_path = entry.path();
         let file_name = entry.file_name();
-        let dest_path = deleted_user_catalog.join(file_name);
+        let dest_path = deleted_organization_dir.join(file_name);
 
         // Check if destination exists using async metadata check
         if let Ok(metadata) = fs::metadata(&dest_path).await {
@@ -185,26 +182,36 @@ pub async fn delete_user_data_gracefully(
     Ok(())
 }
 
-pub fn user_deleted_directory_from_email(
+pub fn organization_deleted_directory(
     data_dir: &PathBuf,
-    email: &str,
+    organization: &str,
 ) -> Result<PathBuf, std::io::Error> {
     let full_path = data_dir
-        .join(USERS_DELETED)
-        .join(sanitize_email_for_path(email));
+        .join(ORGANIZATIONS_DELETED)
+        .join(sanitize_alphanumeric_and_dashes(organization));
     std::fs::create_dir_all(&full_path)?;
     Ok(full_path)
 }
 
-pub fn user_personal_directory_from_email(
+/// Directory with everything an organization owns: catalog, company info,
+/// licenses, calculations and attachments.
+pub fn organization_directory(
     data_dir: &PathBuf,
-    email: &str,
+    organization: &str,
 ) -> Result<PathBuf, std::io::Error> {
-    let full_path = data_dir.join(USERS).join(sanitize_email_for_path(email));
+    let full_path = data_dir
+        .join(ORGANIZATIONS)
+        .join(sanitize_alphanumeric_and_dashes(organization));
     std::fs::create_dir_all(&full_path)?;
     Ok(full_path)
 }
 
+/// Per-user directory of the layout before organizations, only read by the
+/// migration.
+pub fn legacy_user_directory(data_dir: &Path, email: &str) -> PathBuf {
+    data_dir.join(USERS).join(sanitize_email_for_path(email))
+}
+
 pub fn common_directory(data_dir: &PathBuf) -> Result<PathBuf, std::io::Error> {
     let full_path = data_dir.join(COMMON);
     std::fs::create_dir_all(&full_path)?;
@@ -246,7 +253,7 @@ pub async fn list_unique_file_names_two<P: AsRef<Path>>(
 }
 
 pub async fn get_catalog_file_as_string(
-    user_email: &str,
+    organization: &str,
     cache: &DataStorageCache,
     data_dir_path: &PathBuf,
     kind: &str,
@@ -256,7 +263,7 @@ pub async fn get_catalog_file_as_string(
     if path.ends_with(allowed_ext) {
         let file_path = get_file_path_user_common(
             data_dir_path,
-            user_email,
+            organization,
             &PathBuf::from(kind).join(&path),
         )
         .await?;
@@ -274,10 +281,10 @@ pub async fn get_catalog_file_as_string(
 
 pub async fn list_catalog_files_user_common<P: AsRef<Path>>(
     data_dir: &PathBuf,
-    email: &str,
+    organization: &str,
     subpath: &P,
 ) -> io::Result<Vec<String>> {
-    let user_dir = user_catalog_directory_from_email(data_dir, email)?.join(subpath);
+    let user_dir = organization_catalog_directory(data_dir, organization)?.join(subpath);
     let common_dir = common_directory(data_dir)?.join(subpath);
     return list_unique_file_names_two(&user_dir, &common_dir).await;
 }
@@ -333,10 +340,10 @@ pub async fn get_file_bytes_no_cache<P: AsRef<Path>, P2: AsRef<Path>>(
 
 pub async fn get_file_path_user_common<P: AsRef<Path>>(
     data_dir: &PathBuf,
-    email: &str,
+    organization: &str,
     subpath_to_file: &P,
 ) -> io::Result<PathBuf> {
-    let user_file_path = user_catalog_directory_from_email(data_dir, email)?.join(subpath_to_file);
+    let user_file_path = organization_catalog_directory(data_dir, organization)?.join(subpath_to_file);
 
     if fs::metadata(&user_file_path).await.is_ok() {
         Ok(user_file_path)
@@ -428,13 +435,13 @@ pub async fn merge_directories(
 
 pub async fn all_files_with_extension(
     data_dir: &PathBuf,
-    user_email: &str,
+    organization: &str,
     subdir: &str,
     extension: &OsStr,
 ) -> Result<Vec<PathBuf>, AppError> {
     merge_directories(
         &data_dir.join(COMMON).join(subdir),
-        user_catalog_directory_from_email(data_dir, user_email)?
+        organization_catalog_directory(data_dir, organization)?
             .join(subdir)
             .as_path(),
     )
@@ -460,85 +467,3 @@ pub fn sanitize_alphanumeric_and_dashes_and_dots(input: &str) -> String {
         .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_' || *c == '.')
         .collect()
 }
-
-#[derive(Serialize, Debug, Clone)]
-pub struct FileEntry {
-    pub name: String,
-    pub modified: DateTime<Utc>,
-}
-
-#[derive(Serialize, Debug, Default)]
-pub struct FileSummary {
-    pub all_files: Vec<FileEntry>,
-    pub modified_last_24h: Vec<FileEntry>,
-    pub modified_1w_excl_24h: Vec<FileEntry>,
-    pub older_than_1w: Vec<FileEntry>,
-}
-
-async fn directory_exists<P: AsRef<Path>>(path: P) -> bool {
-    match fs::metadata(path).await {
-        Ok(metadata) => metadata.is_dir(),
-        Err(_) => false,
-    }
-}
-
-pub async fn get_file_summary<P: AsRef<Path>>(dir: P) -> io::Result<FileSummary> {
-    let mut all_files = Vec::new();
-    let mut modified_last_24h = Vec::new();
-    let mut modified_1w_excl_24h = Vec::new();
-    let mut older_than_1w = Vec::new();
-
-    let now = Utc::now();
-    let one_day_ago = now - Duration::days(1);
-    let seven_days_ago = now - Duration::days(7);
-
-    if !directory_exists(&dir).await {
-        return Ok(FileSummary::default());
-    }
-    let mut entries = read_dir(dir).await?;
-
-    while let Some(entry) = entries.next_entry().await? {
-        let metadata = entry.metadata().await?;
-        if !metadata.is_file() {
-            continue;
-        }
-
-        let path = entry.path();
-        let filename = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or_default()
-            .to_string();
-
-        let modified: DateTime<Utc> = metadata.modified().map(DateTime::<Utc>::from)?;
-
-        let file_entry = FileEntry {
-            name: filename,
-            modified,
-        };
-
-        if modified > one_day_ago {
-            modified_last_24h.push(file_entry.clone());
-        } else if modified > seven_days_ago {
-            modified_1w_excl_24h.push(file_entry.clone());
-        } else {
-            older_than_1w.push(file_entry.clone());
-        }
-
-        all_files.push(file_entry);
-    }
-
-    let sort_desc = |a: &FileEntry, b: &FileEntry| b.modified.cmp(&a.modified);
-
-    all_files.sort_by(sort_desc);
-    modified_last_24h.sort_by(sort_desc);
-    modified_1w_excl_24h.sort_by(sort_desc);
-    older_than_1w.sort_by(sort_desc);
-
-    Ok(FileSummary {
-        all_files,
-        modified_last_24h,
-        modified_1w_excl_24h,
-        older_than_1w,
-    })
-}
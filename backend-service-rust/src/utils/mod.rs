@@ -21,19 +21,20 @@ use crate::errors::AppError;
 
 // Re-export filesystem types and functions for backward compatibility
 pub use filesystem::{
-    all_files_with_extension, common_directory, delete_user_data_gracefully,
+    all_files_with_extension, common_directory, delete_organization_data_gracefully,
     get_catalog_file_as_string, get_file_as_string_by_path, get_file_bytes_no_cache,
-    get_file_path_user_common, get_file_summary, list_catalog_files_user_common,
-    safe_ensure_directory_exists, safe_join, safe_read, safe_write, safe_write_overwrite,
-    safety_check_only, sanitize_alphanumeric_and_dashes,
-    sanitize_alphanumeric_and_dashes_and_dots, user_catalog_directory_from_email,
-    user_personal_directory_from_email, SafeFsError,
+    get_file_path_user_common, legacy_user_directory, list_catalog_files_user_common,
+    organization_catalog_directory, organization_directory, safe_ensure_directory_exists,
+    safe_join, safe_read, safe_write, safe_write_overwrite, safety_check_only,
+    sanitize_alphanumeric_and_dashes, sanitize_alphanumeric_and_dashes_and_dots, SafeFsError,
 };
 
 // Directory constants
 pub const COMMON: &str = "common";
+/// Per-user data directories of the layout before organizations
 pub const USERS: &str = "users";
-pub const USERS_DELETED: &str = "deleted_users";
+pub const ORGANIZATIONS: &str = "orgs";
+pub const ORGANIZATIONS_DELETED: &str = "deleted_orgs";
 pub const CATALOG: &str = "catalog";
 pub const ATTACHMENTS: &str = "attachments";
 
@@ -9,7 +9,7 @@ const seedCredentials = (index = seededUserIndex()) => ({
   password:
     Cypress.env("E2E_PASSWORD") ||
     Cypress.env("CYPRESS_E2E_PASSWORD") ||
-    `test${index}`,
+    `testpass${index}`,
 });
 
 Cypress.Commands.add("getByTestId", (testId, options = {}) => {
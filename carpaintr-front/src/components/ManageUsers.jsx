@@ -1,6 +1,6 @@
 import React, { useEffect, useState } from "react";
 import { Button, ButtonGroup, Drawer, Input, Loader, Panel } from "rsuite";
-import { authFetch, resetCompanyInfo } from "../utils/authFetch";
+import { authFetch, startImpersonation } from "../utils/authFetch";
 import ReloadIcon from "@rsuite/icons/Reload";
 import LicenseManager from "./LicenseManager";
 import Trans from "../localization/Trans"; // Import Trans component
@@ -80,8 +80,7 @@ const ManageUsers = () => {
 
       const data = await response.json();
       if (data.token) {
-        localStorage.setItem("authToken", data.token); // Store the token in localStorage
-        resetCompanyInfo();
+        startImpersonation(data); // Keeps the admin's tokens for the way back
         navigate("/app/dashboard");
       }
     } catch (error) {
@@ -41,7 +41,7 @@ const CarSelectStage = ({
       setCarClass(car.carClass ?? null);
       setBodyType(car.bodyType ?? null);
       setLicensePlate(car.licensePlate ?? "");
-      setVIN(car.VIN ?? "");
+      setVIN(car.vin ?? car.VIN ?? "");
       setNotes(car.notes ?? "");
       setStoreFileName(car.storeFileName ?? null);
       setIsFromLoading(true);
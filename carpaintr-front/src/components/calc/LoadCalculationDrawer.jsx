@@ -16,7 +16,7 @@ import {
   Placeholder,
 } from "rsuite";
 import { DownloadCloud } from "lucide-react";
-import { capitalizeFirstLetter } from "../../utils/utils";
+import { capitalizeFirstLetter, groupByUpdatedAt } from "../../utils/utils";
 import Trans from "../../localization/Trans";
 import ErrorMessage from "../layout/ErrorMessage";
 
@@ -59,12 +59,13 @@ const LoadCalculationDrawer = React.memo(({ show, onClose }) => {
   const fetchFiles = useCallback(async () => {
     setLoading(true);
     try {
-      const response = await authFetch("/api/v1/user/calculationstore/list");
+      const response = await authFetch("/api/v1/user/calculationstore/list?limit=500");
       if (response.ok) {
         const data = await response.json();
-        setFiles24h(data.modified_last_24h || []);
-        setFiles1w(data.modified_1w_excl_24h || []);
-        setFilesOlder(data.older_than_1w || []);
+        const groups = groupByUpdatedAt(data.items || []);
+        setFiles24h(groups.last24h);
+        setFiles1w(groups.lastWeek);
+        setFilesOlder(groups.older);
         setE(null);
       } else {
         const errorData = await response.text();
@@ -119,11 +120,16 @@ const LoadCalculationDrawer = React.memo(({ show, onClose }) => {
                   <p className="calc-file-load-entry-name">
                     <DownloadCloud />
                     {capitalizeFirstLetter(
-                      file.name.split("_").slice(0, -1).join(" "),
+                      file.title || file.name.split("_").slice(0, -1).join(" "),
                     )}
                   </p>
                   <p className="calc-file-load-entry-date">
-                    <Text as="sub">{formatTimeAgo(file.modified, str)}</Text>
+                    {file.migration_error && (
+                      <Text as="sub" color="red" title={file.migration_error}>
+                        {str("Needs migration")}{" "}
+                      </Text>
+                    )}
+                    <Text as="sub">{formatTimeAgo(file.updated_at, str)}</Text>
                   </p>
                 </HStack>
               </div>
@@ -12,7 +12,7 @@ import {
   Text,
 } from "rsuite";
 import { Wrench, ChevronRight } from "lucide-react";
-import { capitalizeFirstLetter } from "../../utils/utils";
+import { capitalizeFirstLetter, groupByUpdatedAt } from "../../utils/utils";
 import ErrorMessage from "../layout/ErrorMessage";
 
 const formatTimeAgo = (dateString, str) => {
@@ -82,7 +82,7 @@ const iconBubbleStyle = {
 const ProjectCard = React.memo(({ file, onClick, str }) => {
   const [hovered, setHovered] = useState(false);
   const name = capitalizeFirstLetter(
-    file.name.split("_").slice(0, -1).join(" ") || file.name
+    file.title || file.name.split("_").slice(0, -1).join(" ") || file.name
   );
 
   return (
@@ -100,8 +100,17 @@ const ProjectCard = React.memo(({ file, onClick, str }) => {
           {name}
         </div>
         <Text as="span" style={{ fontSize: 12, color: "#9ca3af" }}>
-          {formatTimeAgo(file.modified, str)}
+          {formatTimeAgo(file.updated_at, str)}
         </Text>
+        {file.migration_error && (
+          <Text
+            as="span"
+            style={{ fontSize: 12, color: "#c63215", marginLeft: 8 }}
+            title={file.migration_error}
+          >
+            {str("Needs migration")}
+          </Text>
+        )}
       </div>
       <ChevronRight size={16} color={hovered ? "#c63215" : "#d1d5db"} style={{ flexShrink: 0 }} />
     </div>
@@ -136,12 +145,13 @@ const LoadCalculationMenu = React.memo(
     const fetchFiles = useCallback(async () => {
       setLoading(true);
       try {
-        const response = await authFetch("/api/v1/user/calculationstore/list");
+        const response = await authFetch("/api/v1/user/calculationstore/list?limit=500");
         if (response.ok) {
           const data = await response.json();
-          setFiles24h(data.modified_last_24h || []);
-          setFiles1w(data.modified_1w_excl_24h || []);
-          setFilesOlder(data.older_than_1w || []);
+          const groups = groupByUpdatedAt(data.items || []);
+          setFiles24h(groups.last24h);
+          setFiles1w(groups.lastWeek);
+          setFilesOlder(groups.older);
           setE(null);
         } else {
           const errorData = await response.text();
@@ -52,6 +52,7 @@ registerTranslations("ua", {
   "Modified 1 week excl 24h": "За тиждень",
   "Older than 1 week": "Старіші",
   "No files in this category.": "Немає файлів у цій категорії.",
+  "Needs migration": "Потребує міграції",
   locale_code: "uk-UA",
   "License plate (optional)": "Держ. номер (необов'язково)",
   "VIN (optional)": "VIN (необов'язково)",
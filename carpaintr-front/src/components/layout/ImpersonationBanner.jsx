@@ -0,0 +1,59 @@
+import { useEffect, useState } from "react";
+import { Button, Message } from "rsuite";
+import { useNavigate } from "react-router-dom";
+import { authFetch, endImpersonation } from "../../utils/authFetch";
+
+// Shown while an admin works as another user, see /api/v1/impersonation
+const ImpersonationBanner = () => {
+  const [status, setStatus] = useState(null);
+  const [ending, setEnding] = useState(false);
+  const navigate = useNavigate();
+
+  useEffect(() => {
+    let cancelled = false;
+    authFetch("/api/v1/impersonation")
+      .then((response) => (response.ok ? response.json() : null))
+      .then((data) => {
+        if (!cancelled && data?.impersonating) setStatus(data);
+      })
+      .catch(() => {});
+    return () => {
+      cancelled = true;
+    };
+  }, []);
+
+  if (!status) return null;
+
+  const handleEnd = async () => {
+    setEnding(true);
+    await endImpersonation();
+    navigate("/app/admin");
+  };
+
+  const until = status.expires_at
+    ? new Date(status.expires_at).toLocaleTimeString()
+    : null;
+
+  return (
+    <Message
+      type="warning"
+      showIcon
+      style={{ margin: "0 8pt 8pt" }}
+      data-testid="impersonation-banner"
+    >
+      Ви працюєте як <b>{status.user}</b> (адміністратор {status.admin})
+      {until && <>, сеанс завершиться о {until}</>}.{" "}
+      <Button
+        size="xs"
+        appearance="primary"
+        loading={ending}
+        onClick={handleEnd}
+        data-testid="impersonation-end-button"
+      >
+        Завершити
+      </Button>
+    </Message>
+  );
+};
+
+export default ImpersonationBanner;
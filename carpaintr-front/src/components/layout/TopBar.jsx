@@ -1,7 +1,7 @@
 import { useEffect, useState } from "react";
 import { Navbar, Nav, Loader, Stack } from "rsuite";
 import { useNavigate } from "react-router-dom";
-import { authFetch } from "../../utils/authFetch";
+import { authFetch, logout } from "../../utils/authFetch";
 
 const TopBar = () => {
   const [loading, setLoading] = useState(true);
@@ -29,7 +29,7 @@ const TopBar = () => {
   }, []);
 
   const handleLogout = () => {
-    localStorage.removeItem("authToken"); // Remove the token
+    logout(); // End the session and remove the tokens
     setIsLoggedIn(false);
     navigate("/app/login");
   };
@@ -4,6 +4,7 @@ import { useNavigate } from "react-router-dom";
 import { logout } from "../../utils/authFetch";
 import { useNotificationCount } from "../NotificationCountContext";
 import NotificationsDrawer from "../NotificationsDrawer";
+import ImpersonationBanner from "./ImpersonationBanner";
 import "./TopBarUser.css";
 import { handleOpenNewTab } from "../../utils/utils";
 import { Bell, Menu } from "lucide-react";
@@ -89,6 +90,7 @@ const TopBarUser = () => {
         </Nav>
       </Navbar.Content>
     </Navbar>
+    <ImpersonationBanner />
     <NotificationsDrawer open={drawerOpen} onClose={() => setDrawerOpen(false)} />
     </>
   );
@@ -10,7 +10,12 @@ import {
 } from "rsuite";
 import { Eye, EyeOff } from "lucide-react";
 import { useNavigate, useLocation, Link } from "react-router-dom";
-import { resetCompanyInfo, authFetch, logout } from "../../utils/authFetch";
+import {
+  resetCompanyInfo,
+  authFetch,
+  logout,
+  setAuthTokens,
+} from "../../utils/authFetch";
 import { useDocumentTitle } from "../../hooks/useDocumentTitle";
 import { useLocale, registerTranslations } from "../../localization/LocaleContext";
 import "./LoginPage.css";
@@ -20,6 +25,7 @@ registerTranslations("ua", {
     "Невірна електронна адреса або пароль.",
   "Login failed. Please try again.":
     "Не вдалося увійти. Спробуйте ще раз.",
+  "Invalid code.": "Невірний код.",
 });
 
 const LoginPage = () => {
@@ -30,6 +36,10 @@ const LoginPage = () => {
   const [loading, setLoading] = useState(false);
   const [visible, setVisible] = useState(false);
   const [showPassword, setShowPassword] = useState(false);
+  // Set when the account has two-factor authentication: /login answers with a
+  // challenge that is exchanged for tokens together with a code.
+  const [challenge, setChallenge] = useState(null);
+  const [code, setCode] = useState("");
   // Persistent inline error (key into translations). Toasts are too easy to
   // miss on mobile, so login feedback stays on screen until the next attempt.
   const [errorKey, setErrorKey] = useState(null);
@@ -91,12 +101,52 @@ const LoginPage = () => {
       }
 
       const data = await response.json();
+      if (data?.two_factor_required) {
+        setChallenge(data.challenge);
+        setCode("");
+        return;
+      }
+      if (!data?.token) {
+        setErrorKey("Login failed. Please try again.");
+        return;
+      }
+
+      setAuthTokens(data);
+      resetCompanyInfo();
+      navigate(redirect);
+    } catch {
+      setErrorKey("Login failed. Please try again.");
+    } finally {
+      setLoading(false);
+    }
+  };
+
+  const handleTwoFactor = async () => {
+    setLoading(true);
+    setErrorKey(null);
+    try {
+      const response = await fetch("/api/v1/login/two_factor", {
+        method: "POST",
+        headers: { "Content-Type": "application/json" },
+        body: JSON.stringify({ challenge, code }),
+      });
+
+      if (response.status === 401) {
+        // A wrong code keeps the challenge, an expired one needs the password again
+        const expired = (await response.json().catch(() => null))?.message === "Unauthorized";
+        if (expired) setChallenge(null);
+        setErrorKey(expired ? "Login failed. Please try again." : "Invalid code.");
+        return;
+      }
+
+      const data = response.ok ? await response.json() : null;
       if (!data?.token) {
+        setChallenge(null);
         setErrorKey("Login failed. Please try again.");
         return;
       }
 
-      localStorage.setItem("authToken", data.token);
+      setAuthTokens(data);
       resetCompanyInfo();
       navigate(redirect);
     } catch {
@@ -130,51 +180,82 @@ const LoginPage = () => {
             {str(errorKey)}
           </Message>
         )}
-        <Form fluid>
-          <Form.Group>
-            <Form.ControlLabel>Електронна адреса</Form.ControlLabel>
-            <Input
-              value={username}
-              onChange={(value) => {
-                setUsername(value);
-                setErrorKey(null);
-              }}
-              data-testid="login-email-input"
-            />
-          </Form.Group>
-          <Form.Group>
-            <Form.ControlLabel>Пароль</Form.ControlLabel>
-            <InputGroup inside>
+        {challenge ? (
+          <Form fluid>
+            <Form.Group>
+              <Form.ControlLabel>
+                Код з застосунку автентифікації або резервний код
+              </Form.ControlLabel>
+              <Input
+                value={code}
+                autoComplete="one-time-code"
+                onChange={(value) => {
+                  setCode(value);
+                  setErrorKey(null);
+                }}
+                data-testid="login-two-factor-input"
+              />
+            </Form.Group>
+            <Form.Group>
+              <Button
+                appearance="primary"
+                onClick={handleTwoFactor}
+                loading={loading}
+                disabled={!code.trim()}
+                block
+                data-testid="login-two-factor-submit-button"
+              >
+                Підтвердити
+              </Button>
+            </Form.Group>
+          </Form>
+        ) : (
+          <Form fluid>
+            <Form.Group>
+              <Form.ControlLabel>Електронна адреса</Form.ControlLabel>
               <Input
-                type={showPassword ? "text" : "password"}
-                value={password}
+                value={username}
                 onChange={(value) => {
-                  setPassword(value);
+                  setUsername(value);
                   setErrorKey(null);
                 }}
-                data-testid="login-password-input"
+                data-testid="login-email-input"
               />
-              <InputGroup.Button
-                data-testid="login-password-toggle"
-                onClick={() => setShowPassword((v) => !v)}
+            </Form.Group>
+            <Form.Group>
+              <Form.ControlLabel>Пароль</Form.ControlLabel>
+              <InputGroup inside>
+                <Input
+                  type={showPassword ? "text" : "password"}
+                  value={password}
+                  onChange={(value) => {
+                    setPassword(value);
+                    setErrorKey(null);
+                  }}
+                  data-testid="login-password-input"
+                />
+                <InputGroup.Button
+                  data-testid="login-password-toggle"
+                  onClick={() => setShowPassword((v) => !v)}
+                >
+                  {showPassword ? <EyeOff size={16} /> : <Eye size={16} />}
+                </InputGroup.Button>
+              </InputGroup>
+            </Form.Group>
+            <Form.Group>
+              {" "}
+              <Button
+                appearance="primary"
+                onClick={handleLogin}
+                loading={loading}
+                block
+                data-testid="login-submit-button"
               >
-                {showPassword ? <EyeOff size={16} /> : <Eye size={16} />}
-              </InputGroup.Button>
-            </InputGroup>
-          </Form.Group>
-          <Form.Group>
-            {" "}
-            <Button
-              appearance="primary"
-              onClick={handleLogin}
-              loading={loading}
-              block
-              data-testid="login-submit-button"
-            >
-              Увійти{" "}
-            </Button>
-          </Form.Group>{" "}
-        </Form>
+                Увійти{" "}
+              </Button>
+            </Form.Group>{" "}
+          </Form>
+        )}
         {/* Footer links */}
         <div style={{ marginTop: "16pt", textAlign: "center" }}>
           <Link
@@ -1,16 +1,62 @@
 import YAML from "yaml";
 import Papa from "papaparse";
 
-// Common fetch functionality
-const performAuthFetch = async (url, options = {}) => {
-  const token = localStorage.getItem("authToken");
+export const setAuthTokens = (data) => {
+  localStorage.setItem("authToken", data.token);
+  localStorage.setItem("refreshToken", data.refresh_token);
+};
+
+// Parallel requests share one refresh, a refresh token can be used only once
+let refreshInFlight = null;
+
+const refreshAuthToken = () => {
+  const refreshToken = localStorage.getItem("refreshToken");
+  if (!refreshToken) {
+    return Promise.resolve(false);
+  }
+  if (!refreshInFlight) {
+    refreshInFlight = fetch("/api/v1/refresh", {
+      method: "POST",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({ refresh_token: refreshToken }),
+    })
+      .then(async (response) => {
+        if (!response.ok) {
+          // Another tab may have refreshed meanwhile
+          return localStorage.getItem("refreshToken") !== refreshToken;
+        }
+        setAuthTokens(await response.json());
+        return true;
+      })
+      .catch(() => false)
+      .finally(() => {
+        refreshInFlight = null;
+      });
+  }
+  return refreshInFlight;
+};
 
-  const headers = {
-    ...options.headers,
-    Authorization: token ? `Bearer ${token}` : "",
+// Sends the request with the access token, refreshing it once if it expired
+const fetchWithToken = async (url, options = {}) => {
+  const send = () => {
+    const token = localStorage.getItem("authToken");
+    const headers = {
+      ...options.headers,
+      Authorization: token ? `Bearer ${token}` : "",
+    };
+    return fetch(url, { ...options, headers });
   };
 
-  const response = await fetch(url, { ...options, headers });
+  const response = await send();
+  if (response.status === 401 && (await refreshAuthToken())) {
+    return send();
+  }
+  return response;
+};
+
+// Common fetch functionality
+const performAuthFetch = async (url, options = {}) => {
+  const response = await fetchWithToken(url, options);
 
   if (!response.ok) {
     throw new Error(`HTTP error ${response.status}`);
@@ -89,22 +135,48 @@ export const authFetchJson = async (
 };
 
 export const authFetch = async (url, options = {}) => {
-  const token = localStorage.getItem("authToken");
-
-  const headers = {
-    ...options.headers,
-    Authorization: token ? `Bearer ${token}` : "",
-  };
-
-  const response = await fetch(url, { ...options, headers });
-  return response;
+  return fetchWithToken(url, options);
 };
 
 export const logout = () => {
+  const token = localStorage.getItem("authToken");
+  if (token) {
+    // Ends the session on the server, the local state is cleared regardless
+    fetch("/api/v1/logout", {
+      method: "POST",
+      headers: { Authorization: `Bearer ${token}` },
+    }).catch(() => {});
+  }
   localStorage.removeItem("authToken");
+  localStorage.removeItem("refreshToken");
+  localStorage.removeItem("adminAuthToken");
+  localStorage.removeItem("adminRefreshToken");
   localStorage.removeItem("company");
 };
 
+// The admin's own tokens are kept while impersonating, to return to them
+export const startImpersonation = (data) => {
+  localStorage.setItem("adminAuthToken", localStorage.getItem("authToken"));
+  localStorage.setItem("adminRefreshToken", localStorage.getItem("refreshToken"));
+  setAuthTokens(data);
+  resetCompanyInfo();
+};
+
+export const endImpersonation = async () => {
+  await authFetch("/api/v1/impersonation/end", { method: "POST" }).catch(() => {});
+  const token = localStorage.getItem("adminAuthToken");
+  const refreshToken = localStorage.getItem("adminRefreshToken");
+  localStorage.removeItem("adminAuthToken");
+  localStorage.removeItem("adminRefreshToken");
+  if (token && refreshToken) {
+    setAuthTokens({ token, refresh_token: refreshToken });
+  } else {
+    localStorage.removeItem("authToken");
+    localStorage.removeItem("refreshToken");
+  }
+  resetCompanyInfo();
+};
+
 export const fetchCompanyInfo = async (onError = console.error) => {
   try {
     const response = await authFetch("/api/v1/getcompanyinfo");
@@ -16,3 +16,17 @@ export const toRsuiteList = (items) => {
     return { label: item, value: item };
   });
 };
+
+// Splits calculation list items into the "last 24h / last week / older" groups
+export const groupByUpdatedAt = (items) => {
+  const now = Date.now();
+  const day = 24 * 60 * 60 * 1000;
+  const groups = { last24h: [], lastWeek: [], older: [] };
+  for (const item of items) {
+    const age = now - new Date(item.updated_at).getTime();
+    if (age < day) groups.last24h.push(item);
+    else if (age < 7 * day) groups.lastWeek.push(item);
+    else groups.older.push(item);
+  }
+  return groups;
+};
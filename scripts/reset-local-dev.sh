@@ -36,8 +36,10 @@ rm_path() {
 echo "==> Wiping local database..."
 rm_path "${DATABASE_PATH}" "Sled DB"
 
-echo "==> Removing user file trees..."
-rm_path "${DATA_DIR}/users" "users/"
+echo "==> Removing organization file trees..."
+rm_path "${DATA_DIR}/orgs" "orgs/"
+rm_path "${DATA_DIR}/deleted_orgs" "deleted_orgs/"
+rm_path "${DATA_DIR}/users" "users/ (pre-organization layout)"
 rm_path "${DATA_DIR}/deleted_users" "deleted_users/"
 
 echo "==> Removing dev log artifacts..."